@@ -37,6 +37,8 @@ const FROM_SCALARS_METHOD_NAME: &str = "from_scalars";
 /// The name of the method that converts a base type to a serialized vector of
 /// scalars
 const TO_SCALARS_METHOD_NAME: &str = "to_scalars";
+/// The name of the method that iterates over a base type's serialized scalars
+const SCALARS_ITER_METHOD_NAME: &str = "scalars_iter";
 /// The identifier of the `Scalar` type
 const SCALAR_TYPE_IDENT: &str = "Scalar";
 
@@ -173,9 +175,16 @@ fn build_base_type_impl(base_type: &ItemStruct) -> TokenStream2 {
         base_type,
     );
 
-    // Build the `to_scalars` method
-    let to_scalars_impl =
-        build_serialize_method(&new_ident(TO_SCALARS_METHOD_NAME), &scalar_type_path, base_type);
+    // Build the `scalars_iter` method, and the `to_scalars` method which collects
+    // it
+    let scalars_iter_ident = new_ident(SCALARS_ITER_METHOD_NAME);
+    let scalars_iter_impl =
+        build_serialize_iter_method(&scalars_iter_ident, &scalar_type_path, base_type);
+    let to_scalars_impl = build_collect_method(
+        &new_ident(TO_SCALARS_METHOD_NAME),
+        &scalars_iter_ident,
+        &scalar_type_path,
+    );
 
     let impl_block: ItemImpl = parse_quote! {
         impl #generics #trait_ident for #base_type_ident <#base_type_params>
@@ -185,6 +194,7 @@ fn build_base_type_impl(base_type: &ItemStruct) -> TokenStream2 {
 
             #from_scalars_impl
             #to_scalars_impl
+            #scalars_iter_impl
         }
     };
     impl_block.to_token_stream()
@@ -307,6 +317,42 @@ fn build_serialize_method(
     fn_impl.to_token_stream()
 }
 
+/// Implements a serialization iterator that looks like
+///     fn #method_name(&self) -> impl Iterator<Item = #target_type> + '_ {
+///         iter::empty().chain(self.field1.#method_name()).chain(...)
+///     }
+fn build_serialize_iter_method(
+    method_name: &Ident,
+    target_type: &Path,
+    self_struct: &ItemStruct,
+) -> TokenStream2 {
+    let field_idents = self_struct.fields.iter().map(|field| field.ident.clone());
+    let fn_impl: ItemFn = parse_quote! {
+        fn #method_name(&self) -> impl Iterator<Item = #target_type> + '_ {
+            ::std::iter::empty()
+                #(.chain(self.#field_idents.#method_name()))*
+        }
+    };
+    fn_impl.to_token_stream()
+}
+
+/// Implements a serialization function that collects a serialization iterator
+///     fn #method_name(&self) -> Vec<#target_type> {
+///         self.#iter_method_name().collect()
+///     }
+fn build_collect_method(
+    method_name: &Ident,
+    iter_method_name: &Ident,
+    target_type: &Path,
+) -> TokenStream2 {
+    let fn_impl: ItemFn = parse_quote! {
+        fn #method_name(&self) -> Vec<#target_type> {
+            self.#iter_method_name().collect()
+        }
+    };
+    fn_impl.to_token_stream()
+}
+
 /// Implements a deserialization function for a trait that looks like the
 /// following     fn #method_name<I: Iterator<Item = #from_type>>(i: &mut I) ->
 /// Self {         Self { field1: i.next().unwrap(), field2: , ... }
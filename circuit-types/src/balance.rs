@@ -76,6 +76,14 @@ impl Balance {
         self.amount == 0 && self.relayer_fee_balance == 0 && self.protocol_fee_balance == 0
     }
 
+    /// Whether the balance is dust, i.e. non-zero but below the given minimum
+    /// economically useful amount
+    ///
+    /// A zero'd balance is empty rather than dust, so it is not flagged
+    pub fn is_dust(&self, min_economic_amount: Amount) -> bool {
+        self.amount > 0 && self.amount < min_economic_amount
+    }
+
     /// Construct a zero'd balance from a mint
     pub fn new_from_mint(mint: Address) -> Balance {
         Balance { mint, amount: 0, relayer_fee_balance: 0, protocol_fee_balance: 0 }
@@ -1,6 +1,11 @@
 //! Fee types for circuits in the Renegade system
 #![allow(missing_docs, clippy::missing_docs_in_private_items)]
 
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
 use renegade_crypto::fields::scalar_to_u128;
 use serde::{Deserialize, Serialize};
 
@@ -20,6 +25,25 @@ use {
     mpc_relation::{Variable, traits::Circuit},
 };
 
+/// An error validating a pair of fee take rates
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FeeRateError {
+    /// The relayer fee rate is negative or exceeds one
+    RelayerRateOutOfRange,
+    /// The protocol fee rate is negative or exceeds one
+    ProtocolRateOutOfRange,
+    /// The combined fee rate exceeds one, so fees would exceed the amount
+    /// received
+    TotalRateExceedsOne,
+}
+
+impl Display for FeeRateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:?}", self)
+    }
+}
+impl Error for FeeRateError {}
+
 /// A pair of fee take rates
 ///
 /// Note that these are different from the fee takes, they represent fee rates
@@ -47,8 +71,35 @@ impl FeeTakeRate {
         self.relayer_fee_rate + self.protocol_fee_rate
     }
 
+    /// Whether both the relayer and protocol fee rates are zero
+    pub fn is_zero(&self) -> bool {
+        self.relayer_fee_rate == FixedPoint::zero() && self.protocol_fee_rate == FixedPoint::zero()
+    }
+
+    /// Check that each rate, and their total, lies in `[0, 1]`
+    ///
+    /// A total rate above one would take more in fees than the amount received
+    pub fn validate(&self) -> Result<(), FeeRateError> {
+        let (zero, one) = (FixedPoint::zero(), FixedPoint::one());
+        if !self.relayer_fee_rate.is_within(zero, one) {
+            return Err(FeeRateError::RelayerRateOutOfRange);
+        }
+        if !self.protocol_fee_rate.is_within(zero, one) {
+            return Err(FeeRateError::ProtocolRateOutOfRange);
+        }
+        if !self.total().is_within(zero, one) {
+            return Err(FeeRateError::TotalRateExceedsOne);
+        }
+
+        Ok(())
+    }
+
     /// Get a fee take given an amount received
     pub fn compute_fee_take(&self, amount: Amount) -> FeeTake {
+        if self.is_zero() {
+            return FeeTake::default();
+        }
+
         let amt_scalar = Scalar::from(amount);
         let relayer_fee_scalar = (self.relayer_fee_rate * amt_scalar).floor();
         let protocol_fee_scalar = (self.protocol_fee_rate * amt_scalar).floor();
@@ -79,6 +130,21 @@ impl FeeTake {
     }
 }
 
+/// Fee arithmetic on raw amounts
+pub trait AmountExt {
+    /// Deduct the given fees from the amount
+    ///
+    /// Returns `None` if the fees exceed the amount, rather than wrapping
+    fn checked_sub_fee(self, fee: &FeeTake) -> Option<Amount>;
+}
+
+impl AmountExt for Amount {
+    fn checked_sub_fee(self, fee: &FeeTake) -> Option<Amount> {
+        let total = fee.relayer_fee.checked_add(fee.protocol_fee)?;
+        self.checked_sub(total)
+    }
+}
+
 #[cfg(feature = "proof-system-types")]
 impl AuthenticatedFeeTake {
     /// Get the total fee
@@ -86,3 +152,66 @@ impl AuthenticatedFeeTake {
         &self.relayer_fee + &self.protocol_fee
     }
 }
+
+#[cfg(test)]
+mod test {
+    use constants::Scalar;
+
+    use super::{AmountExt, FeeRateError, FeeTake, FeeTakeRate};
+    use crate::fixed_point::FixedPoint;
+
+    /// Tests subtracting fees from an amount
+    #[test]
+    fn test_checked_sub_fee() {
+        let fees = FeeTake { relayer_fee: 10, protocol_fee: 5 };
+        assert_eq!(fees.total(), 15);
+        assert_eq!(100u128.checked_sub_fee(&fees), Some(85));
+        assert_eq!(15u128.checked_sub_fee(&fees), Some(0));
+        assert_eq!(14u128.checked_sub_fee(&fees), None);
+        assert_eq!(0u128.checked_sub_fee(&fees), None);
+        assert_eq!(0u128.checked_sub_fee(&FeeTake::default()), Some(0));
+
+        // The fee total itself may overflow
+        let fees = FeeTake { relayer_fee: u128::MAX, protocol_fee: 1 };
+        assert_eq!(u128::MAX.checked_sub_fee(&fees), None);
+    }
+
+    /// Tests that a zero fee rate takes no fees
+    #[test]
+    fn test_zero_fee_rate() {
+        let rate = FeeTakeRate::default();
+        assert!(rate.is_zero());
+        assert_eq!(rate.validate(), Ok(()));
+        assert_eq!(rate.compute_fee_take(u128::MAX), FeeTake::default());
+
+        let rate = FeeTakeRate::new(FixedPoint::zero(), FixedPoint::from_f64_round_down(0.001));
+        assert!(!rate.is_zero());
+    }
+
+    /// Tests validating fee rates at the one boundary
+    #[test]
+    fn test_validate_fee_rate() {
+        let zero = FixedPoint::zero();
+        let one = FixedPoint::one();
+        let one_plus_epsilon = FixedPoint::from_repr(one.repr + Scalar::one());
+        let half = FixedPoint::from_f64_round_down(0.5);
+
+        // A 100% fee rate is valid and takes the full amount
+        let rate = FeeTakeRate::new(one, zero);
+        assert_eq!(rate.validate(), Ok(()));
+        assert_eq!(rate.compute_fee_take(100).total(), 100);
+        assert_eq!(FeeTakeRate::new(half, half).validate(), Ok(()));
+
+        // Any rate above 100% is rejected
+        let err = FeeTakeRate::new(one_plus_epsilon, zero).validate();
+        assert_eq!(err, Err(FeeRateError::RelayerRateOutOfRange));
+        let err = FeeTakeRate::new(zero, one_plus_epsilon).validate();
+        assert_eq!(err, Err(FeeRateError::ProtocolRateOutOfRange));
+        let err = FeeTakeRate::new(one, FixedPoint::from_repr(Scalar::one())).validate();
+        assert_eq!(err, Err(FeeRateError::TotalRateExceedsOne));
+
+        // Negative rates are rejected
+        let err = FeeTakeRate::new(-half, half).validate();
+        assert_eq!(err, Err(FeeRateError::RelayerRateOutOfRange));
+    }
+}
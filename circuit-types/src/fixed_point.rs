@@ -65,6 +65,17 @@ pub fn right_shift_scalar_by_m(scalar: Scalar) -> Scalar {
     Scalar::new(ScalarField::new(inner))
 }
 
+/// The mode in which to round a fixed point value to an integer
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// Round down to the nearest integer
+    Floor,
+    /// Round up to the nearest integer
+    Ceil,
+    /// Round to the nearest integer, rounding halves up
+    Nearest,
+}
+
 // ------------------------------
 // | Native Type Implementation |
 // ------------------------------
@@ -189,6 +200,32 @@ impl FixedPoint {
         bigint_to_scalar(&self_bigint)
     }
 
+    /// Rounds up the given value to an integer and returns the integer
+    /// representation
+    ///
+    /// Integral values are returned unchanged
+    pub fn ceil(&self) -> Scalar {
+        let floor = self.floor();
+        if floor * Scalar::new(*TWO_TO_M_SCALAR) == self.repr {
+            floor
+        } else {
+            floor + Scalar::one()
+        }
+    }
+
+    /// Rounds the given value to an integer in the given mode and returns the
+    /// integer representation
+    pub fn round(&self, mode: RoundingMode) -> Scalar {
+        match mode {
+            RoundingMode::Floor => self.floor(),
+            RoundingMode::Ceil => self.ceil(),
+            RoundingMode::Nearest => {
+                let half = Scalar::from(1u64 << (DEFAULT_FP_PRECISION - 1));
+                Self { repr: self.repr + half }.floor()
+            },
+        }
+    }
+
     /// Divides one fixed point by another, rounding down to the nearest
     /// representable real value
     pub fn floor_div(&self, rhs: &Self) -> Self {
@@ -245,6 +282,17 @@ impl FixedPoint {
 
         if r == BigUint::from(0u8) { q_scalar } else { q_scalar + Scalar::one() }
     }
+
+    // --- Comparison --- //
+
+    /// Whether the value lies within the inclusive range `[lower, upper]`
+    ///
+    /// Values are compared by their unsigned representations, matching the
+    /// contracts' price comparisons; so negative values compare greater than
+    /// every non-negative value
+    pub fn is_within(&self, lower: FixedPoint, upper: FixedPoint) -> bool {
+        lower.repr <= self.repr && self.repr <= upper.repr
+    }
 }
 
 impl From<f32> for FixedPoint {
@@ -617,6 +665,26 @@ mod fixed_point_tests {
         check_within_tolerance(val, recovered, F64_TOLERANCE);
     }
 
+    /// Tests checking whether a value lies within a range
+    #[test]
+    fn test_is_within() {
+        let lower = FixedPoint::from_f64_round_down(1.5);
+        let upper = FixedPoint::from_f64_round_down(2.5);
+
+        assert!(FixedPoint::from_integer(2).is_within(lower, upper));
+        assert!(lower.is_within(lower, upper));
+        assert!(upper.is_within(lower, upper));
+        assert!(!FixedPoint::from_integer(1).is_within(lower, upper));
+        assert!(!FixedPoint::from_integer(3).is_within(lower, upper));
+
+        // An empty range contains nothing
+        assert!(!FixedPoint::from_integer(2).is_within(upper, lower));
+
+        // Negative values compare above the range by their representation
+        let negative = FixedPoint::from_f64_round_down(-2.);
+        assert!(!negative.is_within(FixedPoint::zero(), upper));
+    }
+
     /// Tests addition with `FixedPoint` and with `Scalar`
     #[test]
     fn test_add() {
@@ -473,12 +473,21 @@ pub mod native_helpers {
     ) -> (ElGamalCiphertext<N>, EmbeddedScalarField) {
         let mut rng = thread_rng();
         let randomness = EmbeddedScalarField::rand(&mut rng);
+        let cipher = elgamal_encrypt_with_randomness(plaintext, key, randomness);
 
+        (cipher, randomness)
+    }
+
+    /// Encrypt a plaintext buffer under the given key using the given
+    /// randomness
+    pub fn elgamal_encrypt_with_randomness<const N: usize>(
+        plaintext: &[Scalar],
+        key: &EncryptionKey,
+        randomness: EmbeddedScalarField,
+    ) -> ElGamalCiphertext<N> {
         let jf_key = EncKey::from(*key);
         let jf_plaintext = plaintext.iter().map(Scalar::inner).collect_vec();
-        let cipher = jf_key.deterministic_encrypt(randomness, &jf_plaintext);
-
-        (cipher.into(), randomness)
+        jf_key.deterministic_encrypt(randomness, &jf_plaintext).into()
     }
 
     /// Decrypt a ciphertext under the given key
@@ -1,10 +1,20 @@
 //! Groups the type definitions for matches
 #![allow(missing_docs, clippy::missing_docs_in_private_items)]
 
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
 use renegade_crypto::fields::scalar_to_u128;
 use serde::{Deserialize, Serialize};
 
-use crate::{Address, Amount, fixed_point::FixedPoint, order::OrderSide};
+use crate::{
+    Address, Amount,
+    fees::{AmountExt, FeeTakeRate},
+    fixed_point::{FixedPoint, RoundingMode},
+    order::OrderSide,
+};
 
 #[cfg(feature = "proof-system-types")]
 use {
@@ -24,6 +34,67 @@ use {
 // | Match Result |
 // ----------------
 
+/// An error validating a match result
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MatchError {
+    /// The mints of the match do not form a known token pair
+    UnknownPair,
+    /// A fill exceeds the maximum amount a bounded match allows
+    FillExceedsMax,
+    /// The settlement indices of a match do not address a valid pair of
+    /// balances and an order in the modified wallet shares
+    InvalidSettlementIndices(String),
+}
+
+impl Display for MatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:?}", self)
+    }
+}
+impl Error for MatchError {}
+
+/// An error validating a fill of a bounded match
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MatchBoundsError {
+    /// The fill is below the bounded match's minimum base amount
+    BelowMin,
+    /// The fill is above the bounded match's maximum base amount
+    AboveMax,
+    /// The fill receives less or sends more than the caller's limits allow
+    SlippageExceeded,
+}
+
+impl Display for MatchBoundsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:?}", self)
+    }
+}
+impl Error for MatchBoundsError {}
+
+/// An error checking a match's amounts against a reference price
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MatchConsistencyError {
+    /// The match exchanges no base
+    ZeroBaseAmount,
+    /// The match exchanges no quote
+    ZeroQuoteAmount,
+    /// The quote amount is further from the amount implied by the price than
+    /// the tolerance allows
+    QuoteOutOfTolerance {
+        /// The quote amount implied by the price and base amount
+        expected: Amount,
+        /// The quote amount of the match
+        actual: Amount,
+    },
+}
+
+impl Display for MatchConsistencyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:?}", self)
+    }
+}
+impl Error for MatchConsistencyError {}
+
 /// Represents the match result of a matching MPC in the cleartext
 /// in which two tokens are exchanged
 #[cfg_attr(
@@ -57,6 +128,50 @@ pub struct MatchResult {
 }
 
 impl MatchResult {
+    /// Construct a match result, deriving `min_amount_order_index` from the
+    /// amounts of the two matched orders
+    ///
+    /// The order with the smaller amount is the one fully filled by the match.
+    /// If the amounts are equal both orders are fully filled, and party 0's
+    /// order is taken as the minimum
+    pub fn from_match(
+        quote_mint: Address,
+        base_mint: Address,
+        quote_amount: Amount,
+        base_amount: Amount,
+        direction: bool,
+        party0_order_amount: Amount,
+        party1_order_amount: Amount,
+    ) -> Self {
+        Self {
+            quote_mint,
+            base_mint,
+            quote_amount,
+            base_amount,
+            direction,
+            min_amount_order_index: party0_order_amount > party1_order_amount,
+        }
+    }
+
+    /// Serialize the match result to its wire format
+    ///
+    /// Match results are postcard encoded in contract calldata, so the layout
+    /// is the fields in declaration order:
+    /// - Each mint as a varint length followed by its little endian `u32`
+    ///   digits, each a varint
+    /// - Each amount as a varint
+    /// - `direction` then `min_amount_order_index` as one byte each
+    ///
+    /// Reordering or retyping fields changes the wire format
+    pub fn to_bytes(&self) -> Result<Vec<u8>, postcard::Error> {
+        postcard::to_allocvec(self)
+    }
+
+    /// Deserialize a match result from its wire format, see `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, postcard::Error> {
+        postcard::from_bytes(bytes)
+    }
+
     /// Get the send mint and amount given a side of the order
     pub fn send_mint_amount(&self, side: OrderSide) -> (Address, Amount) {
         match side {
@@ -76,6 +191,92 @@ impl MatchResult {
             OrderSide::Sell => (self.quote_mint.clone(), self.quote_amount),
         }
     }
+
+    /// Get the index of the party (0 or 1) whose order is fully filled by the
+    /// match
+    pub fn fully_filled_order_index(&self) -> usize {
+        self.min_amount_order_index as usize
+    }
+
+    /// Get the index of the party (0 or 1) whose order is only partially
+    /// filled by the match
+    pub fn partially_filled_order_index(&self) -> usize {
+        1 - self.fully_filled_order_index()
+    }
+
+    /// Whether the given party's order is fully filled by the match
+    pub fn is_fully_filled(&self, party: usize) -> bool {
+        party == self.fully_filled_order_index()
+    }
+
+    /// Check the mints of the match against their canonical ordering, swapping
+    /// the quote and base sides of the match if they are inverted
+    ///
+    /// `canonical_pair` maps the two mints of a match, in either order, to the
+    /// canonical `(quote, base)` ordering of the pair, or `None` if the pair
+    /// is unknown
+    ///
+    /// Returns `true` if the match was inverted and has been corrected
+    pub fn canonicalize<F>(&mut self, canonical_pair: F) -> Result<bool, MatchError>
+    where
+        F: FnOnce(&Address, &Address) -> Option<(Address, Address)>,
+    {
+        let (quote, base) =
+            canonical_pair(&self.quote_mint, &self.base_mint).ok_or(MatchError::UnknownPair)?;
+        if quote == self.quote_mint && base == self.base_mint {
+            return Ok(false);
+        }
+
+        if quote != self.base_mint || base != self.quote_mint {
+            return Err(MatchError::UnknownPair);
+        }
+
+        // Swap the sides of the match, party 0 now buys the side it previously
+        // sold under the opposite labels, so the direction flips as well
+        std::mem::swap(&mut self.quote_mint, &mut self.base_mint);
+        std::mem::swap(&mut self.quote_amount, &mut self.base_amount);
+        self.direction = !self.direction;
+        Ok(true)
+    }
+}
+
+/// Compute the volume weighted average price of a set of matches, i.e. the
+/// total quote amount exchanged divided by the total base amount exchanged
+///
+/// Returns `None` if the set is empty, if the matches do not all share the
+/// same pair and direction, or if the totals overflow or the base total is
+/// zero
+pub fn volume_weighted_price(matches: &[MatchResult]) -> Option<FixedPoint> {
+    let first = matches.first()?;
+    let mut total_quote: Amount = 0;
+    let mut total_base: Amount = 0;
+    for match_res in matches {
+        if match_res.quote_mint != first.quote_mint
+            || match_res.base_mint != first.base_mint
+            || match_res.direction != first.direction
+        {
+            return None;
+        }
+
+        total_quote = total_quote.checked_add(match_res.quote_amount)?;
+        total_base = total_base.checked_add(match_res.base_amount)?;
+    }
+
+    implied_price(total_quote, total_base)
+}
+
+/// Compute the price implied by a quote and base amount, in units of quote per
+/// base, rounding down
+///
+/// Returns `None` if the base amount is zero
+fn implied_price(quote_amount: Amount, base_amount: Amount) -> Option<FixedPoint> {
+    if base_amount == 0 {
+        return None;
+    }
+
+    let quote = FixedPoint::from(quote_amount);
+    let base = FixedPoint::from(base_amount);
+    Some(quote.floor_div(&base))
 }
 
 /// The indices that specify where settlement logic should modify the wallet
@@ -150,9 +351,62 @@ impl ExternalMatchResult {
         }
     }
 
+    /// Get the receive mint and amount of the external party, net of the fees
+    /// charged at the given rates
+    ///
+    /// Returns `None` if the fees exceed the amount received
+    pub fn external_party_net_receive(&self, fee_rates: &FeeTakeRate) -> Option<(Address, Amount)> {
+        let (mint, receive) = self.external_party_receive();
+        let fees = fee_rates.compute_fee_take(receive);
+        receive.checked_sub_fee(&fees).map(|net| (mint, net))
+    }
+
     /// Get the `OrderSide` for the internal party
     pub fn internal_party_side(&self) -> OrderSide {
-        if self.direction { OrderSide::Sell } else { OrderSide::Buy }
+        OrderSide::from_external_direction(self.direction)
+    }
+
+    /// Get the price at which the match executed, in units of quote per base
+    ///
+    /// The price is rounded down, so for a match produced by a
+    /// `BoundedMatchResult` it is at most the bounded match's price
+    ///
+    /// Panics if the base amount is zero, see `try_effective_price`
+    pub fn effective_price(&self) -> FixedPoint {
+        self.try_effective_price().expect("effective price of a match with zero base amount")
+    }
+
+    /// Get the price at which the match executed, in units of quote per base
+    ///
+    /// Returns `None` if the base amount is zero
+    pub fn try_effective_price(&self) -> Option<FixedPoint> {
+        implied_price(self.quote_amount, self.base_amount)
+    }
+
+    /// Check that the quote amount is within `tolerance` of the quote implied
+    /// by the given price (in quote per base) and the base amount
+    ///
+    /// The implied quote is rounded down, as when settling a bounded match.
+    /// Intended to sanity check matches decoded from untrusted calldata
+    pub fn validate_against_price(
+        &self,
+        price: FixedPoint,
+        tolerance: Amount,
+    ) -> Result<(), MatchConsistencyError> {
+        if self.base_amount == 0 {
+            return Err(MatchConsistencyError::ZeroBaseAmount);
+        }
+        if self.quote_amount == 0 {
+            return Err(MatchConsistencyError::ZeroQuoteAmount);
+        }
+
+        let expected = scalar_to_u128(&price.floor_mul_int(self.base_amount));
+        if self.quote_amount.abs_diff(expected) > tolerance {
+            let actual = self.quote_amount;
+            return Err(MatchConsistencyError::QuoteOutOfTolerance { expected, actual });
+        }
+
+        Ok(())
     }
 
     /// Get a mock `MatchResult` type from an `ExternalMatchResult`
@@ -222,10 +476,99 @@ pub struct BoundedMatchResult {
 }
 
 impl BoundedMatchResult {
+    /// Get the bounded match that remains after a partial fill of the given
+    /// base amount
+    ///
+    /// Both bounds shrink by the filled amount, with the minimum floored at
+    /// zero once the fill has satisfied it
+    pub fn after_fill(&self, filled_base: Amount) -> Result<BoundedMatchResult, MatchError> {
+        if filled_base > self.max_base_amount {
+            return Err(MatchError::FillExceedsMax);
+        }
+
+        Ok(BoundedMatchResult {
+            min_base_amount: self.min_base_amount.saturating_sub(filled_base),
+            max_base_amount: self.max_base_amount - filled_base,
+            ..self.clone()
+        })
+    }
+
+    /// Validate a fill of the given base amount against the bounds of the match
+    /// and the external party's slippage limits
+    ///
+    /// `min_receive` and `max_send` are denominated in the tokens the external
+    /// party receives and sends at this fill, respectively
+    pub fn validate_fill(
+        &self,
+        base_amount: Amount,
+        min_receive: Amount,
+        max_send: Amount,
+    ) -> Result<(), MatchBoundsError> {
+        if base_amount < self.min_base_amount {
+            return Err(MatchBoundsError::BelowMin);
+        }
+        if base_amount > self.max_base_amount {
+            return Err(MatchBoundsError::AboveMax);
+        }
+
+        let (_, receive) = self.external_party_receive(base_amount);
+        let (_, send) = self.external_party_send(base_amount);
+        if receive < min_receive || send > max_send {
+            return Err(MatchBoundsError::SlippageExceeded);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the match's price lies within the inclusive band `[lower,
+    /// upper]`, in units of quote per base
+    ///
+    /// Intended to check a bounded match against a trader's price protection
+    /// before submitting a fill
+    pub fn price_in_band(&self, lower: FixedPoint, upper: FixedPoint) -> bool {
+        self.price.is_within(lower, upper)
+    }
+
+    /// Get the range of base amounts the match allows, as `(min, max)`
+    pub fn base_amount_range(&self) -> (Amount, Amount) {
+        (self.min_base_amount, self.max_base_amount)
+    }
+
+    /// Get the base amount halfway between the bounds, rounded down
+    pub fn midpoint_base_amount(&self) -> Amount {
+        let (min, max) = self.base_amount_range();
+        let (lo, hi) = (Amount::min(min, max), Amount::max(min, max));
+        lo + (hi - lo) / 2
+    }
+
+    /// Get the quote amount at the minimum base amount
+    ///
+    /// The smaller of the quote amounts at the two bounds is returned, so that
+    /// this never exceeds `max_quote_amount`
+    pub fn min_quote_amount(&self) -> Amount {
+        let (min, max) = self.base_amount_range();
+        Amount::min(self.quote_amount(min), self.quote_amount(max))
+    }
+
+    /// Get the quote amount at the maximum base amount
+    ///
+    /// The larger of the quote amounts at the two bounds is returned, so that
+    /// this is never less than `min_quote_amount`
+    pub fn max_quote_amount(&self) -> Amount {
+        let (min, max) = self.base_amount_range();
+        Amount::max(self.quote_amount(min), self.quote_amount(max))
+    }
+
     /// Get the quote amount for a given base amount
     pub fn quote_amount(&self, base_amount: Amount) -> Amount {
+        self.quote_amount_with_rounding(base_amount, RoundingMode::Floor)
+    }
+
+    /// Get the quote amount for a given base amount, rounding the price-base
+    /// product to an integer in the given mode
+    pub fn quote_amount_with_rounding(&self, base_amount: Amount, mode: RoundingMode) -> Amount {
         let quote_amount_fp = self.price * Scalar::from(base_amount);
-        scalar_to_u128(&quote_amount_fp.floor())
+        scalar_to_u128(&quote_amount_fp.round(mode))
     }
 
     /// Get the receive mint and amount of the external party at a given trade
@@ -239,6 +582,18 @@ impl BoundedMatchResult {
         }
     }
 
+    /// Get the receive mint and amount of the external party at a given trade
+    /// size, net of the fees charged at the given rates
+    ///
+    /// Returns `None` if the fees exceed the amount received
+    pub fn external_party_net_receive(
+        &self,
+        base_amount: Amount,
+        fee_rates: &FeeTakeRate,
+    ) -> Option<(Address, Amount)> {
+        self.to_external_match_result(base_amount).external_party_net_receive(fee_rates)
+    }
+
     /// Get the send mint and amount of the external party
     pub fn external_party_send(&self, base_amount: Amount) -> (Address, Amount) {
         // If the direction is true, the external party sells the quote
@@ -249,6 +604,33 @@ impl BoundedMatchResult {
         }
     }
 
+    /// Get the largest amount the external party may send at any fill within
+    /// the bounds, along with the send mint
+    ///
+    /// The external party's send grows with the base amount in both
+    /// directions: the quote sent on a buy and the base sent on a sell are both
+    /// largest at `max_base_amount`. The larger of the sends at the two bounds
+    /// is returned, so this holds even if the bounds are inverted
+    pub fn max_external_send(&self) -> (Address, Amount) {
+        let (min, max) = self.base_amount_range();
+        let (mint, send_at_min) = self.external_party_send(min);
+        let (_, send_at_max) = self.external_party_send(max);
+        (mint, Amount::max(send_at_min, send_at_max))
+    }
+
+    /// Get the smallest amount the external party may receive at any fill
+    /// within the bounds, along with the receive mint
+    ///
+    /// As with `max_external_send`, the receive is smallest at
+    /// `min_base_amount` in both directions, and the smaller of the receives
+    /// at the two bounds is returned
+    pub fn min_external_receive(&self) -> (Address, Amount) {
+        let (min, max) = self.base_amount_range();
+        let (mint, receive_at_min) = self.external_party_receive(min);
+        let (_, receive_at_max) = self.external_party_receive(max);
+        (mint, Amount::min(receive_at_min, receive_at_max))
+    }
+
     /// Get an external match result given a base amount swapped
     pub fn to_external_match_result(&self, base_amount: Amount) -> ExternalMatchResult {
         ExternalMatchResult {
@@ -260,3 +642,451 @@ impl BoundedMatchResult {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use num_bigint::BigUint;
+
+    use crate::{
+        Address, Amount,
+        fees::FeeTakeRate,
+        fixed_point::{FixedPoint, RoundingMode},
+        order::OrderSide,
+    };
+
+    use super::{
+        BoundedMatchResult, ExternalMatchResult, MatchBoundsError, MatchConsistencyError,
+        MatchError, MatchResult, volume_weighted_price,
+    };
+
+    /// The canonical (quote, base) pair used in tests
+    fn canonical_pair() -> (Address, Address) {
+        (BigUint::from(1u8), BigUint::from(2u8))
+    }
+
+    /// An ordering function which only knows the test pair
+    fn ordering(a: &Address, b: &Address) -> Option<(Address, Address)> {
+        let (quote, base) = canonical_pair();
+        let known = (*a == quote && *b == base) || (*a == base && *b == quote);
+        known.then_some((quote, base))
+    }
+
+    /// Build a match result on the canonical pair
+    fn canonical_match() -> MatchResult {
+        let (quote_mint, base_mint) = canonical_pair();
+        MatchResult {
+            quote_mint,
+            base_mint,
+            quote_amount: 200,
+            base_amount: 10,
+            direction: true,
+            min_amount_order_index: false,
+        }
+    }
+
+    /// Tests that a canonically ordered match is left unchanged
+    #[test]
+    fn test_canonicalize_noop() {
+        let mut match_res = canonical_match();
+        let corrected = match_res.canonicalize(ordering).unwrap();
+
+        assert!(!corrected);
+        assert_eq!(match_res, canonical_match());
+    }
+
+    /// Tests that a swapped match is corrected and its direction flipped
+    #[test]
+    fn test_canonicalize_swapped() {
+        let expected = canonical_match();
+        let mut match_res = MatchResult {
+            quote_mint: expected.base_mint.clone(),
+            base_mint: expected.quote_mint.clone(),
+            quote_amount: expected.base_amount,
+            base_amount: expected.quote_amount,
+            direction: !expected.direction,
+            ..expected.clone()
+        };
+
+        // Party 0 should send and receive the same tokens before and after
+        let send_before = match_res.send_mint_amount(OrderSide::Buy);
+        let corrected = match_res.canonicalize(ordering).unwrap();
+        let send_after = match_res.send_mint_amount(OrderSide::Sell);
+
+        assert!(corrected);
+        assert_eq!(match_res, expected);
+        assert_eq!(send_before, send_after);
+    }
+
+    /// Tests that a match on an unknown pair is rejected
+    #[test]
+    fn test_canonicalize_unknown_pair() {
+        let mut match_res = canonical_match();
+        match_res.base_mint = BigUint::from(3u8);
+
+        let res = match_res.canonicalize(ordering);
+        assert_eq!(res, Err(MatchError::UnknownPair));
+    }
+
+    /// Tests that the wire format of a match result matches a fixed layout, so
+    /// that changes to the format are caught
+    #[test]
+    fn match_result_wire_roundtrip() {
+        let match_res = MatchResult {
+            quote_mint: BigUint::from((1u64 << 32) | 5),
+            base_mint: BigUint::from(2u8),
+            quote_amount: 300,
+            base_amount: 10,
+            direction: true,
+            min_amount_order_index: true,
+        };
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            0x02, 0x05, 0x01, // quote mint, two digits
+            0x01, 0x02,       // base mint, one digit
+            0xac, 0x02,       // quote amount
+            0x0a,             // base amount
+            0x01,             // direction
+            0x01,             // min amount order index
+        ];
+
+        let bytes = match_res.to_bytes().unwrap();
+        assert_eq!(bytes, expected, "MatchResult wire format changed");
+        assert_eq!(MatchResult::from_bytes(&bytes).unwrap(), match_res);
+    }
+
+    /// Tests the fill index accessors
+    #[test]
+    fn test_filled_order_indices() {
+        let mut match_res = canonical_match();
+        assert_eq!(match_res.fully_filled_order_index(), 0);
+        assert_eq!(match_res.partially_filled_order_index(), 1);
+        assert!(match_res.is_fully_filled(0));
+        assert!(!match_res.is_fully_filled(1));
+
+        match_res.min_amount_order_index = true;
+        assert_eq!(match_res.fully_filled_order_index(), 1);
+        assert_eq!(match_res.partially_filled_order_index(), 0);
+        assert!(match_res.is_fully_filled(1));
+    }
+
+    /// Tests deriving the fully filled order from the two order amounts
+    #[test]
+    fn test_from_match_min_amount_index() {
+        let (quote_mint, base_mint) = canonical_pair();
+        let build = |amount0, amount1| {
+            MatchResult::from_match(
+                quote_mint.clone(),
+                base_mint.clone(),
+                200,  // quote_amount
+                10,   // base_amount
+                true, // direction
+                amount0,
+                amount1,
+            )
+        };
+
+        let match_res = build(10, 15);
+        assert_eq!(match_res, canonical_match());
+        assert_eq!(match_res.fully_filled_order_index(), 0);
+
+        let match_res = build(15, 10);
+        assert_eq!(match_res.fully_filled_order_index(), 1);
+
+        // On a tie both orders are filled, and party 0's order is the minimum
+        let match_res = build(10, 10);
+        assert_eq!(match_res.fully_filled_order_index(), 0);
+    }
+
+    /// Tests the volume weighted price across matches on the same pair
+    #[test]
+    fn test_volume_weighted_price() {
+        let first = canonical_match();
+        let second = MatchResult { quote_amount: 1000, base_amount: 40, ..first.clone() };
+
+        // (200 + 1000) / (10 + 40) = 24
+        let price = volume_weighted_price(&[first, second]).unwrap();
+        assert_eq!(price.to_f64(), 24.);
+    }
+
+    /// Tests that the volume weighted price rejects empty and inconsistent sets
+    #[test]
+    fn test_volume_weighted_price_invalid() {
+        let first = canonical_match();
+        assert!(volume_weighted_price(&[]).is_none());
+
+        // A match on a different pair
+        let other_pair = MatchResult { base_mint: BigUint::from(3u8), ..first.clone() };
+        assert!(volume_weighted_price(&[first.clone(), other_pair]).is_none());
+
+        // A match in the opposite direction
+        let other_direction = MatchResult { direction: !first.direction, ..first.clone() };
+        assert!(volume_weighted_price(&[first.clone(), other_direction]).is_none());
+
+        // Overflowing totals
+        let large = MatchResult { quote_amount: u128::MAX, ..first.clone() };
+        assert!(volume_weighted_price(&[first, large]).is_none());
+    }
+
+    /// Tests that the effective price of a bounded match's fill is the bounded
+    /// price up to floor rounding
+    #[test]
+    fn test_effective_price_bounded_fill() {
+        let price = FixedPoint::from_f64_round_down(1234.5678);
+        let bounded = BoundedMatchResult { price, max_base_amount: 1000, ..Default::default() };
+
+        let base_amount = 999;
+        let effective = bounded.to_external_match_result(base_amount).effective_price();
+        let diff = price.to_f64() - effective.to_f64();
+        assert!(diff >= 0.);
+        assert!(diff < 1. / base_amount as f64);
+    }
+
+    /// Tests the external party's receive amount net of fees
+    #[test]
+    fn test_external_party_net_receive() {
+        let (quote_mint, base_mint) = canonical_pair();
+        let match_res = ExternalMatchResult {
+            quote_mint,
+            base_mint: base_mint.clone(),
+            quote_amount: 2000,
+            base_amount: 1000,
+            direction: true,
+        };
+
+        // 1% relayer fee and 0.2% protocol fee on the 1000 base received
+        let fee_rates = FeeTakeRate::new(
+            FixedPoint::from_f64_round_down(0.01),
+            FixedPoint::from_f64_round_down(0.002),
+        );
+        let (mint, amount) = match_res.external_party_net_receive(&fee_rates).unwrap();
+        let fees = fee_rates.compute_fee_take(1000).total();
+
+        assert_eq!(mint, base_mint);
+        assert_eq!(amount, 1000 - fees);
+        assert!(amount < 1000);
+
+        // Fees exceeding the receive amount are rejected rather than wrapping
+        let fee_rates = FeeTakeRate::new(FixedPoint::from_integer(1), FixedPoint::from_integer(1));
+        assert!(match_res.external_party_net_receive(&fee_rates).is_none());
+    }
+
+    /// Tests checking a bounded match's price against a price band
+    #[test]
+    fn test_price_in_band() {
+        let bounded_match =
+            BoundedMatchResult { price: FixedPoint::from_integer(2), ..Default::default() };
+
+        let one = FixedPoint::from_integer(1);
+        let two = FixedPoint::from_integer(2);
+        let three = FixedPoint::from_integer(3);
+        assert!(bounded_match.price_in_band(one, three));
+        assert!(bounded_match.price_in_band(two, two));
+        assert!(!bounded_match.price_in_band(FixedPoint::from_f64_round_down(2.01), three));
+        assert!(!bounded_match.price_in_band(one, FixedPoint::from_f64_round_down(1.99)));
+    }
+
+    /// Tests the external party's receive amount net of fees at a fill of a
+    /// bounded match
+    #[test]
+    fn test_bounded_external_party_net_receive() {
+        let (quote_mint, base_mint) = canonical_pair();
+        let bounded_match = BoundedMatchResult {
+            quote_mint,
+            base_mint,
+            price: FixedPoint::from_integer(2),
+            min_base_amount: 0,
+            max_base_amount: 1000,
+            direction: false,
+        };
+
+        let fee_rates = FeeTakeRate::new(
+            FixedPoint::from_f64_round_down(0.01),
+            FixedPoint::from_f64_round_down(0.002),
+        );
+        let expected =
+            bounded_match.to_external_match_result(500).external_party_net_receive(&fee_rates);
+        assert_eq!(bounded_match.external_party_net_receive(500, &fee_rates), expected);
+        assert!(expected.is_some());
+
+        let fee_rates = FeeTakeRate::new(FixedPoint::from_integer(2), FixedPoint::from_integer(0));
+        assert!(bounded_match.external_party_net_receive(500, &fee_rates).is_none());
+    }
+
+    /// Tests the external party's worst case send and receive across the
+    /// bounds in both directions
+    #[test]
+    fn test_bounded_worst_case_external_amounts() {
+        let (quote_mint, base_mint) = canonical_pair();
+        let mut bounded = BoundedMatchResult {
+            quote_mint: quote_mint.clone(),
+            base_mint: base_mint.clone(),
+            price: FixedPoint::from_integer(2),
+            min_base_amount: 10,
+            max_base_amount: 100,
+            direction: true,
+        };
+
+        // The external party buys the base, sending quote
+        assert_eq!(bounded.max_external_send(), (quote_mint.clone(), 200));
+        assert_eq!(bounded.min_external_receive(), (base_mint.clone(), 10));
+
+        // The external party sells the base, receiving quote
+        bounded.direction = false;
+        assert_eq!(bounded.max_external_send(), (base_mint, 100));
+        assert_eq!(bounded.min_external_receive(), (quote_mint, 20));
+    }
+
+    /// Tests that the effective price of a match with no base amount is `None`
+    #[test]
+    fn test_effective_price_zero_base() {
+        let match_res = ExternalMatchResult { quote_amount: 100, ..Default::default() };
+        assert!(match_res.try_effective_price().is_none());
+    }
+
+    /// Tests validating fills of a bounded match against its bounds and the
+    /// external party's slippage limits
+    #[test]
+    fn test_bounded_match_validate_fill() {
+        // The external party buys the base at a price of 2
+        let bounded = BoundedMatchResult {
+            price: FixedPoint::from_integer(2),
+            min_base_amount: 10,
+            max_base_amount: 100,
+            direction: true,
+            ..Default::default()
+        };
+
+        assert_eq!(bounded.validate_fill(50, 50, 100), Ok(()));
+        assert_eq!(bounded.validate_fill(9, 0, u128::MAX), Err(MatchBoundsError::BelowMin));
+        assert_eq!(bounded.validate_fill(101, 0, u128::MAX), Err(MatchBoundsError::AboveMax));
+
+        // Receiving too little or sending too much
+        assert_eq!(bounded.validate_fill(50, 51, 100), Err(MatchBoundsError::SlippageExceeded));
+        assert_eq!(bounded.validate_fill(50, 50, 99), Err(MatchBoundsError::SlippageExceeded));
+    }
+
+    /// Tests the remaining bounds of a bounded match after partial fills
+    #[test]
+    fn test_bounded_match_after_fill() {
+        let bounded =
+            BoundedMatchResult { min_base_amount: 10, max_base_amount: 100, ..Default::default() };
+
+        // A fill below the minimum leaves the remainder of the minimum
+        let remaining = bounded.after_fill(4).unwrap();
+        assert_eq!(remaining.min_base_amount, 6);
+        assert_eq!(remaining.max_base_amount, 96);
+
+        // A fill past the minimum floors it at zero
+        let remaining = bounded.after_fill(40).unwrap();
+        assert_eq!(remaining.min_base_amount, 0);
+        assert_eq!(remaining.max_base_amount, 60);
+
+        // A complete fill leaves nothing, an overfill errors
+        assert_eq!(bounded.after_fill(100).unwrap().max_base_amount, 0);
+        assert_eq!(bounded.after_fill(101), Err(MatchError::FillExceedsMax));
+    }
+
+    /// Tests computing the quote amount of a bounded match in each rounding
+    /// mode
+    #[test]
+    fn test_quote_amount_rounding() {
+        let price = FixedPoint::from_f64_round_down(1.25);
+        let bounded = BoundedMatchResult { price, max_base_amount: 1000, ..Default::default() };
+        let quote = |base, mode| bounded.quote_amount_with_rounding(base, mode);
+
+        // An exact product is unchanged in every mode
+        for mode in [RoundingMode::Floor, RoundingMode::Ceil, RoundingMode::Nearest] {
+            assert_eq!(quote(4, mode), 5);
+        }
+
+        // 1.25 * 3 = 3.75
+        assert_eq!(quote(3, RoundingMode::Floor), 3);
+        assert_eq!(quote(3, RoundingMode::Ceil), 4);
+        assert_eq!(quote(3, RoundingMode::Nearest), 4);
+
+        // 1.25 * 1 = 1.25
+        assert_eq!(quote(1, RoundingMode::Ceil), 2);
+        assert_eq!(quote(1, RoundingMode::Nearest), 1);
+
+        // 1.25 * 2 = 2.5 rounds half up
+        assert_eq!(quote(2, RoundingMode::Nearest), 3);
+
+        // The default quote amount floors
+        assert_eq!(bounded.quote_amount(3), 3);
+    }
+
+    /// Tests the size accessors of a bounded match
+    #[test]
+    fn test_bounded_match_size() {
+        let price = FixedPoint::from_f64_round_down(2.5);
+        let bounded = BoundedMatchResult {
+            price,
+            min_base_amount: 11,
+            max_base_amount: 100,
+            ..Default::default()
+        };
+
+        assert_eq!(bounded.base_amount_range(), (11, 100));
+        assert_eq!(bounded.midpoint_base_amount(), 55);
+        assert_eq!(bounded.min_quote_amount(), 27); // floor(27.5)
+        assert_eq!(bounded.max_quote_amount(), 250);
+
+        // Inverted bounds keep the quote range ordered
+        let inverted = BoundedMatchResult { min_base_amount: 100, max_base_amount: 11, ..bounded };
+        assert_eq!(inverted.midpoint_base_amount(), 55);
+        assert_eq!(inverted.min_quote_amount(), 27);
+        assert_eq!(inverted.max_quote_amount(), 250);
+    }
+
+    /// Tests converting between an external match direction and the internal
+    /// party's side
+    #[test]
+    fn test_external_direction_side() {
+        let (quote_mint, base_mint) = canonical_pair();
+        for side in [OrderSide::Buy, OrderSide::Sell] {
+            let direction = side.match_direction();
+            assert_eq!(OrderSide::from_external_direction(direction), side);
+
+            // The external party receives the base when the internal party sells
+            let res = ExternalMatchResult {
+                quote_mint: quote_mint.clone(),
+                base_mint: base_mint.clone(),
+                quote_amount: 200,
+                base_amount: 10,
+                direction,
+            };
+            assert_eq!(res.internal_party_side(), side);
+            assert_eq!(res.external_party_receive().0 == base_mint, side.is_sell());
+        }
+    }
+
+    /// Tests validating an external match's amounts against a price
+    #[test]
+    fn test_validate_against_price() {
+        let (quote_mint, base_mint) = canonical_pair();
+        let price = FixedPoint::from_f64_round_down(2.5);
+        let res = ExternalMatchResult {
+            quote_mint,
+            base_mint,
+            quote_amount: 250,
+            base_amount: 100,
+            direction: true,
+        };
+        assert_eq!(res.validate_against_price(price, 0), Ok(()));
+
+        // The quote may deviate from the implied quote up to the tolerance
+        let off_by_two = ExternalMatchResult { quote_amount: 252, ..res.clone() };
+        assert_eq!(off_by_two.validate_against_price(price, 2), Ok(()));
+        let expected = MatchConsistencyError::QuoteOutOfTolerance { expected: 250, actual: 252 };
+        assert_eq!(off_by_two.validate_against_price(price, 1), Err(expected));
+
+        // Zero amounts are rejected regardless of the tolerance
+        let zero_base = ExternalMatchResult { base_amount: 0, ..res.clone() };
+        let err = zero_base.validate_against_price(price, Amount::MAX);
+        assert_eq!(err, Err(MatchConsistencyError::ZeroBaseAmount));
+        let zero_quote = ExternalMatchResult { quote_amount: 0, ..res };
+        let err = zero_quote.validate_against_price(price, Amount::MAX);
+        assert_eq!(err, Err(MatchConsistencyError::ZeroQuoteAmount));
+    }
+}
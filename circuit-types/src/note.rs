@@ -12,8 +12,13 @@ use crate::{Address, Amount, balance::Balance, elgamal::EncryptionKey};
 
 #[cfg(feature = "proof-system-types")]
 use {
-    crate::traits::{BaseType, CircuitBaseType, CircuitVarType},
+    crate::{
+        elgamal::ElGamalCiphertext,
+        native_helpers::elgamal_encrypt_with_randomness,
+        traits::{BaseType, CircuitBaseType, CircuitVarType},
+    },
     circuit_macros::circuit_type,
+    constants::EmbeddedScalarField,
     mpc_relation::{Variable, traits::Circuit},
 };
 
@@ -68,8 +73,52 @@ impl Note {
         [biguint_to_scalar(&self.mint), Scalar::from(self.amount), self.blinder]
     }
 
+    /// Check that the given ciphertext is an encryption of the note under
+    /// `key` with the given randomness
+    #[cfg(feature = "proof-system-types")]
+    pub fn verify_ciphertext(
+        &self,
+        ciphertext: &ElGamalCiphertext<NOTE_CIPHERTEXT_SIZE>,
+        key: &EncryptionKey,
+        randomness: EmbeddedScalarField,
+    ) -> bool {
+        let expected: ElGamalCiphertext<NOTE_CIPHERTEXT_SIZE> =
+            elgamal_encrypt_with_randomness(&self.plaintext_elements(), key, randomness);
+        expected.ephemeral_key == ciphertext.ephemeral_key
+            && expected.ciphertext == ciphertext.ciphertext
+    }
+
     /// Get the balance associated with the note
     pub fn as_balance(&self) -> Balance {
         Balance::new_from_mint_and_amount(self.mint.clone(), self.amount)
     }
 }
+
+#[cfg(all(test, feature = "proof-system-types"))]
+mod test {
+    use num_bigint::BigUint;
+    use rand::thread_rng;
+
+    use super::Note;
+    use crate::{elgamal::DecryptionKey, native_helpers::encrypt_note};
+
+    /// Tests verifying a note's ciphertext against the note
+    #[test]
+    fn test_verify_ciphertext() {
+        let mut rng = thread_rng();
+        let (_, key) = DecryptionKey::random_pair(&mut rng);
+        let (_, other_key) = DecryptionKey::random_pair(&mut rng);
+        let note = Note::new(BigUint::from(1u8), 100, key);
+
+        let (ciphertext, randomness) = encrypt_note(&note, &key);
+        assert!(note.verify_ciphertext(&ciphertext, &key, randomness));
+
+        // A ciphertext under a different key should not verify
+        assert!(!note.verify_ciphertext(&ciphertext, &other_key, randomness));
+
+        // A ciphertext of a different note should not verify
+        let mut other_note = note.clone();
+        other_note.amount += 1;
+        assert!(!other_note.verify_ciphertext(&ciphertext, &key, randomness));
+    }
+}
@@ -160,6 +160,16 @@ impl OrderSide {
             OrderSide::Sell => true,
         }
     }
+
+    /// Return the side of the internal party for the given external match
+    /// direction
+    ///
+    /// The direction of an external match is `true` when the external party
+    /// buys the base, i.e. when the internal party sells. The inverse of
+    /// [`OrderSide::match_direction`] for the internal party
+    pub fn from_external_direction(direction: bool) -> OrderSide {
+        if direction { OrderSide::Sell } else { OrderSide::Buy }
+    }
 }
 
 #[cfg(feature = "proof-system-types")]
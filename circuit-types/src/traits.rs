@@ -98,6 +98,15 @@ pub trait BaseType: Clone {
     /// Convert the base type to its serialized scalar representation in the
     /// circuit
     fn to_scalars(&self) -> Vec<Scalar>;
+    /// Iterate over the serialized scalar representation in the order of
+    /// `to_scalars`
+    ///
+    /// Types derived with `circuit_type` iterate over their fields without
+    /// collecting them into a vector, and implement `to_scalars` by
+    /// collecting this iterator
+    fn scalars_iter(&self) -> impl Iterator<Item = Scalar> + '_ {
+        self.to_scalars().into_iter()
+    }
     /// Convert from a serialized scalar representation to the base type
     fn from_scalars<I: Iterator<Item = Scalar>>(i: &mut I) -> Self;
 
@@ -354,6 +363,10 @@ impl BaseType for Scalar {
         vec![*self]
     }
 
+    fn scalars_iter(&self) -> impl Iterator<Item = Scalar> + '_ {
+        iter::once(*self)
+    }
+
     fn from_scalars<I: Iterator<Item = Scalar>>(i: &mut I) -> Self {
         i.next().unwrap()
     }
@@ -449,7 +462,11 @@ impl<const N: usize, T: BaseType> BaseType for [T; N] {
     const NUM_SCALARS: usize = N * T::NUM_SCALARS;
 
     fn to_scalars(&self) -> Vec<Scalar> {
-        self.iter().flat_map(|x| x.to_scalars()).collect()
+        self.scalars_iter().collect()
+    }
+
+    fn scalars_iter(&self) -> impl Iterator<Item = Scalar> + '_ {
+        self.iter().flat_map(|x| x.scalars_iter())
     }
 
     fn from_scalars<I: Iterator<Item = Scalar>>(i: &mut I) -> Self {
@@ -70,6 +70,18 @@ impl Default for ExternalTransferDirection {
     }
 }
 
+impl ExternalTransferDirection {
+    /// Build a direction from a withdrawal flag, as encoded in the contracts
+    pub fn from_is_withdrawal(is_withdrawal: bool) -> Self {
+        if is_withdrawal { Self::Withdrawal } else { Self::Deposit }
+    }
+
+    /// Whether or not the direction is a withdrawal
+    pub fn is_withdrawal(&self) -> bool {
+        *self == Self::Withdrawal
+    }
+}
+
 impl From<ExternalTransferDirection> for Scalar {
     fn from(dir: ExternalTransferDirection) -> Self {
         Scalar::from(dir as u8)
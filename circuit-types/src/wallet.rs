@@ -38,6 +38,15 @@ pub type NoteCommitment = Scalar;
 /// Nullifier type alias for readability
 pub type Nullifier = Scalar;
 
+/// The version tag prefixed to the canonical byte encoding of wallet shares
+///
+/// Must be bumped whenever the canonical encoding changes
+#[cfg(feature = "proof-system-types")]
+pub const CANONICAL_SHARE_ENCODING_VERSION: u8 = 1;
+/// The number of bytes each scalar occupies in the canonical share encoding
+#[cfg(feature = "proof-system-types")]
+const CANONICAL_SCALAR_BYTES: usize = 32;
+
 // --------------------
 // | Wallet Base Type |
 // --------------------
@@ -116,6 +125,33 @@ impl<const MAX_BALANCES: usize, const MAX_ORDERS: usize> WalletShare<MAX_BALANCE
 
         unblinded
     }
+
+    /// Encode the shares in a canonical byte form suitable for hashing and
+    /// comparison across versions
+    ///
+    /// The encoding is:
+    /// - one byte holding `CANONICAL_SHARE_ENCODING_VERSION`
+    /// - the number of scalars as a big-endian `u32`
+    /// - each scalar as 32 big-endian bytes, in the order: balances, orders,
+    ///   keys, max match fee, managing cluster key, blinder
+    ///
+    /// Two share sets encode to equal bytes if and only if they are equal
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let n_scalars = Self::NUM_SCALARS;
+        let mut bytes = Vec::with_capacity(1 + 4 + n_scalars * CANONICAL_SCALAR_BYTES);
+        bytes.push(CANONICAL_SHARE_ENCODING_VERSION);
+        bytes.extend_from_slice(&(n_scalars as u32).to_be_bytes());
+
+        for scalar in self.scalars_iter() {
+            // Left pad each scalar to a fixed width
+            let scalar_bytes = scalar.to_bytes_be();
+            let mut padded = [0u8; CANONICAL_SCALAR_BYTES];
+            padded[CANONICAL_SCALAR_BYTES - scalar_bytes.len()..].copy_from_slice(&scalar_bytes);
+            bytes.extend_from_slice(&padded);
+        }
+
+        bytes
+    }
 }
 
 #[cfg(feature = "proof-system-types")]
@@ -150,3 +186,61 @@ impl<const MAX_BALANCES: usize, const MAX_ORDERS: usize> WalletShareVar<MAX_BALA
         unblinded
     }
 }
+
+#[cfg(all(test, feature = "proof-system-types"))]
+mod test {
+    use std::iter;
+
+    use constants::Scalar;
+
+    use crate::{SizedWalletShare, traits::BaseType};
+
+    use super::CANONICAL_SHARE_ENCODING_VERSION;
+
+    /// Build a set of wallet shares with every scalar set to the given value
+    fn shares_of(value: u64) -> SizedWalletShare {
+        SizedWalletShare::from_scalars(&mut iter::repeat(Scalar::from(value)))
+    }
+
+    /// Tests the layout of the canonical share encoding
+    #[test]
+    fn test_canonical_bytes_layout() {
+        let shares = shares_of(1);
+        let bytes = shares.to_canonical_bytes();
+
+        let n = SizedWalletShare::NUM_SCALARS;
+        assert_eq!(bytes.len(), 1 + 4 + n * 32);
+        assert_eq!(bytes[0], CANONICAL_SHARE_ENCODING_VERSION);
+        assert_eq!(bytes[1..5], (n as u32).to_be_bytes());
+
+        // Each scalar is the value one, left padded to 32 bytes
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert!(bytes[5..].chunks(32).all(|chunk| chunk == one));
+    }
+
+    /// Tests that the encoding distinguishes unequal shares
+    #[test]
+    fn test_canonical_bytes_equality() {
+        assert_eq!(shares_of(1).to_canonical_bytes(), shares_of(1).to_canonical_bytes());
+        assert_ne!(shares_of(1).to_canonical_bytes(), shares_of(2).to_canonical_bytes());
+
+        let mut shares = shares_of(1);
+        shares.blinder = Scalar::from(2u8);
+        assert_ne!(shares.to_canonical_bytes(), shares_of(1).to_canonical_bytes());
+    }
+
+    /// Tests that iterating over the share's scalars follows the layout used
+    /// by `from_scalars` and `to_scalars`
+    #[test]
+    fn test_scalars_iter() {
+        // Give each scalar a distinct value so that the order is checked
+        let shares = SizedWalletShare::from_scalars(&mut (0u64..).map(Scalar::from));
+        let scalars = shares.scalars_iter().collect::<Vec<_>>();
+
+        let n = SizedWalletShare::NUM_SCALARS as u64;
+        let expected = (0..n).map(Scalar::from).collect::<Vec<_>>();
+        assert_eq!(scalars, expected);
+        assert_eq!(scalars, shares.to_scalars());
+    }
+}
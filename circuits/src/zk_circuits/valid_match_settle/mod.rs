@@ -12,7 +12,7 @@ use circuit_types::{
     balance::Balance,
     fees::FeeTake,
     fixed_point::FixedPoint,
-    r#match::{MatchResult, OrderSettlementIndices},
+    r#match::{MatchError, MatchResult, OrderSettlementIndices},
     order::Order,
     traits::{
         BaseType, CircuitBaseType, CircuitVarType, MpcBaseType, MpcType, MultiProverCircuit,
@@ -266,6 +266,65 @@ pub type SizedAuthenticatedMatchSettleStatement =
 pub type SizedValidMatchSettleWithCommitmentsStatement =
     ValidMatchSettleWithCommitmentsStatement<MAX_BALANCES, MAX_ORDERS>;
 
+impl<const MAX_BALANCES: usize, const MAX_ORDERS: usize>
+    ValidMatchSettleStatement<MAX_BALANCES, MAX_ORDERS>
+{
+    /// Check that both parties' settlement indices address a valid send
+    /// balance, receive balance, and order in their modified shares
+    ///
+    /// The modified shares are blinded, so the mints they hold cannot be
+    /// compared across parties directly. Instead this checks the structure
+    /// that a valid match implies: each party sends from one balance and
+    /// receives into a different one, and every index falls within the shares
+    /// that settlement modifies
+    pub fn verify_index_consistency(&self) -> Result<(), MatchError> {
+        Self::verify_party_indices(0, &self.party0_indices, &self.party0_modified_shares)?;
+        Self::verify_party_indices(1, &self.party1_indices, &self.party1_modified_shares)
+    }
+
+    /// Check a single party's settlement indices against its modified shares
+    fn verify_party_indices(
+        party_id: u64,
+        indices: &OrderSettlementIndices,
+        shares: &WalletShare<MAX_BALANCES, MAX_ORDERS>,
+    ) -> Result<(), MatchError> {
+        let n_balances = shares.balances.len();
+        let n_orders = shares.orders.len();
+        let err =
+            |msg: String| MatchError::InvalidSettlementIndices(format!("party {party_id}: {msg}"));
+
+        if indices.balance_send >= n_balances {
+            return Err(err(format!(
+                "send balance index {} out of bounds for {n_balances} balances",
+                indices.balance_send
+            )));
+        }
+
+        if indices.balance_receive >= n_balances {
+            return Err(err(format!(
+                "receive balance index {} out of bounds for {n_balances} balances",
+                indices.balance_receive
+            )));
+        }
+
+        if indices.order >= n_orders {
+            return Err(err(format!(
+                "order index {} out of bounds for {n_orders} orders",
+                indices.order
+            )));
+        }
+
+        if indices.balance_send == indices.balance_receive {
+            return Err(err(format!(
+                "send and receive balances share index {}",
+                indices.balance_send
+            )));
+        }
+
+        Ok(())
+    }
+}
+
 // ---------------------
 // | Prove Verify Flow |
 // ---------------------
@@ -587,6 +646,7 @@ mod tests {
     use circuit_types::{
         AMOUNT_BITS,
         fixed_point::FixedPoint,
+        r#match::MatchError,
         traits::{MpcBaseType, SingleProverCircuit},
     };
 
@@ -1185,4 +1245,45 @@ mod tests {
 
         assert!(!check_witness_statement_with_commitments(&witness, &statement));
     }
+
+    // ---------------------------
+    // | Index Consistency Tests |
+    // ---------------------------
+
+    /// Tests that a valid statement passes the index consistency check
+    #[test]
+    fn test_index_consistency__valid() {
+        let (_, statement) = dummy_witness_and_statement::<MAX_BALANCES, MAX_ORDERS>();
+        assert!(statement.verify_index_consistency().is_ok());
+    }
+
+    /// Tests a statement in which a party sends and receives the same balance
+    #[test]
+    fn test_index_consistency__same_send_receive() {
+        let (_, mut statement) = dummy_witness_and_statement::<MAX_BALANCES, MAX_ORDERS>();
+        rand_branch!(
+            statement.party0_indices.balance_receive = statement.party0_indices.balance_send,
+            statement.party1_indices.balance_receive = statement.party1_indices.balance_send
+        );
+
+        assert!(matches!(
+            statement.verify_index_consistency(),
+            Err(MatchError::InvalidSettlementIndices(_))
+        ));
+    }
+
+    /// Tests a statement in which a party's indices exceed its shares
+    #[test]
+    fn test_index_consistency__out_of_bounds() {
+        let (_, mut statement) = dummy_witness_and_statement::<MAX_BALANCES, MAX_ORDERS>();
+        rand_branch!(
+            statement.party0_indices.balance_send = MAX_BALANCES,
+            statement.party1_indices.order = MAX_ORDERS
+        );
+
+        assert!(matches!(
+            statement.verify_index_consistency(),
+            Err(MatchError::InvalidSettlementIndices(_))
+        ));
+    }
 }
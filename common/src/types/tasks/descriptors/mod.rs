@@ -130,6 +130,8 @@ pub enum TaskDescriptor {
     RefreshWallet(RefreshWalletTaskDescriptor),
     /// The task descriptor for the `PayProtocolFee` task
     OfflineFee(PayOfflineFeeTaskDescriptor),
+    /// The task descriptor for the `PayAllOfflineFees` task
+    AllOfflineFees(PayAllOfflineFeesTaskDescriptor),
     /// The task descriptor for the `PayRelayerFee` task
     RelayerFee(PayRelayerFeeTaskDescriptor),
     /// The task descriptor for the `RedeemFee` task
@@ -158,6 +160,7 @@ impl TaskDescriptor {
             TaskDescriptor::LookupWallet(task) => task.wallet_id,
             TaskDescriptor::RefreshWallet(task) => task.wallet_id,
             TaskDescriptor::OfflineFee(task) => task.wallet_id,
+            TaskDescriptor::AllOfflineFees(task) => task.wallet_id,
             TaskDescriptor::RelayerFee(task) => task.wallet_id,
             TaskDescriptor::RedeemFee(task) => task.wallet_id,
             TaskDescriptor::SettleMatch(_) => {
@@ -181,6 +184,7 @@ impl TaskDescriptor {
             TaskDescriptor::LookupWallet(task) => vec![task.wallet_id],
             TaskDescriptor::RefreshWallet(task) => vec![task.wallet_id],
             TaskDescriptor::OfflineFee(task) => vec![task.wallet_id],
+            TaskDescriptor::AllOfflineFees(task) => vec![task.wallet_id],
             TaskDescriptor::RelayerFee(task) => vec![task.wallet_id],
             TaskDescriptor::RedeemFee(task) => vec![task.wallet_id],
             TaskDescriptor::SettleMatch(task) => vec![task.wallet_id],
@@ -200,6 +204,7 @@ impl TaskDescriptor {
             | TaskDescriptor::LookupWallet(_)
             | TaskDescriptor::RefreshWallet(_)
             | TaskDescriptor::OfflineFee(_)
+            | TaskDescriptor::AllOfflineFees(_)
             | TaskDescriptor::RelayerFee(_)
             | TaskDescriptor::RedeemFee(_)
             | TaskDescriptor::UpdateWallet(_)
@@ -224,6 +229,7 @@ impl TaskDescriptor {
             TaskDescriptor::SettleExternalMatch(_) => "Settle External Match".to_string(),
             TaskDescriptor::SettleMalleableExternalMatch(_) => "Settle Malleable Match".to_string(),
             TaskDescriptor::OfflineFee(_) => "Pay Fee Offline".to_string(),
+            TaskDescriptor::AllOfflineFees(_) => "Pay All Fees Offline".to_string(),
             TaskDescriptor::RelayerFee(_) => "Pay Relayer Fee".to_string(),
             TaskDescriptor::RedeemFee(_) => "Redeem Fee".to_string(),
             TaskDescriptor::UpdateMerkleProof(_) => "Update Merkle Proof".to_string(),
@@ -313,15 +319,18 @@ pub mod mocks {
 
 #[cfg(test)]
 mod test {
+    use circuit_types::balance::Balance;
     use constants::Scalar;
+    use num_bigint::BigUint;
 
     use crate::types::{
-        wallet::OrderIdentifier,
+        wallet::{OrderIdentifier, WalletIdentifier},
         wallet_mocks::{mock_empty_wallet, mock_order},
     };
 
     use super::{
-        NewWalletTaskDescriptor, UpdateWalletTaskDescriptor, mocks::gen_wallet_update_sig,
+        NewWalletTaskDescriptor, PayOfflineFeeTaskDescriptor, UpdateWalletTaskDescriptor,
+        mocks::gen_wallet_update_sig,
     };
 
     /// Tests creating a new wallet task with an invalid secret sharing
@@ -385,4 +394,24 @@ mod test {
         )
         .unwrap();
     }
+
+    /// Tests that an offline fee descriptor serialized without an idempotency
+    /// key deserializes, and that a set key round trips
+    #[test]
+    fn test_offline_fee_idempotency_key() {
+        let balance = Balance::new_from_mint(BigUint::from(1u8));
+        let desc =
+            PayOfflineFeeTaskDescriptor::new_relayer_fee(WalletIdentifier::new_v4(), balance)
+                .unwrap();
+
+        let mut json = serde_json::to_value(&desc).unwrap();
+        json.as_object_mut().unwrap().remove("idempotency_key");
+        let res: PayOfflineFeeTaskDescriptor = serde_json::from_value(json).unwrap();
+        assert!(res.idempotency_key.is_none());
+
+        let desc = desc.with_idempotency_key(Scalar::one());
+        let json = serde_json::to_string(&desc).unwrap();
+        let res: PayOfflineFeeTaskDescriptor = serde_json::from_str(&json).unwrap();
+        assert_eq!(res.idempotency_key, Some(Scalar::one()));
+    }
 }
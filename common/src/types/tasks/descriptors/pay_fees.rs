@@ -1,6 +1,7 @@
 //! Task descriptors for paying fees
 
-use circuit_types::{Amount, balance::Balance};
+use circuit_types::{Amount, balance::Balance, elgamal::EncryptionKey};
+use constants::Scalar;
 use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
 
@@ -22,6 +23,29 @@ pub struct PayOfflineFeeTaskDescriptor {
     /// For now, this is always set to the full balance and is only used for
     /// informational purposes in API queries
     pub amount: Amount,
+    /// Whether to stop after proving the fee payment, without submitting it
+    ///
+    /// A dry run leaves the wallet and chain state untouched
+    #[serde(default)]
+    pub dry_run: bool,
+    /// An optional key to encrypt the fee note under in place of the wallet's
+    /// managing cluster key
+    ///
+    /// Only valid for relayer fees, protocol fees are always encrypted under
+    /// the protocol key. `VALID OFFLINE FEE SETTLEMENT` does not yet support
+    /// other keys for relayer fees, so the task rejects any override other
+    /// than the managing cluster key itself
+    #[serde(default)]
+    pub override_encryption_key: Option<EncryptionKey>,
+    /// The commitment to a fee note settled by a previous attempt at this
+    /// payment, if any
+    ///
+    /// The task records this in its stored descriptor before submitting the
+    /// payment. If a note with this commitment is already in the Merkle tree,
+    /// the task skips proving and submitting the payment, so that a payment
+    /// re-enqueued after a failover is not settled twice
+    #[serde(default)]
+    pub idempotency_key: Option<Scalar>,
 }
 
 impl PayOfflineFeeTaskDescriptor {
@@ -32,6 +56,9 @@ impl PayOfflineFeeTaskDescriptor {
             wallet_id,
             mint: balance.mint,
             amount: balance.relayer_fee_balance,
+            dry_run: false,
+            override_encryption_key: None,
+            idempotency_key: None,
         })
     }
 
@@ -42,8 +69,32 @@ impl PayOfflineFeeTaskDescriptor {
             wallet_id,
             mint: balance.mint,
             amount: balance.protocol_fee_balance,
+            dry_run: false,
+            override_encryption_key: None,
+            idempotency_key: None,
         })
     }
+
+    /// Set whether the task is a dry run, which proves the fee payment but
+    /// does not submit it
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Set a key to encrypt the fee note under in place of the wallet's
+    /// managing cluster key
+    pub fn with_encryption_key_override(mut self, key: EncryptionKey) -> Self {
+        self.override_encryption_key = Some(key);
+        self
+    }
+
+    /// Set the commitment to the fee note settled by a previous attempt at
+    /// this payment
+    pub fn with_idempotency_key(mut self, note_commitment: Scalar) -> Self {
+        self.idempotency_key = Some(note_commitment);
+        self
+    }
 }
 
 impl From<PayOfflineFeeTaskDescriptor> for TaskDescriptor {
@@ -52,6 +103,27 @@ impl From<PayOfflineFeeTaskDescriptor> for TaskDescriptor {
     }
 }
 
+/// The task descriptor for the task that pays all outstanding offline fees on a
+/// wallet
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayAllOfflineFeesTaskDescriptor {
+    /// The wallet to pay fees for
+    pub wallet_id: WalletIdentifier,
+}
+
+impl PayAllOfflineFeesTaskDescriptor {
+    /// Constructor
+    pub fn new(wallet_id: WalletIdentifier) -> Self {
+        PayAllOfflineFeesTaskDescriptor { wallet_id }
+    }
+}
+
+impl From<PayAllOfflineFeesTaskDescriptor> for TaskDescriptor {
+    fn from(descriptor: PayAllOfflineFeesTaskDescriptor) -> Self {
+        TaskDescriptor::AllOfflineFees(descriptor)
+    }
+}
+
 /// The task descriptor for the relayer fee payment task
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PayRelayerFeeTaskDescriptor {
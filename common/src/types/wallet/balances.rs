@@ -1,8 +1,8 @@
 //! Wallet helpers for balances in the wallet
 
-use std::iter;
+use std::{collections::HashMap, iter};
 
-use circuit_types::{Amount, balance::Balance};
+use circuit_types::{Amount, balance::Balance, fees::FeeTake};
 use constants::MAX_BALANCES;
 use itertools::Itertools;
 use num_bigint::BigUint;
@@ -88,6 +88,58 @@ impl Wallet {
         self.balances.values().any(|balance| balance.fees().total() > 0)
     }
 
+    /// Get the fees owed on each balance that has outstanding fees, alongside
+    /// the balance's mint
+    ///
+    /// The split matches the amounts that the relayer and protocol fee notes
+    /// for the balance would be created with
+    pub fn outstanding_fees(&self) -> Vec<(BigUint, FeeTake)> {
+        self.balances
+            .values()
+            .map(|balance| (balance.mint.clone(), balance.fees()))
+            .filter(|(_, fees)| fees.total() > 0)
+            .collect_vec()
+    }
+
+    /// Get the mints of all balances that owe a protocol fee
+    ///
+    /// These are the balances for which a protocol fee payment creates a
+    /// non-zero note
+    pub fn balances_owing_protocol_fee(&self) -> Vec<BigUint> {
+        self.balances
+            .values()
+            .filter(|balance| balance.protocol_fee_balance > 0)
+            .map(|balance| balance.mint.clone())
+            .collect_vec()
+    }
+
+    /// Get the mints of all balances that owe a relayer fee
+    ///
+    /// These are the balances for which a relayer fee payment creates a
+    /// non-zero note
+    pub fn balances_owing_relayer_fee(&self) -> Vec<BigUint> {
+        self.balances
+            .values()
+            .filter(|balance| balance.relayer_fee_balance > 0)
+            .map(|balance| balance.mint.clone())
+            .collect_vec()
+    }
+
+    /// Get the mints of all balances that are dust under the given per-mint
+    /// thresholds
+    ///
+    /// Balances for mints that have no threshold configured are never
+    /// considered dust
+    pub fn dust_balances(&self, thresholds: &HashMap<BigUint, Amount>) -> Vec<BigUint> {
+        self.balances
+            .values()
+            .filter(|balance| {
+                thresholds.get(&balance.mint).is_some_and(|min| balance.is_dust(*min))
+            })
+            .map(|balance| balance.mint.clone())
+            .collect_vec()
+    }
+
     /// Whether the wallet has any zero'd balances that may be used for
     /// receiving a new mint
     pub fn has_empty_balance(&self) -> bool {
@@ -123,7 +175,7 @@ impl Wallet {
     }
 
     /// Find the first replaceable balance
-    fn find_first_replaceable_balance(&self) -> Option<usize> {
+    pub(super) fn find_first_replaceable_balance(&self) -> Option<usize> {
         self.balances.iter().position(|(_, balance)| balance.is_zero())
     }
 
@@ -0,0 +1,92 @@
+//! Helpers for diffing the contents of two wallets
+//!
+//! These are diagnostic only, e.g. for inspecting how a wallet reconstructed
+//! from its shares diverges from the wallet it was expected to match
+
+use std::collections::BTreeMap;
+
+use circuit_types::balance::Balance;
+
+use crate::keyed_list::KeyedList;
+
+use super::{Order, OrderIdentifier, Wallet};
+
+/// The differences between the balances, orders, and blinder of two wallets
+///
+/// Entries are taken relative to the wallet `diff` is called on, so "added"
+/// entries appear only in the other wallet and "removed" entries appear only
+/// in this one. Each list is sorted by its key: balances by mint, orders by
+/// order ID
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WalletDiff {
+    /// Balances present only in the other wallet
+    pub added_balances: Vec<Balance>,
+    /// Balances present only in this wallet
+    pub removed_balances: Vec<Balance>,
+    /// Balances present in both wallets that differ, as `(this, other)`
+    pub changed_balances: Vec<(Balance, Balance)>,
+    /// Orders present only in the other wallet
+    pub added_orders: Vec<(OrderIdentifier, Order)>,
+    /// Orders present only in this wallet
+    pub removed_orders: Vec<(OrderIdentifier, Order)>,
+    /// Orders present in both wallets that differ, as `(id, this, other)`
+    pub changed_orders: Vec<(OrderIdentifier, Order, Order)>,
+    /// Whether the wallets' blinders differ
+    pub blinder_differs: bool,
+}
+
+impl WalletDiff {
+    /// Whether the two wallets have identical balances, orders, and blinders
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// The entries added, removed, and changed between two keyed lists
+type KeyedListDiff<K, V> = (Vec<(K, V)>, Vec<(K, V)>, Vec<(K, V, V)>);
+
+impl Wallet {
+    /// Diff the balances, orders, and blinder of this wallet against another
+    pub fn diff(&self, other: &Wallet) -> WalletDiff {
+        let (added_balances, removed_balances, changed_balances) =
+            diff_keyed_lists(&self.balances, &other.balances);
+        let (added_orders, removed_orders, changed_orders) =
+            diff_keyed_lists(&self.orders, &other.orders);
+
+        WalletDiff {
+            added_balances: added_balances.into_iter().map(|(_, bal)| bal).collect(),
+            removed_balances: removed_balances.into_iter().map(|(_, bal)| bal).collect(),
+            changed_balances: changed_balances
+                .into_iter()
+                .map(|(_, this, other)| (this, other))
+                .collect(),
+            added_orders,
+            removed_orders,
+            changed_orders,
+            blinder_differs: self.blinder != other.blinder,
+        }
+    }
+}
+
+/// Diff two keyed lists, returning entries sorted by key
+fn diff_keyed_lists<K: Ord + Clone, V: Clone + PartialEq>(
+    this: &KeyedList<K, V>,
+    other: &KeyedList<K, V>,
+) -> KeyedListDiff<K, V> {
+    let this_entries: BTreeMap<_, _> = this.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    let mut other_entries: BTreeMap<_, _> =
+        other.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for (key, this_val) in this_entries {
+        match other_entries.remove(&key) {
+            Some(other_val) if other_val != this_val => changed.push((key, this_val, other_val)),
+            Some(_) => {},
+            None => removed.push((key, this_val)),
+        }
+    }
+
+    let added = other_entries.into_iter().collect();
+    (added, removed, changed)
+}
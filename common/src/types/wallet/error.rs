@@ -0,0 +1,57 @@
+//! Error types emitted by wallet helpers
+
+use std::{error::Error, fmt::Display};
+
+use circuit_types::Address;
+
+/// The error type emitted by wallet helpers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WalletError {
+    /// The requested order does not exist in the wallet
+    OrderNotFound,
+    /// The wallet has no balance for the mint an order sends
+    SendBalanceNotFound,
+    /// The wallet has no balance for the mint an order receives, and no
+    /// empty balance slot to receive into
+    BalancesFull,
+}
+
+impl Display for WalletError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for WalletError {}
+
+/// The first field of a wallet that diverges from the wallet recovered from
+/// its secret shares
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShareMismatch {
+    /// The balance for the given mint diverges
+    Balance {
+        /// The mint of the wallet's balance
+        mint: Address,
+    },
+    /// The order at the given index diverges
+    Order {
+        /// The index of the order in the wallet
+        index: usize,
+    },
+    /// The wallet's public keys diverge
+    Keys,
+    /// The wallet's max match fee diverges
+    MaxMatchFee,
+    /// The wallet's managing cluster key diverges
+    ManagingCluster,
+    /// The wallet's blinder diverges
+    Blinder,
+}
+
+impl Display for ShareMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for ShareMismatch {}
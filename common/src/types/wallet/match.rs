@@ -1,28 +1,76 @@
 //! Match settlement helpers
 
-use circuit_types::{r#match::MatchResult, native_helpers::create_wallet_shares_from_private};
+use circuit_types::{
+    fees::AmountExt,
+    r#match::{MatchResult, OrderSettlementIndices},
+    native_helpers::create_wallet_shares_from_private,
+};
+use constants::MAX_BALANCES;
 use util::matching_engine::compute_fee_obligation;
 
-use super::{OrderIdentifier, Wallet};
+use super::{OrderIdentifier, Wallet, WalletError};
 
 impl Wallet {
+    /// Compute the indices at which a match on the given order settles into
+    /// the wallet
+    ///
+    /// The send balance must already exist in the wallet. The receive balance
+    /// is the existing balance for the receive mint if one exists, otherwise
+    /// the slot that a new balance would be placed into; this mirrors the
+    /// placement used by `apply_match`
+    pub fn compute_settlement_indices(
+        &self,
+        order_id: &OrderIdentifier,
+    ) -> Result<OrderSettlementIndices, WalletError> {
+        let order_index = self.orders.index_of(order_id).ok_or(WalletError::OrderNotFound)?;
+        let order = self.get_order(order_id).ok_or(WalletError::OrderNotFound)?;
+
+        let balance_send =
+            self.get_balance_index(order.send_mint()).ok_or(WalletError::SendBalanceNotFound)?;
+        let balance_receive = self
+            .get_balance_index(order.receive_mint())
+            .or_else(|| self.find_first_replaceable_balance())
+            .or_else(|| (self.balances.len() < MAX_BALANCES).then_some(self.balances.len()))
+            .ok_or(WalletError::BalancesFull)?;
+
+        Ok(OrderSettlementIndices { order: order_index, balance_send, balance_receive })
+    }
+
+    /// Get the indices at which a match on the given order settles into the
+    /// wallet, for simulating a match without decoding contract calldata
+    ///
+    /// Unlike `compute_settlement_indices`, no slot is chosen for a missing
+    /// receive balance; returns `None` unless the order and both its send and
+    /// receive balances exist in the wallet
+    pub fn settlement_indices_for_order(
+        &self,
+        order_id: &OrderIdentifier,
+    ) -> Option<OrderSettlementIndices> {
+        let order_index = self.orders.index_of(order_id)?;
+        let order = self.get_order(order_id)?;
+        let balance_send = self.get_balance_index(order.send_mint())?;
+        let balance_receive = self.get_balance_index(order.receive_mint())?;
+
+        Some(OrderSettlementIndices { order: order_index, balance_send, balance_receive })
+    }
+
     /// Settle a match on the given order into the wallet
     pub fn apply_match(
         &mut self,
         match_res: &MatchResult,
         order_id: &OrderIdentifier,
     ) -> Result<(), String> {
-        // Subtract the matched volume from the order
+        // Select the correct mints and amounts based on the order side
         let match_fee = self.max_match_fee;
         let order = self.get_order_mut(order_id).unwrap();
-        order.amount =
-            order.amount.checked_sub(match_res.base_amount).expect("order volume underflow");
-
-        // Select the correct mints and amounts based on the order side
         let (send_mint, send_amt) = match_res.send_mint_amount(order.side);
         let (recv_mint, recv_amt) = match_res.receive_mint_amount(order.side);
         let fees = compute_fee_obligation(match_fee, order.side, match_res);
-        let net_recv = recv_amt - fees.total();
+        let net_recv = recv_amt.checked_sub_fee(&fees).ok_or("fees exceed receive amount")?;
+
+        // Subtract the matched volume from the order
+        order.amount =
+            order.amount.checked_sub(match_res.base_amount).expect("order volume underflow");
 
         // Update the balances
         let send_balance = self.get_balance_mut(&send_mint).unwrap();
@@ -41,12 +89,12 @@ impl Wallet {
 
         // Update the public shares of the wallet, reblinding the wallet should be done
         // separately
-        let (_, new_public_share) = create_wallet_shares_from_private(
+        let (private_shares, new_public_share) = create_wallet_shares_from_private(
             &self.clone().into(),
             &self.private_shares,
             self.blinder,
         );
-        self.blinded_public_shares = new_public_share;
+        self.set_shares(private_shares, new_public_share);
 
         // Invalidate the Merkle opening
         self.invalidate_merkle_opening();
@@ -7,6 +7,7 @@ use std::{
 
 use circuit_types::{
     SizedWalletShare,
+    balance::Balance,
     elgamal::DecryptionKey,
     fixed_point::FixedPoint,
     keychain::{PublicKeyChain, PublicSigningKey, SecretIdentificationKey, SecretSigningKey},
@@ -16,8 +17,8 @@ use circuit_types::{
 use constants::{ADDRESS_BYTE_LENGTH, MERKLE_HEIGHT, Scalar};
 use k256::ecdsa::SigningKey as K256SigningKey;
 use num_bigint::BigUint;
-use rand::{Rng, RngCore, thread_rng};
-use uuid::Uuid;
+use rand::{CryptoRng, Rng, RngCore, SeedableRng, rngs::StdRng, thread_rng};
+use uuid::Builder as UuidBuilder;
 
 use crate::{
     keyed_list::KeyedList,
@@ -30,41 +31,73 @@ use super::{
     orders::{Order, OrderBuilder},
 };
 
+/// The seed from which `Wallet::mock_with_balances` samples its wallet
+pub const DEFAULT_MOCK_WALLET_SEED: u64 = 0;
+
+impl Wallet {
+    /// Create a mock wallet holding the given balances, sampled
+    /// deterministically from `DEFAULT_MOCK_WALLET_SEED`
+    ///
+    /// The wallet's shares are a valid sharing of its contents
+    pub fn mock_with_balances(balances: Vec<Balance>) -> Wallet {
+        Self::mock_with_balances_seeded(balances, DEFAULT_MOCK_WALLET_SEED)
+    }
+
+    /// Create a mock wallet holding the given balances, sampled
+    /// deterministically from the given seed
+    ///
+    /// Panics if the balances do not fit in the wallet
+    pub fn mock_with_balances_seeded(balances: Vec<Balance>, seed: u64) -> Wallet {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut wallet = mock_wallet_from_rng(&mut rng);
+        for balance in balances {
+            wallet.add_balance(balance).expect("invalid mock balance");
+        }
+
+        // Reblind so that the shares include the balances
+        wallet.reblind_wallet();
+        wallet
+    }
+}
+
 /// Create a mock empty wallet
 pub fn mock_empty_wallet() -> Wallet {
-    // Create an initial wallet
-    let mut rng = thread_rng();
+    mock_wallet_from_rng(&mut thread_rng())
+}
 
+/// Create a mock empty wallet, sampling its keys and shares from the given rng
+fn mock_wallet_from_rng<R: Rng + CryptoRng>(rng: &mut R) -> Wallet {
     // Sample a valid signing key
-    let key = K256SigningKey::random(&mut rng);
+    let key = K256SigningKey::random(&mut *rng);
     let sk_root = Some(SecretSigningKey::from(&key));
     let pk_root = PublicSigningKey::from(key.verifying_key());
 
-    let sk_match = SecretIdentificationKey::from(Scalar::random(&mut rng));
+    let sk_match = SecretIdentificationKey::from(Scalar::random(rng));
     let pk_match = sk_match.get_public_key();
-    let symmetric_key = HmacKey::random();
+    let symmetric_key = HmacKey(rng.r#gen());
 
-    let (_, managing_cluster_key) = DecryptionKey::random_pair(&mut rng);
+    let (_, managing_cluster_key) = DecryptionKey::random_pair(rng);
 
     let mut wallet = Wallet {
-        wallet_id: Uuid::new_v4(),
+        wallet_id: UuidBuilder::from_random_bytes(rng.r#gen()).into_uuid(),
         orders: KeyedList::default(),
         balances: KeyedList::default(),
         key_chain: KeyChain {
             public_keys: PublicKeyChain::new(pk_root, pk_match),
             secret_keys: PrivateKeyChain { sk_root, sk_match, symmetric_key },
         },
-        blinder: Scalar::random(&mut rng),
+        blinder: Scalar::random(rng),
         max_match_fee: FixedPoint::from_integer(0),
         managing_cluster: managing_cluster_key,
         private_shares: SizedWalletShare::from_scalars(&mut iter::repeat_with(|| {
-            Scalar::random(&mut rng)
+            Scalar::random(rng)
         })),
         blinded_public_shares: SizedWalletShare::from_scalars(&mut iter::repeat_with(|| {
-            Scalar::random(&mut rng)
+            Scalar::random(rng)
         })),
-        merkle_proof: Some(mock_merkle_path()),
+        merkle_proof: Some(mock_merkle_path_from_rng(rng)),
         merkle_staleness: Arc::new(AtomicUsize::default()),
+        share_commitment: None,
     };
 
     // Reblind the wallet so that the secret shares a valid sharing of the wallet
@@ -88,11 +121,15 @@ pub fn mock_order() -> Order {
 
 /// Create a mock Merkle path for a wallet
 pub fn mock_merkle_path() -> MerkleAuthenticationPath {
-    let mut rng = thread_rng();
+    mock_merkle_path_from_rng(&mut thread_rng())
+}
+
+/// Create a mock Merkle path, sampled from the given rng
+fn mock_merkle_path_from_rng<R: Rng + CryptoRng>(rng: &mut R) -> MerkleAuthenticationPath {
     MerkleAuthenticationPath::new(
-        [Scalar::random(&mut rng); MERKLE_HEIGHT],
+        [Scalar::random(rng); MERKLE_HEIGHT],
         BigUint::from(0u8),
-        Scalar::random(&mut rng),
+        Scalar::random(rng),
     )
 }
 
@@ -4,6 +4,8 @@
 
 mod balances;
 pub mod derivation;
+mod diff;
+mod error;
 pub mod keychain;
 #[cfg(feature = "proof-system-types")]
 mod r#match;
@@ -14,6 +16,8 @@ mod orders;
 mod shares;
 mod types;
 
+pub use diff::WalletDiff;
+pub use error::{ShareMismatch, WalletError};
 pub use orders::{Order, OrderBuilder, Pair, pair_from_mints};
 pub use types::*;
 
@@ -23,15 +27,17 @@ pub use types::*;
 
 #[cfg(test)]
 mod test {
+    use std::collections::HashMap;
+
     use circuit_types::{Amount, balance::Balance, fixed_point::FixedPoint, order::OrderSide};
-    use constants::{MAX_BALANCES, MAX_ORDERS};
+    use constants::{MAX_BALANCES, MAX_ORDERS, Scalar};
     use num_bigint::BigUint;
     use rand::{distributions::uniform::SampleRange, thread_rng};
     use uuid::Uuid;
 
-    use crate::types::wallet::mocks::{mock_empty_wallet, mock_order};
+    use crate::types::wallet::mocks::{DEFAULT_MOCK_WALLET_SEED, mock_empty_wallet, mock_order};
 
-    use super::OrderBuilder;
+    use super::{OrderBuilder, ShareMismatch, Wallet, WalletDiff};
 
     /// Tests adding a balance to an empty wallet
     #[test]
@@ -101,6 +107,21 @@ mod test {
         wallet.add_balance(balance).unwrap();
     }
 
+    /// Tests finding the dust balances in a wallet
+    #[test]
+    fn test_dust_balances() {
+        let mut wallet = mock_empty_wallet();
+        let dust = Balance::new_from_mint_and_amount(BigUint::from(1u8), 5);
+        let large = Balance::new_from_mint_and_amount(BigUint::from(2u8), 100);
+        let no_threshold = Balance::new_from_mint_and_amount(BigUint::from(3u8), 1);
+        wallet.add_balance(dust.clone()).unwrap();
+        wallet.add_balance(large.clone()).unwrap();
+        wallet.add_balance(no_threshold).unwrap();
+
+        let thresholds = HashMap::from([(dust.mint.clone(), 10), (large.mint.clone(), 10)]);
+        assert_eq!(wallet.dust_balances(&thresholds), vec![dust.mint]);
+    }
+
     /// Tests adding an order that appends to the wallet
     #[test]
     fn test_add_order_append() {
@@ -168,4 +189,303 @@ mod test {
 
         wallet.add_balance(balance).unwrap();
     }
+
+    /// Tests computing the settlement indices for an order
+    #[test]
+    fn test_compute_settlement_indices() {
+        let mut wallet = mock_empty_wallet();
+        let id = Uuid::new_v4();
+        let order = mock_order();
+
+        // The order has no send balance yet
+        wallet.add_order(id, order.clone()).unwrap();
+        assert!(wallet.compute_settlement_indices(&id).is_err());
+        assert!(wallet.settlement_indices_for_order(&id).is_none());
+
+        // Add an unrelated balance, then the send balance for the buy side order
+        let other = Balance::new_from_mint_and_amount(BigUint::from(1u8), 10);
+        let send = Balance::new_from_mint_and_amount(order.quote_mint.clone(), 10);
+        wallet.add_balance(other).unwrap();
+        wallet.add_balance(send).unwrap();
+
+        // The receive balance should be placed in the next open slot
+        let indices = wallet.compute_settlement_indices(&id).unwrap();
+        assert_eq!(indices.order, 0);
+        assert_eq!(indices.balance_send, 1);
+        assert_eq!(indices.balance_receive, 2);
+
+        // Once the receive balance exists its index should be used
+        let receive = Balance::new_from_mint_and_amount(order.base_mint.clone(), 10);
+        wallet.balances.replace_at_index(0, receive.mint.clone(), receive);
+        let indices = wallet.compute_settlement_indices(&id).unwrap();
+        assert_eq!(indices.balance_receive, 0);
+        let simulated = wallet.settlement_indices_for_order(&id).unwrap();
+        assert_eq!(simulated.order, indices.order);
+        assert_eq!(simulated.balance_send, indices.balance_send);
+        assert_eq!(simulated.balance_receive, indices.balance_receive);
+    }
+
+    /// Tests that simulated settlement indices are not computed for an order
+    /// whose receive balance is missing
+    #[test]
+    fn test_settlement_indices_missing_receive() {
+        let mut wallet = mock_empty_wallet();
+        let id = Uuid::new_v4();
+        let order = mock_order();
+        wallet.add_order(id, order.clone()).unwrap();
+
+        // Only the send balance for the buy side order exists
+        let send = Balance::new_from_mint_and_amount(order.quote_mint.clone(), 10);
+        wallet.add_balance(send).unwrap();
+        assert!(wallet.compute_settlement_indices(&id).is_ok());
+        assert!(wallet.settlement_indices_for_order(&id).is_none());
+
+        // Once the receive balance exists the indices are computed
+        let receive = Balance::new_from_mint_and_amount(order.base_mint.clone(), 10);
+        wallet.add_balance(receive).unwrap();
+        let indices = wallet.settlement_indices_for_order(&id).unwrap();
+        assert_eq!(indices.balance_send, 0);
+        assert_eq!(indices.balance_receive, 1);
+    }
+
+    /// Tests that verifying a wallet's shares reports the diverging field
+    #[test]
+    fn test_verify_wallet_shares() {
+        let mut wallet = mock_empty_wallet();
+        assert_eq!(wallet.verify_wallet_shares(), Ok(()));
+
+        // Modify the blinder without updating the shares
+        let mut bad_blinder = wallet.clone();
+        bad_blinder.blinder = bad_blinder.blinder + Scalar::one();
+        assert_eq!(bad_blinder.verify_wallet_shares(), Err(ShareMismatch::Blinder));
+
+        // Add a balance without updating the shares
+        let balance = Balance::new_from_mint_and_amount(BigUint::from(1u8), 10);
+        wallet.add_balance(balance.clone()).unwrap();
+        let expected = ShareMismatch::Balance { mint: balance.mint };
+        assert_eq!(wallet.verify_wallet_shares(), Err(expected));
+        assert!(!wallet.check_wallet_shares());
+    }
+
+    /// Tests that reblinding `n` times at once matches reblinding `n` times in
+    /// sequence
+    #[test]
+    fn test_reblind_n() {
+        const N: usize = 3;
+        let mut wallet = mock_empty_wallet();
+        let mut expected = wallet.clone();
+
+        let mut expected_blinders = Vec::with_capacity(N);
+        for _ in 0..N {
+            expected.reblind_wallet();
+            expected_blinders.push(expected.public_blinder());
+        }
+
+        let blinders = wallet.reblind_n(N);
+        assert_eq!(blinders, expected_blinders);
+        assert_eq!(wallet.blinder, expected.blinder);
+        assert_eq!(wallet.private_shares, expected.private_shares);
+        assert_eq!(wallet.blinded_public_shares, expected.blinded_public_shares);
+        assert!(wallet.reblind_n(0).is_empty());
+    }
+
+    /// Tests summarizing the outstanding fees on a wallet's balances
+    #[test]
+    fn test_outstanding_fees() {
+        let mut wallet = mock_empty_wallet();
+        assert!(wallet.outstanding_fees().is_empty());
+
+        let mut both = Balance::new_from_mint_and_amount(BigUint::from(1u8), 100);
+        both.relayer_fee_balance = 5;
+        both.protocol_fee_balance = 2;
+        let mut relayer_only = Balance::new_from_mint_and_amount(BigUint::from(2u8), 100);
+        relayer_only.relayer_fee_balance = 3;
+        let no_fees = Balance::new_from_mint_and_amount(BigUint::from(3u8), 100);
+
+        for balance in [both.clone(), relayer_only.clone(), no_fees] {
+            wallet.add_balance(balance).unwrap();
+        }
+
+        let fees = wallet.outstanding_fees();
+        let expected = vec![
+            (both.mint.clone(), both.fees()),
+            (relayer_only.mint.clone(), relayer_only.fees()),
+        ];
+        assert_eq!(fees, expected);
+        assert_eq!((fees[0].1.relayer_fee, fees[0].1.protocol_fee), (5, 2));
+        assert_eq!((fees[1].1.relayer_fee, fees[1].1.protocol_fee), (3, 0));
+    }
+
+    /// Tests partitioning a wallet's balances by the fee type they owe
+    #[test]
+    fn test_balances_owing_fees() {
+        let mut wallet = mock_empty_wallet();
+        assert!(wallet.balances_owing_protocol_fee().is_empty());
+        assert!(wallet.balances_owing_relayer_fee().is_empty());
+
+        let mut both = Balance::new_from_mint_and_amount(BigUint::from(1u8), 100);
+        both.relayer_fee_balance = 5;
+        both.protocol_fee_balance = 2;
+        let mut relayer_only = Balance::new_from_mint_and_amount(BigUint::from(2u8), 100);
+        relayer_only.relayer_fee_balance = 3;
+        let mut protocol_only = Balance::new_from_mint_and_amount(BigUint::from(3u8), 100);
+        protocol_only.protocol_fee_balance = 1;
+        let no_fees = Balance::new_from_mint_and_amount(BigUint::from(4u8), 100);
+
+        for balance in [both.clone(), relayer_only.clone(), protocol_only.clone(), no_fees] {
+            wallet.add_balance(balance).unwrap();
+        }
+
+        let protocol = wallet.balances_owing_protocol_fee();
+        let relayer = wallet.balances_owing_relayer_fee();
+        assert_eq!(protocol, vec![both.mint.clone(), protocol_only.mint]);
+        assert_eq!(relayer, vec![both.mint, relayer_only.mint]);
+    }
+
+    /// Tests that seeded mock wallets are valid and deterministic in their seed
+    #[test]
+    fn test_mock_with_balances() {
+        let balances = vec![
+            Balance::new_from_mint_and_amount(BigUint::from(1u8), 10),
+            Balance::new_from_mint_and_amount(BigUint::from(2u8), 20),
+        ];
+
+        let wallet = Wallet::mock_with_balances(balances.clone());
+        assert!(wallet.check_wallet_shares());
+        for balance in balances.iter() {
+            assert_eq!(wallet.balances.get(&balance.mint), Some(balance));
+        }
+
+        // The same seed should produce the same wallet
+        let same = Wallet::mock_with_balances_seeded(balances.clone(), DEFAULT_MOCK_WALLET_SEED);
+        assert_eq!(same.wallet_id, wallet.wallet_id);
+        assert_eq!(same.blinder, wallet.blinder);
+        assert_eq!(same.private_shares, wallet.private_shares);
+        assert_eq!(same.blinded_public_shares, wallet.blinded_public_shares);
+
+        // A different seed should produce a different wallet
+        let other = Wallet::mock_with_balances_seeded(balances, DEFAULT_MOCK_WALLET_SEED + 1);
+        assert!(other.check_wallet_shares());
+        assert_ne!(other.wallet_id, wallet.wallet_id);
+        assert_ne!(other.private_shares, wallet.private_shares);
+    }
+
+    /// Tests reconstructing a wallet from its shares
+    #[test]
+    fn test_wallet_from_shares() {
+        let mut wallet = mock_empty_wallet();
+        let balance = Balance::new_from_mint_and_amount(BigUint::from(1u8), 10);
+        let order = mock_order();
+        wallet.add_balance(balance.clone()).unwrap();
+        wallet.add_order(Uuid::new_v4(), order.clone()).unwrap();
+        wallet.reblind_wallet();
+
+        let recovered = Wallet::from_shares(
+            wallet.private_shares.clone(),
+            wallet.blinded_public_shares.clone(),
+            wallet.key_chain.clone(),
+        )
+        .unwrap();
+        assert!(recovered.check_wallet_shares());
+        assert_eq!(recovered.key_chain, wallet.key_chain);
+        assert!(recovered.merkle_proof.is_none());
+        assert_eq!(recovered.blinder, wallet.blinder);
+        assert_eq!(recovered.balances.get(&balance.mint), Some(&balance));
+
+        let recovered_order = recovered.orders.values().next().unwrap();
+        assert_eq!(recovered_order.side, order.side);
+        assert_eq!(recovered_order.amount, order.amount);
+        assert_eq!(recovered_order.worst_case_price, order.worst_case_price);
+
+        // A key chain that does not match the shares is rejected
+        let mut key_chain = wallet.key_chain.clone();
+        key_chain.increment_nonce();
+        let res = Wallet::from_shares(
+            wallet.private_shares.clone(),
+            wallet.blinded_public_shares.clone(),
+            key_chain,
+        );
+        assert_eq!(res.err(), Some(ShareMismatch::Keys));
+    }
+
+    /// Tests predicting the public blinders of future reblinds
+    #[test]
+    fn test_future_public_blinders() {
+        const N: usize = 3;
+        let wallet = mock_empty_wallet();
+        let blinders = wallet.future_public_blinders(N);
+        assert_eq!(blinders.len(), N);
+        assert_eq!(blinders[0], wallet.next_public_blinder());
+
+        let mut reblinded = wallet.clone();
+        for expected in blinders {
+            reblinded.reblind_wallet();
+            assert_eq!(reblinded.public_blinder(), expected);
+        }
+
+        assert!(wallet.future_public_blinders(0).is_empty());
+    }
+
+    /// Tests that the cached share commitment tracks the wallet's shares
+    #[test]
+    fn test_share_commitment_cached() {
+        let mut wallet = mock_empty_wallet();
+        let comm = wallet.share_commitment_cached();
+        assert_eq!(comm, wallet.get_wallet_share_commitment());
+        assert_eq!(wallet.share_commitment_cached(), comm);
+
+        wallet.reblind_wallet();
+        assert_eq!(wallet.share_commitment_cached(), wallet.get_wallet_share_commitment());
+
+        wallet.reblind_n(2);
+        assert_eq!(wallet.share_commitment_cached(), wallet.get_wallet_share_commitment());
+
+        let mut updated = wallet.clone();
+        updated.reblind_wallet();
+        wallet.update_from_shares(&updated.private_shares, &updated.blinded_public_shares);
+        assert_eq!(wallet.share_commitment_cached(), wallet.get_wallet_share_commitment());
+        assert_ne!(wallet.share_commitment_cached(), comm);
+
+        updated.reblind_wallet();
+        wallet.set_shares(updated.private_shares.clone(), updated.blinded_public_shares.clone());
+        assert_eq!(wallet.share_commitment_cached(), updated.get_wallet_share_commitment());
+    }
+
+    /// Tests diffing the balances, orders, and blinder of two wallets
+    #[test]
+    fn test_wallet_diff() {
+        let mut wallet = mock_empty_wallet();
+        let balance1 = Balance::new_from_mint_and_amount(BigUint::from(1u8), 10);
+        let balance2 = Balance::new_from_mint_and_amount(BigUint::from(2u8), 10);
+        let (order_id1, order_id2) = (Uuid::new_v4(), Uuid::new_v4());
+        wallet.add_balance(balance1.clone()).unwrap();
+        wallet.add_balance(balance2.clone()).unwrap();
+        wallet.add_order(order_id1, mock_order()).unwrap();
+        assert!(wallet.diff(&wallet).is_empty());
+
+        let mut other = wallet.clone();
+        other.blinder = other.blinder + Scalar::one();
+        other.balances.remove(&balance1.mint);
+        other.balances.get_mut(&balance2.mint).unwrap().amount = 20;
+        let balance3 = Balance::new_from_mint_and_amount(BigUint::from(3u8), 10);
+        other.add_balance(balance3.clone()).unwrap();
+        other.remove_order(&order_id1);
+        let order2 = mock_order();
+        other.add_order(order_id2, order2.clone()).unwrap();
+
+        let diff = wallet.diff(&other);
+        let order1 = wallet.orders.get(&order_id1).unwrap().clone();
+        let changed_balance = other.balances.get(&balance2.mint).unwrap().clone();
+        let expected = WalletDiff {
+            added_balances: vec![balance3],
+            removed_balances: vec![balance1],
+            changed_balances: vec![(balance2, changed_balance)],
+            added_orders: vec![(order_id2, order2)],
+            removed_orders: vec![(order_id1, order1)],
+            changed_orders: vec![],
+            blinder_differs: true,
+        };
+        assert_eq!(diff, expected);
+        assert!(!diff.is_empty());
+    }
 }
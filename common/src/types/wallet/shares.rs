@@ -12,21 +12,79 @@ use circuit_types::{
 };
 use constants::Scalar;
 use renegade_crypto::hash::evaluate_hash_chain;
+use uuid::Uuid;
 
-use super::Wallet;
+use super::{ShareMismatch, Wallet, keychain::KeyChain};
 
 impl Wallet {
+    // ----------------
+    // | Constructors |
+    // ----------------
+
+    /// Construct a wallet from a private and (blinded) public secret share
+    /// pair, e.g. one decoded from a darkpool transaction
+    ///
+    /// The wallet is assigned a new wallet ID and new order IDs, and has no
+    /// Merkle opening. Errors if the key chain's public keys differ from those
+    /// recovered from the shares
+    pub fn from_shares(
+        private_shares: SizedWalletShare,
+        blinded_public_shares: SizedWalletShare,
+        key_chain: KeyChain,
+    ) -> Result<Wallet, ShareMismatch> {
+        let recovered = wallet_from_blinded_shares(&private_shares, &blinded_public_shares);
+        if recovered.keys != key_chain.public_keys {
+            return Err(ShareMismatch::Keys);
+        }
+
+        Ok(Wallet::new_from_shares(
+            Uuid::new_v4(),
+            key_chain.secret_keys,
+            blinded_public_shares,
+            private_shares,
+        ))
+    }
+
     // -----------
     // | Getters |
     // -----------
 
     /// Check that the wallet's shares correctly add to its contents
     pub fn check_wallet_shares(&self) -> bool {
+        self.verify_wallet_shares().is_ok()
+    }
+
+    /// Verify that the wallet's shares correctly add to its contents,
+    /// returning the first field that diverges if not
+    pub fn verify_wallet_shares(&self) -> Result<(), ShareMismatch> {
         let circuit_wallet: SizedWallet = self.clone().into();
         let recovered_wallet =
             wallet_from_blinded_shares(&self.private_shares, &self.blinded_public_shares);
 
-        circuit_wallet == recovered_wallet
+        let mut balances = circuit_wallet.balances.iter().zip(recovered_wallet.balances.iter());
+        if let Some((balance, _)) = balances.find(|(expected, actual)| expected != actual) {
+            return Err(ShareMismatch::Balance { mint: balance.mint.clone() });
+        }
+
+        let mut orders = circuit_wallet.orders.iter().zip(recovered_wallet.orders.iter());
+        if let Some(index) = orders.position(|(expected, actual)| expected != actual) {
+            return Err(ShareMismatch::Order { index });
+        }
+
+        if circuit_wallet.keys != recovered_wallet.keys {
+            return Err(ShareMismatch::Keys);
+        }
+        if circuit_wallet.max_match_fee != recovered_wallet.max_match_fee {
+            return Err(ShareMismatch::MaxMatchFee);
+        }
+        if circuit_wallet.managing_cluster != recovered_wallet.managing_cluster {
+            return Err(ShareMismatch::ManagingCluster);
+        }
+        if circuit_wallet.blinder != recovered_wallet.blinder {
+            return Err(ShareMismatch::Blinder);
+        }
+
+        Ok(())
     }
 
     /// Computes the commitment to the private shares of the wallet
@@ -39,6 +97,14 @@ impl Wallet {
         compute_wallet_share_commitment(&self.blinded_public_shares, &self.private_shares)
     }
 
+    /// Get the commitment to the full wallet shares, computing it only if the
+    /// shares have changed since it was last computed
+    pub fn share_commitment_cached(&mut self) -> WalletShareStateCommitment {
+        *self.share_commitment.get_or_insert_with(|| {
+            compute_wallet_share_commitment(&self.blinded_public_shares, &self.private_shares)
+        })
+    }
+
     /// Compute the wallet nullifier
     pub fn get_wallet_nullifier(&self) -> Nullifier {
         compute_wallet_share_nullifier(self.get_wallet_share_commitment(), self.blinder)
@@ -60,6 +126,27 @@ impl Wallet {
         new_blinder - new_blinder_private
     }
 
+    /// Get the public blinders of the wallet after each of the next `n`
+    /// reblinds, without reblinding the wallet
+    ///
+    /// The first element is the `next_public_blinder`
+    pub fn future_public_blinders(&self, n: usize) -> Vec<Scalar> {
+        self.future_blinders_and_private_shares(n)
+            .into_iter()
+            .map(|(blinder, blinder_private)| blinder - blinder_private)
+            .collect()
+    }
+
+    /// Get the blinder and private blinder share sampled by each of the next
+    /// `n` reblinds
+    ///
+    /// Returned in order `(blinder, blinder_private_share)`
+    fn future_blinders_and_private_shares(&self, n: usize) -> Vec<(Scalar, Scalar)> {
+        // Each reblind consumes a blinder and its private share from the chain
+        let blinder_chain = evaluate_hash_chain(self.private_blinder_share(), 2 * n);
+        blinder_chain.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+
     /// Get the last non-blinder wallet share
     pub fn get_last_private_share(&self) -> Scalar {
         let shares = self.private_shares.to_scalars();
@@ -72,6 +159,18 @@ impl Wallet {
     // | Setters |
     // -----------
 
+    /// Set the wallet's private and (blinded) public secret shares, clearing
+    /// the cached share commitment
+    pub fn set_shares(
+        &mut self,
+        private_shares: SizedWalletShare,
+        blinded_public_shares: SizedWalletShare,
+    ) {
+        self.private_shares = private_shares;
+        self.blinded_public_shares = blinded_public_shares;
+        self.share_commitment = None;
+    }
+
     /// Sample a new blinder and private blinder share
     ///
     /// Returned in order `(new_blinder, new_blinder_private_share)`
@@ -85,6 +184,13 @@ impl Wallet {
     }
 
     /// Reblind the wallet, consuming the next set of blinders and secret shares
+    ///
+    /// The blinder and the private shares are each sampled from their own hash
+    /// chain:
+    /// - The blinder and its private share are the next two elements of the
+    ///   chain seeded by the current private blinder share
+    /// - The remaining private shares are the next `n_shares - 1` elements of
+    ///   the chain seeded by the current last non-blinder private share
     pub fn reblind_wallet(&mut self) {
         let private_shares_serialized: Vec<Scalar> = self.private_shares.to_scalars();
 
@@ -103,10 +209,47 @@ impl Wallet {
             new_blinder,
         );
 
-        self.private_shares = new_private_share;
-        self.blinded_public_shares = new_public_share;
+        self.set_shares(new_private_share, new_public_share);
+        self.blinder = new_blinder;
+        self.invalidate_merkle_opening();
+    }
+
+    /// Reblind the wallet `n` times, leaving it in the same state as `n` calls
+    /// to `reblind_wallet`
+    ///
+    /// Because each reblind seeds its chains with the last element sampled by
+    /// the previous reblind, `n` reblinds sample consecutive elements of the
+    /// same two chains. So we evaluate each chain once and only construct the
+    /// final shares
+    ///
+    /// Returns the public blinder share after each reblind, in order
+    pub fn reblind_n(&mut self, n: usize) -> Vec<Scalar> {
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let blinders = self.future_blinders_and_private_shares(n);
+        let public_blinders =
+            blinders.iter().map(|&(blinder, private)| blinder - private).collect();
+        let (new_blinder, new_blinder_private_share) = blinders[n - 1];
+
+        // Each reblind consumes `n_shares - 1` private shares, keep the last set
+        let n_shares = self.private_shares.to_scalars().len();
+        let share_chain = evaluate_hash_chain(self.get_last_private_share(), n * (n_shares - 1));
+        let mut new_private_shares = share_chain[(n - 1) * (n_shares - 1)..].to_vec();
+        new_private_shares.push(new_blinder_private_share);
+
+        let (new_private_share, new_public_share) = create_wallet_shares_from_private(
+            &self.clone().into(),
+            &WalletShare::from_scalars(&mut new_private_shares.into_iter()),
+            new_blinder,
+        );
+
+        self.set_shares(new_private_share, new_public_share);
         self.blinder = new_blinder;
         self.invalidate_merkle_opening();
+
+        public_blinders
     }
 
     /// Update a wallet from a given set of private and (blinded) public secret
@@ -128,8 +271,7 @@ impl Wallet {
         }
 
         // Update the wallet shares
-        self.private_shares = private_shares.clone();
-        self.blinded_public_shares = blinded_public_shares.clone();
+        self.set_shares(private_shares.clone(), blinded_public_shares.clone());
 
         // The Merkle proof is now invalid
         self.invalidate_merkle_opening();
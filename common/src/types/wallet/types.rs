@@ -6,7 +6,10 @@ use std::sync::{
     atomic::{AtomicUsize, Ordering},
 };
 
-use circuit_types::{balance::Balance, elgamal::EncryptionKey, fixed_point::FixedPoint};
+use circuit_types::{
+    balance::Balance, elgamal::EncryptionKey, fixed_point::FixedPoint,
+    wallet::WalletShareStateCommitment,
+};
 use circuit_types::{native_helpers::create_wallet_shares_with_randomness, traits::BaseType};
 use constants::Scalar;
 use derivative::Derivative;
@@ -60,8 +63,14 @@ pub struct Wallet {
     /// The key of the cluster that the wallet has delegated management to
     pub managing_cluster: EncryptionKey,
     /// The private secret shares of the wallet
+    ///
+    /// Assign through `set_shares`, so that the cached share commitment is
+    /// cleared
     pub private_shares: SizedWalletShare,
     /// The public secret shares of the wallet
+    ///
+    /// Assign through `set_shares`, so that the cached share commitment is
+    /// cleared
     pub blinded_public_shares: SizedWalletShare,
     /// The authentication paths for the public and private shares of the wallet
     #[serde(default)]
@@ -72,6 +81,13 @@ pub struct Wallet {
     #[serde(skip_serializing, skip_deserializing, default)]
     #[derivative(PartialEq = "ignore")]
     pub merkle_staleness: Arc<AtomicUsize>,
+    /// The commitment to the wallet's shares, as last computed by
+    /// `share_commitment_cached`
+    ///
+    /// Cleared by `set_shares` whenever the wallet's shares change
+    #[serde(skip_serializing, skip_deserializing, default)]
+    #[derivative(PartialEq = "ignore")]
+    pub(super) share_commitment: Option<WalletShareStateCommitment>,
 }
 
 impl From<Wallet> for SizedCircuitWallet {
@@ -90,6 +106,37 @@ impl From<Wallet> for SizedCircuitWallet {
 }
 
 impl Wallet {
+    /// Construct a wallet from its contents and secret shares
+    ///
+    /// The wallet has no Merkle opening
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        wallet_id: WalletIdentifier,
+        orders: KeyedList<OrderIdentifier, Order>,
+        balances: KeyedList<BigUint, Balance>,
+        key_chain: KeyChain,
+        blinder: Scalar,
+        max_match_fee: FixedPoint,
+        managing_cluster: EncryptionKey,
+        private_shares: SizedWalletShare,
+        blinded_public_shares: SizedWalletShare,
+    ) -> Self {
+        Self {
+            wallet_id,
+            orders,
+            balances,
+            key_chain,
+            blinder,
+            max_match_fee,
+            managing_cluster,
+            private_shares,
+            blinded_public_shares,
+            merkle_proof: None,
+            merkle_staleness: Default::default(),
+            share_commitment: None,
+        }
+    }
+
     /// Create a new empty wallet from the given seed information
     #[cfg(feature = "all-types")]
     pub fn new_empty_wallet(
@@ -114,6 +161,7 @@ impl Wallet {
             blinder: Scalar::zero(),
             merkle_proof: None,
             merkle_staleness: Arc::new(AtomicUsize::new(0)),
+            share_commitment: None,
         };
 
         // Cast the wallet to a circuit type to use the circuit helpers
@@ -132,8 +180,7 @@ impl Wallet {
             blinder_private,
             private_shares,
         );
-        wallet.private_shares = private_shares;
-        wallet.blinded_public_shares = blinded_public_shares;
+        wallet.set_shares(private_shares, blinded_public_shares);
         wallet.blinder = blinder;
 
         wallet
@@ -175,6 +222,7 @@ impl Wallet {
             blinded_public_shares,
             merkle_proof: None,
             merkle_staleness: Default::default(),
+            share_commitment: None,
         }
     }
 
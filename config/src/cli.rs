@@ -22,7 +22,7 @@ use std::{
     path::Path,
 };
 use url::Url;
-use util::telemetry::configure_telemetry;
+use util::telemetry::{TelemetryGuard, configure_telemetry};
 
 use crate::parsing::{RelayerFeeWhitelistEntry, parse_config_from_args};
 
@@ -440,7 +440,9 @@ impl RelayerConfig {
     }
 
     /// Configure the telemetry layers from the relayer config
-    pub fn configure_telemetry(&self) -> Result<(), String> {
+    ///
+    /// The returned guard should be held for the lifetime of the process
+    pub fn configure_telemetry(&self) -> Result<TelemetryGuard, String> {
         configure_telemetry(
             self.datadog_enabled,
             self.otlp_enabled,
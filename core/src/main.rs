@@ -28,7 +28,7 @@ use job_types::handshake_manager::new_handshake_manager_queue;
 use job_types::network_manager::new_network_manager_queue;
 use job_types::price_reporter::new_price_reporter_queue;
 use job_types::proof_manager::new_proof_manager_queue;
-use job_types::task_driver::new_task_driver_queue;
+use job_types::task_driver::{TaskDriverJob, TaskDriverQueue, new_task_driver_queue};
 use job_types::{event_manager::new_event_manager_queue, gossip_server::new_gossip_server_queue};
 use network_manager::{worker::NetworkManager, worker::NetworkManagerConfig};
 use price_reporter::worker::ExchangeConnectionsConfig;
@@ -41,9 +41,9 @@ use error::CoordinatorError;
 use metrics_sampler::setup_metrics_samplers;
 use system_clock::SystemClock;
 use task_driver::worker::{TaskDriver, TaskDriverConfig};
-use tokio::select;
-use tracing::info;
-use util::err_str;
+use tokio::{select, sync::watch::Sender as WatchSender, time::timeout};
+use tracing::{info, warn};
+use util::{err_str, telemetry::TelemetryGuard};
 
 use crate::setup::node_setup;
 
@@ -87,7 +87,7 @@ async fn main() -> Result<(), CoordinatorError> {
         .expect("error blocking on config parse")
         .expect("error parsing command line args");
     let setup_config = args.clone();
-    args.configure_telemetry().expect("failed to configure telemetry");
+    let telemetry_guard = args.configure_telemetry().expect("failed to configure telemetry");
     let min_order_size = args.min_fill_size_decimal_adjusted();
 
     info!(
@@ -419,8 +419,7 @@ async fn main() -> Result<(), CoordinatorError> {
     let err = loop_res.err().unwrap();
     info!("Error in coordinator thread: {:?}", err);
 
-    // Send cancel signals to all workers
-    for cancel_channel in [
+    let cancel_channels = [
         network_cancel_sender,
         gossip_cancel_sender,
         handshake_cancel_sender,
@@ -428,21 +427,47 @@ async fn main() -> Result<(), CoordinatorError> {
         chain_listener_cancel_sender,
         api_cancel_sender,
         proof_manager_cancel_sender,
-    ]
-    .iter()
-    {
-        cancel_channel.send(()).unwrap();
+    ];
+    shutdown(&task_sender, &cancel_channels, telemetry_guard).await;
+    Err(err)
+}
+
+/// Shut down the relayer
+///
+/// The steps are ordered so that no in-flight work or telemetry is lost:
+///     1. Drain the task driver, running tasks past their commit point to
+///        completion and halting all others
+///     2. Cancel the workers and give them time to teardown
+///     3. Flush metrics and traces, then stop the telemetry exporters
+async fn shutdown(
+    task_queue: &TaskDriverQueue,
+    cancel_channels: &[WatchSender<()>],
+    telemetry_guard: TelemetryGuard,
+) {
+    // Drain the task driver
+    info!("Draining task driver...");
+    let termination_timeout = Duration::from_millis(TERMINATION_TIMEOUT_MS);
+    let (job, drain_recv) = TaskDriverJob::new_drain();
+    if task_queue.send(job).is_err() {
+        warn!("task driver queue closed, skipping drain");
+    } else if timeout(termination_timeout, drain_recv).await.is_err() {
+        warn!("timed out draining task driver");
+    }
+
+    // Send cancel signals to all workers
+    for cancel_channel in cancel_channels.iter() {
+        if let Err(e) = cancel_channel.send(()) {
+            warn!("error sending cancel signal: {e}");
+        }
     }
 
     // Give workers time to teardown execution then terminate
     info!("Tearing down workers...");
-    thread::sleep(Duration::from_millis(TERMINATION_TIMEOUT_MS));
+    thread::sleep(termination_timeout);
     info!("Terminating...");
 
-    if args.otlp_enabled {
-        opentelemetry::global::shutdown_tracer_provider();
-    }
-    Err(err)
+    // Flush and stop telemetry last so that the steps above are recorded
+    drop(telemetry_guard);
 }
 
 /// Attempt to recover a failed module by cleaning up its resources and
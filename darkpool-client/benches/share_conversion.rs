@@ -0,0 +1,80 @@
+//! Benchmarks converting wallet shares and statements to their contract types
+
+#![allow(missing_docs)]
+
+use std::iter;
+
+use circuit_types::{
+    SizedWalletShare, fixed_point::FixedPoint, r#match::OrderSettlementIndices, traits::BaseType,
+};
+use circuits::zk_circuits::valid_match_settle::SizedValidMatchSettleStatement;
+use constants::{Scalar, ScalarField};
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use darkpool_client::arbitrum::contract_types::conversion::to_contract_valid_match_settle_statement;
+use rand::thread_rng;
+
+// -----------
+// | Helpers |
+// -----------
+
+/// Build a set of wallet shares from random scalars
+fn random_shares() -> SizedWalletShare {
+    let mut rng = thread_rng();
+    SizedWalletShare::from_scalars(&mut iter::repeat_with(|| Scalar::random(&mut rng)))
+}
+
+/// Build a `VALID MATCH SETTLE` statement with random shares
+fn random_match_settle_statement() -> SizedValidMatchSettleStatement {
+    let indices = OrderSettlementIndices { balance_send: 0, balance_receive: 1, order: 0 };
+    SizedValidMatchSettleStatement {
+        party0_modified_shares: random_shares(),
+        party1_modified_shares: random_shares(),
+        party0_indices: indices,
+        party1_indices: indices,
+        protocol_fee: FixedPoint::from_f64_round_down(0.0002),
+    }
+}
+
+// --------------
+// | Benchmarks |
+// --------------
+
+/// Compare converting wallet shares through an intermediate vector of scalars
+/// against converting them through an iterator
+fn bench_share_conversion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("share_conversion");
+    let shares = random_shares();
+
+    group.bench_function(BenchmarkId::new("wallet_shares", "to_scalars"), |b| {
+        b.iter(|| {
+            let scalars: Vec<ScalarField> =
+                black_box(&shares).to_scalars().into_iter().map(|s| s.inner()).collect();
+            black_box(scalars)
+        });
+    });
+
+    group.bench_function(BenchmarkId::new("wallet_shares", "scalars_iter"), |b| {
+        b.iter(|| {
+            let scalars: Vec<ScalarField> =
+                black_box(&shares).scalars_iter().map(|s| s.inner()).collect();
+            black_box(scalars)
+        });
+    });
+}
+
+/// Benchmark converting a `VALID MATCH SETTLE` statement to its contract type
+fn bench_match_settle_conversion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("statement_conversion");
+    let statement = random_match_settle_statement();
+
+    group.bench_function(BenchmarkId::new("valid_match_settle", "to_contract"), |b| {
+        b.iter(|| black_box(to_contract_valid_match_settle_statement(black_box(&statement))));
+    });
+}
+
+criterion_group!(
+    name = share_conversion;
+    config = Criterion::default();
+    targets = bench_share_conversion, bench_match_settle_conversion
+);
+criterion_main!(share_conversion);
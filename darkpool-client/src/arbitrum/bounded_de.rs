@@ -0,0 +1,346 @@
+//! A deserializer wrapper that rejects collections whose declared length
+//! exceeds a bound
+//!
+//! Postcard prefixes sequences and maps with their length, which a malicious
+//! calldata may set arbitrarily. Wrapping the deserializer lets us reject such
+//! inputs as soon as the length is read, before any elements are allocated
+
+use std::fmt::{Formatter, Result as FmtResult};
+
+use serde::de::{
+    DeserializeSeed, Deserializer, EnumAccess, Error as DeError, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+
+/// Construct the error emitted when a collection exceeds the length bound
+fn length_error<E: DeError>(len: usize, max_len: usize) -> E {
+    E::custom(format!("collection length {len} exceeds maximum of {max_len}"))
+}
+
+/// Check a declared collection length against the bound
+fn check_len<E: DeError>(len: Option<usize>, max_len: usize) -> Result<(), E> {
+    match len {
+        Some(len) if len > max_len => Err(length_error(len, max_len)),
+        _ => Ok(()),
+    }
+}
+
+// ----------------
+// | Deserializer |
+// ----------------
+
+/// A deserializer that bounds the declared length of every sequence and map
+/// it deserializes, including nested ones
+///
+/// Fixed-length tuples, arrays, and structs are not bounded, as their length
+/// is determined by the type rather than the input
+pub(crate) struct LenBoundedDeserializer<D> {
+    /// The underlying deserializer
+    inner: D,
+    /// The maximum declared length of a collection
+    max_len: usize,
+}
+
+impl<D> LenBoundedDeserializer<D> {
+    /// Constructor
+    pub fn new(inner: D, max_len: usize) -> Self {
+        Self { inner, max_len }
+    }
+}
+
+/// Forward deserializer methods to the inner deserializer, wrapping the visitor
+/// so that nested values are bounded as well
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, Self::Error> {
+                let visitor = LenBoundedVisitor::new(visitor, self.max_len, false /* check_len */);
+                self.inner.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for LenBoundedDeserializer<D> {
+    type Error = D::Error;
+
+    forward_deserialize!(
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    );
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let visitor = LenBoundedVisitor::new(visitor, self.max_len, true /* check_len */);
+        self.inner.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let visitor = LenBoundedVisitor::new(visitor, self.max_len, true /* check_len */);
+        self.inner.deserialize_map(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// A seed that deserializes its value through a `LenBoundedDeserializer`
+struct LenBoundedSeed<S> {
+    /// The underlying seed
+    inner: S,
+    /// The maximum declared length of a collection
+    max_len: usize,
+}
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for LenBoundedSeed<S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        self.inner.deserialize(LenBoundedDeserializer::new(deserializer, self.max_len))
+    }
+}
+
+// -----------
+// | Visitor |
+// -----------
+
+/// A visitor that bounds the collections it is handed
+struct LenBoundedVisitor<V> {
+    /// The underlying visitor
+    inner: V,
+    /// The maximum declared length of a collection
+    max_len: usize,
+    /// Whether to check the length of a sequence or map passed to this
+    /// visitor
+    ///
+    /// Only set when the type requested a variable length collection
+    check_len: bool,
+}
+
+impl<V> LenBoundedVisitor<V> {
+    /// Constructor
+    fn new(inner: V, max_len: usize, check_len: bool) -> Self {
+        Self { inner, max_len, check_len }
+    }
+}
+
+/// Forward visitor methods for primitive values to the inner visitor
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<E: DeError>(self, v: $ty) -> Result<Self::Value, E> {
+                self.inner.$method(v)
+            }
+        )*
+    };
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for LenBoundedVisitor<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        self.inner.expecting(f)
+    }
+
+    forward_visit!(
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_borrowed_str(&'de str),
+        visit_string(String),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>),
+    );
+
+    fn visit_none<E: DeError>(self) -> Result<Self::Value, E> {
+        self.inner.visit_none()
+    }
+
+    fn visit_unit<E: DeError>(self) -> Result<Self::Value, E> {
+        self.inner.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.inner.visit_some(LenBoundedDeserializer::new(deserializer, self.max_len))
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        self.inner.visit_newtype_struct(LenBoundedDeserializer::new(deserializer, self.max_len))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        if self.check_len {
+            check_len(seq.size_hint(), self.max_len)?;
+        }
+
+        self.inner.visit_seq(LenBoundedAccess { inner: seq, max_len: self.max_len })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        if self.check_len {
+            check_len(map.size_hint(), self.max_len)?;
+        }
+
+        self.inner.visit_map(LenBoundedAccess { inner: map, max_len: self.max_len })
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        self.inner.visit_enum(LenBoundedAccess { inner: data, max_len: self.max_len })
+    }
+}
+
+// -------------
+// | Accessors |
+// -------------
+
+/// A wrapper around a seq, map, enum, or variant accessor that bounds the
+/// values it yields
+struct LenBoundedAccess<A> {
+    /// The underlying accessor
+    inner: A,
+    /// The maximum declared length of a collection
+    max_len: usize,
+}
+
+impl<A> LenBoundedAccess<A> {
+    /// Wrap a seed so that its value is bounded
+    fn seed<S>(&self, seed: S) -> LenBoundedSeed<S> {
+        LenBoundedSeed { inner: seed, max_len: self.max_len }
+    }
+}
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'de> for LenBoundedAccess<A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        let seed = self.seed(seed);
+        self.inner.next_element_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for LenBoundedAccess<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let seed = self.seed(seed);
+        self.inner.next_key_seed(seed)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let seed = self.seed(seed);
+        self.inner.next_value_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, A: EnumAccess<'de>> EnumAccess<'de> for LenBoundedAccess<A> {
+    type Error = A::Error;
+    type Variant = LenBoundedAccess<A::Variant>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let max_len = self.max_len;
+        let seed = self.seed(seed);
+        let (value, variant) = self.inner.variant_seed(seed)?;
+        Ok((value, LenBoundedAccess { inner: variant, max_len }))
+    }
+}
+
+impl<'de, A: VariantAccess<'de>> VariantAccess<'de> for LenBoundedAccess<A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        self.inner.unit_variant()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        let seed = self.seed(seed);
+        self.inner.newtype_variant_seed(seed)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let visitor = LenBoundedVisitor::new(visitor, self.max_len, false /* check_len */);
+        self.inner.tuple_variant(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let visitor = LenBoundedVisitor::new(visitor, self.max_len, false /* check_len */);
+        self.inner.struct_variant(fields, visitor)
+    }
+}
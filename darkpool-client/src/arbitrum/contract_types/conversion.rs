@@ -6,6 +6,7 @@ use std::str::FromStr;
 use alloy_primitives::U256;
 use ark_bn254::g1::Config as G1Config;
 use ark_ec::short_weierstrass::Affine;
+use ark_ff::PrimeField;
 use circuit_types::{
     PlonkLinkProof, PlonkProof, PolynomialCommitment, SizedWalletShare,
     elgamal::{ElGamalCiphertext, EncryptionKey},
@@ -33,7 +34,7 @@ use common::types::{
     proof_bundles::{MatchBundle, OrderValidityProofBundle},
     transfer_auth::TransferAuth,
 };
-use constants::{Scalar, ScalarField};
+use constants::{MAX_BALANCES, MAX_ORDERS, Scalar, ScalarField};
 use util::hex::biguint_to_hex_string;
 
 use super::{
@@ -72,23 +73,17 @@ pub type G1Affine = Affine<G1Config>;
 /// Convert a [`PlonkProof`] to its corresponding smart contract type
 pub fn to_contract_proof(proof: &PlonkProof) -> Result<ContractProof, ConversionError> {
     Ok(ContractProof {
-        wire_comms: try_unwrap_commitments(&proof.wires_poly_comms)?,
+        wire_comms: try_unwrap_commitments(&proof.wires_poly_comms)
+            .map_err(|e| e.with_field("wire_comms"))?,
         z_comm: proof.prod_perm_poly_comm.0,
-        quotient_comms: try_unwrap_commitments(&proof.split_quot_poly_comms)?,
+        quotient_comms: try_unwrap_commitments(&proof.split_quot_poly_comms)
+            .map_err(|e| e.with_field("quotient_comms"))?,
         w_zeta: proof.opening_proof.0,
         w_zeta_omega: proof.shifted_opening_proof.0,
-        wire_evals: proof
-            .poly_evals
-            .wires_evals
-            .clone()
-            .try_into()
-            .map_err(|_| ConversionError::InvalidLength)?,
-        sigma_evals: proof
-            .poly_evals
-            .wire_sigma_evals
-            .clone()
-            .try_into()
-            .map_err(|_| ConversionError::InvalidLength)?,
+        wire_evals: try_size_vec(proof.poly_evals.wires_evals.clone())
+            .map_err(|e| e.with_field("wire_evals"))?,
+        sigma_evals: try_size_vec(proof.poly_evals.wire_sigma_evals.clone())
+            .map_err(|e| e.with_field("sigma_evals"))?,
         z_bar: proof.poly_evals.perm_next_eval,
     })
 }
@@ -107,34 +102,64 @@ pub fn to_contract_link_proof(
 pub fn to_contract_external_transfer(
     external_transfer: &ExternalTransfer,
 ) -> Result<ContractExternalTransfer, ConversionError> {
-    let account_addr = biguint_to_address(&external_transfer.account_addr)?;
-    let mint = biguint_to_address(&external_transfer.mint)?;
-    let amount = amount_to_u256(external_transfer.amount)?;
+    let account_addr = biguint_to_address(&external_transfer.account_addr)
+        .map_err(|e| e.with_field("account_addr"))?;
+    let mint = biguint_to_address(&external_transfer.mint).map_err(|e| e.with_field("mint"))?;
+    let amount = amount_to_u256(external_transfer.amount).map_err(|e| e.with_field("amount"))?;
 
     Ok(ContractExternalTransfer {
         account_addr,
         mint,
         amount,
-        is_withdrawal: external_transfer.direction == ExternalTransferDirection::Withdrawal,
+        is_withdrawal: external_transfer.direction.is_withdrawal(),
     })
 }
 
+/// Convert a contract [`ExternalTransfer`] to an [`ExternalTransfer`]
+pub fn to_circuit_external_transfer(
+    external_transfer: &ContractExternalTransfer,
+) -> Result<ExternalTransfer, ConversionError> {
+    let account_addr = address_to_biguint(&external_transfer.account_addr)
+        .map_err(|e| e.with_field("account_addr"))?;
+    let mint = address_to_biguint(&external_transfer.mint).map_err(|e| e.with_field("mint"))?;
+    let amount = u256_to_amount(external_transfer.amount).map_err(|e| e.with_field("amount"))?;
+    let direction = ExternalTransferDirection::from_is_withdrawal(external_transfer.is_withdrawal);
+
+    Ok(ExternalTransfer { account_addr, mint, amount, direction })
+}
+
 /// Convert a [`PublicSigningKey`] to its corresponding smart contract type
 pub fn to_contract_public_signing_key(
     public_signing_key: &PublicSigningKey,
 ) -> Result<ContractPublicSigningKey, ConversionError> {
-    let x = try_unwrap_scalars(&public_signing_key.x.to_scalars())?;
-    let y = try_unwrap_scalars(&public_signing_key.y.to_scalars())?;
+    let x =
+        try_unwrap_scalars(&public_signing_key.x.to_scalars()).map_err(|e| e.with_field("x"))?;
+    let y =
+        try_unwrap_scalars(&public_signing_key.y.to_scalars()).map_err(|e| e.with_field("y"))?;
 
     Ok(ContractPublicSigningKey { x, y })
 }
 
+/// Convert a contract [`PublicSigningKey`] to a [`PublicSigningKey`]
+pub fn to_circuit_public_signing_key(
+    public_signing_key: &ContractPublicSigningKey,
+) -> Result<PublicSigningKey, ConversionError> {
+    let scalars = public_signing_key
+        .x
+        .iter()
+        .chain(public_signing_key.y.iter())
+        .copied()
+        .map(try_to_scalar)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(PublicSigningKey::from_scalars(&mut scalars.into_iter()))
+}
+
 /// Convert a [`SizedValidWalletCreateStatement`] to its corresponding smart
 /// contract type
 pub fn to_contract_valid_wallet_create_statement(
     statement: &SizedValidWalletCreateStatement,
 ) -> ContractValidWalletCreateStatement {
-    let public_wallet_shares = wallet_shares_to_scalar_vec(&statement.public_wallet_shares);
+    let public_wallet_shares = scalars_to_field_vec(&statement.public_wallet_shares);
 
     ContractValidWalletCreateStatement {
         wallet_share_commitment: statement.wallet_share_commitment.inner(),
@@ -147,15 +172,19 @@ pub fn to_contract_valid_wallet_create_statement(
 pub fn to_contract_valid_wallet_update_statement(
     statement: &SizedValidWalletUpdateStatement,
 ) -> Result<ContractValidWalletUpdateStatement, ConversionError> {
-    let new_public_shares = wallet_shares_to_scalar_vec(&statement.new_public_shares);
+    let new_public_shares = scalars_to_field_vec(&statement.new_public_shares);
     let external_transfer: Option<ContractExternalTransfer> =
         if statement.external_transfer.is_default() {
             None
         } else {
-            Some(to_contract_external_transfer(&statement.external_transfer)?)
+            Some(
+                to_contract_external_transfer(&statement.external_transfer)
+                    .map_err(|e| e.with_field("external_transfer"))?,
+            )
         };
 
-    let old_pk_root = to_contract_public_signing_key(&statement.old_pk_root)?;
+    let old_pk_root = to_contract_public_signing_key(&statement.old_pk_root)
+        .map_err(|e| e.with_field("old_pk_root"))?;
 
     Ok(ContractValidWalletUpdateStatement {
         old_shares_nullifier: statement.old_shares_nullifier.inner(),
@@ -167,6 +196,36 @@ pub fn to_contract_valid_wallet_update_statement(
     })
 }
 
+/// Convert a contract [`ValidWalletUpdateStatement`] to a
+/// [`SizedValidWalletUpdateStatement`]
+///
+/// A missing external transfer maps back to the default [`ExternalTransfer`]
+pub fn to_circuit_valid_wallet_update_statement(
+    statement: &ContractValidWalletUpdateStatement,
+) -> Result<SizedValidWalletUpdateStatement, ConversionError> {
+    let new_public_shares = scalar_vec_to_wallet_shares(&statement.new_public_shares)
+        .map_err(|e| e.with_field("new_public_shares"))?;
+    let external_transfer = match &statement.external_transfer {
+        Some(transfer) => {
+            to_circuit_external_transfer(transfer).map_err(|e| e.with_field("external_transfer"))?
+        },
+        None => ExternalTransfer::default(),
+    };
+
+    Ok(SizedValidWalletUpdateStatement {
+        old_shares_nullifier: try_to_scalar(statement.old_shares_nullifier)
+            .map_err(|e| e.with_field("old_shares_nullifier"))?,
+        new_wallet_commitment: try_to_scalar(statement.new_wallet_commitment)
+            .map_err(|e| e.with_field("new_wallet_commitment"))?,
+        new_public_shares,
+        merkle_root: try_to_scalar(statement.merkle_root)
+            .map_err(|e| e.with_field("merkle_root"))?,
+        external_transfer,
+        old_pk_root: to_circuit_public_signing_key(&statement.old_pk_root)
+            .map_err(|e| e.with_field("old_pk_root"))?,
+    })
+}
+
 /// Convert a [`TransferAuth`] to its corresponding smart contract type
 pub fn to_contract_transfer_aux_data(
     data: TransferAuth,
@@ -222,6 +281,10 @@ pub fn to_contract_valid_commitments_statement(
 pub fn to_contract_order_settlement_indices(
     indices: &OrderSettlementIndices,
 ) -> ContractOrderSettlementIndices {
+    debug_assert!(indices.balance_send < MAX_BALANCES, "balance_send out of range");
+    debug_assert!(indices.balance_receive < MAX_BALANCES, "balance_receive out of range");
+    debug_assert!(indices.order < MAX_ORDERS, "order out of range");
+
     ContractOrderSettlementIndices {
         balance_send: indices.balance_send as u64,
         balance_receive: indices.balance_receive as u64,
@@ -231,13 +294,23 @@ pub fn to_contract_order_settlement_indices(
 
 /// Convert a contract [`OrderSettlementIndices`] to a
 /// [`OrderSettlementIndices`]
+///
+/// Errors if an index does not address a balance or order in a wallet
 pub fn to_circuit_order_settlement_indices(
     indices: &ContractOrderSettlementIndices,
-) -> OrderSettlementIndices {
-    OrderSettlementIndices {
-        balance_send: indices.balance_send as usize,
-        balance_receive: indices.balance_receive as usize,
-        order: indices.order as usize,
+) -> Result<OrderSettlementIndices, ConversionError> {
+    Ok(OrderSettlementIndices {
+        balance_send: check_index("balance_send", indices.balance_send, MAX_BALANCES)?,
+        balance_receive: check_index("balance_receive", indices.balance_receive, MAX_BALANCES)?,
+        order: check_index("order", indices.order, MAX_ORDERS)?,
+    })
+}
+
+/// Check that an index addresses an element of an array of size `max`
+fn check_index(field: &'static str, index: u64, max: usize) -> Result<usize, ConversionError> {
+    match usize::try_from(index) {
+        Ok(idx) if idx < max => Ok(idx),
+        _ => Err(ConversionError::IndexOutOfRange { field, index, max }),
     }
 }
 
@@ -246,8 +319,8 @@ pub fn to_circuit_order_settlement_indices(
 pub fn to_contract_valid_match_settle_statement(
     statement: &SizedValidMatchSettleStatement,
 ) -> ContractValidMatchSettleStatement {
-    let party0_modified_shares = wallet_shares_to_scalar_vec(&statement.party0_modified_shares);
-    let party1_modified_shares = wallet_shares_to_scalar_vec(&statement.party1_modified_shares);
+    let party0_modified_shares = scalars_to_field_vec(&statement.party0_modified_shares);
+    let party1_modified_shares = scalars_to_field_vec(&statement.party1_modified_shares);
     let party0_indices = to_contract_order_settlement_indices(&statement.party0_indices);
     let party1_indices = to_contract_order_settlement_indices(&statement.party1_indices);
 
@@ -260,6 +333,32 @@ pub fn to_contract_valid_match_settle_statement(
     }
 }
 
+/// Convert a contract [`ValidMatchSettleStatement`] to a
+/// [`SizedValidMatchSettleStatement`]
+pub fn to_circuit_valid_match_settle_statement(
+    statement: &ContractValidMatchSettleStatement,
+) -> Result<SizedValidMatchSettleStatement, ConversionError> {
+    let party0_modified_shares = scalar_vec_to_wallet_shares(&statement.party0_modified_shares)
+        .map_err(|e| e.with_field("party0_modified_shares"))?;
+    let party1_modified_shares = scalar_vec_to_wallet_shares(&statement.party1_modified_shares)
+        .map_err(|e| e.with_field("party1_modified_shares"))?;
+    let party0_indices = to_circuit_order_settlement_indices(&statement.party0_indices)
+        .map_err(|e| e.with_field("party0_indices"))?;
+    let party1_indices = to_circuit_order_settlement_indices(&statement.party1_indices)
+        .map_err(|e| e.with_field("party1_indices"))?;
+    let protocol_fee =
+        try_to_circuit_fixed_point(&ContractFixedPoint { repr: statement.protocol_fee })
+            .map_err(|e| e.with_field("protocol_fee"))?;
+
+    Ok(SizedValidMatchSettleStatement {
+        party0_modified_shares,
+        party1_modified_shares,
+        party0_indices,
+        party1_indices,
+        protocol_fee,
+    })
+}
+
 /// Convert a [`ExternalMatchResult`] to its corresponding smart contract type
 pub fn to_contract_external_match_result(
     match_result: &ExternalMatchResult,
@@ -316,7 +415,8 @@ pub fn to_circuit_bounded_match_result(
 ) -> Result<BoundedMatchResult, ConversionError> {
     let quote_mint = address_to_biguint(&match_result.quote_mint)?;
     let base_mint = address_to_biguint(&match_result.base_mint)?;
-    let price = to_circuit_fixed_point(&match_result.price);
+    let price =
+        try_to_circuit_fixed_point(&match_result.price).map_err(|e| e.with_field("price"))?;
     let min_base_amount = u256_to_amount(match_result.min_base_amount)?;
     let max_base_amount = u256_to_amount(match_result.max_base_amount)?;
 
@@ -348,10 +448,14 @@ pub fn to_contract_fee_rates(fee_rates: &FeeTakeRate) -> Result<ContractFeeRates
 
 /// Convert a contract [`FeeRates`] to a [`FeeRates`]
 pub fn to_circuit_fee_rates(fee_rates: &ContractFeeRates) -> Result<FeeTakeRate, ConversionError> {
-    Ok(FeeTakeRate {
-        relayer_fee_rate: to_circuit_fixed_point(&fee_rates.relayer_fee_rate),
-        protocol_fee_rate: to_circuit_fixed_point(&fee_rates.protocol_fee_rate),
-    })
+    let relayer_fee_rate = try_to_circuit_fixed_point(&fee_rates.relayer_fee_rate)
+        .map_err(|e| e.with_field("relayer_fee_rate"))?;
+    let protocol_fee_rate = try_to_circuit_fixed_point(&fee_rates.protocol_fee_rate)
+        .map_err(|e| e.with_field("protocol_fee_rate"))?;
+
+    let rates = FeeTakeRate { relayer_fee_rate, protocol_fee_rate };
+    rates.validate().map_err(ConversionError::InvalidFeeRate)?;
+    Ok(rates)
 }
 
 /// Convert a [`SizedValidMatchSettleAtomicStatement`] to its corresponding
@@ -360,17 +464,20 @@ pub fn to_contract_valid_match_settle_atomic_statement(
     statement: &SizedValidMatchSettleAtomicStatement,
 ) -> Result<ContractValidMatchSettleAtomicStatement, ConversionError> {
     let internal_party_modified_shares =
-        wallet_shares_to_scalar_vec(&statement.internal_party_modified_shares);
+        scalars_to_field_vec(&statement.internal_party_modified_shares);
     let internal_party_indices =
         to_contract_order_settlement_indices(&statement.internal_party_indices);
 
     Ok(ContractValidMatchSettleAtomicStatement {
-        match_result: to_contract_external_match_result(&statement.match_result)?,
-        external_party_fees: to_contract_fee_take(&statement.external_party_fees)?,
+        match_result: to_contract_external_match_result(&statement.match_result)
+            .map_err(|e| e.with_field("match_result"))?,
+        external_party_fees: to_contract_fee_take(&statement.external_party_fees)
+            .map_err(|e| e.with_field("external_party_fees"))?,
         internal_party_modified_shares,
         internal_party_indices,
         protocol_fee: statement.protocol_fee.repr.inner(),
-        relayer_fee_address: biguint_to_address(&statement.relayer_fee_address)?,
+        relayer_fee_address: biguint_to_address(&statement.relayer_fee_address)
+            .map_err(|e| e.with_field("relayer_fee_address"))?,
     })
 }
 
@@ -380,14 +487,18 @@ pub fn to_contract_valid_malleable_match_settle_atomic_statement(
     statement: &SizedValidMalleableMatchSettleAtomicStatement,
 ) -> Result<ContractValidMalleableMatchSettleAtomicStatement, ConversionError> {
     let internal_party_public_shares =
-        wallet_shares_to_scalar_vec(&statement.internal_party_public_shares);
+        scalars_to_field_vec(&statement.internal_party_public_shares);
 
     Ok(ContractValidMalleableMatchSettleAtomicStatement {
-        match_result: to_contract_bounded_match_result(&statement.bounded_match_result)?,
-        external_fee_rates: to_contract_fee_rates(&statement.external_fee_rates)?,
-        internal_fee_rates: to_contract_fee_rates(&statement.internal_fee_rates)?,
+        match_result: to_contract_bounded_match_result(&statement.bounded_match_result)
+            .map_err(|e| e.with_field("match_result"))?,
+        external_fee_rates: to_contract_fee_rates(&statement.external_fee_rates)
+            .map_err(|e| e.with_field("external_fee_rates"))?,
+        internal_fee_rates: to_contract_fee_rates(&statement.internal_fee_rates)
+            .map_err(|e| e.with_field("internal_fee_rates"))?,
         internal_party_public_shares,
-        relayer_fee_address: biguint_to_address(&statement.relayer_fee_address)?,
+        relayer_fee_address: biguint_to_address(&statement.relayer_fee_address)
+            .map_err(|e| e.with_field("relayer_fee_address"))?,
     })
 }
 
@@ -462,19 +573,12 @@ pub fn to_contract_valid_relayer_fee_settlement_statement(
         recipient_nullifier: statement.recipient_nullifier.inner(),
         sender_wallet_commitment: statement.sender_wallet_commitment.inner(),
         recipient_wallet_commitment: statement.recipient_wallet_commitment.inner(),
-        sender_updated_public_shares: statement
-            .sender_updated_public_shares
-            .to_scalars()
-            .iter()
-            .map(|s| s.inner())
-            .collect(),
-        recipient_updated_public_shares: statement
-            .recipient_updated_public_shares
-            .to_scalars()
-            .iter()
-            .map(|s| s.inner())
-            .collect(),
-        recipient_pk_root: to_contract_public_signing_key(&statement.recipient_pk_root)?,
+        sender_updated_public_shares: scalars_to_field_vec(&statement.sender_updated_public_shares),
+        recipient_updated_public_shares: scalars_to_field_vec(
+            &statement.recipient_updated_public_shares,
+        ),
+        recipient_pk_root: to_contract_public_signing_key(&statement.recipient_pk_root)
+            .map_err(|e| e.with_field("recipient_pk_root"))?,
     })
 }
 
@@ -494,6 +598,21 @@ pub fn to_contract_note_ciphertext(
     )
 }
 
+/// Converts a [`ContractNoteCiphertext`] to a [`ElGamalCiphertext`]
+pub fn to_circuit_note_ciphertext(
+    note_ciphertext: &ContractNoteCiphertext,
+) -> Result<ElGamalCiphertext<NOTE_CIPHERTEXT_SIZE>, ConversionError> {
+    Ok(ElGamalCiphertext {
+        ephemeral_key: to_circuit_public_encryption_key(&note_ciphertext.0)
+            .map_err(|e| e.with_field("ephemeral_key"))?,
+        ciphertext: [
+            try_to_scalar(note_ciphertext.1).map_err(|e| e.with_field("ciphertext"))?,
+            try_to_scalar(note_ciphertext.2).map_err(|e| e.with_field("ciphertext"))?,
+            try_to_scalar(note_ciphertext.3).map_err(|e| e.with_field("ciphertext"))?,
+        ],
+    })
+}
+
 /// Converts an [`EncryptionKey`] (from prover-side code) to a
 /// [`ContractPublicEncryptionKey`]
 pub fn to_contract_public_encryption_key(
@@ -505,6 +624,25 @@ pub fn to_contract_public_encryption_key(
     }
 }
 
+/// Converts a [`ContractPublicEncryptionKey`] to an [`EncryptionKey`]
+///
+/// Rejects keys with a zero x coordinate. Of these, only the identity and the
+/// point of order two lie on the curve, and neither is a valid key; the zero
+/// point most likely comes from corrupted calldata
+pub fn to_circuit_public_encryption_key(
+    public_encryption_key: &ContractPublicEncryptionKey,
+) -> Result<EncryptionKey, ConversionError> {
+    let key = EncryptionKey {
+        x: try_to_scalar(public_encryption_key.x).map_err(|e| e.with_field("x"))?,
+        y: try_to_scalar(public_encryption_key.y).map_err(|e| e.with_field("y"))?,
+    };
+
+    if key.x == Scalar::zero() {
+        return Err(ConversionError::InvalidEncryptionKey);
+    }
+    Ok(key)
+}
+
 /// Converts a [`SizedValidOfflineFeeSettlementStatement`] (from prover-side
 /// code) to a [`ContractValidOfflineFeeSettlementStatement`]
 pub fn to_contract_valid_offline_fee_settlement_statement(
@@ -514,12 +652,7 @@ pub fn to_contract_valid_offline_fee_settlement_statement(
         merkle_root: statement.merkle_root.inner(),
         nullifier: statement.nullifier.inner(),
         new_wallet_commitment: statement.new_wallet_commitment.inner(),
-        updated_wallet_public_shares: statement
-            .updated_wallet_public_shares
-            .to_scalars()
-            .iter()
-            .map(|s| s.inner())
-            .collect(),
+        updated_wallet_public_shares: scalars_to_field_vec(&statement.updated_wallet_public_shares),
         note_ciphertext: to_contract_note_ciphertext(&statement.note_ciphertext),
         note_commitment: statement.note_commitment.inner(),
         protocol_key: to_contract_public_encryption_key(&statement.protocol_key),
@@ -527,6 +660,31 @@ pub fn to_contract_valid_offline_fee_settlement_statement(
     }
 }
 
+/// Converts a [`ContractValidOfflineFeeSettlementStatement`] to a
+/// [`SizedValidOfflineFeeSettlementStatement`]
+pub fn to_circuit_valid_offline_fee_settlement_statement(
+    statement: &ContractValidOfflineFeeSettlementStatement,
+) -> Result<SizedValidOfflineFeeSettlementStatement, ConversionError> {
+    Ok(SizedValidOfflineFeeSettlementStatement {
+        merkle_root: try_to_scalar(statement.merkle_root)
+            .map_err(|e| e.with_field("merkle_root"))?,
+        nullifier: try_to_scalar(statement.nullifier).map_err(|e| e.with_field("nullifier"))?,
+        new_wallet_commitment: try_to_scalar(statement.new_wallet_commitment)
+            .map_err(|e| e.with_field("new_wallet_commitment"))?,
+        updated_wallet_public_shares: scalar_vec_to_wallet_shares(
+            &statement.updated_wallet_public_shares,
+        )
+        .map_err(|e| e.with_field("updated_wallet_public_shares"))?,
+        note_ciphertext: to_circuit_note_ciphertext(&statement.note_ciphertext)
+            .map_err(|e| e.with_field("note_ciphertext"))?,
+        note_commitment: try_to_scalar(statement.note_commitment)
+            .map_err(|e| e.with_field("note_commitment"))?,
+        protocol_key: to_circuit_public_encryption_key(&statement.protocol_key)
+            .map_err(|e| e.with_field("protocol_key"))?,
+        is_protocol_fee: statement.is_protocol_fee,
+    })
+}
+
 /// Converts a [`SizedValidFeeRedemptionStatement`] (from prover-side code) to a
 /// [`ContractValidFeeRedemptionStatement`]
 pub fn to_contract_valid_fee_redemption_statement(
@@ -538,13 +696,9 @@ pub fn to_contract_valid_fee_redemption_statement(
         nullifier: statement.wallet_nullifier.inner(),
         note_nullifier: statement.note_nullifier.inner(),
         new_shares_commitment: statement.new_shares_commitment.inner(),
-        new_wallet_public_shares: statement
-            .new_wallet_public_shares
-            .to_scalars()
-            .iter()
-            .map(|s| s.inner())
-            .collect(),
-        old_pk_root: to_contract_public_signing_key(&statement.recipient_root_key)?,
+        new_wallet_public_shares: scalars_to_field_vec(&statement.new_wallet_public_shares),
+        old_pk_root: to_contract_public_signing_key(&statement.recipient_root_key)
+            .map_err(|e| e.with_field("recipient_root_key"))?,
     })
 }
 
@@ -563,17 +717,21 @@ pub fn to_circuit_fixed_point(fixed_point: &ContractFixedPoint) -> FixedPoint {
     FixedPoint::from_repr(repr)
 }
 
+/// Convert a contract [`FixedPoint`] to a [`FixedPoint`], rejecting a
+/// representation that is not a canonical field element
+pub fn try_to_circuit_fixed_point(
+    fixed_point: &ContractFixedPoint,
+) -> Result<FixedPoint, ConversionError> {
+    check_canonical_scalar(&fixed_point.repr)?;
+    Ok(to_circuit_fixed_point(fixed_point))
+}
+
 /// Try to extract a fixed-length array of G1Affine points
 /// from a slice of proof system commitments
 pub fn try_unwrap_commitments<const N: usize>(
     comms: &[PolynomialCommitment],
 ) -> Result<[G1Affine; N], ConversionError> {
-    comms
-        .iter()
-        .map(|c| c.0)
-        .collect::<Vec<_>>()
-        .try_into()
-        .map_err(|_| ConversionError::InvalidLength)
+    try_size_vec(comms.iter().map(|c| c.0).collect())
 }
 
 /// Try to extract a fixed-length array of `ScalarField` elements
@@ -581,16 +739,504 @@ pub fn try_unwrap_commitments<const N: usize>(
 fn try_unwrap_scalars<const N: usize>(
     scalars: &[Scalar],
 ) -> Result<[ScalarField; N], ConversionError> {
-    scalars
-        .iter()
-        .map(|s| s.inner())
-        .collect::<Vec<_>>()
-        .try_into()
-        .map_err(|_| ConversionError::InvalidLength)
+    let scalars = scalar_slice_to_field_vec(scalars);
+    scalars.iter().try_for_each(check_canonical_scalar)?;
+    try_size_vec(scalars)
+}
+
+/// Convert a decoded `ScalarField` element to a [`Scalar`], rejecting a
+/// representation that is not a canonical field element
+fn try_to_scalar(scalar: ScalarField) -> Result<Scalar, ConversionError> {
+    check_canonical_scalar(&scalar)?;
+    Ok(Scalar::new(scalar))
+}
+
+/// Check that a decoded `ScalarField` element is canonical, i.e. that its
+/// (Montgomery form) representation is less than the field modulus
+///
+/// Deserialization takes the representation as-is, so a non-canonical value
+/// would otherwise be silently reduced in arithmetic
+fn check_canonical_scalar(scalar: &ScalarField) -> Result<(), ConversionError> {
+    if scalar.0 >= ScalarField::MODULUS {
+        return Err(ConversionError::NonCanonicalScalar);
+    }
+
+    Ok(())
+}
+
+/// Try to coerce a vector into a fixed-length array, reporting the expected
+/// and actual lengths on failure
+fn try_size_vec<const N: usize, T>(vec: Vec<T>) -> Result<[T; N], ConversionError> {
+    let actual = vec.len();
+    vec.try_into().map_err(|_| ConversionError::InvalidLength { expected: N, actual })
+}
+
+/// Convert a slice of `Scalar`s into a vector of `ScalarField` elements
+fn scalar_slice_to_field_vec(scalars: &[Scalar]) -> Vec<ScalarField> {
+    scalars.iter().map(Scalar::inner).collect()
 }
 
 /// Convert a set of wallet secret shares into a vector of `ScalarField`
 /// elements
-fn wallet_shares_to_scalar_vec(shares: &SizedWalletShare) -> Vec<ScalarField> {
-    shares.to_scalars().into_iter().map(|s| s.inner()).collect()
+///
+/// The contracts decode shares positionally, so the vector must hold exactly
+/// one element per share scalar
+fn scalars_to_field_vec(shares: &SizedWalletShare) -> Vec<ScalarField> {
+    let scalars: Vec<ScalarField> = shares.scalars_iter().map(|s| s.inner()).collect();
+    debug_assert_eq!(scalars.len(), SizedWalletShare::NUM_SCALARS, "wallet share length mismatch");
+    scalars
+}
+
+/// Convert a vector of `ScalarField` elements into a set of wallet secret
+/// shares
+fn scalar_vec_to_wallet_shares(
+    scalars: &[ScalarField],
+) -> Result<SizedWalletShare, ConversionError> {
+    let expected = SizedWalletShare::NUM_SCALARS;
+    if scalars.len() != expected {
+        return Err(ConversionError::InvalidLength { expected, actual: scalars.len() });
+    }
+
+    let scalars = scalars.iter().copied().map(try_to_scalar).collect::<Result<Vec<_>, _>>()?;
+    Ok(SizedWalletShare::from_scalars(&mut scalars.into_iter()))
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use std::iter;
+
+    use alloy_primitives::U256;
+    use ark_ff::{BigInt, BigInteger, PrimeField};
+    use circuit_types::{
+        SizedWalletShare,
+        elgamal::{DecryptionKey, ElGamalCiphertext, EncryptionKey},
+        fees::{FeeRateError, FeeTakeRate},
+        fixed_point::FixedPoint,
+        keychain::PublicSigningKey,
+        r#match::OrderSettlementIndices,
+        native_helpers::encrypt_note,
+        note::Note,
+        traits::BaseType,
+        transfers::{ExternalTransfer, ExternalTransferDirection},
+    };
+    use circuits::zk_circuits::{
+        valid_match_settle::SizedValidMatchSettleStatement,
+        valid_offline_fee_settlement::SizedValidOfflineFeeSettlementStatement,
+        valid_wallet_update::SizedValidWalletUpdateStatement,
+    };
+    use constants::{MAX_BALANCES, MAX_ORDERS, Scalar, ScalarField};
+    use num_bigint::BigUint;
+    use rand::{Rng, thread_rng};
+
+    use super::{
+        ContractFixedPoint, ContractPublicEncryptionKey,
+        ContractValidOfflineFeeSettlementStatement, ContractValidWalletUpdateStatement,
+        scalar_vec_to_wallet_shares, scalars_to_field_vec, to_circuit_external_transfer,
+        to_circuit_fee_rates, to_circuit_note_ciphertext, to_circuit_public_encryption_key,
+        to_circuit_valid_match_settle_statement, to_circuit_valid_offline_fee_settlement_statement,
+        to_circuit_valid_wallet_update_statement, to_contract_external_transfer,
+        to_contract_fee_rates, to_contract_note_ciphertext, to_contract_public_encryption_key,
+        to_contract_valid_match_settle_statement,
+        to_contract_valid_offline_fee_settlement_statement,
+        to_contract_valid_wallet_update_statement, try_to_circuit_fixed_point, try_unwrap_scalars,
+    };
+    use crate::{conversion::try_u256_to_scalar, errors::ConversionError};
+
+    // -----------
+    // | Helpers |
+    // -----------
+
+    /// Build a value of a base type from random scalars
+    fn random_base_type<T: BaseType>() -> T {
+        let mut rng = thread_rng();
+        T::from_scalars(&mut iter::repeat_with(|| Scalar::random(&mut rng)))
+    }
+
+    /// Build a random external transfer that fits in the contract types
+    fn random_external_transfer() -> ExternalTransfer {
+        let mut rng = thread_rng();
+        let direction = if rng.gen_bool(0.5) {
+            ExternalTransferDirection::Deposit
+        } else {
+            ExternalTransferDirection::Withdrawal
+        };
+
+        ExternalTransfer {
+            account_addr: BigUint::from(rng.r#gen::<u128>()),
+            mint: BigUint::from(rng.r#gen::<u128>()),
+            amount: rng.r#gen(),
+            direction,
+        }
+    }
+
+    /// Build random settlement indices
+    fn random_indices() -> OrderSettlementIndices {
+        let mut rng = thread_rng();
+        OrderSettlementIndices {
+            balance_send: rng.gen_range(0..MAX_BALANCES),
+            balance_receive: rng.gen_range(0..MAX_BALANCES),
+            order: rng.gen_range(0..MAX_ORDERS),
+        }
+    }
+
+    /// Build a random `VALID OFFLINE FEE SETTLEMENT` statement
+    fn random_offline_fee_statement() -> SizedValidOfflineFeeSettlementStatement {
+        SizedValidOfflineFeeSettlementStatement {
+            merkle_root: random_base_type(),
+            nullifier: random_base_type(),
+            new_wallet_commitment: random_base_type(),
+            updated_wallet_public_shares: random_base_type(),
+            note_ciphertext: random_base_type::<ElGamalCiphertext<_>>(),
+            note_commitment: random_base_type(),
+            protocol_key: random_base_type::<EncryptionKey>(),
+            is_protocol_fee: thread_rng().gen_bool(0.5),
+        }
+    }
+
+    /// A field element whose representation is the modulus itself
+    fn non_canonical_scalar() -> ScalarField {
+        ScalarField::new_unchecked(ScalarField::MODULUS)
+    }
+
+    /// Build a random `VALID WALLET UPDATE` statement with the given transfer
+    fn random_wallet_update_statement(
+        external_transfer: ExternalTransfer,
+    ) -> SizedValidWalletUpdateStatement {
+        SizedValidWalletUpdateStatement {
+            old_shares_nullifier: random_base_type(),
+            new_wallet_commitment: random_base_type(),
+            new_public_shares: random_base_type(),
+            merkle_root: random_base_type(),
+            external_transfer,
+            old_pk_root: random_base_type::<PublicSigningKey>(),
+        }
+    }
+
+    // ---------
+    // | Tests |
+    // ---------
+
+    /// Tests the round trip conversion of deposits and withdrawals, including
+    /// zero amount transfers
+    #[test]
+    fn test_external_transfer_round_trip() {
+        let directions =
+            [ExternalTransferDirection::Deposit, ExternalTransferDirection::Withdrawal];
+        for direction in directions {
+            let mut transfer = random_external_transfer();
+            transfer.direction = direction;
+
+            let contract_transfer = to_contract_external_transfer(&transfer).unwrap();
+            assert_eq!(contract_transfer.is_withdrawal, direction.is_withdrawal());
+            let res = to_circuit_external_transfer(&contract_transfer).unwrap();
+            assert_eq!(res, transfer);
+
+            // A zero amount transfer keeps its direction and addresses
+            transfer.amount = 0;
+            let contract_transfer = to_contract_external_transfer(&transfer).unwrap();
+            let res = to_circuit_external_transfer(&contract_transfer).unwrap();
+            assert_eq!(res, transfer);
+        }
+
+        // The default transfer, which wallet update conversion treats as no
+        // transfer, also round trips on its own
+        let transfer = ExternalTransfer::default();
+        let contract_transfer = to_contract_external_transfer(&transfer).unwrap();
+        let res = to_circuit_external_transfer(&contract_transfer).unwrap();
+        assert!(res.is_default());
+    }
+
+    /// Tests the round trip conversion of a `VALID WALLET UPDATE` statement
+    /// with an external transfer
+    #[test]
+    fn test_wallet_update_round_trip() {
+        let statement = random_wallet_update_statement(random_external_transfer());
+        let contract_statement = to_contract_valid_wallet_update_statement(&statement).unwrap();
+        let res = to_circuit_valid_wallet_update_statement(&contract_statement).unwrap();
+
+        assert!(contract_statement.external_transfer.is_some());
+        assert_eq!(res.to_scalars(), statement.to_scalars());
+    }
+
+    /// Tests the round trip conversion of a `VALID WALLET UPDATE` statement
+    /// without an external transfer
+    #[test]
+    fn test_wallet_update_round_trip__no_transfer() {
+        let statement = random_wallet_update_statement(ExternalTransfer::default());
+        let contract_statement = to_contract_valid_wallet_update_statement(&statement).unwrap();
+        let res = to_circuit_valid_wallet_update_statement(&contract_statement).unwrap();
+
+        assert!(contract_statement.external_transfer.is_none());
+        assert!(res.external_transfer.is_default());
+        assert_eq!(res.to_scalars(), statement.to_scalars());
+    }
+
+    /// Tests the round trip conversion of a `VALID MATCH SETTLE` statement
+    #[test]
+    fn test_match_settle_round_trip() {
+        let statement = SizedValidMatchSettleStatement {
+            party0_modified_shares: random_base_type(),
+            party1_modified_shares: random_base_type(),
+            party0_indices: random_indices(),
+            party1_indices: random_indices(),
+            protocol_fee: random_base_type::<FixedPoint>(),
+        };
+
+        let contract_statement = to_contract_valid_match_settle_statement(&statement);
+        let res = to_circuit_valid_match_settle_statement(&contract_statement).unwrap();
+        assert_eq!(res.to_scalars(), statement.to_scalars());
+    }
+
+    /// Tests the round trip conversion of a `VALID OFFLINE FEE SETTLEMENT`
+    /// statement
+    #[test]
+    fn test_offline_fee_settlement_round_trip() {
+        let statement = random_offline_fee_statement();
+        let contract_statement = to_contract_valid_offline_fee_settlement_statement(&statement);
+        let res = to_circuit_valid_offline_fee_settlement_statement(&contract_statement).unwrap();
+        assert_eq!(res.to_scalars(), statement.to_scalars());
+    }
+
+    /// Tests the round trip conversion of a public encryption key
+    #[test]
+    fn test_encryption_key_round_trip() {
+        let key = random_base_type::<EncryptionKey>();
+        let contract_key = to_contract_public_encryption_key(&key);
+        let res = to_circuit_public_encryption_key(&contract_key).unwrap();
+        assert_eq!(res, key);
+    }
+
+    /// Tests the round trip conversion of a note ciphertext produced by
+    /// `encrypt_note`
+    #[test]
+    fn test_note_ciphertext_round_trip() {
+        let (_, key) = DecryptionKey::random_pair(&mut thread_rng());
+        let note = Note::new(BigUint::from(1u8), 100 /* amount */, key);
+        let (ciphertext, _) = encrypt_note(&note, &key);
+
+        let contract_ciphertext = to_contract_note_ciphertext(&ciphertext);
+        let res = to_circuit_note_ciphertext(&contract_ciphertext).unwrap();
+        assert_eq!(res.ephemeral_key, ciphertext.ephemeral_key);
+        assert_eq!(res.ciphertext, ciphertext.ciphertext);
+    }
+
+    /// Tests that the zero point and the identity are rejected as encryption
+    /// keys
+    #[test]
+    fn test_encryption_key__zero_and_identity() {
+        for y in [Scalar::zero(), Scalar::one()] {
+            let key = EncryptionKey { x: Scalar::zero(), y };
+            let contract_key = to_contract_public_encryption_key(&key);
+            let res = to_circuit_public_encryption_key(&contract_key);
+            assert!(matches!(res, Err(ConversionError::InvalidEncryptionKey)));
+        }
+
+        // The offline fee statement annotates the key that failed
+        let mut contract_statement =
+            to_contract_valid_offline_fee_settlement_statement(&random_offline_fee_statement());
+        contract_statement.protocol_key.x = ScalarField::from(0u8);
+        let err = to_circuit_valid_offline_fee_settlement_statement(&contract_statement).err();
+        assert_eq!(err.unwrap().to_string(), "protocol_key: InvalidEncryptionKey");
+    }
+
+    /// Tests converting shares from a scalar vector of the wrong length
+    #[test]
+    fn test_wallet_shares__invalid_length() {
+        let shares: SizedWalletShare = random_base_type();
+        let mut scalars = scalars_to_field_vec(&shares);
+        scalars.pop();
+
+        let res = scalar_vec_to_wallet_shares(&scalars);
+        let Err(ConversionError::InvalidLength { expected, actual }) = res else {
+            panic!("expected an invalid length error");
+        };
+        assert_eq!(expected, SizedWalletShare::NUM_SCALARS);
+        assert_eq!(actual, scalars.len());
+    }
+
+    /// Tests that a statement conversion annotates the field that failed
+    #[test]
+    fn test_field_error__wallet_update() {
+        let mut statement = random_wallet_update_statement(random_external_transfer());
+        let mut contract_statement = to_contract_valid_wallet_update_statement(&statement).unwrap();
+
+        // Truncate the shares so that the inverse conversion fails
+        contract_statement.new_public_shares.pop();
+        let err = to_circuit_valid_wallet_update_statement(&contract_statement).err().unwrap();
+        assert!(matches!(err, ConversionError::FieldError { field: "new_public_shares", .. }));
+
+        // An external transfer mint that overflows an address fails on the
+        // forward conversion
+        statement.external_transfer.mint = BigUint::from(1u8) << 160;
+        let err = to_contract_valid_wallet_update_statement(&statement).err().unwrap();
+        assert_eq!(err.to_string(), "external_transfer: mint: InvalidUint");
+    }
+
+    /// Tests that out of range settlement indices are rejected
+    #[test]
+    fn test_match_settle_indices__out_of_range() {
+        let statement = SizedValidMatchSettleStatement {
+            party0_modified_shares: random_base_type(),
+            party1_modified_shares: random_base_type(),
+            party0_indices: random_indices(),
+            party1_indices: random_indices(),
+            protocol_fee: random_base_type::<FixedPoint>(),
+        };
+
+        let mut contract_statement = to_contract_valid_match_settle_statement(&statement);
+        contract_statement.party1_indices.order = MAX_ORDERS as u64;
+        let err = to_circuit_valid_match_settle_statement(&contract_statement).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "party1_indices: order: index {MAX_ORDERS} out of range for {MAX_ORDERS} elements"
+            )
+        );
+    }
+
+    /// Tests that a non-canonical protocol fee is rejected
+    #[test]
+    fn test_match_settle_protocol_fee__non_canonical() {
+        let statement = SizedValidMatchSettleStatement {
+            party0_modified_shares: random_base_type(),
+            party1_modified_shares: random_base_type(),
+            party0_indices: random_indices(),
+            party1_indices: random_indices(),
+            protocol_fee: random_base_type::<FixedPoint>(),
+        };
+
+        let mut contract_statement = to_contract_valid_match_settle_statement(&statement);
+        contract_statement.protocol_fee = ScalarField::new_unchecked(ScalarField::MODULUS);
+        let err = to_circuit_valid_match_settle_statement(&contract_statement).err().unwrap();
+        assert_eq!(err.to_string(), "protocol_fee: NonCanonicalScalar");
+    }
+
+    /// Tests that field elements are rejected once they reach the modulus
+    #[test]
+    fn test_non_canonical_scalar() {
+        let modulus = ScalarField::MODULUS;
+        let mut below = modulus;
+        below.sub_with_borrow(&BigInt::from(1u64));
+        let mut above = modulus;
+        above.add_with_carry(&BigInt::from(1u64));
+
+        // Raw representations decoded from calldata
+        let fixed_point = |repr| ContractFixedPoint { repr: ScalarField::new_unchecked(repr) };
+        assert!(try_to_circuit_fixed_point(&fixed_point(below)).is_ok());
+        for repr in [modulus, above] {
+            let res = try_to_circuit_fixed_point(&fixed_point(repr));
+            assert!(matches!(res, Err(ConversionError::NonCanonicalScalar)));
+        }
+
+        // ABI-encoded words
+        let word = |repr: BigInt<4>| U256::from_limbs(repr.0);
+        assert_eq!(try_u256_to_scalar(word(below)).unwrap(), -Scalar::one());
+        for repr in [modulus, above] {
+            let res = try_u256_to_scalar(word(repr));
+            assert!(matches!(res, Err(ConversionError::NonCanonicalScalar)));
+        }
+    }
+
+    /// Tests that decoded fee rates above one are rejected
+    #[test]
+    fn test_fee_rates__out_of_range() {
+        let one = FixedPoint::one();
+        let rates = FeeTakeRate::new(one, FixedPoint::zero());
+        let contract_rates = to_contract_fee_rates(&rates).unwrap();
+        assert_eq!(to_circuit_fee_rates(&contract_rates).unwrap(), rates);
+
+        let epsilon = FixedPoint::from_repr(Scalar::one());
+        let contract_rates = to_contract_fee_rates(&FeeTakeRate::new(one, epsilon)).unwrap();
+        let res = to_circuit_fee_rates(&contract_rates);
+        assert!(matches!(
+            res,
+            Err(ConversionError::InvalidFeeRate(FeeRateError::TotalRateExceedsOne))
+        ));
+    }
+
+    /// Tests that non-canonical scalars in a `VALID WALLET UPDATE` statement
+    /// are rejected
+    #[test]
+    fn test_wallet_update__non_canonical() {
+        let statement = random_wallet_update_statement(random_external_transfer());
+        let cases: [(fn(&mut ContractValidWalletUpdateStatement), _); 4] = [
+            (|s| s.old_shares_nullifier = non_canonical_scalar(), "old_shares_nullifier"),
+            (|s| s.new_wallet_commitment = non_canonical_scalar(), "new_wallet_commitment"),
+            (|s| s.merkle_root = non_canonical_scalar(), "merkle_root"),
+            (|s| s.old_pk_root.x[0] = non_canonical_scalar(), "old_pk_root"),
+        ];
+
+        for (corrupt, field) in cases {
+            let mut contract_statement =
+                to_contract_valid_wallet_update_statement(&statement).unwrap();
+            corrupt(&mut contract_statement);
+
+            let err = to_circuit_valid_wallet_update_statement(&contract_statement).err().unwrap();
+            assert_eq!(err.to_string(), format!("{field}: NonCanonicalScalar"));
+        }
+    }
+
+    /// Tests that a note ciphertext with a non-canonical scalar is rejected
+    #[test]
+    fn test_note_ciphertext__non_canonical() {
+        let ciphertext = random_base_type::<ElGamalCiphertext<_>>();
+        let mut contract_ciphertext = to_contract_note_ciphertext(&ciphertext);
+        contract_ciphertext.2 = non_canonical_scalar();
+
+        let err = to_circuit_note_ciphertext(&contract_ciphertext).err().unwrap();
+        assert_eq!(err.to_string(), "ciphertext: NonCanonicalScalar");
+    }
+
+    /// Tests that an encryption key with a non-canonical coordinate is rejected
+    #[test]
+    fn test_encryption_key__non_canonical() {
+        let key = random_base_type::<EncryptionKey>();
+        let cases: [(fn(&mut ContractPublicEncryptionKey), _); 2] =
+            [(|k| k.x = non_canonical_scalar(), "x"), (|k| k.y = non_canonical_scalar(), "y")];
+
+        for (corrupt, field) in cases {
+            let mut contract_key = to_contract_public_encryption_key(&key);
+            corrupt(&mut contract_key);
+
+            let err = to_circuit_public_encryption_key(&contract_key).err().unwrap();
+            assert_eq!(err.to_string(), format!("{field}: NonCanonicalScalar"));
+        }
+    }
+
+    /// Tests that non-canonical scalars in a `VALID OFFLINE FEE SETTLEMENT`
+    /// statement are rejected
+    #[test]
+    fn test_offline_fee_settlement__non_canonical() {
+        let statement = random_offline_fee_statement();
+        let cases: [(fn(&mut ContractValidOfflineFeeSettlementStatement), _); 5] = [
+            (|s| s.merkle_root = non_canonical_scalar(), "merkle_root"),
+            (|s| s.nullifier = non_canonical_scalar(), "nullifier"),
+            (|s| s.new_wallet_commitment = non_canonical_scalar(), "new_wallet_commitment"),
+            (|s| s.note_commitment = non_canonical_scalar(), "note_commitment"),
+            (|s| s.note_ciphertext.1 = non_canonical_scalar(), "note_ciphertext: ciphertext"),
+        ];
+
+        for (corrupt, field) in cases {
+            let mut contract_statement =
+                to_contract_valid_offline_fee_settlement_statement(&statement);
+            corrupt(&mut contract_statement);
+
+            let err = to_circuit_valid_offline_fee_settlement_statement(&contract_statement).err();
+            assert_eq!(err.unwrap().to_string(), format!("{field}: NonCanonicalScalar"));
+        }
+    }
+
+    /// Tests that unwrapping a non-canonical scalar into a fixed-length array
+    /// is rejected
+    #[test]
+    fn test_unwrap_scalars__non_canonical() {
+        let canonical = [Scalar::one(), -Scalar::one()];
+        let res = try_unwrap_scalars::<2>(&canonical).unwrap();
+        assert_eq!(res, [ScalarField::from(1u8), -ScalarField::from(1u8)]);
+
+        let scalars = [Scalar::one(), Scalar::new(non_canonical_scalar())];
+        let res = try_unwrap_scalars::<2>(&scalars);
+        assert!(matches!(res, Err(ConversionError::NonCanonicalScalar)));
+    }
 }
@@ -1,13 +1,21 @@
 //! Various helpers for darkpool client execution
 
-use alloy::primitives::{Bytes, U256};
+use alloy::{
+    consensus::constants::SELECTOR_LEN,
+    primitives::{Address, Bytes, U256},
+};
 use alloy_sol_types::SolCall;
-use circuit_types::{Amount, SizedWalletShare, r#match::OrderSettlementIndices, traits::BaseType};
+use circuit_types::{
+    Amount, SizedWalletShare,
+    fees::{AmountExt, FeeTake, FeeTakeRate},
+    r#match::{BoundedMatchResult, OrderSettlementIndices},
+    traits::BaseType,
+};
 use constants::Scalar;
 use serde::{Deserialize, Serialize};
 use util::matching_engine::apply_match_to_shares;
 
-use crate::errors::DarkpoolClientError;
+use crate::errors::{ConversionError, DarkpoolClientError};
 
 use super::{
     abi::Darkpool::{
@@ -16,8 +24,10 @@ use super::{
         processMatchSettleCall, redeemFeeCall, settleOfflineFeeCall, settleOnlineRelayerFeeCall,
         updateWalletCall,
     },
+    bounded_de::LenBoundedDeserializer,
     contract_types::{
-        MatchPayload, ValidFeeRedemptionStatement as ContractValidFeeRedemptionStatement,
+        MatchAtomicLinkingProofs, MatchAtomicProofs, MatchPayload, ScalarField,
+        ValidFeeRedemptionStatement as ContractValidFeeRedemptionStatement,
         ValidMalleableMatchSettleAtomicStatement as ContractValidMalleableMatchSettleAtomicStatement,
         ValidMatchSettleAtomicStatement as ContractValidMatchSettleAtomicStatement,
         ValidMatchSettleStatement as ContractValidMatchSettleStatement,
@@ -32,6 +42,17 @@ use super::{
     },
 };
 
+/// The version tag prefixed to calldata serialized with
+/// `serialize_calldata_versioned`
+///
+/// Must be bumped whenever a versioned type's encoding changes
+pub const CALLDATA_ENCODING_VERSION: u8 = 1;
+
+/// The maximum length of a collection in a statement parsed for wallet shares
+///
+/// The wallet share vectors are the largest collections in these statements
+const MAX_SHARES_LEN: usize = SizedWalletShare::NUM_SCALARS;
+
 // ---------------------
 // | (De)serialization |
 // ---------------------
@@ -50,23 +71,260 @@ pub fn deserialize_calldata<'de, T: Deserialize<'de>>(
     postcard::from_bytes(calldata).map_err(|e| DarkpoolClientError::Serde(e.to_string()))
 }
 
+/// Deserializes a calldata element, rejecting any collection within it whose
+/// declared length exceeds `max_len`
+///
+/// The bound is checked when the length prefix is read, so adversarial
+/// calldata cannot trigger a large allocation
+pub fn deserialize_calldata_bounded<'de, T: Deserialize<'de>>(
+    calldata: &'de [u8],
+    max_len: usize,
+) -> Result<T, DarkpoolClientError> {
+    let mut deserializer = postcard::Deserializer::from_bytes(calldata);
+    T::deserialize(LenBoundedDeserializer::new(&mut deserializer, max_len))
+        .map_err(|e| DarkpoolClientError::Serde(e.to_string()))
+}
+
+/// A calldata element tagged with the version of its encoding
+///
+/// Used for calldata the relayer persists itself, where the format is not
+/// dictated by the contract
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VersionedCalldata<T> {
+    /// The version of the encoding
+    pub version: u8,
+    /// The calldata element
+    pub inner: T,
+}
+
+/// Serializes a calldata element, tagged with `CALLDATA_ENCODING_VERSION`
+pub fn serialize_calldata_versioned<T: Serialize>(data: &T) -> Result<Bytes, DarkpoolClientError> {
+    serialize_calldata(&VersionedCalldata { version: CALLDATA_ENCODING_VERSION, inner: data })
+}
+
+/// Deserializes a calldata element serialized with
+/// `serialize_calldata_versioned`
+///
+/// The version is checked before the element is decoded, so that calldata in
+/// another encoding is rejected rather than misread
+pub fn deserialize_calldata_versioned<'de, T: Deserialize<'de>>(
+    calldata: &'de [u8],
+) -> Result<T, DarkpoolClientError> {
+    // Postcard encodes a `u8` as a single byte, so the version leads the payload
+    let version = *calldata
+        .first()
+        .ok_or_else(|| DarkpoolClientError::Serde("empty versioned calldata".to_string()))?;
+    if version != CALLDATA_ENCODING_VERSION {
+        return Err(DarkpoolClientError::UnsupportedVersion(version));
+    }
+
+    let versioned: VersionedCalldata<T> = deserialize_calldata(calldata)?;
+    Ok(versioned.inner)
+}
+
+// ------------------------
+// | Call Classification |
+// ------------------------
+
+/// The darkpool methods that update wallets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DarkpoolCall {
+    /// A `newWallet` call
+    NewWallet,
+    /// An `updateWallet` call
+    UpdateWallet,
+    /// A `processMatchSettle` call
+    ProcessMatchSettle,
+    /// A `processAtomicMatchSettle` call
+    ProcessAtomicMatchSettle,
+    /// A `processAtomicMatchSettleWithReceiver` call
+    ProcessAtomicMatchSettleWithReceiver,
+    /// A `processMalleableAtomicMatchSettle` call
+    ProcessMalleableAtomicMatchSettle,
+    /// A `processMalleableAtomicMatchSettleWithReceiver` call
+    ProcessMalleableAtomicMatchSettleWithReceiver,
+    /// A `settleOnlineRelayerFee` call
+    SettleOnlineRelayerFee,
+    /// A `settleOfflineFee` call
+    SettleOfflineFee,
+    /// A `redeemFee` call
+    RedeemFee,
+    /// Calldata whose selector is not a known darkpool method, or which is too
+    /// short to hold a selector
+    Unknown,
+}
+
+impl DarkpoolCall {
+    /// All darkpool calls with a known selector
+    pub const KNOWN: [DarkpoolCall; 10] = [
+        DarkpoolCall::NewWallet,
+        DarkpoolCall::UpdateWallet,
+        DarkpoolCall::ProcessMatchSettle,
+        DarkpoolCall::ProcessAtomicMatchSettle,
+        DarkpoolCall::ProcessAtomicMatchSettleWithReceiver,
+        DarkpoolCall::ProcessMalleableAtomicMatchSettle,
+        DarkpoolCall::ProcessMalleableAtomicMatchSettleWithReceiver,
+        DarkpoolCall::SettleOnlineRelayerFee,
+        DarkpoolCall::SettleOfflineFee,
+        DarkpoolCall::RedeemFee,
+    ];
+
+    /// The selector of the darkpool method, `None` for `Unknown`
+    pub fn selector(&self) -> Option<Selector> {
+        let selector = match self {
+            DarkpoolCall::NewWallet => Selector::NEW_WALLET,
+            DarkpoolCall::UpdateWallet => Selector::UPDATE_WALLET,
+            DarkpoolCall::ProcessMatchSettle => Selector::PROCESS_MATCH_SETTLE,
+            DarkpoolCall::ProcessAtomicMatchSettle => Selector::PROCESS_ATOMIC_MATCH_SETTLE,
+            DarkpoolCall::ProcessAtomicMatchSettleWithReceiver => {
+                Selector::PROCESS_ATOMIC_MATCH_SETTLE_WITH_RECEIVER
+            },
+            DarkpoolCall::ProcessMalleableAtomicMatchSettle => {
+                Selector::PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE
+            },
+            DarkpoolCall::ProcessMalleableAtomicMatchSettleWithReceiver => {
+                Selector::PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE_WITH_RECEIVER
+            },
+            DarkpoolCall::SettleOnlineRelayerFee => Selector::SETTLE_ONLINE_RELAYER_FEE,
+            DarkpoolCall::SettleOfflineFee => Selector::SETTLE_OFFLINE_FEE,
+            DarkpoolCall::RedeemFee => Selector::REDEEM_FEE,
+            DarkpoolCall::Unknown => return None,
+        };
+
+        Some(selector)
+    }
+}
+
+/// The 4-byte function selector at the start of a darkpool call's calldata
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Selector(pub [u8; SELECTOR_LEN]);
+
+impl Selector {
+    /// The selector of `newWallet`
+    pub const NEW_WALLET: Self = Self(newWalletCall::SELECTOR);
+    /// The selector of `updateWallet`
+    pub const UPDATE_WALLET: Self = Self(updateWalletCall::SELECTOR);
+    /// The selector of `processMatchSettle`
+    pub const PROCESS_MATCH_SETTLE: Self = Self(processMatchSettleCall::SELECTOR);
+    /// The selector of `processAtomicMatchSettle`
+    pub const PROCESS_ATOMIC_MATCH_SETTLE: Self = Self(processAtomicMatchSettleCall::SELECTOR);
+    /// The selector of `processAtomicMatchSettleWithReceiver`
+    pub const PROCESS_ATOMIC_MATCH_SETTLE_WITH_RECEIVER: Self =
+        Self(processAtomicMatchSettleWithReceiverCall::SELECTOR);
+    /// The selector of `processMalleableAtomicMatchSettle`
+    pub const PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE: Self =
+        Self(processMalleableAtomicMatchSettleCall::SELECTOR);
+    /// The selector of `processMalleableAtomicMatchSettleWithReceiver`
+    pub const PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE_WITH_RECEIVER: Self =
+        Self(processMalleableAtomicMatchSettleWithReceiverCall::SELECTOR);
+    /// The selector of `settleOnlineRelayerFee`
+    pub const SETTLE_ONLINE_RELAYER_FEE: Self = Self(settleOnlineRelayerFeeCall::SELECTOR);
+    /// The selector of `settleOfflineFee`
+    pub const SETTLE_OFFLINE_FEE: Self = Self(settleOfflineFeeCall::SELECTOR);
+    /// The selector of `redeemFee`
+    pub const REDEEM_FEE: Self = Self(redeemFeeCall::SELECTOR);
+
+    /// Read the selector from the start of the given calldata
+    ///
+    /// Errors with `InvalidSelector` if the calldata is too short to hold one
+    pub fn from_calldata(calldata: &[u8]) -> Result<Self, DarkpoolClientError> {
+        calldata
+            .get(..SELECTOR_LEN)
+            .and_then(|s| s.try_into().ok())
+            .map(Self)
+            .ok_or(DarkpoolClientError::InvalidSelector)
+    }
+
+    /// Classify the darkpool method this selector calls
+    pub fn classify(&self) -> DarkpoolCall {
+        match *self {
+            Self::NEW_WALLET => DarkpoolCall::NewWallet,
+            Self::UPDATE_WALLET => DarkpoolCall::UpdateWallet,
+            Self::PROCESS_MATCH_SETTLE => DarkpoolCall::ProcessMatchSettle,
+            Self::PROCESS_ATOMIC_MATCH_SETTLE => DarkpoolCall::ProcessAtomicMatchSettle,
+            Self::PROCESS_ATOMIC_MATCH_SETTLE_WITH_RECEIVER => {
+                DarkpoolCall::ProcessAtomicMatchSettleWithReceiver
+            },
+            Self::PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE => {
+                DarkpoolCall::ProcessMalleableAtomicMatchSettle
+            },
+            Self::PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE_WITH_RECEIVER => {
+                DarkpoolCall::ProcessMalleableAtomicMatchSettleWithReceiver
+            },
+            Self::SETTLE_ONLINE_RELAYER_FEE => DarkpoolCall::SettleOnlineRelayerFee,
+            Self::SETTLE_OFFLINE_FEE => DarkpoolCall::SettleOfflineFee,
+            Self::REDEEM_FEE => DarkpoolCall::RedeemFee,
+            _ => DarkpoolCall::Unknown,
+        }
+    }
+}
+
+/// Classify the darkpool method called by the given calldata
+///
+/// Only reads the selector, the rest of the calldata is not validated
+pub fn classify_darkpool_call(calldata: &[u8]) -> DarkpoolCall {
+    Selector::from_calldata(calldata).map_or(DarkpoolCall::Unknown, |s| s.classify())
+}
+
 // ----------------
 // | Parse Shares |
 // ----------------
 
+/// Parses the updated wallet shares from the calldata of any darkpool call
+/// that updates wallets, dispatching on the calldata's selector
+///
+/// A `processMatchSettle` call yields the shares of both parties in party
+/// order, a `settleOnlineRelayerFee` call yields the shares of whichever party
+/// holds `public_blinder_share`, all other calls yield the shares of the single
+/// wallet they update
+pub fn parse_shares_from_darkpool_calldata(
+    calldata: &[u8],
+    public_blinder_share: Scalar,
+) -> Result<Vec<SizedWalletShare>, DarkpoolClientError> {
+    let selector = Selector::from_calldata(calldata)?;
+    let shares = match selector.classify() {
+        DarkpoolCall::NewWallet => parse_shares_from_new_wallet(calldata)?,
+        DarkpoolCall::UpdateWallet => parse_shares_from_update_wallet(calldata)?,
+        DarkpoolCall::ProcessMatchSettle => {
+            let (party0_shares, party1_shares) =
+                parse_both_shares_from_process_match_settle(calldata)?;
+            return Ok(vec![party0_shares, party1_shares]);
+        },
+        DarkpoolCall::ProcessAtomicMatchSettle => {
+            parse_shares_from_process_atomic_match_settle(calldata)?
+        },
+        DarkpoolCall::ProcessAtomicMatchSettleWithReceiver => {
+            parse_shares_from_process_atomic_match_settle_with_receiver(calldata)?
+        },
+        DarkpoolCall::ProcessMalleableAtomicMatchSettle => {
+            parse_shares_from_process_malleable_atomic_match_settle(calldata)?
+        },
+        DarkpoolCall::ProcessMalleableAtomicMatchSettleWithReceiver => {
+            parse_shares_from_process_malleable_atomic_match_settle_with_receiver(calldata)?
+        },
+        DarkpoolCall::SettleOnlineRelayerFee => {
+            parse_shares_from_settle_online_relayer_fee(calldata, public_blinder_share)?
+        },
+        DarkpoolCall::SettleOfflineFee => parse_shares_from_settle_offline_fee(calldata)?,
+        DarkpoolCall::RedeemFee => parse_shares_from_redeem_fee(calldata)?,
+        DarkpoolCall::Unknown => return Err(DarkpoolClientError::UnknownSelector(selector.0)),
+    };
+
+    Ok(vec![shares])
+}
+
 /// Parses wallet shares from the calldata of a `newWallet` call
 pub fn parse_shares_from_new_wallet(
     calldata: &[u8],
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
     let call = newWalletCall::abi_decode(calldata)?;
 
-    let statement = deserialize_calldata::<ContractValidWalletCreateStatement>(
+    let statement = deserialize_calldata_bounded::<ContractValidWalletCreateStatement>(
         &call.valid_wallet_create_statement_bytes,
+        MAX_SHARES_LEN,
     )?;
 
-    let mut shares = statement.public_wallet_shares.into_iter().map(Scalar::new);
-
-    Ok(SizedWalletShare::from_scalars(&mut shares))
+    wallet_share_from_calldata(statement.public_wallet_shares)
 }
 
 /// Parses wallet shares from the calldata of an `updateWallet` call
@@ -75,13 +333,12 @@ pub fn parse_shares_from_update_wallet(
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
     let call = updateWalletCall::abi_decode(calldata)?;
 
-    let statement = deserialize_calldata::<ContractValidWalletUpdateStatement>(
+    let statement = deserialize_calldata_bounded::<ContractValidWalletUpdateStatement>(
         &call.valid_wallet_update_statement_bytes,
+        MAX_SHARES_LEN,
     )?;
 
-    let mut shares = statement.new_public_shares.into_iter().map(Scalar::new);
-
-    Ok(SizedWalletShare::from_scalars(&mut shares))
+    wallet_share_from_calldata(statement.new_public_shares)
 }
 
 /// Parses wallet shares from the calldata of a `processMatchSettle` call
@@ -89,31 +346,35 @@ pub fn parse_shares_from_process_match_settle(
     calldata: &[u8],
     public_blinder_share: Scalar,
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
-    let call = processMatchSettleCall::abi_decode(calldata)?;
-
-    let valid_match_settle_statement = deserialize_calldata::<ContractValidMatchSettleStatement>(
-        &call.valid_match_settle_statement,
-    )?;
+    let (party_0_shares, party_1_shares) = parse_both_shares_from_process_match_settle(calldata)?;
+    if party_0_shares.blinder == public_blinder_share {
+        Ok(party_0_shares)
+    } else if party_1_shares.blinder == public_blinder_share {
+        Ok(party_1_shares)
+    } else {
+        Err(DarkpoolClientError::BlinderNotFound)
+    }
+}
 
-    let party_0_shares = valid_match_settle_statement.party0_modified_shares;
-    // The blinder is expected to be the last public wallet share
-    let party_0_blinder_share = party_0_shares.last().unwrap();
+/// Parses the wallet shares of both parties from the calldata of a
+/// `processMatchSettle` call
+///
+/// Returns the shares in party order, i.e. `(party0, party1)`
+pub fn parse_both_shares_from_process_match_settle(
+    calldata: &[u8],
+) -> Result<(SizedWalletShare, SizedWalletShare), DarkpoolClientError> {
+    let call = processMatchSettleCall::abi_decode(calldata)?;
 
-    let party_1_shares = valid_match_settle_statement.party1_modified_shares;
-    // The blinder is expected to be the last public wallet share
-    let party_1_blinder_share = party_1_shares.last().unwrap();
+    let valid_match_settle_statement = deserialize_calldata_bounded::<
+        ContractValidMatchSettleStatement,
+    >(&call.valid_match_settle_statement, MAX_SHARES_LEN)?;
 
-    let target_share = public_blinder_share.inner();
-    let selected_shares = if party_0_blinder_share == &target_share {
-        party_0_shares
-    } else if party_1_blinder_share == &target_share {
-        party_1_shares
-    } else {
-        return Err(DarkpoolClientError::BlinderNotFound);
-    };
+    let party_0_shares =
+        wallet_share_from_calldata(valid_match_settle_statement.party0_modified_shares)?;
+    let party_1_shares =
+        wallet_share_from_calldata(valid_match_settle_statement.party1_modified_shares)?;
 
-    let mut shares = selected_shares.into_iter().map(Scalar::new);
-    Ok(SizedWalletShare::from_scalars(&mut shares))
+    Ok((party_0_shares, party_1_shares))
 }
 
 /// Parses wallet shares from the calldata of a `processAtomicMatchSettle` call
@@ -121,12 +382,12 @@ pub fn parse_shares_from_process_atomic_match_settle(
     calldata: &[u8],
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
     let call = processAtomicMatchSettleCall::abi_decode(calldata)?;
-    let statement = deserialize_calldata::<ContractValidMatchSettleAtomicStatement>(
+    let statement = deserialize_calldata_bounded::<ContractValidMatchSettleAtomicStatement>(
         &call.valid_match_settle_atomic_statement,
+        MAX_SHARES_LEN,
     )?;
 
-    let mut shares = statement.internal_party_modified_shares.into_iter().map(Scalar::new);
-    Ok(SizedWalletShare::from_scalars(&mut shares))
+    wallet_share_from_calldata(statement.internal_party_modified_shares)
 }
 
 /// Parses wallet shares from the calldata of a
@@ -135,12 +396,12 @@ pub fn parse_shares_from_process_atomic_match_settle_with_receiver(
     calldata: &[u8],
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
     let call = processAtomicMatchSettleWithReceiverCall::abi_decode(calldata)?;
-    let statement = deserialize_calldata::<ContractValidMatchSettleAtomicStatement>(
+    let statement = deserialize_calldata_bounded::<ContractValidMatchSettleAtomicStatement>(
         &call.valid_match_settle_atomic_statement,
+        MAX_SHARES_LEN,
     )?;
 
-    let mut shares = statement.internal_party_modified_shares.into_iter().map(Scalar::new);
-    Ok(SizedWalletShare::from_scalars(&mut shares))
+    wallet_share_from_calldata(statement.internal_party_modified_shares)
 }
 
 /// Parses wallet shares from the calldata of a
@@ -150,16 +411,20 @@ pub fn parse_shares_from_process_malleable_atomic_match_settle(
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
     // Parse the pre-update shares from the calldata
     let call = processMalleableAtomicMatchSettleCall::abi_decode(calldata)?;
-    let statement = deserialize_calldata::<ContractValidMalleableMatchSettleAtomicStatement>(
+    let statement = deserialize_calldata_bounded::<ContractValidMalleableMatchSettleAtomicStatement>(
         &call.valid_match_settle_statement,
+        MAX_SHARES_LEN,
     )?;
-    let mut shares = statement.internal_party_public_shares.clone().into_iter().map(Scalar::new);
-    let mut wallet_share = SizedWalletShare::from_scalars(&mut shares);
+    let mut wallet_share =
+        wallet_share_from_calldata(statement.internal_party_public_shares.clone())?;
 
     // Update the shares with the match result
-    let validity_proofs = deserialize_calldata::<MatchPayload>(&call.internal_party_match_payload)?;
+    let validity_proofs = deserialize_calldata_bounded::<MatchPayload>(
+        &call.internal_party_match_payload,
+        MAX_SHARES_LEN,
+    )?;
     let indices =
-        to_circuit_order_settlement_indices(&validity_proofs.valid_commitments_statement.indices);
+        to_circuit_order_settlement_indices(&validity_proofs.valid_commitments_statement.indices)?;
     apply_malleable_match_result_to_wallet_share(
         &mut wallet_share,
         call.base_amount,
@@ -176,17 +441,21 @@ pub fn parse_shares_from_process_malleable_atomic_match_settle_with_receiver(
     calldata: &[u8],
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
     let call = processMalleableAtomicMatchSettleWithReceiverCall::abi_decode(calldata)?;
-    let statement = deserialize_calldata::<ContractValidMalleableMatchSettleAtomicStatement>(
+    let statement = deserialize_calldata_bounded::<ContractValidMalleableMatchSettleAtomicStatement>(
         &call.valid_match_settle_statement,
+        MAX_SHARES_LEN,
     )?;
 
-    let mut shares = statement.internal_party_public_shares.clone().into_iter().map(Scalar::new);
-    let mut wallet_share = SizedWalletShare::from_scalars(&mut shares);
+    let mut wallet_share =
+        wallet_share_from_calldata(statement.internal_party_public_shares.clone())?;
 
     // Update the shares with the match result
-    let validity_proofs = deserialize_calldata::<MatchPayload>(&call.internal_party_match_payload)?;
+    let validity_proofs = deserialize_calldata_bounded::<MatchPayload>(
+        &call.internal_party_match_payload,
+        MAX_SHARES_LEN,
+    )?;
     let indices =
-        to_circuit_order_settlement_indices(&validity_proofs.valid_commitments_statement.indices);
+        to_circuit_order_settlement_indices(&validity_proofs.valid_commitments_statement.indices)?;
     apply_malleable_match_result_to_wallet_share(
         &mut wallet_share,
         call.base_amount,
@@ -205,29 +474,25 @@ pub fn parse_shares_from_settle_online_relayer_fee(
     let call = settleOnlineRelayerFeeCall::abi_decode(calldata)?;
 
     let valid_relayer_fee_settlement_statement =
-        deserialize_calldata::<ContractValidRelayerFeeSettlementStatement>(
+        deserialize_calldata_bounded::<ContractValidRelayerFeeSettlementStatement>(
             &call.valid_relayer_fee_settlement_statement,
+            MAX_SHARES_LEN,
         )?;
 
-    let sender_shares = valid_relayer_fee_settlement_statement.sender_updated_public_shares;
-    // The blinder is expected to be the last public wallet share
-    let sender_blinder_share = sender_shares.last().unwrap();
-
-    let recipient_shares = valid_relayer_fee_settlement_statement.recipient_updated_public_shares;
-    // The blinder is expected to be the last public wallet share
-    let recipient_blinder_share = recipient_shares.last().unwrap();
+    let sender_shares = wallet_share_from_calldata(
+        valid_relayer_fee_settlement_statement.sender_updated_public_shares,
+    )?;
+    let recipient_shares = wallet_share_from_calldata(
+        valid_relayer_fee_settlement_statement.recipient_updated_public_shares,
+    )?;
 
-    let target_share = public_blinder_share.inner();
-    let selected_shares = if sender_blinder_share == &target_share {
-        sender_shares
-    } else if recipient_blinder_share == &target_share {
-        recipient_shares
+    if sender_shares.blinder == public_blinder_share {
+        Ok(sender_shares)
+    } else if recipient_shares.blinder == public_blinder_share {
+        Ok(recipient_shares)
     } else {
-        return Err(DarkpoolClientError::BlinderNotFound);
-    };
-
-    let mut shares = selected_shares.into_iter().map(Scalar::new);
-    Ok(SizedWalletShare::from_scalars(&mut shares))
+        Err(DarkpoolClientError::BlinderNotFound)
+    }
 }
 
 /// Parses wallet shares from the calldata of a `settleOfflineFee` call
@@ -236,13 +501,12 @@ pub fn parse_shares_from_settle_offline_fee(
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
     let call = settleOfflineFeeCall::abi_decode(calldata)?;
 
-    let statement = deserialize_calldata::<ContractValidOfflineFeeSettlementStatement>(
+    let statement = deserialize_calldata_bounded::<ContractValidOfflineFeeSettlementStatement>(
         &call.valid_offline_fee_settlement_statement,
+        MAX_SHARES_LEN,
     )?;
 
-    let mut shares = statement.updated_wallet_public_shares.into_iter().map(Scalar::new);
-
-    Ok(SizedWalletShare::from_scalars(&mut shares))
+    wallet_share_from_calldata(statement.updated_wallet_public_shares)
 }
 
 /// Parses wallet shares from the calldata of a `redeemFee` call
@@ -251,13 +515,145 @@ pub fn parse_shares_from_redeem_fee(
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
     let call = redeemFeeCall::abi_decode(calldata)?;
 
-    let statement = deserialize_calldata::<ContractValidFeeRedemptionStatement>(
+    let statement = deserialize_calldata_bounded::<ContractValidFeeRedemptionStatement>(
         &call.valid_fee_redemption_statement,
+        MAX_SHARES_LEN,
     )?;
 
-    let mut shares = statement.new_wallet_public_shares.into_iter().map(Scalar::new);
+    wallet_share_from_calldata(statement.new_wallet_public_shares)
+}
+
+/// Convert a wallet share vector parsed from calldata into a wallet share
+///
+/// The vector must hold exactly one element per share scalar; `from_scalars`
+/// would otherwise panic on a short vector and ignore trailing elements
+fn wallet_share_from_calldata(
+    shares: Vec<ScalarField>,
+) -> Result<SizedWalletShare, DarkpoolClientError> {
+    let expected = SizedWalletShare::NUM_SCALARS;
+    if shares.len() != expected {
+        return Err(ConversionError::InvalidLength { expected, actual: shares.len() }.into());
+    }
+
+    let mut scalars = shares.into_iter().map(Scalar::new);
+    Ok(SizedWalletShare::from_scalars(&mut scalars))
+}
+
+// ------------------
+// | Build Calldata |
+// ------------------
+
+/// Builds the calldata of a `newWallet` call settling the given statement
+///
+/// The proof is left empty, so the calldata is only suitable for parsing, e.g.
+/// by `parse_shares_from_new_wallet`
+pub fn build_new_wallet_calldata(
+    statement: &ContractValidWalletCreateStatement,
+) -> Result<Bytes, DarkpoolClientError> {
+    let call = newWalletCall {
+        proof: Bytes::new(),
+        valid_wallet_create_statement_bytes: serialize_calldata(statement)?,
+    };
+
+    Ok(call.abi_encode().into())
+}
+
+/// Builds the calldata of an `updateWallet` call settling the given statement
+///
+/// The proof, signature, and transfer auxiliary data are left empty, so the
+/// calldata is only suitable for parsing, e.g. by
+/// `parse_shares_from_update_wallet`
+pub fn build_update_wallet_calldata(
+    statement: &ContractValidWalletUpdateStatement,
+) -> Result<Bytes, DarkpoolClientError> {
+    let call = updateWalletCall {
+        proof: Bytes::new(),
+        valid_wallet_update_statement_bytes: serialize_calldata(statement)?,
+        wallet_commitment_signature: Bytes::new(),
+        transfer_aux_data: Bytes::new(),
+    };
+
+    Ok(call.abi_encode().into())
+}
+
+/// Builds the calldata of a `processMatchSettle` call settling the given
+/// statement
+///
+/// The match payloads and proofs are left empty, so the calldata is only
+/// suitable for parsing, e.g. by `parse_both_shares_from_process_match_settle`
+pub fn build_process_match_settle_calldata(
+    statement: &ContractValidMatchSettleStatement,
+) -> Result<Bytes, DarkpoolClientError> {
+    let call = processMatchSettleCall {
+        party_0_match_payload: Bytes::new(),
+        party_1_match_payload: Bytes::new(),
+        valid_match_settle_statement: serialize_calldata(statement)?,
+        match_proofs: Bytes::new(),
+        match_linking_proofs: Bytes::new(),
+    };
+
+    Ok(call.abi_encode().into())
+}
+
+/// Builds the calldata of a `processAtomicMatchSettle` call, or of a
+/// `processAtomicMatchSettleWithReceiver` call if a receiver is given
+///
+/// Unlike the other builders, the payload and proofs are included, so the
+/// calldata is byte-identical to that which the relayer submits for the same
+/// statement and proofs
+pub fn build_atomic_match_settle_calldata(
+    receiver: Option<Address>,
+    internal_party_match_payload: &MatchPayload,
+    statement: &ContractValidMatchSettleAtomicStatement,
+    match_proofs: &MatchAtomicProofs,
+    match_linking_proofs: &MatchAtomicLinkingProofs,
+) -> Result<Bytes, DarkpoolClientError> {
+    let internal_party_match_payload = serialize_calldata(internal_party_match_payload)?;
+    let valid_match_settle_atomic_statement = serialize_calldata(statement)?;
+    let match_proofs = serialize_calldata(match_proofs)?;
+    let match_linking_proofs = serialize_calldata(match_linking_proofs)?;
+
+    let calldata = match receiver {
+        Some(receiver) => processAtomicMatchSettleWithReceiverCall {
+            receiver,
+            internal_party_match_payload,
+            valid_match_settle_atomic_statement,
+            match_proofs,
+            match_linking_proofs,
+        }
+        .abi_encode(),
+        None => processAtomicMatchSettleCall {
+            internal_party_match_payload,
+            valid_match_settle_atomic_statement,
+            match_proofs,
+            match_linking_proofs,
+        }
+        .abi_encode(),
+    };
 
-    Ok(SizedWalletShare::from_scalars(&mut shares))
+    Ok(calldata.into())
+}
+
+// ------------------
+// | Parse Receiver |
+// ------------------
+
+/// Parses the receiver of the external party's funds from the calldata of a
+/// `processAtomicMatchSettleWithReceiver` call
+pub fn parse_receiver_from_atomic_match_settle_with_receiver(
+    calldata: &[u8],
+) -> Result<Address, DarkpoolClientError> {
+    let call = processAtomicMatchSettleWithReceiverCall::abi_decode(calldata)?;
+    Ok(call.receiver)
+}
+
+/// Parses the receiver of the external party's funds from the calldata of a
+/// `processMalleableAtomicMatchSettleWithReceiver` call
+pub fn parse_receiver_from_malleable_atomic_match_settle_with_receiver(
+    calldata: &[u8],
+) -> Result<Address, DarkpoolClientError> {
+    let call = processMalleableAtomicMatchSettleWithReceiverCall::abi_decode(calldata)?;
+    Ok(call.receiver)
 }
 
 // ---------------------
@@ -274,20 +670,347 @@ pub fn apply_malleable_match_result_to_wallet_share(
     indices: OrderSettlementIndices,
     statement: &ContractValidMalleableMatchSettleAtomicStatement,
 ) -> Result<(), DarkpoolClientError> {
-    let base_amt: Amount = base_amount.try_into().expect("base amount too large");
+    let base_amt: Amount = base_amount
+        .try_into()
+        .map_err(|_| ConversionError::InvalidUint.with_field("base_amount"))?;
+    let bounded_match = to_circuit_bounded_match_result(&statement.match_result)?;
+    let fees = to_circuit_fee_rates(&statement.internal_fee_rates)?;
+
+    apply_malleable_match_for_base_amount(wallet_share, base_amt, &indices, &bounded_match, &fees)
+}
 
+/// Apply a malleable match to a wallet share at the given base amount
+///
+/// Takes the already converted bounded match and fee rates, so that callers
+/// evaluating a match at many base amounts need only decode them once
+///
+/// Errors if the internal party's fees exceed the amount it receives, which
+/// would otherwise wrap its receive balance share
+pub fn apply_malleable_match_for_base_amount(
+    wallet_share: &mut SizedWalletShare,
+    base_amount: Amount,
+    indices: &OrderSettlementIndices,
+    bounded_match: &BoundedMatchResult,
+    internal_fee_rates: &FeeTakeRate,
+) -> Result<(), DarkpoolClientError> {
     // Compute the amounts traded
-    let bounded_match = to_circuit_bounded_match_result(&statement.match_result)?;
-    let external_match_res = bounded_match.to_external_match_result(base_amt);
+    let external_match_res = bounded_match.to_external_match_result(base_amount);
     let match_res = external_match_res.to_match_result();
 
     // Compute the fees due by the internal party
     let (_, recv_amount) = external_match_res.external_party_send();
-    let fees = to_circuit_fee_rates(&statement.internal_fee_rates)?;
-    let fee_take = fees.compute_fee_take(recv_amount);
+    let fee_take = internal_fee_rates.compute_fee_take(recv_amount);
+    if recv_amount.checked_sub_fee(&fee_take).is_none() {
+        let FeeTake { relayer_fee, protocol_fee } = fee_take;
+        let msg = format!(
+            "relayer fee {relayer_fee} and protocol fee {protocol_fee} exceed receive amount \
+             {recv_amount}"
+        );
+        return Err(DarkpoolClientError::InvalidMatch(msg));
+    }
 
     // Apply the match to the wallet share
     let side = external_match_res.internal_party_side();
-    apply_match_to_shares(wallet_share, &indices, fee_take, &match_res, side);
+    apply_match_to_shares(wallet_share, indices, fee_take, &match_res, side);
     Ok(())
 }
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use circuit_types::{
+        SizedWalletShare,
+        fees::FeeTakeRate,
+        fixed_point::FixedPoint,
+        r#match::{BoundedMatchResult, OrderSettlementIndices as CircuitOrderSettlementIndices},
+        traits::BaseType,
+    };
+    use constants::Scalar;
+    use rand::thread_rng;
+
+    use crate::{
+        arbitrum::contract_types::{OrderSettlementIndices, PublicSigningKey, ScalarField},
+        errors::{ConversionError, DarkpoolClientError},
+    };
+
+    use alloy::primitives::{Address, Bytes};
+    use alloy_sol_types::SolCall;
+
+    use super::{
+        CALLDATA_ENCODING_VERSION, ContractValidMatchSettleStatement,
+        ContractValidWalletCreateStatement, ContractValidWalletUpdateStatement, DarkpoolCall,
+        Selector, apply_malleable_match_for_base_amount, build_new_wallet_calldata,
+        build_process_match_settle_calldata, build_update_wallet_calldata, classify_darkpool_call,
+        deserialize_calldata_bounded, deserialize_calldata_versioned,
+        parse_receiver_from_atomic_match_settle_with_receiver,
+        parse_receiver_from_malleable_atomic_match_settle_with_receiver,
+        parse_shares_from_darkpool_calldata, processAtomicMatchSettleWithReceiverCall,
+        serialize_calldata, serialize_calldata_versioned,
+    };
+
+    /// Generate a random wallet share and its contract representation
+    fn random_share() -> (SizedWalletShare, Vec<ScalarField>) {
+        let mut rng = thread_rng();
+        let mut scalars = std::iter::repeat_with(|| Scalar::random(&mut rng));
+        let share = SizedWalletShare::from_scalars(&mut scalars);
+        let fields = share.to_scalars().iter().map(Scalar::inner).collect();
+
+        (share, fields)
+    }
+
+    /// Tests that calldata with an unknown selector is rejected
+    #[test]
+    fn test_parse_shares__unknown_selector() {
+        let calldata = [0xde, 0xad, 0xbe, 0xef, 0x00];
+        let res = parse_shares_from_darkpool_calldata(&calldata, Scalar::zero());
+
+        let Err(DarkpoolClientError::UnknownSelector(selector)) = res else {
+            panic!("expected an unknown selector error");
+        };
+        assert_eq!(selector, [0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    /// Tests classifying calldata by its selector
+    #[test]
+    fn test_classify_darkpool_call() {
+        let mut calldata = processAtomicMatchSettleWithReceiverCall::SELECTOR.to_vec();
+        assert_eq!(
+            classify_darkpool_call(&calldata),
+            DarkpoolCall::ProcessAtomicMatchSettleWithReceiver
+        );
+
+        // Trailing bytes are ignored
+        calldata.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(
+            classify_darkpool_call(&calldata),
+            DarkpoolCall::ProcessAtomicMatchSettleWithReceiver
+        );
+
+        assert_eq!(classify_darkpool_call(&[0xde, 0xad, 0xbe, 0xef]), DarkpoolCall::Unknown);
+        assert_eq!(classify_darkpool_call(&[0x01]), DarkpoolCall::Unknown);
+    }
+
+    /// Tests that every known call round trips through its selector
+    #[test]
+    fn test_selector_round_trip() {
+        for call in DarkpoolCall::KNOWN {
+            let selector = call.selector().unwrap();
+            assert_eq!(Selector::from_calldata(&selector.0).unwrap().classify(), call);
+        }
+
+        assert_eq!(DarkpoolCall::Unknown.selector(), None);
+    }
+
+    /// Tests that calldata too short to hold a selector is rejected
+    #[test]
+    fn test_selector__short_calldata() {
+        for calldata in [&[][..], &[0x01, 0x02, 0x03]] {
+            let res = Selector::from_calldata(calldata);
+            assert!(matches!(res, Err(DarkpoolClientError::InvalidSelector)));
+        }
+    }
+
+    /// Tests parsing the receiver from an atomic match settle call
+    #[test]
+    fn test_parse_receiver() {
+        let receiver = Address::repeat_byte(0xab);
+        let call = processAtomicMatchSettleWithReceiverCall {
+            receiver,
+            internal_party_match_payload: Bytes::new(),
+            valid_match_settle_atomic_statement: Bytes::new(),
+            match_proofs: Bytes::new(),
+            match_linking_proofs: Bytes::new(),
+        };
+
+        let calldata = call.abi_encode();
+        let res = parse_receiver_from_atomic_match_settle_with_receiver(&calldata).unwrap();
+        assert_eq!(res, receiver);
+
+        // Calldata for a different method is rejected
+        assert!(
+            parse_receiver_from_malleable_atomic_match_settle_with_receiver(&calldata).is_err()
+        );
+    }
+
+    /// Tests that calldata too short to hold a selector is rejected
+    #[test]
+    fn test_parse_shares__short_calldata() {
+        let res = parse_shares_from_darkpool_calldata(&[0x01, 0x02], Scalar::zero());
+        assert!(matches!(res, Err(DarkpoolClientError::InvalidSelector)));
+    }
+
+    /// Tests round tripping versioned calldata and rejecting other versions
+    #[test]
+    fn test_versioned_calldata() {
+        let data = vec![1u64, 2, 3];
+        let calldata = serialize_calldata_versioned(&data).unwrap();
+        assert_eq!(calldata[0], CALLDATA_ENCODING_VERSION);
+        let res: Vec<u64> = deserialize_calldata_versioned(&calldata).unwrap();
+        assert_eq!(res, data);
+
+        let mut other_version = calldata.to_vec();
+        other_version[0] = CALLDATA_ENCODING_VERSION + 1;
+        let res = deserialize_calldata_versioned::<Vec<u64>>(&other_version);
+        assert!(matches!(
+            res,
+            Err(DarkpoolClientError::UnsupportedVersion(v)) if v == CALLDATA_ENCODING_VERSION + 1
+        ));
+
+        let res = deserialize_calldata_versioned::<Vec<u64>>(&[]);
+        assert!(matches!(res, Err(DarkpoolClientError::Serde(_))));
+    }
+
+    /// Tests that bounded deserialization rejects oversized collections
+    #[test]
+    fn test_deserialize_calldata_bounded() {
+        let calldata = serialize_calldata(&vec![1u64; 10]).unwrap();
+        let res = deserialize_calldata_bounded::<Vec<u64>>(&calldata, 10).unwrap();
+        assert_eq!(res, vec![1u64; 10]);
+
+        let res = deserialize_calldata_bounded::<Vec<u64>>(&calldata, 9);
+        assert!(matches!(res, Err(DarkpoolClientError::Serde(_))));
+
+        // Nested collections are bounded as well
+        let nested = (1u8, vec![vec![1u64; 10]]);
+        let calldata = serialize_calldata(&nested).unwrap();
+        let res = deserialize_calldata_bounded::<(u8, Vec<Vec<u64>>)>(&calldata, 9);
+        assert!(matches!(res, Err(DarkpoolClientError::Serde(_))));
+
+        // Fixed length arrays are not bounded
+        let calldata = serialize_calldata(&[1u64; 16]).unwrap();
+        let res = deserialize_calldata_bounded::<[u64; 16]>(&calldata, 4).unwrap();
+        assert_eq!(res, [1u64; 16]);
+    }
+
+    /// Tests that a huge declared length is rejected before the elements are
+    /// read
+    #[test]
+    fn test_deserialize_calldata_bounded__huge_length() {
+        // A varint encoding of `u32::MAX` with no elements following it
+        let calldata = [0xff, 0xff, 0xff, 0xff, 0x0f];
+        let res = deserialize_calldata_bounded::<Vec<u64>>(&calldata, 100);
+        assert!(matches!(res, Err(DarkpoolClientError::Serde(_))));
+    }
+
+    /// Tests building and parsing `newWallet` calldata
+    #[test]
+    fn test_build_new_wallet_calldata() {
+        let (share, public_wallet_shares) = random_share();
+        let statement = ContractValidWalletCreateStatement {
+            wallet_share_commitment: ScalarField::from(1u8),
+            public_wallet_shares,
+        };
+
+        let calldata = build_new_wallet_calldata(&statement).unwrap();
+        assert_eq!(classify_darkpool_call(&calldata), DarkpoolCall::NewWallet);
+
+        let res = parse_shares_from_darkpool_calldata(&calldata, share.blinder).unwrap();
+        assert_eq!(res, vec![share]);
+    }
+
+    /// Tests that calldata whose wallet shares are not exactly the length of a
+    /// wallet share is rejected rather than panicking
+    #[test]
+    fn test_parse_shares__invalid_share_length() {
+        let (_, mut public_wallet_shares) = random_share();
+        let expected = SizedWalletShare::NUM_SCALARS;
+
+        // A short share vector
+        public_wallet_shares.pop();
+        let statement = ContractValidWalletCreateStatement {
+            wallet_share_commitment: ScalarField::from(1u8),
+            public_wallet_shares,
+        };
+        let calldata = build_new_wallet_calldata(&statement).unwrap();
+        let res = parse_shares_from_darkpool_calldata(&calldata, Scalar::zero());
+        let Err(DarkpoolClientError::Conversion(ConversionError::InvalidLength {
+            expected: e,
+            actual,
+        })) = res
+        else {
+            panic!("expected an invalid length error, got {res:?}");
+        };
+        assert_eq!((e, actual), (expected, expected - 1));
+
+        // An empty share vector
+        let statement = ContractValidWalletCreateStatement {
+            wallet_share_commitment: ScalarField::from(1u8),
+            public_wallet_shares: Vec::new(),
+        };
+        let calldata = build_new_wallet_calldata(&statement).unwrap();
+        let res = parse_shares_from_darkpool_calldata(&calldata, Scalar::zero());
+        assert!(matches!(res, Err(DarkpoolClientError::Conversion(_))));
+    }
+
+    /// Tests building and parsing `updateWallet` calldata
+    #[test]
+    fn test_build_update_wallet_calldata() {
+        let (share, new_public_shares) = random_share();
+        let zero = ScalarField::from(0u8);
+        let statement = ContractValidWalletUpdateStatement {
+            old_shares_nullifier: zero,
+            new_wallet_commitment: zero,
+            new_public_shares,
+            merkle_root: zero,
+            external_transfer: None,
+            old_pk_root: PublicSigningKey { x: [zero; 2], y: [zero; 2] },
+        };
+
+        let calldata = build_update_wallet_calldata(&statement).unwrap();
+        assert_eq!(classify_darkpool_call(&calldata), DarkpoolCall::UpdateWallet);
+
+        let res = parse_shares_from_darkpool_calldata(&calldata, share.blinder).unwrap();
+        assert_eq!(res, vec![share]);
+    }
+
+    /// Tests building and parsing `processMatchSettle` calldata
+    #[test]
+    fn test_build_process_match_settle_calldata() {
+        let (party0_share, party0_modified_shares) = random_share();
+        let (party1_share, party1_modified_shares) = random_share();
+        let indices = OrderSettlementIndices { balance_send: 0, balance_receive: 1, order: 0 };
+        let statement = ContractValidMatchSettleStatement {
+            party0_modified_shares,
+            party1_modified_shares,
+            party0_indices: indices.clone(),
+            party1_indices: indices,
+            protocol_fee: ScalarField::from(0u8),
+        };
+
+        let calldata = build_process_match_settle_calldata(&statement).unwrap();
+        assert_eq!(classify_darkpool_call(&calldata), DarkpoolCall::ProcessMatchSettle);
+
+        let res = parse_shares_from_darkpool_calldata(&calldata, Scalar::zero()).unwrap();
+        assert_eq!(res, vec![party0_share, party1_share]);
+    }
+
+    /// Tests that a malleable match whose fees exceed the internal party's
+    /// receive amount is rejected
+    #[test]
+    fn test_apply_malleable_match__fees_exceed_receive() {
+        let (mut share, _) = random_share();
+        let original_share = share.clone();
+        let indices =
+            CircuitOrderSettlementIndices { balance_send: 0, balance_receive: 1, order: 0 };
+        let bounded_match = BoundedMatchResult {
+            price: FixedPoint::from_integer(2),
+            min_base_amount: 0,
+            max_base_amount: 100,
+            ..Default::default()
+        };
+
+        // A 150% total fee rate exceeds the amount received
+        let fee_rates =
+            FeeTakeRate::new(FixedPoint::from_integer(1), FixedPoint::from_f64_round_down(0.5));
+        let res = apply_malleable_match_for_base_amount(
+            &mut share,
+            100, // base_amount
+            &indices,
+            &bounded_match,
+            &fee_rates,
+        );
+
+        assert!(matches!(res, Err(DarkpoolClientError::InvalidMatch(_))));
+        assert_eq!(share, original_share);
+    }
+}
@@ -1,6 +1,7 @@
 //! Arbitrum implementation of the darkpool client
 
 pub mod abi;
+mod bounded_de;
 pub mod contract_types;
 pub mod helpers;
 
@@ -22,15 +23,9 @@ use abi::{
         processMalleableAtomicMatchSettleWithReceiverCall, processMatchSettleCall, redeemFeeCall,
         settleOfflineFeeCall, settleOnlineRelayerFeeCall, updateWalletCall,
     },
-    KNOWN_SELECTORS, PROCESS_ATOMIC_MATCH_SETTLE_SELECTOR,
-    PROCESS_ATOMIC_MATCH_SETTLE_WITH_RECEIVER_SELECTOR,
-    PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE_SELECTOR,
-    PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE_WITH_RECEIVER_SELECTOR,
-};
-use alloy::{
-    consensus::constants::SELECTOR_LEN,
-    rpc::types::{TransactionReceipt, TransactionRequest},
+    KNOWN_SELECTORS,
 };
+use alloy::rpc::types::{TransactionReceipt, TransactionRequest};
 use alloy_primitives::{Address, Bytes, Selector, U256};
 use alloy_sol_types::SolCall;
 use async_trait::async_trait;
@@ -67,7 +62,7 @@ use contract_types::{
     },
 };
 use helpers::{
-    deserialize_calldata, parse_shares_from_new_wallet,
+    DarkpoolCall, Selector as CallSelector, deserialize_calldata, parse_shares_from_new_wallet,
     parse_shares_from_process_atomic_match_settle,
     parse_shares_from_process_atomic_match_settle_with_receiver,
     parse_shares_from_process_malleable_atomic_match_settle,
@@ -262,6 +257,11 @@ impl DarkpoolImpl for ArbitrumDarkpool {
             proof: valid_match_settle_proof,
         } = match_bundle.copy_match_proof();
 
+        // Reject a mis-paired statement before it is submitted on-chain
+        valid_match_settle_statement
+            .verify_index_consistency()
+            .map_err(|e| DarkpoolClientError::InvalidStatement(e.to_string()))?;
+
         let party_0_valid_commitments_statement = party0_validity_proofs.commitment_proof.statement;
 
         let party_0_valid_reblind_statement =
@@ -524,6 +524,26 @@ impl DarkpoolImpl for ArbitrumDarkpool {
         ))
     }
 
+    /// Generate calldata for a `settleOfflineFee` call
+    fn gen_settle_offline_fee_calldata(
+        &self,
+        valid_offline_fee_settlement: &SizedOfflineFeeSettlementBundle,
+    ) -> Result<TransactionRequest, DarkpoolClientError> {
+        let GenericOfflineFeeSettlementBundle { statement, proof } = valid_offline_fee_settlement;
+
+        let contract_proof = to_contract_proof(proof)?;
+        let proof_calldata = serialize_calldata(&contract_proof)?;
+
+        let contract_statement = to_contract_valid_offline_fee_settlement_statement(statement);
+        let valid_offline_fee_settlement_statement_calldata =
+            serialize_calldata(&contract_statement)?;
+
+        Ok(self
+            .darkpool()
+            .settleOfflineFee(proof_calldata, valid_offline_fee_settlement_statement_calldata)
+            .into_transaction_request())
+    }
+
     /// Parse wallet shares from a given transaction's calldata and selector
     fn parse_shares(
         selector: Selector,
@@ -566,26 +586,26 @@ impl DarkpoolImpl for ArbitrumDarkpool {
     fn parse_external_match(
         calldata: &[u8],
     ) -> Result<Option<ExternalMatchResult>, DarkpoolClientError> {
-        let selector = calldata[..SELECTOR_LEN].try_into().unwrap();
+        let darkpool_call = CallSelector::from_calldata(calldata)?.classify();
 
         // Parse the `VALID MATCH SETTLE ATOMIC` statement from the calldata
-        let match_res = match selector {
-            PROCESS_ATOMIC_MATCH_SETTLE_SELECTOR => {
+        let match_res = match darkpool_call {
+            DarkpoolCall::ProcessAtomicMatchSettle => {
                 let call = processAtomicMatchSettleCall::abi_decode(calldata)?;
                 Self::parse_external_match_from_calldata(&call.valid_match_settle_atomic_statement)
             },
-            PROCESS_ATOMIC_MATCH_SETTLE_WITH_RECEIVER_SELECTOR => {
+            DarkpoolCall::ProcessAtomicMatchSettleWithReceiver => {
                 let call = processAtomicMatchSettleWithReceiverCall::abi_decode(calldata)?;
                 Self::parse_external_match_from_calldata(&call.valid_match_settle_atomic_statement)
             },
-            PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE_WITH_RECEIVER_SELECTOR => {
+            DarkpoolCall::ProcessMalleableAtomicMatchSettleWithReceiver => {
                 let call = processMalleableAtomicMatchSettleWithReceiverCall::abi_decode(calldata)?;
                 Self::parse_external_match_from_malleable(
                     call.base_amount,
                     &call.valid_match_settle_statement,
                 )
             },
-            PROCESS_MALLEABLE_ATOMIC_MATCH_SETTLE_SELECTOR => {
+            DarkpoolCall::ProcessMalleableAtomicMatchSettle => {
                 let call = processMalleableAtomicMatchSettleCall::abi_decode(calldata)?;
                 Self::parse_external_match_from_malleable(
                     call.base_amount,
@@ -40,8 +40,8 @@ use crate::conversion::address_to_biguint;
 use crate::conversion::biguint_to_address;
 use crate::conversion::biguint_to_u256;
 use crate::conversion::scalar_to_u256;
+use crate::conversion::try_u256_to_scalar;
 use crate::conversion::u256_to_amount;
-use crate::conversion::u256_to_scalar;
 use crate::errors::ConversionError;
 use crate::errors::DarkpoolClientError;
 
@@ -487,7 +487,7 @@ impl ToCircuitType for FixedPoint {
     type CircuitType = CircuitFixedPoint;
 
     fn to_circuit_type(&self) -> Result<Self::CircuitType, DarkpoolClientError> {
-        let repr = u256_to_scalar(self.repr);
+        let repr = try_u256_to_scalar(self.repr)?;
         Ok(Self::CircuitType { repr })
     }
 }
@@ -565,7 +565,10 @@ impl ToContractType for PolynomialCommitment {
 
 /// Size a vector of values to be a known fixed size
 pub fn size_vec<const N: usize, T>(vec: Vec<T>) -> Result<[T; N], DarkpoolClientError> {
-    vec.try_into().map_err(|_| DarkpoolClientError::Conversion(ConversionError::InvalidLength))
+    let actual = vec.len();
+    vec.try_into().map_err(|_| {
+        DarkpoolClientError::Conversion(ConversionError::InvalidLength { expected: N, actual })
+    })
 }
 
 /// Convert a `Fr` to a `U256`
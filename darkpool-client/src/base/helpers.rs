@@ -3,7 +3,11 @@
 
 use alloy::primitives::U256;
 use alloy_sol_types::SolCall;
-use circuit_types::{SizedWalletShare, traits::BaseType};
+use circuit_types::{
+    SizedWalletShare,
+    fees::{AmountExt, FeeTake},
+    traits::BaseType,
+};
 use constants::Scalar;
 use renegade_solidity_abi::IDarkpool::{
     createWalletCall, processAtomicMatchSettleCall, processMalleableAtomicMatchSettleCall,
@@ -12,7 +16,7 @@ use renegade_solidity_abi::IDarkpool::{
 use util::matching_engine::apply_match_to_shares;
 
 use crate::{
-    conversion::{scalar_to_u256, u256_to_amount, u256_to_scalar},
+    conversion::{u256_to_amount, u256_to_scalar},
     errors::DarkpoolClientError,
 };
 
@@ -49,23 +53,30 @@ pub fn parse_shares_from_process_match_settle(
     calldata: &[u8],
     public_blinder_share: Scalar,
 ) -> Result<SizedWalletShare, DarkpoolClientError> {
-    let call = processMatchSettleCall::abi_decode(calldata)?;
-    let party0_shares = &call.matchSettleStatement.firstPartyPublicShares;
-    let party1_shares = &call.matchSettleStatement.secondPartyPublicShares;
-    let party0_blinder = party0_shares.last().unwrap();
-    let party1_blinder = party1_shares.last().unwrap();
-
     // Select the shares between the two parties
-    let target_share = scalar_to_u256(public_blinder_share);
-    let selected_shares = if party0_blinder == &target_share {
-        party0_shares
-    } else if party1_blinder == &target_share {
-        party1_shares
+    let (party0_shares, party1_shares) = parse_both_shares_from_process_match_settle(calldata)?;
+    if party0_shares.blinder == public_blinder_share {
+        Ok(party0_shares)
+    } else if party1_shares.blinder == public_blinder_share {
+        Ok(party1_shares)
     } else {
-        return Err(DarkpoolClientError::BlinderNotFound);
-    };
+        Err(DarkpoolClientError::BlinderNotFound)
+    }
+}
 
-    Ok(wallet_share_from_u256s(selected_shares.clone()))
+/// Parses the wallet shares of both parties from the calldata of a
+/// `processMatchSettle` call
+///
+/// Returns the shares in party order, i.e. `(party0, party1)`
+pub fn parse_both_shares_from_process_match_settle(
+    calldata: &[u8],
+) -> Result<(SizedWalletShare, SizedWalletShare), DarkpoolClientError> {
+    let call = processMatchSettleCall::abi_decode(calldata)?;
+    let statement = call.matchSettleStatement;
+    Ok((
+        wallet_share_from_u256s(statement.firstPartyPublicShares),
+        wallet_share_from_u256s(statement.secondPartyPublicShares),
+    ))
 }
 
 /// Parses wallet shares from the calldata of a `processAtomicMatchSettle` call
@@ -100,6 +111,13 @@ pub fn parse_shares_from_process_malleable_atomic_match_settle(
     // Compute the fees due by the internal party
     let fee_rate = call.matchSettleStatement.internalFeeRates.to_circuit_type()?;
     let fees = fee_rate.compute_fee_take(recv);
+    if recv.checked_sub_fee(&fees).is_none() {
+        let FeeTake { relayer_fee, protocol_fee } = fees;
+        let msg = format!(
+            "relayer fee {relayer_fee} and protocol fee {protocol_fee} exceed receive amount {recv}"
+        );
+        return Err(DarkpoolClientError::InvalidMatch(msg));
+    }
 
     // Apply the match to the wallet share
     apply_match_to_shares(&mut shares, &indices, fees, &match_res, side);
@@ -216,6 +216,13 @@ impl DarkpoolImpl for BaseDarkpool {
         party1_validity: &OrderValidityProofBundle,
         match_bundle: &MatchBundle,
     ) -> Result<TransactionReceipt, DarkpoolClientError> {
+        // Reject a mis-paired statement before it is submitted on-chain
+        match_bundle
+            .match_proof
+            .statement
+            .verify_index_consistency()
+            .map_err(|e| DarkpoolClientError::InvalidStatement(e.to_string()))?;
+
         let party0_payload = party0_validity.to_contract_type()?;
         let party1_payload = party1_validity.to_contract_type()?;
         let statement = match_bundle.match_proof.statement.to_contract_type()?;
@@ -390,6 +397,15 @@ impl DarkpoolImpl for BaseDarkpool {
         Ok(req)
     }
 
+    fn gen_settle_offline_fee_calldata(
+        &self,
+        valid_offline_fee_settlement: &SizedOfflineFeeSettlementBundle,
+    ) -> Result<TransactionRequest, DarkpoolClientError> {
+        let statement = valid_offline_fee_settlement.statement.to_contract_type()?;
+        let proof = valid_offline_fee_settlement.proof.to_contract_type()?;
+        Ok(self.darkpool.settleOfflineFee(statement, proof).into_transaction_request())
+    }
+
     fn parse_shares(
         selector: Selector,
         calldata: &[u8],
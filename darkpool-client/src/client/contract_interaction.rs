@@ -194,6 +194,17 @@ impl<D: DarkpoolImpl> DarkpoolClientInner<D> {
         )
     }
 
+    /// Return the tx parameters for a `settle_offline_fee` call
+    ///
+    /// The transaction is not submitted, allowing the caller to inspect or
+    /// simulate it
+    pub fn gen_settle_offline_fee_calldata(
+        &self,
+        valid_offline_fee_settlement: &SizedOfflineFeeSettlementBundle,
+    ) -> Result<TransactionRequest, DarkpoolClientError> {
+        self.darkpool.gen_settle_offline_fee_calldata(valid_offline_fee_settlement)
+    }
+
     /// Call the `settle_online_relayer_fee` contract method with the given
     /// `VALID RELAYER FEE SETTLEMENT` statement
     ///
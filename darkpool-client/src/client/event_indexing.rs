@@ -75,15 +75,36 @@ impl<D: DarkpoolImpl> DarkpoolClientInner<D> {
         &self,
         commitment: Scalar,
     ) -> Result<MerkleAuthenticationPath, DarkpoolClientError> {
-        let (_, tx) = self.find_commitment_in_state_with_tx(commitment).await?;
-        let tx: TransactionReceipt = self
+        let tx = self
+            .find_commitment_tx(commitment)
+            .await?
+            .ok_or(DarkpoolClientError::CommitmentNotFound)?;
+
+        self.find_merkle_authentication_path_with_tx(commitment, &tx)
+    }
+
+    /// Fetch the receipt of the transaction that inserted the given commitment
+    /// into the Merkle tree
+    ///
+    /// Returns `None` if the commitment has not been inserted
+    #[instrument(skip_all, err, fields(commitment = %commitment))]
+    pub async fn find_commitment_tx(
+        &self,
+        commitment: Scalar,
+    ) -> Result<Option<TransactionReceipt>, DarkpoolClientError> {
+        let tx = match self.find_commitment_in_state_with_tx(commitment).await {
+            Ok((_, tx)) => tx,
+            Err(DarkpoolClientError::CommitmentNotFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let receipt = self
             .provider()
             .get_transaction_receipt(tx)
             .await
             .map_err(|e| DarkpoolClientError::TxQuerying(e.to_string()))?
             .ok_or(DarkpoolClientError::TxNotFound(tx.to_string()))?;
-
-        self.find_merkle_authentication_path_with_tx(commitment, &tx)
+        Ok(Some(receipt))
     }
 
     /// Parses the Merkle authentication path from a transaction receipt
@@ -1,7 +1,12 @@
 //! The definition of the darkpool client, which holds the configuration
 //! details, along with a lower-level handle for the darkpool smart contract
 
-use std::{str::FromStr, time::Duration};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 use alloy::{
     providers::{
@@ -28,6 +33,7 @@ use crate::{
 
 mod contract_interaction;
 mod event_indexing;
+mod token;
 
 /// A type alias for the RPC client, which is an alloy middleware stack that
 /// includes a signer derived from a raw private key, and a provider that
@@ -102,6 +108,8 @@ pub struct DarkpoolClientInner<D: DarkpoolImpl> {
     darkpool: D,
     /// The block number at which the darkpool was deployed
     deploy_block: BlockNumber,
+    /// A cache of ERC20 token decimals, keyed by token address
+    token_decimals: Arc<RwLock<HashMap<Address, u8>>>,
 }
 
 impl<D: DarkpoolImpl> DarkpoolClientInner<D> {
@@ -113,7 +121,7 @@ impl<D: DarkpoolImpl> DarkpoolClientInner<D> {
         let darkpool = D::new(darkpool_address, provider);
         let deploy_block = config.get_deploy_block();
 
-        Ok(Self { darkpool, deploy_block })
+        Ok(Self { darkpool, deploy_block, token_decimals: Default::default() })
     }
 
     /// Get a darkpool contract client
@@ -0,0 +1,41 @@
+//! Helpers for querying ERC20 token metadata
+
+use alloy_sol_types::sol;
+use num_bigint::BigUint;
+use tracing::instrument;
+
+use crate::{conversion::biguint_to_address, errors::DarkpoolClientError, traits::DarkpoolImpl};
+
+use super::DarkpoolClientInner;
+
+/// The error message emitted when the token decimals cache lock is poisoned
+const ERR_LOCK_POISONED: &str = "token decimals lock poisoned";
+
+sol! {
+    #[sol(rpc)]
+    interface IERC20 {
+        function decimals() external view returns (uint8);
+    }
+}
+
+impl<D: DarkpoolImpl> DarkpoolClientInner<D> {
+    /// Get the number of decimals used by the ERC20 token at the given mint
+    ///
+    /// Token decimals are immutable, so results are cached for the lifetime of
+    /// the client
+    #[instrument(skip_all, err, fields(mint = %mint))]
+    pub async fn get_token_decimals(&self, mint: &BigUint) -> Result<u8, DarkpoolClientError> {
+        let addr = biguint_to_address(mint)?;
+        let cached = self.token_decimals.read().expect(ERR_LOCK_POISONED).get(&addr).copied();
+        if let Some(decimals) = cached {
+            return Ok(decimals);
+        }
+
+        let token = IERC20::new(addr, self.provider());
+        let decimals =
+            token.decimals().call().await.map_err(DarkpoolClientError::contract_interaction)?;
+
+        self.token_decimals.write().expect(ERR_LOCK_POISONED).insert(addr, decimals);
+        Ok(decimals)
+    }
+}
@@ -2,8 +2,9 @@
 //! proofs, and their analogues as expected by the smart contracts.
 
 use alloy_primitives::{Address, U160, U256};
+use ark_ff::PrimeField;
 use circuit_types::Amount;
-use constants::Scalar;
+use constants::{Scalar, ScalarField};
 use num_bigint::BigUint;
 
 use crate::errors::ConversionError;
@@ -55,7 +56,21 @@ pub fn scalar_to_u256(scalar: Scalar) -> U256 {
 }
 
 /// Converts an alloy `U256` to a `Scalar`
+///
+/// Values outside of the scalar field are reduced, use `try_u256_to_scalar`
+/// for untrusted inputs
 pub fn u256_to_scalar(u256: U256) -> Scalar {
     let bytes = u256.to_be_bytes_vec();
     Scalar::from_be_bytes_mod_order(&bytes)
 }
+
+/// Converts an alloy `U256` to a `Scalar`, rejecting values that are not
+/// less than the scalar field modulus
+pub fn try_u256_to_scalar(u256: U256) -> Result<Scalar, ConversionError> {
+    let modulus = U256::from_limbs(ScalarField::MODULUS.0);
+    if u256 >= modulus {
+        return Err(ConversionError::NonCanonicalScalar);
+    }
+
+    Ok(u256_to_scalar(u256))
+}
@@ -3,6 +3,12 @@
 use std::{error::Error, fmt::Display};
 
 use alloy_sol_types::Error as SolError;
+use circuit_types::fees::FeeRateError;
+
+/// Fragments of contract interaction error messages that indicate a transient
+/// submission failure, after which resubmitting the transaction may succeed
+const TRANSIENT_SUBMISSION_ERRORS: &[&str] =
+    &["nonce too low", "replacement transaction underpriced", "timed out", "connection"];
 
 /// The error type returned by the darkpool client interface
 #[derive(Clone, Debug)]
@@ -35,9 +41,21 @@ pub enum DarkpoolClientError {
     /// one of the supported ones
     /// (`newWallet`, `updateWallet`, `processMatchSettle`)
     InvalidSelector,
+    /// Error thrown when calldata's selector does not match any known darkpool
+    /// method
+    UnknownSelector([u8; 4]),
     /// Error thrown when a target public blinder share was not found
     /// in a given transaction
     BlinderNotFound,
+    /// Error thrown when a statement is malformed and would settle corrupt
+    /// shares if submitted
+    InvalidStatement(String),
+    /// Error thrown when a match decoded from calldata is inconsistent, e.g.
+    /// its fees exceed the amount received
+    InvalidMatch(String),
+    /// Error thrown when versioned calldata carries an encoding version this
+    /// client does not support
+    UnsupportedVersion(u8),
 }
 
 impl DarkpoolClientError {
@@ -70,6 +88,19 @@ impl DarkpoolClientError {
     pub fn tx_querying<T: ToString>(msg: T) -> Self {
         Self::TxQuerying(msg.to_string())
     }
+
+    /// Whether the error is transient, i.e. the request that produced it may
+    /// succeed if retried
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Rpc(_) | Self::TxQuerying(_) | Self::TxDropped => true,
+            Self::ContractInteraction(msg) => {
+                let msg = msg.to_lowercase();
+                TRANSIENT_SUBMISSION_ERRORS.iter().any(|fragment| msg.contains(fragment))
+            },
+            _ => false,
+        }
+    }
 }
 
 impl Display for DarkpoolClientError {
@@ -106,17 +137,75 @@ impl From<DarkpoolClientConfigError> for DarkpoolClientError {
 pub enum ConversionError {
     /// Error thrown when a variable-length input
     /// can't be coerced into a fixed-length array
-    InvalidLength,
+    InvalidLength {
+        /// The length of the fixed-length array
+        expected: usize,
+        /// The length of the input
+        actual: usize,
+    },
     /// Error thrown when converting between uint types
     InvalidUint,
+    /// Error thrown when a value decoded as a field element is not less than
+    /// the scalar field modulus
+    NonCanonicalScalar,
+    /// Error thrown when a decoded public encryption key is the zero point or
+    /// another point that cannot be a valid key
+    InvalidEncryptionKey,
+    /// Error thrown when an index does not address an element of the
+    /// fixed-size array it indexes into
+    IndexOutOfRange {
+        /// The name of the index
+        field: &'static str,
+        /// The out of range index
+        index: u64,
+        /// The size of the array being indexed
+        max: usize,
+    },
+    /// Error thrown when decoded fee rates are out of range
+    InvalidFeeRate(FeeRateError),
+    /// Error thrown when converting a named field of a larger type
+    FieldError {
+        /// The name of the field that failed to convert
+        field: &'static str,
+        /// The underlying conversion error
+        source: Box<ConversionError>,
+    },
+}
+
+impl ConversionError {
+    /// Annotate the error with the name of the field being converted
+    pub fn with_field(self, field: &'static str) -> Self {
+        Self::FieldError { field, source: Box::new(self) }
+    }
 }
 
 impl Display for ConversionError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{self:?}")
+        match self {
+            Self::InvalidLength { expected, actual } => {
+                write!(f, "InvalidLength: expected {expected} elements, got {actual}")
+            },
+            Self::InvalidUint => write!(f, "InvalidUint"),
+            Self::NonCanonicalScalar => write!(f, "NonCanonicalScalar"),
+            Self::InvalidEncryptionKey => write!(f, "InvalidEncryptionKey"),
+            Self::IndexOutOfRange { field, index, max } => {
+                write!(f, "{field}: index {index} out of range for {max} elements")
+            },
+            Self::InvalidFeeRate(e) => write!(f, "InvalidFeeRate: {e}"),
+            Self::FieldError { field, source } => write!(f, "{field}: {source}"),
+        }
+    }
+}
+
+impl Error for ConversionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::FieldError { source, .. } => Some(source.as_ref()),
+            Self::InvalidFeeRate(e) => Some(e),
+            _ => None,
+        }
     }
 }
-impl Error for ConversionError {}
 
 impl From<ConversionError> for DarkpoolClientError {
     fn from(e: ConversionError) -> Self {
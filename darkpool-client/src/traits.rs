@@ -154,6 +154,12 @@ pub trait DarkpoolImpl: Clone {
         match_atomic_bundle: &MalleableAtomicMatchSettleBundle,
     ) -> Result<TransactionRequest, DarkpoolClientError>;
 
+    /// Generate calldata for a `settleOfflineFee` call
+    fn gen_settle_offline_fee_calldata(
+        &self,
+        valid_offline_fee_settlement: &SizedOfflineFeeSettlementBundle,
+    ) -> Result<TransactionRequest, DarkpoolClientError>;
+
     // ------------
     // | Recovery |
     // ------------
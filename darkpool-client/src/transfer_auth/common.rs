@@ -111,10 +111,12 @@ pub(crate) fn sign_bytes(
 /// Converts a [`PublicSigningKey`] to a fixed-length array of [`AlloyU256`]
 /// elements
 fn pk_to_u256s(pk: &PublicSigningKey) -> Result<[U256; NUM_SCALARS_PK], ConversionError> {
-    pk.to_scalars()
+    let scalars = pk.to_scalars();
+    let actual = scalars.len();
+    scalars
         .iter()
         .map(|s| scalar_to_u256(*s))
         .collect::<Vec<_>>()
         .try_into()
-        .map_err(|_| ConversionError::InvalidLength)
+        .map_err(|_| ConversionError::InvalidLength { expected: NUM_SCALARS_PK, actual })
 }
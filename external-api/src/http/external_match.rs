@@ -11,7 +11,7 @@
 use alloy::rpc::types::TransactionRequest;
 use circuit_types::{
     Amount,
-    fees::{FeeTake, FeeTakeRate},
+    fees::{AmountExt, FeeTake, FeeTakeRate},
     fixed_point::FixedPoint,
     r#match::{BoundedMatchResult, ExternalMatchResult},
     max_price,
@@ -49,6 +49,9 @@ const ERR_MULTIPLE_SIZING_PARAMS: &str =
 /// min fill size is also specified
 const ERR_MIN_FILL_SIZE_NOT_ZERO: &str =
     "Cannot set `min_fill_size` if `exact_output_amount` is specified";
+/// The error message emitted when the fees on a match exceed the amount the
+/// external party receives
+const ERR_FEES_EXCEED_RECEIVE: &str = "fees exceed the amount received";
 
 // ---------------
 // | HTTP Routes |
@@ -389,23 +392,26 @@ pub struct AtomicMatchApiBundle {
 impl AtomicMatchApiBundle {
     /// Create a new bundle from a `VALID MATCH SETTLE ATOMIC` bundle and a
     /// settlement transaction
+    ///
+    /// Errors if the fees exceed the amount the external party receives
     #[cfg(feature = "full-api")]
     pub fn new(
         match_bundle: &AtomicMatchSettleBundle,
         mut settlement_tx: TransactionRequest,
-    ) -> Self {
+    ) -> Result<Self, &'static str> {
         let statement = &match_bundle.atomic_match_proof.statement;
         let match_result = statement.match_result.clone();
         let fees = statement.external_party_fees;
 
         // Compute the received and sent assets net of fees
-        let (received_mint, mut received_amount) = match_result.external_party_receive();
+        let (received_mint, received_amount) = match_result.external_party_receive();
         let (sent_mint, sent_amount) = match_result.external_party_send();
-        received_amount -= fees.total();
+        let received_amount =
+            received_amount.checked_sub_fee(&fees).ok_or(ERR_FEES_EXCEED_RECEIVE)?;
 
         // Update the format of the settlement transaction
         process_settlement_tx(&mut settlement_tx);
-        Self {
+        Ok(Self {
             match_result: ApiExternalMatchResult::from(match_result),
             fees,
             receive: ApiExternalAssetTransfer {
@@ -417,7 +423,7 @@ impl AtomicMatchApiBundle {
                 amount: sent_amount,
             },
             settlement_tx,
-        }
+        })
     }
 }
 
@@ -523,7 +529,7 @@ impl From<ExternalMatchResult> for ApiExternalMatchResult {
         let quote_mint = biguint_to_hex_addr(&result.quote_mint);
         let base_mint = biguint_to_hex_addr(&result.base_mint);
         // Convert the match direction to the side of the external party
-        let direction = if result.direction { OrderSide::Buy } else { OrderSide::Sell };
+        let direction = OrderSide::from_external_direction(result.direction).opposite();
 
         Self {
             quote_mint,
@@ -556,7 +562,7 @@ impl From<BoundedMatchResult> for ApiBoundedMatchResult {
     fn from(result: BoundedMatchResult) -> Self {
         let quote_mint = biguint_to_hex_addr(&result.quote_mint);
         let base_mint = biguint_to_hex_addr(&result.base_mint);
-        let direction = if result.direction { OrderSide::Buy } else { OrderSide::Sell };
+        let direction = OrderSide::from_external_direction(result.direction).opposite();
 
         Self {
             quote_mint,
@@ -621,11 +627,18 @@ pub struct ApiExternalQuote {
 
 impl ApiExternalQuote {
     /// Create a new quote from an external match result and order
-    pub fn new(order: ExternalOrder, result: &ExternalMatchResult, fees: FeeTake) -> Self {
+    ///
+    /// Errors if the fees exceed the amount the external party receives
+    pub fn new(
+        order: ExternalOrder,
+        result: &ExternalMatchResult,
+        fees: FeeTake,
+    ) -> Result<Self, &'static str> {
         // Compute the sent and received assets
         let (send_mint, send_amount) = result.external_party_send();
-        let (receive_mint, mut receive_amount) = result.external_party_receive();
-        receive_amount -= fees.total();
+        let (receive_mint, receive_amount) = result.external_party_receive();
+        let receive_amount =
+            receive_amount.checked_sub_fee(&fees).ok_or(ERR_FEES_EXCEED_RECEIVE)?;
 
         // Calculate implied price as quote_amount / base_amount
         let base_amt_f64 = result.base_amount as f64;
@@ -633,7 +646,7 @@ impl ApiExternalQuote {
         let price = quote_amt_f64 / base_amt_f64;
         let timestamp = get_current_time_millis();
 
-        Self {
+        Ok(Self {
             order,
             match_result: result.clone().into(),
             fees,
@@ -647,7 +660,7 @@ impl ApiExternalQuote {
             },
             price: TimestampedPrice::new(price).into(),
             timestamp,
-        }
+        })
     }
 }
 
@@ -697,4 +710,24 @@ mod test {
         let net_amt = match_amt_scalar - fee_amt.floor();
         assert_eq!(scalar_to_u128(&net_amt), desired_amount);
     }
+
+    /// Tests that a quote is rejected if its fees exceed the amount received
+    #[test]
+    fn test_quote_fees_exceed_receive() {
+        // The external party buys 10 units of the base
+        let result = ExternalMatchResult {
+            quote_amount: 100,
+            base_amount: 10,
+            direction: true,
+            ..Default::default()
+        };
+
+        let fees = FeeTake { relayer_fee: 5, protocol_fee: 5 };
+        let quote = ApiExternalQuote::new(ExternalOrder::default(), &result, fees).unwrap();
+        assert_eq!(quote.receive.amount, 0);
+
+        let fees = FeeTake { relayer_fee: 6, protocol_fee: 5 };
+        let res = ApiExternalQuote::new(ExternalOrder::default(), &result, fees);
+        assert_eq!(res.err(), Some(ERR_FEES_EXCEED_RECEIVE));
+    }
 }
@@ -117,19 +117,17 @@ impl TryFrom<ApiWallet> for Wallet {
 
         let managing_cluster = jubjub_from_hex_string(&wallet.managing_cluster)?;
 
-        Ok(Wallet {
-            wallet_id: wallet.id,
+        Ok(Wallet::new(
+            wallet.id,
             orders,
             balances,
-            key_chain: wallet.key_chain.try_into()?,
-            max_match_fee: wallet.match_fee,
+            wallet.key_chain.try_into()?,
+            biguint_to_scalar(&wallet.blinder),
+            wallet.match_fee,
             managing_cluster,
-            blinder: biguint_to_scalar(&wallet.blinder),
-            blinded_public_shares,
             private_shares,
-            merkle_proof: None,
-            merkle_staleness: Default::default(),
-        })
+            blinded_public_shares,
+        ))
     }
 }
 
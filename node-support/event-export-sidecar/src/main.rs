@@ -42,7 +42,8 @@ async fn main() -> Result<(), Error> {
     let cli = Cli::parse();
     let relayer_config =
         parse_config_from_file(&cli.config_path).expect("could not parse relayer config");
-    relayer_config.configure_telemetry().expect("failed to configure telemetry");
+    let _telemetry_guard =
+        relayer_config.configure_telemetry().expect("failed to configure telemetry");
 
     if relayer_config.event_export_url.is_none() {
         warn!("Event export disabled, not creating event sidecar");
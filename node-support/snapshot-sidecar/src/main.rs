@@ -61,7 +61,8 @@ async fn main() {
     let cli = Cli::parse();
     let relayer_config =
         parse_config_from_file(&cli.config_path).expect("could not parse relayer config");
-    relayer_config.configure_telemetry().expect("failed to configure telemetry");
+    let _telemetry_guard =
+        relayer_config.configure_telemetry().expect("failed to configure telemetry");
 
     let region = Region::new(cli.region.clone());
     let config = aws_config::from_env().region(region).load().await;
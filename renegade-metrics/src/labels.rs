@@ -38,6 +38,18 @@ pub const NUM_REMOTE_PEERS_METRIC: &str = "num_remote_peers";
 pub const NUM_INFLIGHT_TASKS_METRIC: &str = "num_inflight_tasks";
 /// Metric describing the number of tasks completed
 pub const NUM_COMPLETED_TASKS_METRIC: &str = "num_completed_tasks";
+/// Metric describing the time (in seconds) a task spends in a given state
+pub const TASK_STATE_DURATION_METRIC: &str = "task_state_duration";
+/// Metric describing the time (in seconds) a task waits between creation and
+/// the driver beginning to execute it
+pub const TASK_PENDING_DURATION_METRIC: &str = "task_pending_duration";
+/// Metric describing the number of tasks that reached their `Completed` state
+pub const TASK_COMPLETED_METRIC: &str = "task_completed";
+/// Metric describing the number of task steps that failed
+pub const TASK_FAILED_METRIC: &str = "task_failed";
+/// Metric describing the time (in seconds) between enqueuing a proof job and
+/// receiving the proof
+pub const PROOF_GENERATION_LATENCY_METRIC: &str = "proof_generation_latency";
 
 // Event metrics
 
@@ -58,6 +70,17 @@ pub const ASSET_METRIC_TAG: &str = "asset";
 pub const BASE_ASSET_METRIC_TAG: &str = "base_asset";
 /// Metric tag for whether a match is external
 pub const EXTERNAL_MATCH_METRIC_TAG: &str = "is_external_match";
+/// Metric tag for the name of a task
+pub const TASK_NAME_METRIC_TAG: &str = "task";
+/// Metric tag for the state of a task
+pub const TASK_STATE_METRIC_TAG: &str = "state";
+/// Metric tag for the error variant with which a task step failed
+pub const TASK_ERROR_METRIC_TAG: &str = "error";
+/// Metric tag for the task-independent kind of error with which a task step
+/// failed
+pub const TASK_ERROR_KIND_METRIC_TAG: &str = "error_kind";
+/// Metric tag for the circuit a proof is generated for
+pub const CIRCUIT_METRIC_TAG: &str = "circuit";
 /// Helper to generate wallet ID tag names
 pub fn wallet_id_tag(n: usize) -> String {
     format!("wallet_id{}", n)
@@ -100,6 +100,9 @@ impl StateApplicator {
             StateTransition::TransitionTask { task_id, state } => {
                 self.transition_task_state(task_id, state)
             },
+            StateTransition::UpdateTaskDescriptor { task_id, descriptor } => {
+                self.update_task_descriptor(task_id, descriptor)
+            },
             StateTransition::ClearTaskQueue { queue } => self.clear_queue(queue),
             StateTransition::EnqueuePreemptiveTask { keys, task, executor, serial } => {
                 self.enqueue_preemptive_task(&keys, &task, &executor, serial)
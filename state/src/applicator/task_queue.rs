@@ -2,7 +2,9 @@
 
 use common::types::{
     gossip::WrappedPeerId,
-    tasks::{HistoricalTask, QueuedTask, QueuedTaskState, TaskIdentifier, TaskQueueKey},
+    tasks::{
+        HistoricalTask, QueuedTask, QueuedTaskState, TaskDescriptor, TaskIdentifier, TaskQueueKey,
+    },
     wallet::WalletIdentifier,
 };
 use external_api::{
@@ -32,6 +34,8 @@ const PENDING_STATE: &str = "Pending";
 const ERR_UNASSIGNED_TASK: &str = "task not assigned";
 /// Error emitted when a key cannot be found for a task
 const ERR_NO_KEY: &str = "key not found for task";
+/// Error emitted when a descriptor update would move a task to other queues
+const ERR_DESCRIPTOR_QUEUES_CHANGED: &str = "descriptor update changes the task's queues";
 
 /// Construct an invalid task queue key error
 fn invalid_task_id(key: TaskIdentifier) -> String {
@@ -146,6 +150,31 @@ impl StateApplicator {
         Ok(ApplicatorReturnType::None)
     }
 
+    /// Update the descriptor of a running task
+    #[instrument(skip_all, err, fields(task_id = %task_id))]
+    pub fn update_task_descriptor(
+        &self,
+        task_id: TaskIdentifier,
+        descriptor: TaskDescriptor,
+    ) -> Result<ApplicatorReturnType> {
+        let tx = self.db().new_write_tx()?;
+        let task = tx
+            .get_task(&task_id)?
+            .ok_or_else(|| StateApplicatorError::reject(invalid_task_id(task_id)))?;
+        if !task.state.is_running() {
+            return Err(StateApplicatorError::reject(task_not_running(task_id)));
+        }
+
+        // The task is indexed by the wallets it affects, which must not change
+        if descriptor.affected_wallets() != task.descriptor.affected_wallets() {
+            return Err(StateApplicatorError::reject(ERR_DESCRIPTOR_QUEUES_CHANGED));
+        }
+
+        tx.update_task_descriptor(&task_id, descriptor)?;
+        tx.commit()?;
+        Ok(ApplicatorReturnType::None)
+    }
+
     /// Clear the task queue, marking all tasks as failed
     #[instrument(skip_all, err, fields(queue_key = %key))]
     pub fn clear_queue(&self, key: TaskQueueKey) -> Result<ApplicatorReturnType> {
@@ -435,7 +464,10 @@ impl StateApplicator {
 mod test {
     use common::types::{
         gossip::{WrappedPeerId, mocks::mock_peer},
-        tasks::{QueuedTaskState, TaskIdentifier, TaskQueueKey, mocks::mock_queued_task},
+        tasks::{
+            QueuedTaskState, TaskDescriptor, TaskIdentifier, TaskQueueKey,
+            mocks::{mock_queued_task, mock_task_descriptor},
+        },
         wallet::WalletIdentifier,
         wallet_mocks::mock_empty_wallet,
     };
@@ -798,6 +830,55 @@ mod test {
         Ok(())
     }
 
+    /// Tests updating the descriptor of a running task
+    #[test]
+    fn test_update_task_descriptor() -> Result<()> {
+        let (applicator, _task_recv) = setup_mock_applicator_with_driver_queue();
+        let my_peer_id = get_local_peer_id(&applicator);
+
+        let task_queue_key = TaskQueueKey::new_v4();
+        let task = mock_queued_task(task_queue_key);
+        applicator.append_task(&task, &my_peer_id /* executor */)?;
+
+        // Update the descriptor on the same queue
+        let descriptor = mock_task_descriptor(task_queue_key);
+        let TaskDescriptor::NewWallet(ref expected) = descriptor else { unreachable!() };
+        let expected_seed = expected.blinder_seed;
+        applicator.update_task_descriptor(task.id, descriptor)?;
+
+        let tx = applicator.db().new_read_tx()?;
+        let task_info = tx.get_task(&task.id)?.unwrap();
+        tx.commit()?;
+        let TaskDescriptor::NewWallet(desc) = task_info.descriptor else { unreachable!() };
+        assert_eq!(desc.blinder_seed, expected_seed);
+
+        // A descriptor on another queue is rejected
+        let descriptor = mock_task_descriptor(TaskQueueKey::new_v4());
+        let err = applicator.update_task_descriptor(task.id, descriptor).unwrap_err();
+        assert!(matches!(err, StateApplicatorError::Rejected(_)));
+        Ok(())
+    }
+
+    /// Tests updating the descriptor of a task that is not running
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_update_task_descriptor__not_running() -> Result<()> {
+        let (applicator, _task_recv) = setup_mock_applicator_with_driver_queue();
+        let my_peer_id = get_local_peer_id(&applicator);
+
+        // The second task on the queue is queued behind the first
+        let task_queue_key = TaskQueueKey::new_v4();
+        let task1 = mock_queued_task(task_queue_key);
+        let task2 = mock_queued_task(task_queue_key);
+        applicator.append_task(&task1, &my_peer_id /* executor */)?;
+        applicator.append_task(&task2, &my_peer_id /* executor */)?;
+
+        let descriptor = mock_task_descriptor(task_queue_key);
+        let err = applicator.update_task_descriptor(task2.id, descriptor).unwrap_err();
+        assert!(matches!(err, StateApplicatorError::Rejected(_)));
+        Ok(())
+    }
+
     /// Tests clearing an empty queue
     #[test]
     #[allow(non_snake_case)]
@@ -174,6 +174,16 @@ impl StateInner {
         self.send_proposal(StateTransition::TransitionTask { task_id, state }).await
     }
 
+    /// Update the descriptor of a running task, so that the task resumes from
+    /// the updated descriptor if it is re-enqueued
+    pub async fn update_task_descriptor(
+        &self,
+        task_id: TaskIdentifier,
+        descriptor: TaskDescriptor,
+    ) -> Result<ProposalWaiter, StateError> {
+        self.send_proposal(StateTransition::UpdateTaskDescriptor { task_id, descriptor }).await
+    }
+
     /// Clear a task queue
     pub async fn clear_task_queue(&self, key: &TaskQueueKey) -> Result<ProposalWaiter, StateError> {
         self.send_proposal(StateTransition::ClearTaskQueue { queue: *key }).await
@@ -18,7 +18,7 @@ use common::types::{
     MatchingPoolName,
     gossip::WrappedPeerId,
     proof_bundles::{OrderValidityProofBundle, OrderValidityWitnessBundle},
-    tasks::{QueuedTask, QueuedTaskState, TaskIdentifier, TaskQueueKey},
+    tasks::{QueuedTask, QueuedTaskState, TaskDescriptor, TaskIdentifier, TaskQueueKey},
     wallet::{OrderIdentifier, Wallet, order_metadata::OrderMetadata},
 };
 use notifications::ProposalId;
@@ -159,6 +159,11 @@ pub enum StateTransition {
     PopTask { task_id: TaskIdentifier, success: bool },
     /// Transition the state of the top task in the task queue
     TransitionTask { task_id: TaskIdentifier, state: QueuedTaskState },
+    /// Update the descriptor of a running task
+    ///
+    /// Used by tasks to record progress that must survive the task being
+    /// re-enqueued, e.g. after a preemption or a failover
+    UpdateTaskDescriptor { task_id: TaskIdentifier, descriptor: TaskDescriptor },
     /// Clear all tasks in the queue, marking them as failed
     ClearTaskQueue { queue: TaskQueueKey },
     /// Enqueue a preemptive task for the given task queues
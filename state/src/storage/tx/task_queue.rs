@@ -9,7 +9,9 @@
 //! - Each task ID maps to a `QueuedTask` containing the task's description and
 //!   metadata.
 
-use common::types::tasks::{QueuedTask, QueuedTaskState, TaskIdentifier, TaskQueueKey};
+use common::types::tasks::{
+    QueuedTask, QueuedTaskState, TaskDescriptor, TaskIdentifier, TaskQueueKey,
+};
 use libmdbx::{RW, TransactionKind};
 use serde::{Deserialize, Serialize};
 use util::res_some;
@@ -502,6 +504,21 @@ impl StateTxn<'_, RW> {
         self.update_task(id, &task)
     }
 
+    /// Replace the descriptor of a task
+    pub fn update_task_descriptor(
+        &self,
+        id: &TaskIdentifier,
+        descriptor: TaskDescriptor,
+    ) -> Result<(), StorageError> {
+        let mut task = match self.get_task(id)? {
+            Some(t) => t,
+            None => return Err(StorageError::not_found(ERR_TASK_NOT_FOUND)),
+        };
+
+        task.descriptor = descriptor;
+        self.update_task(id, &task)
+    }
+
     // --- Helpers --- //
 
     /// Write the task queue to storage
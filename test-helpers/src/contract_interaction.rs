@@ -94,7 +94,6 @@ pub fn setup_wallet_shares(
     let (private_shares, blinded_public_shares) =
         create_wallet_shares_from_private(&wallet.clone().into(), &private_shares, new_blinder);
 
-    wallet.blinded_public_shares = blinded_public_shares;
-    wallet.private_shares = private_shares;
+    wallet.set_shares(private_shares, blinded_public_shares);
     wallet.blinder = new_blinder;
 }
@@ -3,7 +3,7 @@
 use circuit_types::{
     Amount,
     balance::Balance,
-    fees::FeeTake,
+    fees::{AmountExt, FeeTake},
     fixed_point::FixedPoint,
     r#match::{MatchResult, OrderSettlementIndices},
     order::{Order, OrderSide},
@@ -14,6 +14,10 @@ use renegade_crypto::fields::scalar_to_u128;
 
 use crate::on_chain::get_protocol_fee;
 
+/// The error message emitted when the fees on a match exceed the amount
+/// received
+const ERR_FEES_EXCEED_RECEIVE: &str = "fees exceed the amount received";
+
 // ------------
 // | Matching |
 // ------------
@@ -117,14 +121,15 @@ pub fn match_orders_with_max_min_amounts(
         return None;
     }
 
-    Some(MatchResult {
-        base_mint: o1.base_mint.clone(),
-        quote_mint: o1.quote_mint.clone(),
-        base_amount,
+    Some(MatchResult::from_match(
+        o1.quote_mint.clone(),
+        o1.base_mint.clone(),
         quote_amount,
-        direction: matches!(o1.side, OrderSide::Sell),
-        min_amount_order_index: max1 > max2,
-    })
+        base_amount,
+        matches!(o1.side, OrderSide::Sell),
+        max1,
+        max2,
+    ))
 }
 
 /// Compute the maximum matchable amount for an order and balance
@@ -149,6 +154,9 @@ pub fn compute_max_amount(price: &FixedPoint, order: &Order, balance: &Balance)
 // --------------
 
 /// Apply a match to two wallet secret shares
+///
+/// Party 0 settles the side given by the match direction and party 1 settles
+/// the opposite side, so callers need not derive each party's side
 pub fn settle_match_into_wallets<const MAX_BALANCES: usize, const MAX_ORDERS: usize>(
     wallet0_share: &mut WalletShare<MAX_BALANCES, MAX_ORDERS>,
     wallet1_share: &mut WalletShare<MAX_BALANCES, MAX_ORDERS>,
@@ -172,6 +180,10 @@ pub fn settle_match_into_wallets<const MAX_BALANCES: usize, const MAX_ORDERS: us
 /// Applies a match to the shares of a wallet
 ///
 /// Returns a new wallet share with the match applied
+///
+/// Panics if the fees exceed the amount received. Fees computed from valid
+/// rates never do, so callers applying a match decoded from untrusted input
+/// must check the fees first
 pub fn apply_match_to_shares<const MAX_BALANCES: usize, const MAX_ORDERS: usize>(
     shares: &mut WalletShare<MAX_BALANCES, MAX_ORDERS>,
     indices: &OrderSettlementIndices,
@@ -188,7 +200,7 @@ pub fn apply_match_to_shares<const MAX_BALANCES: usize, const MAX_ORDERS: usize>
     shares.balances[indices.balance_send].amount -= Scalar::from(send_amt);
 
     // Update the receive balance including fees
-    let trader_net = recv_amt - fees.total();
+    let trader_net = recv_amt.checked_sub_fee(&fees).expect(ERR_FEES_EXCEED_RECEIVE);
     shares.balances[indices.balance_receive].amount += Scalar::from(trader_net);
     shares.balances[indices.balance_receive].relayer_fee_balance += Scalar::from(fees.relayer_fee);
     shares.balances[indices.balance_receive].protocol_fee_balance +=
@@ -230,7 +242,9 @@ mod tests {
 
     use crate::matching_engine::{compute_fee_obligation, match_orders_with_min_base_amount};
 
-    use super::{apply_match_to_shares, compute_max_amount, match_orders};
+    use super::{
+        apply_match_to_shares, compute_max_amount, match_orders, settle_match_into_wallets,
+    };
     use circuit_types::{
         Amount, SizedWalletShare,
         balance::Balance,
@@ -748,4 +762,34 @@ mod tests {
         );
         assert_eq!(new_shares.orders[indices.order as usize].amount, expected_order_amt);
     }
+
+    /// Tests that settling a match into both wallets applies complementary
+    /// sides to the two parties
+    #[test]
+    fn test_settle_match_into_wallets() {
+        let match_res = random_match_result();
+        let side0 = OrderSide::from(match_res.direction);
+        let (indices0, indices1) = (random_settlement_indices(), random_settlement_indices());
+        let fees0 = compute_fee_obligation(random_relayer_fee(), side0, &match_res);
+        let fees1 = compute_fee_obligation(random_relayer_fee(), side0.opposite(), &match_res);
+
+        let original0 = random_wallet_share();
+        let original1 = random_wallet_share();
+        let (mut shares0, mut shares1) = (original0.clone(), original1.clone());
+        settle_match_into_wallets(
+            &mut shares0,
+            &mut shares1,
+            fees0,
+            fees1,
+            indices0,
+            indices1,
+            &match_res,
+        );
+
+        let (mut expected0, mut expected1) = (original0, original1);
+        apply_match_to_shares(&mut expected0, &indices0, fees0, &match_res, side0);
+        apply_match_to_shares(&mut expected1, &indices1, fees1, &match_res, side0.opposite());
+        assert_eq!(shares0, expected0);
+        assert_eq!(shares1, expected1);
+    }
 }
@@ -0,0 +1,247 @@
+//! An in-memory telemetry layer which records spans and events, for asserting
+//! on emitted telemetry in tests
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{
+    Event, Level, Subscriber,
+    field::{Field, Visit},
+    span::{Attributes, Id},
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+/// The name of the field holding an event's message
+const MESSAGE_FIELD: &str = "message";
+
+/// An event recorded by a `TelemetryCapture`
+#[derive(Clone, Debug)]
+pub struct CapturedEvent {
+    /// The level of the event
+    pub level: Level,
+    /// The target of the event, i.e. the module path that emitted it
+    pub target: String,
+    /// The event's message, empty if the event has none
+    pub message: String,
+    /// The event's fields other than its message, formatted as strings
+    pub fields: HashMap<String, String>,
+    /// The name of the span the event was emitted in, if any
+    pub span: Option<String>,
+}
+
+/// A span recorded by a `TelemetryCapture`
+#[derive(Clone, Debug)]
+pub struct CapturedSpan {
+    /// The ID of the span
+    pub id: Id,
+    /// The ID of the span's parent, if any
+    pub parent: Option<Id>,
+    /// The name of the span
+    pub name: String,
+    /// The span's fields as of its creation, formatted as strings
+    pub fields: HashMap<String, String>,
+}
+
+/// A subscriber layer which records the spans and events it sees in memory
+///
+/// Clones share the same records, so a test may keep a clone to inspect what
+/// the layer captured
+#[derive(Clone, Default)]
+pub struct TelemetryCapture {
+    /// The events recorded so far
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+    /// The spans created so far
+    spans: Arc<Mutex<Vec<CapturedSpan>>>,
+}
+
+impl TelemetryCapture {
+    /// Get a snapshot of the events recorded so far
+    pub fn events(&self) -> Vec<CapturedEvent> {
+        self.events.lock().expect("capture lock poisoned").clone()
+    }
+
+    /// Get a snapshot of the names of the spans created so far
+    pub fn spans(&self) -> Vec<String> {
+        self.captured_spans().into_iter().map(|span| span.name).collect()
+    }
+
+    /// Get a snapshot of the spans created so far, with their fields
+    pub fn captured_spans(&self) -> Vec<CapturedSpan> {
+        self.spans.lock().expect("capture lock poisoned").clone()
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for TelemetryCapture {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+
+        let span = ctx.span(id).expect("new span must be registered");
+        let parent = span.parent().map(|parent| parent.id());
+        let name = attrs.metadata().name().to_string();
+        let captured = CapturedSpan { id: id.clone(), parent, name, fields: visitor.fields };
+        self.spans.lock().expect("capture lock poisoned").push(captured);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let captured = CapturedEvent {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+            span: ctx.event_span(event).map(|span| span.name().to_string()),
+        };
+        self.events.lock().expect("capture lock poisoned").push(captured);
+    }
+}
+
+/// Collects the fields of an event as strings
+#[derive(Default)]
+struct FieldVisitor {
+    /// The event's message
+    message: String,
+    /// The event's other fields
+    fields: HashMap<String, String>,
+}
+
+impl FieldVisitor {
+    /// Record a formatted field value
+    fn record(&mut self, field: &Field, value: String) {
+        if field.name() == MESSAGE_FIELD {
+            self.message = value;
+        } else {
+            self.fields.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.record(field, format!("{value:?}"));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tracing::{Level, info, info_span, warn};
+
+    use crate::telemetry::{LevelFilter, TelemetryBuilder, helpers::default_noisy_filter};
+
+    /// Tests that a scoped subscriber captures spans and events
+    #[test]
+    fn test_capture_scoped() {
+        let (builder, capture) = TelemetryBuilder::default().with_capture();
+        let _guard = builder.build_scoped();
+
+        info_span!("settle_match").in_scope(|| {
+            info!(wallet = "abcd", amount = 10, "settling match");
+            warn!("balance low");
+        });
+
+        assert_eq!(capture.spans(), vec!["settle_match".to_string()]);
+
+        let events = capture.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].level, Level::INFO);
+        assert_eq!(events[0].message, "settling match");
+        assert_eq!(events[0].fields["wallet"], "abcd");
+        assert_eq!(events[0].fields["amount"], "10");
+        assert_eq!(events[1].level, Level::WARN);
+        assert_eq!(events[1].message, "balance low");
+        assert_eq!(events[1].span.as_deref(), Some("settle_match"));
+    }
+
+    /// Tests that scoped subscribers on separate threads do not interfere
+    #[test]
+    fn test_capture_scoped_isolated() {
+        let (builder, capture) = TelemetryBuilder::default().with_capture();
+        let _guard = builder.build_scoped();
+
+        std::thread::spawn(|| info!("another thread")).join().unwrap();
+        info!("this thread");
+
+        let messages: Vec<_> = capture.events().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["this thread".to_string()]);
+    }
+
+    /// Tests that events below the configured default level are filtered
+    #[test]
+    fn test_capture_default_level() {
+        let (builder, capture) =
+            TelemetryBuilder::default().with_default_level(LevelFilter::WARN).with_capture();
+        let _guard = builder.build_scoped();
+
+        info!("filtered");
+        warn!("kept");
+
+        let messages: Vec<_> = capture.events().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["kept".to_string()]);
+    }
+
+    /// Tests that a base filter suppresses noisy dependencies while the
+    /// default level still applies to other targets
+    #[test]
+    fn test_capture_base_filter() {
+        let (builder, capture) =
+            TelemetryBuilder::default().with_base_filter(default_noisy_filter()).with_capture();
+        let _guard = builder.build_scoped();
+
+        info!(target: "hyper::client", "filtered");
+        warn!(target: "hyper::client", "kept dependency");
+        info!("kept relayer");
+
+        let messages: Vec<_> = capture.events().into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["kept dependency".to_string(), "kept relayer".to_string()]);
+    }
+
+    /// Tests that captured spans record their own fields and their parent
+    #[test]
+    fn test_capture_span_fields() {
+        let (builder, capture) = TelemetryBuilder::default().with_capture();
+        let _guard = builder.build_scoped();
+
+        info_span!("task", wallet_id = "abcd", state = "pending").in_scope(|| {
+            let _span = info_span!("prove", state = "proving").entered();
+        });
+
+        let spans = capture.captured_spans();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].parent, None);
+        assert_eq!(spans[0].fields["wallet_id"], "abcd");
+        assert_eq!(spans[1].name, "prove");
+        assert_eq!(spans[1].parent, Some(spans[0].id.clone()));
+        assert_eq!(spans[1].fields["state"], "proving");
+        assert!(!spans[1].fields.contains_key("wallet_id"));
+    }
+
+    /// Tests that a panic is logged in the span it occurs in
+    #[test]
+    fn test_panic_logging() {
+        let (builder, capture) = TelemetryBuilder::default().with_panic_logging().with_capture();
+        let _guard = builder.build_scoped();
+
+        let res = std::panic::catch_unwind(|| {
+            info_span!("settle_match").in_scope(|| panic!("balance underflow"))
+        });
+        assert!(res.is_err());
+
+        let events = capture.events();
+        let event = events.iter().find(|e| e.message == "thread panicked").unwrap();
+        assert_eq!(event.level, Level::ERROR);
+        assert_eq!(event.span.as_deref(), Some("settle_match"));
+        assert_eq!(event.fields["panic.payload"], "balance underflow");
+        assert!(event.fields["panic.location"].contains("capture.rs"));
+        assert!(event.fields.contains_key("panic.backtrace"));
+    }
+}
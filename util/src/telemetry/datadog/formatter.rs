@@ -80,10 +80,34 @@ where
     })
 }
 
+/// Configures the optional fields emitted by the `DatadogFormatter`
+///
+/// The default emits neither, matching the formatter's original output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DatadogFormatterConfig {
+    /// Whether to emit the source file and line of the event as `span.file`
+    /// and `span.line`
+    pub include_source_location: bool,
+    /// Whether to emit the name of the thread that recorded the event as
+    /// `logger.thread_name`
+    pub include_thread: bool,
+}
+
 /// The event formatter that adds the Datadog-compatible
 /// trace span IDs to the event
 // mostly stolen from here: https://github.com/tokio-rs/tracing/issues/1531
-pub struct DatadogFormatter;
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DatadogFormatter {
+    /// The optional fields to emit
+    config: DatadogFormatterConfig,
+}
+
+impl DatadogFormatter {
+    /// Create a formatter that emits the optional fields enabled in the config
+    pub fn new(config: DatadogFormatterConfig) -> Self {
+        Self { config }
+    }
+}
 
 impl<S, N> FormatEvent<S, N> for DatadogFormatter
 where
@@ -116,6 +140,22 @@ where
                 serializer.serialize_entry("line_number", &line_number)?;
             }
 
+            if self.config.include_source_location {
+                if let Some(filename) = meta.file() {
+                    serializer.serialize_entry("span.file", filename)?;
+                }
+
+                if let Some(line_number) = meta.line() {
+                    serializer.serialize_entry("span.line", &line_number)?;
+                }
+            }
+
+            if self.config.include_thread {
+                if let Some(thread_name) = std::thread::current().name() {
+                    serializer.serialize_entry("logger.thread_name", thread_name)?;
+                }
+            }
+
             // fields -> stolen from https://github.com/tokio-rs/tracing/blob/tracing-subscriber-0.3.17/tracing-subscriber/src/fmt/format/json.rs#L263-L268
             let mut visitor = tracing_serde::SerdeMapVisitor::new(serializer);
             event.record(&mut visitor);
@@ -179,8 +219,89 @@ impl io::Write for WriteAdapter<'_> {
 
 #[cfg(test)]
 mod tests {
-    use super::DatadogId;
-    use opentelemetry::trace::{SpanId, TraceId};
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{DatadogFormatter, DatadogFormatterConfig, DatadogId};
+    use opentelemetry::trace::{SpanId, TraceId, TracerProvider as _};
+    use opentelemetry_sdk::trace::TracerProvider;
+    use serde_json::Value;
+    use tracing::info;
+    use tracing_subscriber::{fmt, layer::SubscriberExt};
+
+    /// A writer which buffers formatted logs for inspection
+    #[derive(Clone, Default)]
+    struct BufWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for BufWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Log a single event within a traced span using the given config, and
+    /// return the formatted event
+    fn format_event(config: DatadogFormatterConfig) -> serde_json::Map<String, Value> {
+        let writer = BufWriter::default();
+        let make_writer = writer.clone();
+        let tracer = TracerProvider::builder().build().tracer("test");
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer))
+            .with(
+                fmt::layer()
+                    .json()
+                    .event_format(DatadogFormatter::new(config))
+                    .with_writer(move || make_writer.clone()),
+            );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("test_span").entered();
+            info!("test event");
+        });
+
+        let buf = writer.0.lock().unwrap();
+        match serde_json::from_slice(&buf).unwrap() {
+            Value::Object(event) => event,
+            event => panic!("expected a JSON object, got {event}"),
+        }
+    }
+
+    /// Tests that the default config emits no optional fields
+    #[test]
+    fn test_default_config_fields() {
+        let event = format_event(DatadogFormatterConfig::default());
+        assert!(event.contains_key("filename"));
+        assert!(event.contains_key("line_number"));
+        assert!(!event.contains_key("span.file"));
+        assert!(!event.contains_key("span.line"));
+        assert!(!event.contains_key("logger.thread_name"));
+
+        // Trace correlation fields are emitted
+        assert!(event.contains_key("dd.trace_id"));
+        assert!(event.contains_key("dd.span_id"));
+    }
+
+    /// Tests that the optional fields are emitted when enabled
+    #[test]
+    fn test_optional_fields() {
+        let config = DatadogFormatterConfig { include_source_location: true, include_thread: true };
+        let event = format_event(config);
+        assert_eq!(event["span.file"], event["filename"]);
+        assert_eq!(event["span.line"], event["line_number"]);
+
+        let thread_name = std::thread::current().name().map(Value::from).unwrap_or(Value::Null);
+        assert_eq!(event.get("logger.thread_name").cloned().unwrap_or(Value::Null), thread_name);
+
+        // Trace correlation fields are unaffected
+        assert!(event.contains_key("dd.trace_id"));
+        assert!(event.contains_key("dd.span_id"));
+    }
 
     #[test]
     fn test_trace_id_converted_to_datadog_id() {
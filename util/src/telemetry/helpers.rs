@@ -1,6 +1,11 @@
 //! Helper methods for capturing telemetry information throughout the relayer
 
 use tracing::Value;
+use tracing_subscriber::EnvFilter;
+
+/// The directives suppressing chatty logs from common dependencies
+const NOISY_DEPENDENCY_DIRECTIVES: &str = "hyper=warn,hyper_util=warn,h2=warn,tower=warn,\
+    tonic=warn,reqwest=warn,rustls=warn,opentelemetry=warn,opentelemetry_sdk=warn";
 
 /// Fills in field `field_name` with `field_value` on the current span.
 ///
@@ -10,3 +15,11 @@ use tracing::Value;
 pub fn backfill_trace_field<V: Value>(field_name: &str, field_value: V) {
     tracing::Span::current().record(field_name, field_value);
 }
+
+/// Build a log filter which raises common, noisy dependencies to the `WARN`
+/// level
+///
+/// Intended to be passed to `TelemetryBuilder::with_base_filter`
+pub fn default_noisy_filter() -> EnvFilter {
+    EnvFilter::new(NOISY_DEPENDENCY_DIRECTIVES)
+}
@@ -1,8 +1,30 @@
 //! Configures a metrics recorder to send metrics to a statsd server
 
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::{SocketAddr, UdpSocket},
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use cadence::{
+    BufferedUdpMetricSink, BufferedUnixMetricSink, MetricSink, QueuingMetricSink, SinkStats,
+};
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Label, Metadata,
+    Recorder, SharedString, Unit,
+};
+use metrics_exporter_prometheus::PrometheusBuilder;
 use metrics_exporter_statsd::StatsdBuilder;
 use metrics_tracing_context::TracingContextLayer;
-use metrics_util::layers::Layer;
+use metrics_util::layers::{Layer, PrefixLayer};
+use serde::{Deserialize, Serialize};
+use tracing::error;
 
 use crate::err_str;
 
@@ -17,6 +39,16 @@ pub const DEFAULT_RELAYER_METRICS_PREFIX: &str = "renegade_relayer";
 pub const DEFAULT_METRICS_BUFFER_SIZE: usize = 1024;
 /// Default queue size for metrics in number of elements
 pub const DEFAULT_METRICS_QUEUE_SIZE: usize = 1024 * 1024;
+/// The interval at which to poll the metrics queue while flushing
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// The default maximum amount of time to wait for queued metrics to drain
+/// when flushing
+pub const DEFAULT_METRICS_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+/// The minimum interval at which buffered metrics may be periodically flushed
+pub const MIN_METRICS_FLUSH_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The flush handle for the global StatsD recorder, set once it is configured
+static GLOBAL_FLUSH_HANDLE: OnceLock<MetricsFlushHandle> = OnceLock::new();
 
 /// Configuration for metrics collection
 #[derive(Debug, Clone)]
@@ -24,7 +56,7 @@ pub struct MetricsConfig {
     /// The prefix to use for metrics emitted by the relayer
     pub metrics_prefix: String,
     /// The size (in bytes) of the buffer which metrics data must fill before
-    /// being flushed out over UDP
+    /// being flushed out over the socket
     pub buffer_size: usize,
     /// The size (in # of elements) of the queue which the metrics exporter
     /// maintains.
@@ -34,6 +66,26 @@ pub struct MetricsConfig {
     /// We effectively want an unbounded queue, but the `StatsdBuilder` doesn't
     /// support this, so we set a sufficiently large value here.
     pub queue_size: usize,
+    /// Labels attached to every metric, e.g. `("region", "us-east-1")`
+    ///
+    /// Labels are exported as DogStatsD tags. A label set at the call site
+    /// takes precedence over a default label with the same key
+    pub default_labels: Vec<(String, String)>,
+    /// The interval at which buffered metrics are flushed out over the socket,
+    /// whether or not the buffer is full
+    ///
+    /// If unset, metrics are only sent once the buffer fills or on an explicit
+    /// call to `flush`. A periodic flush only sends the metrics that have
+    /// left the sink's queue, whereas `flush` also waits for the queue to
+    /// drain, so short-lived processes should still call `flush` before
+    /// exiting. Must be at least `MIN_METRICS_FLUSH_INTERVAL`
+    pub flush_interval: Option<Duration>,
+    /// Whether a failure to set up the StatsD recorder fails telemetry setup
+    ///
+    /// If `false`, the failure is logged and metrics are discarded by the
+    /// default no-op recorder. Invalid configuration, e.g. a flush interval
+    /// that is too short, is always an error
+    pub required: bool,
 }
 
 impl Default for MetricsConfig {
@@ -42,20 +94,178 @@ impl Default for MetricsConfig {
             metrics_prefix: DEFAULT_RELAYER_METRICS_PREFIX.to_string(),
             buffer_size: DEFAULT_METRICS_BUFFER_SIZE,
             queue_size: DEFAULT_METRICS_QUEUE_SIZE,
+            default_labels: Vec::new(),
+            flush_interval: None,
+            required: true,
         }
     }
 }
 
+/// The destination to which StatsD metrics are exported
+#[derive(Clone, Debug)]
+pub enum StatsdSink {
+    /// A StatsD server listening over UDP
+    Udp {
+        /// The host of the server
+        host: String,
+        /// The port of the server
+        port: u16,
+    },
+    /// A StatsD server listening on a unix domain datagram socket, e.g. a
+    /// DogStatsD agent's socket
+    Uds {
+        /// The path of the socket file
+        path: PathBuf,
+    },
+}
+
+/// A handle to the StatsD metrics sink, used to flush buffered metrics before
+/// the process exits
+#[derive(Clone)]
+pub struct MetricsFlushHandle {
+    /// The queuing sink shared with the global recorder
+    sink: Arc<QueuingMetricSink>,
+}
+
+impl MetricsFlushHandle {
+    /// Wait for the sink's queue to empty, up to the given timeout, then flush
+    /// the buffered metrics out over the socket
+    ///
+    /// This is best-effort: over UDP, a flushed metric may still be dropped
+    /// in transit without an error being reported. Unlike the periodic flush
+    /// configured by `MetricsConfig::flush_interval`, this also sends the
+    /// metrics still waiting in the queue
+    pub fn flush(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while self.sink.queued() > 0 && Instant::now() < deadline {
+            thread::sleep(FLUSH_POLL_INTERVAL);
+        }
+
+        if let Err(e) = self.sink.flush() {
+            error!("error flushing metrics: {e}");
+        }
+    }
+}
+
+/// A metric sink that shares its underlying sink with a
+/// [`MetricsFlushHandle`]
+struct SharedMetricSink(Arc<QueuingMetricSink>);
+
+impl MetricSink for SharedMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.0.emit(metric)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.flush()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.0.stats()
+    }
+}
+
+/// Check that the configured flush interval, if any, is not too short
+pub(super) fn validate_flush_interval(config: &MetricsConfig) -> Result<(), TelemetrySetupError> {
+    match config.flush_interval {
+        Some(interval) if interval < MIN_METRICS_FLUSH_INTERVAL => {
+            let msg = format!(
+                "metrics flush interval {interval:?} is below the minimum of \
+                 {MIN_METRICS_FLUSH_INTERVAL:?}"
+            );
+            Err(TelemetrySetupError::Metrics(msg))
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Spawn a thread which flushes the sink's buffer at the given interval
+///
+/// The thread runs for the lifetime of the process, as does the global
+/// recorder that shares the sink
+fn spawn_periodic_flush(
+    sink: Arc<QueuingMetricSink>,
+    interval: Duration,
+) -> Result<(), TelemetrySetupError> {
+    thread::Builder::new()
+        .name("metrics-flush".to_string())
+        .spawn(move || {
+            loop {
+                thread::sleep(interval);
+                if let Err(e) = sink.flush() {
+                    error!("error flushing metrics: {e}");
+                }
+            }
+        })
+        .map_err(err_str!(TelemetrySetupError::Metrics))?;
+
+    Ok(())
+}
+
+/// Build the queuing sink that metrics are exported through
+///
+/// Mirrors the sink the `StatsdBuilder` constructs by default, so that a handle
+/// to it may be kept for flushing
+fn build_statsd_sink(
+    sink: &StatsdSink,
+    config: &MetricsConfig,
+) -> Result<QueuingMetricSink, TelemetrySetupError> {
+    match sink {
+        StatsdSink::Udp { host, port } => {
+            let socket =
+                UdpSocket::bind("0.0.0.0:0").map_err(err_str!(TelemetrySetupError::Metrics))?;
+            socket.set_nonblocking(true).map_err(err_str!(TelemetrySetupError::Metrics))?;
+
+            let udp_sink = BufferedUdpMetricSink::with_capacity(
+                (host.as_str(), *port),
+                socket,
+                config.buffer_size,
+            )
+            .map_err(err_str!(TelemetrySetupError::Metrics))?;
+            Ok(QueuingMetricSink::with_capacity(udp_sink, config.queue_size))
+        },
+        StatsdSink::Uds { path } => {
+            // The sink only fails on write, so check for the socket up front
+            if !path.exists() {
+                let msg = format!("statsd socket not found: {}", path.display());
+                return Err(TelemetrySetupError::Metrics(msg));
+            }
+
+            let socket = UnixDatagram::unbound().map_err(err_str!(TelemetrySetupError::Metrics))?;
+            socket.set_nonblocking(true).map_err(err_str!(TelemetrySetupError::Metrics))?;
+
+            let uds_sink = BufferedUnixMetricSink::with_capacity(path, socket, config.buffer_size);
+            Ok(QueuingMetricSink::with_capacity(uds_sink, config.queue_size))
+        },
+    }
+}
+
 /// Configures a statsd metrics recorder with custom configuration
+///
+/// Returns a handle through which buffered metrics may be flushed
 pub fn configure_metrics_statsd_recorder_with_config(
     datadog_enabled: bool,
     statsd_host: &str,
     statsd_port: u16,
     config: &MetricsConfig,
-) -> Result<(), TelemetrySetupError> {
-    let mut builder = StatsdBuilder::from(statsd_host, statsd_port)
-        .with_buffer_size(config.buffer_size)
-        .with_queue_size(config.queue_size);
+) -> Result<MetricsFlushHandle, TelemetrySetupError> {
+    let sink = StatsdSink::Udp { host: statsd_host.to_string(), port: statsd_port };
+    configure_metrics_statsd_recorder_with_sink(datadog_enabled, &sink, config)
+}
+
+/// Configures a statsd metrics recorder which exports to the given sink
+///
+/// Returns a handle through which buffered metrics may be flushed. If the
+/// config sets a flush interval, a background thread also flushes the buffer
+/// at that interval
+pub fn configure_metrics_statsd_recorder_with_sink(
+    datadog_enabled: bool,
+    sink: &StatsdSink,
+    config: &MetricsConfig,
+) -> Result<MetricsFlushHandle, TelemetrySetupError> {
+    validate_flush_interval(config)?;
+    let sink = Arc::new(build_statsd_sink(sink, config)?);
+    let mut builder = StatsdBuilder::default().with_sink(SharedMetricSink(sink.clone()));
 
     if datadog_enabled {
         let UnifiedServiceTags { service, env, version } = get_unified_service_tags()?;
@@ -65,15 +275,59 @@ pub fn configure_metrics_statsd_recorder_with_config(
             .with_default_tag("version", version);
     };
 
-    let recorder = TracingContextLayer::all().layer(
-        builder
-            .build(Some(&config.metrics_prefix))
-            .map_err(err_str!(TelemetrySetupError::Metrics))?,
-    );
+    let statsd_recorder = builder
+        .build(Some(&config.metrics_prefix))
+        .map_err(err_str!(TelemetrySetupError::Metrics))?;
+    let labeled = DefaultLabelsLayer::new(&config.default_labels).layer(statsd_recorder);
+    let recorder = TracingContextLayer::all().layer(labeled);
 
     metrics::set_global_recorder(recorder).unwrap();
+    if let Some(interval) = config.flush_interval {
+        spawn_periodic_flush(sink.clone(), interval)?;
+    }
 
-    Ok(())
+    let handle = MetricsFlushHandle { sink };
+    let _ = GLOBAL_FLUSH_HANDLE.set(handle.clone());
+    Ok(handle)
+}
+
+/// Synchronously flush the metrics buffered by the global StatsD recorder,
+/// waiting up to `DEFAULT_METRICS_FLUSH_TIMEOUT` for its queue to drain
+///
+/// Intended for short-lived processes which may exit before the buffer fills.
+/// This is a no-op if no StatsD recorder is configured, and is best-effort for
+/// UDP sinks, over which flushed metrics may still be dropped
+pub fn flush() {
+    if let Some(handle) = GLOBAL_FLUSH_HANDLE.get() {
+        handle.flush(DEFAULT_METRICS_FLUSH_TIMEOUT);
+    }
+}
+
+/// Configures a Prometheus metrics recorder which serves scrapes over HTTP on
+/// the given address
+///
+/// Only the metrics prefix of the config applies, the buffer and queue sizes,
+/// default labels, and flush interval are specific to StatsD. Must be called
+/// from within a Tokio runtime, onto which the exporter's HTTP listener is
+/// spawned
+pub fn configure_metrics_prometheus_recorder_with_config(
+    listen_addr: SocketAddr,
+    config: &MetricsConfig,
+) -> Result<(), TelemetrySetupError> {
+    let (recorder, exporter) = PrometheusBuilder::new()
+        .with_http_listener(listen_addr)
+        .build()
+        .map_err(err_str!(TelemetrySetupError::Metrics))?;
+
+    tokio::spawn(async move {
+        if let Err(e) = exporter.await {
+            error!("prometheus metrics exporter failed: {e}");
+        }
+    });
+
+    let prefixed = PrefixLayer::new(&config.metrics_prefix).layer(recorder);
+    let recorder = TracingContextLayer::all().layer(prefixed);
+    metrics::set_global_recorder(recorder).map_err(err_str!(TelemetrySetupError::Metrics))
 }
 
 /// Configures a statsd metrics recorder with default configuration
@@ -81,7 +335,7 @@ pub fn configure_metrics_statsd_recorder(
     datadog_enabled: bool,
     statsd_host: &str,
     statsd_port: u16,
-) -> Result<(), TelemetrySetupError> {
+) -> Result<MetricsFlushHandle, TelemetrySetupError> {
     configure_metrics_statsd_recorder_with_config(
         datadog_enabled,
         statsd_host,
@@ -89,3 +343,486 @@ pub fn configure_metrics_statsd_recorder(
         &MetricsConfig::default(),
     )
 }
+
+// ------------------
+// | Default Labels |
+// ------------------
+
+/// A recorder layer which attaches a set of default labels to every metric
+///
+/// Labels already present on a metric's key are left untouched, so call site
+/// labels take precedence over the defaults
+struct DefaultLabelsLayer {
+    /// The labels to attach
+    labels: Vec<Label>,
+}
+
+impl DefaultLabelsLayer {
+    /// Constructor
+    fn new(labels: &[(String, String)]) -> Self {
+        let labels = labels.iter().map(|(k, v)| Label::new(k.clone(), v.clone())).collect();
+        Self { labels }
+    }
+}
+
+impl<R> Layer<R> for DefaultLabelsLayer {
+    type Output = DefaultLabels<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        DefaultLabels { labels: self.labels.clone(), inner }
+    }
+}
+
+/// A recorder which attaches default labels before delegating to an inner
+/// recorder
+struct DefaultLabels<R> {
+    /// The labels to attach
+    labels: Vec<Label>,
+    /// The inner recorder
+    inner: R,
+}
+
+impl<R> DefaultLabels<R> {
+    /// Add any default labels whose keys are missing from the given key
+    fn label_key(&self, key: &Key) -> Key {
+        with_default_labels(key, &self.labels)
+    }
+}
+
+impl<R: Recorder> Recorder for DefaultLabels<R> {
+    fn describe_counter(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_counter(key, unit, description)
+    }
+
+    fn describe_gauge(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_gauge(key, unit, description)
+    }
+
+    fn describe_histogram(&self, key: KeyName, unit: Option<Unit>, description: SharedString) {
+        self.inner.describe_histogram(key, unit, description)
+    }
+
+    fn register_counter(&self, key: &Key, metadata: &Metadata<'_>) -> Counter {
+        self.inner.register_counter(&self.label_key(key), metadata)
+    }
+
+    fn register_gauge(&self, key: &Key, metadata: &Metadata<'_>) -> Gauge {
+        self.inner.register_gauge(&self.label_key(key), metadata)
+    }
+
+    fn register_histogram(&self, key: &Key, metadata: &Metadata<'_>) -> Histogram {
+        self.inner.register_histogram(&self.label_key(key), metadata)
+    }
+}
+
+/// Append the default labels whose keys are not already set on the given key
+fn with_default_labels(key: &Key, defaults: &[Label]) -> Key {
+    let missing = defaults.iter().filter(|d| !key.labels().any(|l| l.key() == d.key()));
+    let labels: Vec<Label> = key.labels().chain(missing).cloned().collect();
+    Key::from_parts(key.name().to_string(), labels)
+}
+
+// ----------
+// | Replay |
+// ----------
+
+/// The kind of a recorded metric emission
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedMetricKind {
+    /// A counter increment
+    Counter,
+    /// A gauge update
+    Gauge,
+    /// A histogram sample
+    Histogram,
+}
+
+/// A single recorded metric emission
+///
+/// A capture file holds one JSON-encoded emission per line, ordered by offset,
+/// as written by `MetricsCapture::dump_to_file`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedMetric {
+    /// The time in milliseconds since the start of the capture at which the
+    /// metric was emitted
+    pub offset_ms: u64,
+    /// The kind of metric emitted
+    pub kind: RecordedMetricKind,
+    /// The name of the metric
+    pub name: String,
+    /// The value emitted, counter increments are truncated to integers
+    pub value: f64,
+    /// The labels attached to the metric
+    #[serde(default)]
+    pub labels: Vec<(String, String)>,
+}
+
+impl RecordedMetric {
+    /// Emit the metric through the global recorder
+    fn emit(&self) {
+        let name = self.name.clone();
+        let labels = self.labels.as_slice();
+        match self.kind {
+            RecordedMetricKind::Counter => {
+                metrics::counter!(name, labels).increment(self.value as u64)
+            },
+            RecordedMetricKind::Gauge => metrics::gauge!(name, labels).set(self.value),
+            RecordedMetricKind::Histogram => metrics::histogram!(name, labels).record(self.value),
+        }
+    }
+}
+
+/// A recorder which captures metric emissions in memory, so that they may be
+/// asserted on in tests or dumped to a capture file for `replay_from_file`
+///
+/// Gauge updates are captured as the gauge's resulting value and absolute
+/// counter updates as the increment they imply, so that a replay reproduces
+/// the values the metrics took. Clones share the same captured emissions
+#[derive(Clone)]
+pub struct MetricsCapture {
+    /// The time at which the capture started, emission offsets are taken
+    /// relative to it
+    start: Instant,
+    /// The captured emissions and the current value of each metric
+    state: Arc<Mutex<MetricsCaptureState>>,
+}
+
+/// The mutable state of a `MetricsCapture`
+#[derive(Default)]
+struct MetricsCaptureState {
+    /// The emissions captured so far, in the order they were made
+    emissions: Vec<RecordedMetric>,
+    /// The current value of each counter and gauge
+    values: HashMap<(RecordedMetricKind, Key), f64>,
+}
+
+impl Default for MetricsCapture {
+    fn default() -> Self {
+        Self { start: Instant::now(), state: Default::default() }
+    }
+}
+
+impl MetricsCapture {
+    /// Get a snapshot of the emissions captured so far
+    pub fn recorded(&self) -> Vec<RecordedMetric> {
+        self.state.lock().expect("capture lock poisoned").emissions.clone()
+    }
+
+    /// Write the emissions captured so far to a capture file, in the format
+    /// read by `replay_from_file`
+    pub fn dump_to_file(&self, path: &Path) -> Result<(), TelemetrySetupError> {
+        let file = File::create(path).map_err(err_str!(TelemetrySetupError::Metrics))?;
+        let mut writer = BufWriter::new(file);
+        for metric in self.recorded() {
+            serde_json::to_writer(&mut writer, &metric)
+                .map_err(err_str!(TelemetrySetupError::Metrics))?;
+            writeln!(writer).map_err(err_str!(TelemetrySetupError::Metrics))?;
+        }
+
+        writer.flush().map_err(err_str!(TelemetrySetupError::Metrics))
+    }
+
+    /// Capture an emission of the given metric
+    ///
+    /// `update` is given the metric's current value to update, and returns the
+    /// value to record for the emission
+    fn capture(&self, kind: RecordedMetricKind, key: &Key, update: impl FnOnce(&mut f64) -> f64) {
+        let mut state = self.state.lock().expect("capture lock poisoned");
+        let value = update(state.values.entry((kind, key.clone())).or_default());
+        let labels = key.labels().map(|l| (l.key().to_string(), l.value().to_string())).collect();
+        let offset_ms = self.start.elapsed().as_millis() as u64;
+
+        let name = key.name().to_string();
+        state.emissions.push(RecordedMetric { offset_ms, kind, name, value, labels });
+    }
+
+    /// Create a handle capturing emissions of the given metric
+    fn handle(&self, key: &Key) -> Arc<MetricsCaptureHandle> {
+        Arc::new(MetricsCaptureHandle { key: key.clone(), capture: self.clone() })
+    }
+}
+
+impl Recorder for MetricsCapture {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::from_arc(self.handle(key))
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(self.handle(key))
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(self.handle(key))
+    }
+}
+
+/// A handle through which a `MetricsCapture` captures the emissions of a
+/// single metric
+struct MetricsCaptureHandle {
+    /// The key of the metric
+    key: Key,
+    /// The capture to record emissions in
+    capture: MetricsCapture,
+}
+
+impl CounterFn for MetricsCaptureHandle {
+    fn increment(&self, value: u64) {
+        self.capture.capture(RecordedMetricKind::Counter, &self.key, |total| {
+            *total += value as f64;
+            value as f64
+        })
+    }
+
+    fn absolute(&self, value: u64) {
+        self.capture.capture(RecordedMetricKind::Counter, &self.key, |total| {
+            let increment = (value as f64 - *total).max(0.);
+            *total += increment;
+            increment
+        })
+    }
+}
+
+impl GaugeFn for MetricsCaptureHandle {
+    fn increment(&self, value: f64) {
+        self.capture.capture(RecordedMetricKind::Gauge, &self.key, |current| {
+            *current += value;
+            *current
+        })
+    }
+
+    fn decrement(&self, value: f64) {
+        self.capture.capture(RecordedMetricKind::Gauge, &self.key, |current| {
+            *current -= value;
+            *current
+        })
+    }
+
+    fn set(&self, value: f64) {
+        self.capture.capture(RecordedMetricKind::Gauge, &self.key, |current| {
+            *current = value;
+            value
+        })
+    }
+}
+
+impl HistogramFn for MetricsCaptureHandle {
+    fn record(&self, value: f64) {
+        self.capture.capture(RecordedMetricKind::Histogram, &self.key, |_| value)
+    }
+}
+
+/// Replay a captured stream of metric emissions through the global recorder
+///
+/// Emissions are re-timed relative to the start of the replay, with `rate`
+/// acting as a speedup multiplier; e.g. a rate of `2.0` replays the capture in
+/// half of its original duration. This blocks the calling thread until the
+/// capture has been fully replayed
+pub fn replay_from_file(path: &Path, rate: f64) -> Result<(), TelemetrySetupError> {
+    if !rate.is_finite() || rate <= 0. {
+        return Err(TelemetrySetupError::Metrics(format!("invalid replay rate: {rate}")));
+    }
+
+    let file = File::open(path).map_err(err_str!(TelemetrySetupError::Metrics))?;
+    let start = Instant::now();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(err_str!(TelemetrySetupError::Metrics))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let event: RecordedMetric =
+            serde_json::from_str(&line).map_err(err_str!(TelemetrySetupError::Metrics))?;
+
+        // Wait until the scaled offset of the emission
+        let target = Duration::from_secs_f64(event.offset_ms as f64 / 1000. / rate);
+        if let Some(wait) = target.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+
+        event.emit();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, os::unix::net::UnixDatagram, path::Path, sync::Arc, time::Duration};
+
+    use cadence::MetricSink;
+    use metrics::{Key, Label};
+
+    use crate::telemetry::{TelemetryBuilder, TelemetrySetupError};
+
+    use super::{
+        MetricsCapture, MetricsConfig, MetricsFlushHandle, RecordedMetric, StatsdSink,
+        build_statsd_sink, replay_from_file, spawn_periodic_flush, validate_flush_interval,
+        with_default_labels,
+    };
+
+    /// Tests exporting metrics over a unix domain socket
+    #[test]
+    fn test_uds_sink() {
+        let path = env::temp_dir().join(format!("renegade-statsd-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let server = UnixDatagram::bind(&path).unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let sink = StatsdSink::Uds { path: path.clone() };
+        let sink = Arc::new(build_statsd_sink(&sink, &MetricsConfig::default()).unwrap());
+        sink.emit("test.count:1|c").unwrap();
+        MetricsFlushHandle { sink }.flush(Duration::from_secs(5));
+
+        let mut buf = [0u8; 64];
+        let n = server.recv(&mut buf).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(&buf[..n], b"test.count:1|c\n");
+    }
+
+    /// Tests that a periodic flush sends buffered metrics without an explicit
+    /// flush
+    #[test]
+    fn test_periodic_flush() {
+        let path = env::temp_dir().join(format!("renegade-flush-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let server = UnixDatagram::bind(&path).unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let sink = StatsdSink::Uds { path: path.clone() };
+        let sink = Arc::new(build_statsd_sink(&sink, &MetricsConfig::default()).unwrap());
+        spawn_periodic_flush(sink.clone(), Duration::from_millis(10)).unwrap();
+        sink.emit("test.count:1|c").unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = server.recv(&mut buf).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(&buf[..n], b"test.count:1|c\n");
+    }
+
+    /// Tests validating the configured flush interval
+    #[test]
+    fn test_flush_interval_validation() {
+        let config_with =
+            |interval| MetricsConfig { flush_interval: interval, ..Default::default() };
+
+        for interval in [Duration::ZERO, Duration::from_millis(9)] {
+            let res = validate_flush_interval(&config_with(Some(interval)));
+            assert!(matches!(res, Err(TelemetrySetupError::Metrics(_))));
+        }
+
+        assert!(validate_flush_interval(&config_with(None)).is_ok());
+        assert!(validate_flush_interval(&config_with(Some(Duration::from_millis(10)))).is_ok());
+    }
+
+    /// Tests that a recorder setup failure only fails telemetry setup when
+    /// metrics are required
+    #[test]
+    fn test_metrics_not_required() {
+        let sink = StatsdSink::Uds { path: "does-not-exist.sock".into() };
+        let config = |required| Some(MetricsConfig { required, ..Default::default() });
+
+        let res = TelemetryBuilder::default().with_metrics_sink(false, sink.clone(), config(true));
+        assert!(matches!(res, Err(TelemetrySetupError::Metrics(_))));
+        let res = TelemetryBuilder::default().with_metrics_sink(false, sink.clone(), config(false));
+        assert!(res.is_ok());
+
+        // Invalid configuration is an error either way
+        let config = MetricsConfig {
+            required: false,
+            flush_interval: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        let res = TelemetryBuilder::default().with_metrics_sink(false, sink, Some(config));
+        assert!(matches!(res, Err(TelemetrySetupError::Metrics(_))));
+    }
+
+    /// Tests that a missing socket file is reported as a metrics setup error
+    #[test]
+    fn test_uds_sink_missing_socket() {
+        let sink = StatsdSink::Uds { path: "does-not-exist.sock".into() };
+        let res = build_statsd_sink(&sink, &MetricsConfig::default());
+        assert!(matches!(res, Err(TelemetrySetupError::Metrics(_))));
+    }
+
+    /// Tests that default labels are added without overriding call site labels
+    #[test]
+    fn test_default_labels() {
+        let defaults = vec![Label::new("region", "us-east-1"), Label::new("relayer_id", "a")];
+        let key = Key::from_parts("matches", vec![Label::new("relayer_id", "b")]);
+
+        let labeled = with_default_labels(&key, &defaults);
+        let mut labels: Vec<_> = labeled.labels().map(|l| (l.key(), l.value())).collect();
+        labels.sort();
+
+        assert_eq!(labeled.name(), "matches");
+        assert_eq!(labels, vec![("region", "us-east-1"), ("relayer_id", "b")]);
+    }
+
+    /// Tests that a non-positive replay rate is rejected
+    #[test]
+    fn test_replay_invalid_rate() {
+        let path = Path::new("does-not-exist");
+        assert!(replay_from_file(path, 0.).is_err());
+        assert!(replay_from_file(path, f64::NAN).is_err());
+    }
+
+    /// Tests replaying a small capture file
+    #[test]
+    fn test_replay_from_file() {
+        let path = env::temp_dir().join("renegade-metrics-replay-test.jsonl");
+        let capture = [
+            r#"{"offset_ms":0,"kind":"counter","name":"test.count","value":1}"#,
+            "",
+            r#"{"offset_ms":10,"kind":"gauge","name":"test.gauge","value":2.5,"labels":[["a","b"]]}"#,
+            r#"{"offset_ms":20,"kind":"histogram","name":"test.hist","value":0.1}"#,
+        ];
+        fs::write(&path, capture.join("\n")).unwrap();
+
+        let res = replay_from_file(&path, 10.);
+        fs::remove_file(&path).unwrap();
+        res.unwrap();
+
+        // A malformed line should error
+        fs::write(&path, "not json").unwrap();
+        let res = replay_from_file(&path, 1.);
+        fs::remove_file(&path).unwrap();
+        assert!(res.is_err());
+    }
+
+    /// Tests that a dumped capture replays to the same emissions
+    #[test]
+    fn test_capture_dump_round_trip() {
+        let capture = MetricsCapture::default();
+        metrics::with_local_recorder(&capture, || {
+            metrics::counter!("test.count", "a" => "b").increment(2);
+            metrics::counter!("test.count", "a" => "b").absolute(5);
+            metrics::gauge!("test.gauge").set(1.5);
+            metrics::gauge!("test.gauge").increment(1.);
+            metrics::histogram!("test.hist").record(0.1);
+        });
+
+        let path = env::temp_dir().join("renegade-metrics-dump-test.jsonl");
+        capture.dump_to_file(&path).unwrap();
+        let replayed = MetricsCapture::default();
+        let res = metrics::with_local_recorder(&replayed, || replay_from_file(&path, 100.));
+        fs::remove_file(&path).unwrap();
+        res.unwrap();
+
+        // Offsets are re-timed by the replay, so only the emissions are compared
+        let strip_offset = |metrics: Vec<RecordedMetric>| {
+            metrics.into_iter().map(|m| RecordedMetric { offset_ms: 0, ..m }).collect::<Vec<_>>()
+        };
+        let expected = strip_offset(capture.recorded());
+        assert_eq!(expected.len(), 5);
+        assert_eq!(expected[1].value, 3.);
+        assert_eq!(expected[3].value, 2.5);
+        assert_eq!(strip_offset(replayed.recorded()), expected);
+    }
+}
@@ -1,17 +1,46 @@
 //! Defines helpers for logging
 
-use std::{error::Error, fmt::Display};
+use opentelemetry_sdk::trace::TracerProvider;
+use std::{
+    error::Error,
+    fmt::Display,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+};
+use tracing::{subscriber::DefaultGuard, warn};
 use tracing_subscriber::{
-    EnvFilter, Layer, Registry, fmt, layer::SubscriberExt, util::SubscriberInitExt,
+    EnvFilter, Layer, Registry, filter::Directive, fmt, layer::SubscriberExt, reload,
+    util::SubscriberInitExt,
 };
 pub use tracing_subscriber::{filter::LevelFilter, fmt::format::Format};
 
+use crate::err_str;
+
+pub mod capture;
 pub mod datadog;
 pub mod helpers;
 pub mod metrics;
 pub mod otlp_tracer;
+pub mod panic_hook;
 pub mod propagation;
 
+/// The error message emitted when both StatsD and Prometheus metrics are
+/// enabled
+const ERR_MULTIPLE_METRICS_EXPORTERS: &str =
+    "only one of StatsD or Prometheus metrics may be enabled";
+/// The warning logged when tokio-console is requested without the
+/// `tokio-console` feature
+#[cfg(not(feature = "tokio-console"))]
+const WARN_TOKIO_CONSOLE_DISABLED: &str =
+    "tokio-console requested but the `tokio-console` feature is disabled";
+/// The warning logged when tokio-console is requested in a build without
+/// `--cfg tokio_unstable`
+#[cfg(feature = "tokio-console")]
+const WARN_TOKIO_UNSTABLE_DISABLED: &str =
+    "tokio-console requested but the relayer was not built with `--cfg tokio_unstable`";
+/// The default address on which the tokio-console server listens
+pub const DEFAULT_TOKIO_CONSOLE_ADDR: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 6669));
+
 /// Possible errors that occur when setting up telemetry
 /// for the relayer
 #[derive(Debug)]
@@ -27,6 +56,8 @@ pub enum TelemetrySetupError {
     CollectorEndpointUnset,
     /// Error emitted when setting up the statsd metrics recorder
     Metrics(String),
+    /// Error emitted when a log filter cannot be parsed or applied
+    InvalidFilter(String),
 }
 
 impl Error for TelemetrySetupError {}
@@ -41,11 +72,94 @@ pub fn setup_system_logger(level: LevelFilter) {
     tracing_subscriber::fmt().event_format(Format::default().pretty()).with_max_level(level).init();
 }
 
+/// The format in which logs are emitted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable, multi-line logs
+    #[default]
+    Pretty,
+    /// Structured JSON logs, with event fields flattened into the top level
+    /// object
+    Json,
+    /// Structured JSON logs in the format expected by Datadog, with trace IDs
+    /// converted for log-trace correlation
+    Datadog(datadog::formatter::DatadogFormatterConfig),
+}
+
+/// A guard which flushes buffered metrics, then flushes and shuts down the
+/// OTLP tracer provider when dropped
+///
+/// Callers should hold the guard for the lifetime of the process, otherwise
+/// metrics and spans batched in the exporters are lost on exit
+#[must_use = "dropping the guard shuts down the tracer provider"]
+#[derive(Default)]
+pub struct TelemetryGuard {
+    /// The StatsD sink to flush, `None` if StatsD metrics are disabled
+    metrics_sink: Option<metrics::MetricsFlushHandle>,
+    /// The tracer provider to shut down, `None` if OTLP tracing is disabled
+    tracer_provider: Option<TracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(sink) = self.metrics_sink.take() {
+            sink.flush(metrics::DEFAULT_METRICS_FLUSH_TIMEOUT);
+        }
+
+        if let Some(provider) = self.tracer_provider.take() {
+            provider.force_flush();
+
+            // Release our handle first so that shutting down the global provider
+            // drops the last reference and shuts down the span processors
+            drop(provider);
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// A handle used to change the log filter of the global subscriber at runtime
+#[derive(Clone)]
+pub struct LogReloadHandle {
+    /// The handle to the reloadable filter layer
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogReloadHandle {
+    /// Replace the log filter with one parsed from the given directives
+    ///
+    /// Directives use the `RUST_LOG` syntax, e.g. `info,task_driver=trace`
+    pub fn set_filter(&self, directives: &str) -> Result<(), TelemetrySetupError> {
+        let filter = EnvFilter::builder()
+            .parse(directives)
+            .map_err(err_str!(TelemetrySetupError::InvalidFilter))?;
+        self.handle.reload(filter).map_err(err_str!(TelemetrySetupError::InvalidFilter))
+    }
+}
+
+/// A boxed subscriber layer
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync + 'static>;
+
 /// A builder for configuring telemetry for the relayer
 #[derive(Default)]
 pub struct TelemetryBuilder {
     /// The subscriber layers to add to the telemetry stack
-    layers: Vec<Box<dyn Layer<Registry> + Send + Sync + 'static>>,
+    layers: Vec<BoxedLayer>,
+    /// The subscriber layers which bypass the log filter
+    unfiltered_layers: Vec<BoxedLayer>,
+    /// Warnings raised while configuring telemetry, logged once the
+    /// subscriber is initialized
+    warnings: Vec<String>,
+    /// The collectors to which spans are exported, the OTLP layer is only
+    /// added if at least one is configured
+    otlp_collectors: Vec<otlp_tracer::OtlpCollector>,
+    /// The StatsD sink backing the metrics recorder, if one is configured
+    metrics_sink: Option<metrics::MetricsFlushHandle>,
+    /// The level logged for targets without a `RUST_LOG` directive, `INFO` if
+    /// unset
+    default_level: Option<LevelFilter>,
+    /// The filter onto which the default level and `RUST_LOG` directives are
+    /// layered, if one is configured
+    base_filter: Option<EnvFilter>,
 }
 
 impl TelemetryBuilder {
@@ -55,32 +169,130 @@ impl TelemetryBuilder {
         self
     }
 
-    /// Configure logging for the relayer
-    pub fn with_logging(self, datadog_enabled: bool) -> Self {
-        if datadog_enabled {
-            opentelemetry::global::set_text_map_propagator(
-                opentelemetry_datadog::DatadogPropagator::new(),
-            );
+    /// Set the level logged for targets that `RUST_LOG` does not configure
+    pub fn with_default_level(mut self, level: LevelFilter) -> Self {
+        self.default_level = Some(level);
+        self
+    }
 
-            self.with_layer(fmt::layer().json().event_format(datadog::formatter::DatadogFormatter))
-        } else {
-            self.with_layer(fmt::layer().pretty())
+    /// Layer the default level and `RUST_LOG` directives on top of the given
+    /// filter, e.g. `helpers::default_noisy_filter`
+    ///
+    /// Directives from `RUST_LOG` take precedence over those in the base filter
+    /// for the same target
+    pub fn with_base_filter(mut self, filter: EnvFilter) -> Self {
+        self.base_filter = Some(filter);
+        self
+    }
+
+    /// Configure logging for the relayer in the given format
+    pub fn with_logging(self, format: LogFormat) -> Self {
+        match format {
+            LogFormat::Pretty => self.with_layer(fmt::layer().pretty()),
+            // The default system timer formats timestamps per RFC 3339, an
+            // ISO-8601 profile
+            LogFormat::Json => self.with_layer(
+                fmt::layer().json().flatten_event(true).with_timer(fmt::time::SystemTime),
+            ),
+            LogFormat::Datadog(config) => {
+                opentelemetry::global::set_text_map_propagator(
+                    opentelemetry_datadog::DatadogPropagator::new(),
+                );
+
+                let formatter = datadog::formatter::DatadogFormatter::new(config);
+                self.with_layer(fmt::layer().json().event_format(formatter))
+            },
         }
     }
 
+    /// Configure logging for the relayer, using the Datadog format if enabled
+    /// and the pretty format otherwise
+    pub fn with_datadog_logging(self, datadog_enabled: bool) -> Self {
+        let format = if datadog_enabled {
+            LogFormat::Datadog(datadog::formatter::DatadogFormatterConfig::default())
+        } else {
+            LogFormat::Pretty
+        };
+        self.with_logging(format)
+    }
+
     /// Configure OTLP tracing for the relayer
+    ///
+    /// May be called more than once to export every span to multiple
+    /// collectors, e.g. while migrating between tracing vendors. Each
+    /// collector has its own sampling ratio, headers, and resource
     pub fn with_tracing(
-        self,
+        mut self,
         datadog_enabled: bool,
         collector_endpoint: String,
+        protocol: otlp_tracer::OtlpProtocol,
+        config: Option<otlp_tracer::OtlpConfig>,
     ) -> Result<Self, TelemetrySetupError> {
-        let otlp_tracer = otlp_tracer::configure_otlp_tracer(datadog_enabled, collector_endpoint)?;
-        let otlp_trace_layer = tracing_opentelemetry::layer().with_tracer(otlp_tracer);
+        let collector = otlp_tracer::configure_otlp_collector(
+            datadog_enabled,
+            collector_endpoint,
+            protocol,
+            &config.unwrap_or_default(),
+        )?;
+        self.otlp_collectors.push(collector);
+
+        Ok(self)
+    }
 
-        Ok(self.with_layer(otlp_trace_layer))
+    /// Serve task diagnostics to `tokio-console` on the given address, or
+    /// `DEFAULT_TOKIO_CONSOLE_ADDR` if none is given
+    ///
+    /// The console layer sees every tokio runtime event regardless of the log
+    /// filter
+    ///
+    /// Requires the `tokio-console` feature and a build with
+    /// `--cfg tokio_unstable`, without which the runtime does not emit task
+    /// events. Otherwise this logs a warning and leaves the telemetry stack
+    /// unchanged
+    #[cfg(feature = "tokio-console")]
+    pub fn with_tokio_console(mut self, bind_addr: Option<SocketAddr>) -> Self {
+        // The console layer panics on construction without `tokio_unstable`
+        if !cfg!(tokio_unstable) {
+            self.warnings.push(WARN_TOKIO_UNSTABLE_DISABLED.to_string());
+            return self;
+        }
+
+        let addr = bind_addr.unwrap_or(DEFAULT_TOKIO_CONSOLE_ADDR);
+        let layer = console_subscriber::ConsoleLayer::builder().server_addr(addr).spawn();
+        self.unfiltered_layers.push(layer.boxed());
+        self
     }
 
-    /// Configure StatsD metrics for the relayer
+    /// Serve task diagnostics to `tokio-console` on the given address, or
+    /// `DEFAULT_TOKIO_CONSOLE_ADDR` if none is given
+    ///
+    /// Requires the `tokio-console` feature and a build with
+    /// `--cfg tokio_unstable`, otherwise this logs a warning and leaves the
+    /// telemetry stack unchanged
+    #[cfg(not(feature = "tokio-console"))]
+    pub fn with_tokio_console(mut self, _bind_addr: Option<SocketAddr>) -> Self {
+        self.warnings.push(WARN_TOKIO_CONSOLE_DISABLED.to_string());
+        self
+    }
+
+    /// Record spans and events in memory, returning a handle through which the
+    /// captured telemetry may be inspected
+    pub fn with_capture(self) -> (Self, capture::TelemetryCapture) {
+        let capture = capture::TelemetryCapture::default();
+        (self.with_layer(capture.clone()), capture)
+    }
+
+    /// Log panics as error events, so that they reach the configured log
+    /// pipeline along with the span they occur in
+    ///
+    /// The panic hook is installed process-wide when this is called, and
+    /// chains to the previously installed hook
+    pub fn with_panic_logging(self) -> Self {
+        panic_hook::install_panic_hook();
+        self
+    }
+
+    /// Configure StatsD metrics for the relayer, exported over UDP
     pub fn with_metrics(
         self,
         datadog_enabled: bool,
@@ -88,10 +300,49 @@ impl TelemetryBuilder {
         statsd_port: u16,
         config: Option<metrics::MetricsConfig>,
     ) -> Result<Self, TelemetrySetupError> {
-        metrics::configure_metrics_statsd_recorder_with_config(
-            datadog_enabled,
-            statsd_host,
-            statsd_port,
+        let sink = metrics::StatsdSink::Udp { host: statsd_host.to_string(), port: statsd_port };
+        self.with_metrics_sink(datadog_enabled, sink, config)
+    }
+
+    /// Configure StatsD metrics for the relayer, exported to the given sink
+    ///
+    /// If the config marks metrics as not required, a failure to set up the
+    /// recorder, e.g. an unreachable StatsD host, is logged and the rest of
+    /// the telemetry stack is configured without metrics
+    pub fn with_metrics_sink(
+        mut self,
+        datadog_enabled: bool,
+        sink: metrics::StatsdSink,
+        config: Option<metrics::MetricsConfig>,
+    ) -> Result<Self, TelemetrySetupError> {
+        let config = config.unwrap_or_default();
+        metrics::validate_flush_interval(&config)?;
+
+        let res =
+            metrics::configure_metrics_statsd_recorder_with_sink(datadog_enabled, &sink, &config);
+        let sink = match res {
+            Ok(sink) => sink,
+            Err(e) if !config.required => {
+                let msg = format!("continuing without metrics, StatsD recorder setup failed: {e}");
+                self.warnings.push(msg);
+                return Ok(self);
+            },
+            Err(e) => return Err(e),
+        };
+        self.metrics_sink = Some(sink);
+
+        Ok(self.with_layer(metrics_tracing_context::MetricsLayer::new()))
+    }
+
+    /// Configure Prometheus metrics for the relayer, served for scraping on
+    /// the given address
+    pub fn with_prometheus_metrics(
+        self,
+        listen_addr: SocketAddr,
+        config: Option<metrics::MetricsConfig>,
+    ) -> Result<Self, TelemetrySetupError> {
+        metrics::configure_metrics_prometheus_recorder_with_config(
+            listen_addr,
             &config.unwrap_or_default(),
         )?;
 
@@ -99,12 +350,129 @@ impl TelemetryBuilder {
     }
 
     /// Initialize the global subscriber with the configured telemetry layers
-    pub fn build(self) {
-        let layers = self.layers.with_filter(
-            EnvFilter::builder().with_default_directive(LevelFilter::INFO.into()).from_env_lossy(),
-        );
-        tracing_subscriber::registry().with(layers).init()
+    ///
+    /// Returns a guard which flushes buffered metrics and spans when dropped
+    pub fn build(self) -> TelemetryGuard {
+        let (guard, _) = self.build_with_reload_handle();
+        guard
+    }
+
+    /// Initialize the global subscriber with the configured telemetry layers,
+    /// returning a handle through which the log filter may be changed at
+    /// runtime alongside the flush guard
+    pub fn build_with_reload_handle(mut self) -> (TelemetryGuard, LogReloadHandle) {
+        let tracer_provider = self.add_otlp_layer();
+        let filter = self.build_filter();
+        let (filter, handle) = reload::Layer::new(filter);
+        let mut layers = self.unfiltered_layers;
+        layers.push(self.layers.with_filter(filter).boxed());
+        tracing_subscriber::registry().with(layers).init();
+
+        for warning in self.warnings {
+            warn!("{warning}");
+        }
+
+        let guard = TelemetryGuard { metrics_sink: self.metrics_sink, tracer_provider };
+        (guard, LogReloadHandle { handle })
+    }
+
+    /// Add a single OTLP layer which exports to every configured collector
+    ///
+    /// The layers must not be split per collector, as each layer stores its
+    /// span data in the span's extensions and a second layer would conflict
+    /// with the first. Returns the tracer provider backing the layer, if any
+    /// collectors are configured
+    fn add_otlp_layer(&mut self) -> Option<TracerProvider> {
+        if self.otlp_collectors.is_empty() {
+            return None;
+        }
+
+        let collectors = std::mem::take(&mut self.otlp_collectors);
+        let otlp_tracer = otlp_tracer::configure_otlp_tracer_for_collectors(collectors);
+        let tracer_provider = otlp_tracer.provider();
+        let otlp_trace_layer = tracing_opentelemetry::layer().with_tracer(otlp_tracer);
+        self.layers.push(otlp_trace_layer.boxed());
+
+        tracer_provider
+    }
+
+    /// Build the log filter from the base filter, default level, and
+    /// `RUST_LOG`
+    fn build_filter(&mut self) -> EnvFilter {
+        let level = self.default_level.unwrap_or(LevelFilter::INFO);
+        let Some(base) = self.base_filter.take() else {
+            return default_filter(level);
+        };
+
+        // Later directives replace earlier ones for the same target, so the env
+        // directives are added last
+        let env_directives = std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_default();
+        add_directives(base.add_directive(level.into()), &env_directives, &mut self.warnings)
+    }
+
+    /// Set the configured telemetry layers as the subscriber for the current
+    /// thread only, until the returned guard is dropped
+    ///
+    /// Unlike `build`, this may be called any number of times, so tests can
+    /// each install their own subscriber, e.g. one with a capture layer from
+    /// `with_capture`
+    pub fn build_scoped(mut self) -> DefaultGuard {
+        self.add_otlp_layer();
+        let filter = self.build_filter();
+        let mut layers = self.unfiltered_layers;
+        layers.push(self.layers.with_filter(filter).boxed());
+
+        let subscriber = tracing_subscriber::registry().with(layers);
+        let guard = tracing::subscriber::set_default(subscriber);
+        for warning in self.warnings {
+            warn!("{warning}");
+        }
+
+        guard
+    }
+}
+
+/// Build the log filter from `RUST_LOG`, defaulting to the given level
+fn default_filter(level: LevelFilter) -> EnvFilter {
+    EnvFilter::builder().with_default_directive(level.into()).from_env_lossy()
+}
+
+/// Add the directives in a `RUST_LOG` formatted string to the filter in order,
+/// recording a warning for each directive that fails to parse
+fn add_directives(
+    mut filter: EnvFilter,
+    directives: &str,
+    warnings: &mut Vec<String>,
+) -> EnvFilter {
+    for directive in split_directives(directives) {
+        match directive.parse::<Directive>() {
+            Ok(parsed) => filter = filter.add_directive(parsed),
+            Err(e) => warnings.push(format!("ignoring log directive `{directive}`: {e}")),
+        }
     }
+
+    filter
+}
+
+/// Split a `RUST_LOG` formatted string into its directives
+///
+/// `EnvFilter` splits on every comma, which breaks span directives that match
+/// on several fields, e.g. `target[span{a=1,b=2}]=debug`, so only commas
+/// outside of a directive's brackets separate directives here
+fn split_directives(directives: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0usize;
+    directives
+        .split(move |c| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth = depth.saturating_sub(1),
+                ',' => return depth == 0,
+                _ => {},
+            }
+
+            false
+        })
+        .filter(|directive| !directive.is_empty())
 }
 
 /// Configures logging, tracing, and metrics for the relayer
@@ -116,7 +484,7 @@ pub fn configure_telemetry(
     collector_endpoint: String,
     statsd_host: &str,
     statsd_port: u16,
-) -> Result<(), TelemetrySetupError> {
+) -> Result<TelemetryGuard, TelemetrySetupError> {
     configure_telemetry_with_metrics_config(
         datadog_enabled,
         otlp_enabled,
@@ -124,12 +492,17 @@ pub fn configure_telemetry(
         collector_endpoint,
         statsd_host,
         statsd_port,
-        None,
+        None, // metrics_config
+        None, // prometheus_listen
     )
 }
 
 /// Configures logging, tracing, and metrics for the relayer with optional
 /// metrics configuration
+///
+/// If `prometheus_listen` is set, metrics are served for Prometheus scrapes on
+/// that address instead of being pushed to StatsD; enabling both is an error
+#[allow(clippy::too_many_arguments)]
 pub fn configure_telemetry_with_metrics_config(
     datadog_enabled: bool,
     otlp_enabled: bool,
@@ -138,19 +511,82 @@ pub fn configure_telemetry_with_metrics_config(
     statsd_host: &str,
     statsd_port: u16,
     metrics_config: Option<metrics::MetricsConfig>,
-) -> Result<(), TelemetrySetupError> {
-    let mut telemetry = TelemetryBuilder::default().with_logging(datadog_enabled);
+    prometheus_listen: Option<SocketAddr>,
+) -> Result<TelemetryGuard, TelemetrySetupError> {
+    if metrics_enabled && prometheus_listen.is_some() {
+        return Err(TelemetrySetupError::Metrics(ERR_MULTIPLE_METRICS_EXPORTERS.to_string()));
+    }
+
+    let mut telemetry = TelemetryBuilder::default().with_datadog_logging(datadog_enabled);
 
     if otlp_enabled {
-        telemetry = telemetry.with_tracing(datadog_enabled, collector_endpoint)?;
+        telemetry = telemetry.with_tracing(
+            datadog_enabled,
+            collector_endpoint,
+            otlp_tracer::OtlpProtocol::Grpc,
+            None, // config
+        )?;
     }
 
     if metrics_enabled {
         telemetry =
             telemetry.with_metrics(datadog_enabled, statsd_host, statsd_port, metrics_config)?;
+    } else if let Some(listen_addr) = prometheus_listen {
+        telemetry = telemetry.with_prometheus_metrics(listen_addr, metrics_config)?;
     }
 
-    telemetry.build();
+    Ok(telemetry.build())
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    use tracing_subscriber::{EnvFilter, filter::Directive};
 
-    Ok(())
+    use super::{TelemetryBuilder, add_directives, split_directives};
+
+    /// Tests that span directives matching on several fields are not split on
+    /// the commas between their fields
+    #[test]
+    fn test_multi_field_span_directive() {
+        let span_directive = "task_driver[task_run{task=a,wallet_id=b}]=debug";
+        let directives = format!("info,{span_directive},hyper=warn");
+        let split: Vec<_> = split_directives(&directives).collect();
+        assert_eq!(split, vec!["info", span_directive, "hyper=warn"]);
+
+        let mut warnings = Vec::new();
+        let filter = add_directives(EnvFilter::default(), &directives, &mut warnings);
+        assert!(warnings.is_empty(), "unexpected warnings: {warnings:?}");
+        let parsed = span_directive.parse::<Directive>().unwrap();
+        assert!(filter.to_string().contains(&parsed.to_string()));
+    }
+
+    /// Tests that invalid directives are skipped with a warning while the
+    /// valid ones are still added
+    #[test]
+    fn test_invalid_directive() {
+        let mut warnings = Vec::new();
+        let filter = add_directives(EnvFilter::default(), "hyper=warn,=,", &mut warnings);
+        assert_eq!(warnings.len(), 1);
+        assert!(filter.to_string().contains("hyper=warn"));
+    }
+
+    /// Tests that the tokio-console layer is added only when it is requested
+    /// in a build with both the `tokio-console` feature and `tokio_unstable`,
+    /// and that a warning is raised when it is requested without them
+    #[test]
+    fn test_tokio_console_toggle() {
+        let builder = TelemetryBuilder::default();
+        assert!(builder.unfiltered_layers.is_empty());
+
+        // Bind to an ephemeral port so the console server cannot conflict
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, 0));
+        let builder = builder.with_tokio_console(Some(addr));
+
+        let console_enabled = cfg!(all(feature = "tokio-console", tokio_unstable));
+        assert_eq!(builder.unfiltered_layers.len(), usize::from(console_enabled));
+        assert_eq!(builder.warnings.is_empty(), console_enabled);
+        assert!(builder.layers.is_empty());
+    }
 }
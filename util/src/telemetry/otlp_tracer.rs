@@ -1,15 +1,35 @@
 //! Configuration for exporting traces to an OTLP collector
 
-use opentelemetry::KeyValue;
-use opentelemetry_otlp::WithExportConfig;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs, future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use http::Uri;
+use opentelemetry::{
+    KeyValue, Value, global,
+    trace::{Status, TraceId, TracerProvider as _},
+};
+use opentelemetry_otlp::{SpanExporter as OtlpSpanExporter, WithExportConfig};
 use opentelemetry_sdk::{
-    Resource, runtime,
-    trace::{self, BatchConfig, Tracer},
+    Resource,
+    export::trace::{ExportResult, SpanData, SpanExporter},
+    runtime,
+    trace::{self, BatchConfig, BatchSpanProcessor, Sampler, Tracer, TracerProvider},
 };
 use opentelemetry_semantic_conventions::{
     SCHEMA_URL,
     resource::{DEPLOYMENT_ENVIRONMENT, SERVICE_NAME, SERVICE_VERSION},
 };
+use serde_json::Value as JsonValue;
+use tonic::{
+    metadata::{MetadataKey, MetadataMap, MetadataValue},
+    transport::{Certificate, ClientTlsConfig},
+};
 
 use crate::err_str;
 
@@ -18,6 +38,111 @@ use super::{
     datadog::{UnifiedServiceTags, get_unified_service_tags},
 };
 
+/// The name of the tracer registered with the global tracer provider
+const TRACER_NAME: &str = "renegade-relayer";
+/// The default ratio of traces to sample
+const DEFAULT_SAMPLING_RATIO: f64 = 1.0;
+/// The domain of Honeycomb's OTLP ingest endpoints
+const HONEYCOMB_DOMAIN: &str = "honeycomb.io";
+/// The header carrying the Honeycomb API key
+const HONEYCOMB_TEAM_HEADER: &str = "x-honeycomb-team";
+/// The header selecting the Honeycomb dataset
+const HONEYCOMB_DATASET_HEADER: &str = "x-honeycomb-dataset";
+/// The path of the OTLP/HTTP traces endpoint
+const HTTP_TRACES_PATH: &str = "/v1/traces";
+
+/// The protocol over which spans are exported to the collector
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OtlpProtocol {
+    /// OTLP over gRPC, with an endpoint of the form `http://host:4317`
+    #[default]
+    Grpc,
+    /// OTLP over HTTP with protobuf payloads, with an endpoint including the
+    /// traces path, e.g. `http://host:4318/v1/traces`
+    HttpProtobuf,
+}
+
+/// Determines how span fields are mapped onto OTLP span attributes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpanFieldMapping {
+    /// Export span fields as they are recorded, structured values are kept
+    /// nested in a single attribute
+    #[default]
+    Nested,
+    /// Flatten structured (JSON object) field values into dotted attribute
+    /// keys, e.g. `wallet.id`
+    ///
+    /// Useful for backends which cannot query nested attributes
+    Flattened,
+}
+
+/// Configuration for the OTLP tracer
+#[derive(Clone, Debug)]
+pub struct OtlpConfig {
+    /// How span fields are mapped onto OTLP attributes
+    pub span_field_mapping: SpanFieldMapping,
+    /// The ratio of root traces to sample, in the range [0, 1]
+    ///
+    /// Spans with a parent follow their parent's sampling decision, so a
+    /// sampled trace is always exported in full. When exporting to multiple
+    /// collectors, each collector exports the traces within its own ratio
+    pub sampling_ratio: f64,
+    /// Headers attached to every export request, e.g. collector auth tokens
+    pub headers: Vec<(String, String)>,
+    /// The path to a PEM encoded CA certificate used to verify the collector
+    ///
+    /// If set, the exporter connects to the collector over TLS
+    pub tls_ca_cert: Option<PathBuf>,
+    /// Extra attributes merged into the tracer's resource, e.g.
+    /// `service.version` or `host.name`
+    ///
+    /// These take precedence over the unified service tags set when Datadog
+    /// is enabled
+    pub resource_attributes: Vec<KeyValue>,
+    /// The minimum duration of an exported span, shorter spans are dropped
+    /// when they end rather than exported
+    ///
+    /// Spans with an error status are exported regardless of their duration.
+    /// Only export is affected, events within dropped spans are still logged
+    pub min_span_duration: Option<Duration>,
+}
+
+impl OtlpConfig {
+    /// Add the headers required to export to Honeycomb
+    ///
+    /// The dataset is only needed for Honeycomb Classic environments
+    pub fn with_honeycomb(mut self, api_key: String, dataset: Option<String>) -> Self {
+        self.headers.push((HONEYCOMB_TEAM_HEADER.to_string(), api_key));
+        if let Some(dataset) = dataset {
+            self.headers.push((HONEYCOMB_DATASET_HEADER.to_string(), dataset));
+        }
+
+        self
+    }
+
+    /// Add attributes to the tracer's resource
+    pub fn with_resource_attributes(
+        mut self,
+        attributes: impl IntoIterator<Item = KeyValue>,
+    ) -> Self {
+        self.resource_attributes.extend(attributes);
+        self
+    }
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            span_field_mapping: SpanFieldMapping::default(),
+            sampling_ratio: DEFAULT_SAMPLING_RATIO,
+            headers: Vec::new(),
+            tls_ca_cert: None,
+            resource_attributes: Vec::new(),
+            min_span_duration: None,
+        }
+    }
+}
+
 /// Constructs the resource tags for OTLP traces
 fn otlp_resource() -> Result<Resource, TelemetrySetupError> {
     let UnifiedServiceTags { service, env, version } = get_unified_service_tags()?;
@@ -32,22 +157,687 @@ fn otlp_resource() -> Result<Resource, TelemetrySetupError> {
     ))
 }
 
+/// Constructs the resource for the tracer, if it differs from the SDK default
+///
+/// The given attributes are merged over the unified service tags when Datadog
+/// is enabled, and over the SDK's default resource otherwise
+fn tracer_resource(
+    datadog_enabled: bool,
+    attributes: &[KeyValue],
+) -> Result<Option<Resource>, TelemetrySetupError> {
+    let base = if datadog_enabled { Some(otlp_resource()?) } else { None };
+    let resource = if attributes.is_empty() {
+        base
+    } else {
+        let extra = Resource::new(attributes.iter().cloned());
+        Some(base.unwrap_or_default().merge(&extra))
+    };
+
+    if datadog_enabled {
+        check_deployment_env(resource.as_ref())?;
+    }
+
+    Ok(resource)
+}
+
+/// Check that the resource sets a non-empty deployment environment
+fn check_deployment_env(resource: Option<&Resource>) -> Result<(), TelemetrySetupError> {
+    let env = resource.and_then(|r| r.get(DEPLOYMENT_ENVIRONMENT));
+    match env {
+        Some(env) if !env.as_str().is_empty() => Ok(()),
+        _ => Err(TelemetrySetupError::DeploymentEnvUnset),
+    }
+}
+
+/// A collector to which spans are exported, with its own exporter, sampling
+/// ratio, and resource
+#[derive(Debug)]
+pub struct OtlpCollector {
+    /// The exporter which sends spans to the collector
+    exporter: Box<dyn SpanExporter>,
+    /// The ratio of root traces to export to the collector
+    sampling_ratio: f64,
+    /// The resource attached to spans exported to the collector
+    resource: Resource,
+    /// The minimum duration of a span exported to the collector
+    min_span_duration: Option<Duration>,
+}
+
+/// Validates the config for a collector and builds its exporter
+pub fn configure_otlp_collector(
+    datadog_enabled: bool,
+    collector_endpoint: String,
+    protocol: OtlpProtocol,
+    config: &OtlpConfig,
+) -> Result<OtlpCollector, TelemetrySetupError> {
+    sampler(config.sampling_ratio)?;
+    let resource =
+        tracer_resource(datadog_enabled, &config.resource_attributes)?.unwrap_or_default();
+
+    check_honeycomb_headers(&collector_endpoint, &config.headers)?;
+    check_endpoint(&collector_endpoint, protocol)?;
+    let exporter = match protocol {
+        OtlpProtocol::Grpc => grpc_exporter(collector_endpoint, config)?,
+        OtlpProtocol::HttpProtobuf => http_exporter(&collector_endpoint, config)?,
+    };
+
+    let exporter: Box<dyn SpanExporter> = match config.span_field_mapping {
+        SpanFieldMapping::Nested => Box::new(exporter),
+        SpanFieldMapping::Flattened => Box::new(FlattenedAttributesExporter { inner: exporter }),
+    };
+
+    Ok(OtlpCollector {
+        exporter,
+        sampling_ratio: config.sampling_ratio,
+        resource,
+        min_span_duration: config.min_span_duration,
+    })
+}
+
 /// Creates an OTLP tracing pipeline for sending spans to the collector
 pub fn configure_otlp_tracer(
     datadog_enabled: bool,
     collector_endpoint: String,
+    protocol: OtlpProtocol,
+    config: &OtlpConfig,
 ) -> Result<Tracer, TelemetrySetupError> {
-    let trace_config = if datadog_enabled {
-        trace::Config::default().with_resource(otlp_resource()?)
-    } else {
-        trace::Config::default()
+    let collector =
+        configure_otlp_collector(datadog_enabled, collector_endpoint, protocol, config)?;
+    Ok(configure_otlp_tracer_for_collectors(vec![collector]))
+}
+
+/// Creates an OTLP tracing pipeline which sends each span to every one of the
+/// given collectors
+///
+/// The collectors share a single tracer provider, which samples at the highest
+/// of their ratios and carries the first collector's resource. Each collector
+/// then drops the traces outside of its own ratio and swaps in its own
+/// resource before exporting
+pub fn configure_otlp_tracer_for_collectors(collectors: Vec<OtlpCollector>) -> Tracer {
+    let max_ratio = collectors.iter().map(|c| c.sampling_ratio).fold(0., f64::max);
+    let provider_resource = collectors.first().map(|c| c.resource.clone()).unwrap_or_default();
+
+    let mut builder = TracerProvider::builder();
+    for OtlpCollector { exporter, sampling_ratio, resource, min_span_duration } in collectors {
+        let exporter = CollectorExporter {
+            inner: exporter,
+            sampling_ratio: (sampling_ratio < max_ratio).then_some(sampling_ratio),
+            resource: (resource != provider_resource).then_some(resource),
+            min_span_duration,
+        };
+        builder = builder.with_span_processor(batch_processor(exporter));
+    }
+
+    // The ratios are validated when each collector is configured
+    let sampler = sampler(max_ratio).expect("invalid sampling ratio");
+    let trace_config =
+        trace::Config::default().with_sampler(sampler).with_resource(provider_resource);
+    let provider = builder.with_config(trace_config).build();
+    let tracer = provider.versioned_tracer(TRACER_NAME, None::<&str>, Some(SCHEMA_URL), None);
+    global::set_tracer_provider(provider);
+
+    tracer
+}
+
+/// Build a span exporter which exports to the collector over gRPC
+fn grpc_exporter(
+    endpoint: String,
+    config: &OtlpConfig,
+) -> Result<OtlpSpanExporter, TelemetrySetupError> {
+    let mut exporter_builder = opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint);
+    if !config.headers.is_empty() {
+        exporter_builder = exporter_builder.with_metadata(export_metadata(&config.headers)?);
+    }
+    if let Some(ca_path) = &config.tls_ca_cert {
+        exporter_builder = exporter_builder.with_tls_config(tls_config(ca_path)?);
+    }
+
+    exporter_builder.build_span_exporter().map_err(err_str!(TelemetrySetupError::Tracer))
+}
+
+/// Build a span exporter which exports to the collector over HTTP
+///
+/// HTTPS endpoints are verified against the system's root certificates, a
+/// custom CA certificate is only supported for gRPC
+fn http_exporter(
+    endpoint: &str,
+    config: &OtlpConfig,
+) -> Result<OtlpSpanExporter, TelemetrySetupError> {
+    if config.tls_ca_cert.is_some() {
+        let msg = "a custom CA certificate is only supported for gRPC export".to_string();
+        return Err(TelemetrySetupError::Tracer(msg));
+    }
+
+    // The exporter appends the traces path to the endpoint itself
+    let base_endpoint = endpoint.trim_end_matches(HTTP_TRACES_PATH);
+    let headers: HashMap<String, String> = config.headers.iter().cloned().collect();
+    opentelemetry_otlp::new_exporter()
+        .http()
+        .with_endpoint(base_endpoint)
+        .with_headers(headers)
+        .build_span_exporter()
+        .map_err(err_str!(TelemetrySetupError::Tracer))
+}
+
+/// Check that the collector endpoint is well formed for the export protocol
+///
+/// gRPC endpoints carry no path, e.g. `http://host:4317`, while HTTP endpoints
+/// include the full traces path, e.g. `http://host:4318/v1/traces`
+fn check_endpoint(endpoint: &str, protocol: OtlpProtocol) -> Result<(), TelemetrySetupError> {
+    let invalid = |reason: &str| {
+        let msg = format!("invalid {protocol:?} OTLP endpoint {endpoint}: {reason}");
+        Err(TelemetrySetupError::Tracer(msg))
+    };
+
+    let Ok(uri) = endpoint.parse::<Uri>() else {
+        return invalid("not a valid URL");
     };
+    if !matches!(uri.scheme_str(), Some("http" | "https")) {
+        return invalid("scheme must be `http` or `https`");
+    }
+    if uri.authority().is_none() {
+        return invalid("missing host");
+    }
 
-    opentelemetry_otlp::new_pipeline()
-        .tracing()
-        .with_trace_config(trace_config)
+    match protocol {
+        OtlpProtocol::Grpc if !matches!(uri.path(), "" | "/") => {
+            invalid("gRPC endpoints must not include a path")
+        },
+        OtlpProtocol::HttpProtobuf if uri.path() != HTTP_TRACES_PATH => {
+            invalid("HTTP endpoints must include the `/v1/traces` path")
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Build the gRPC metadata attached to export requests from a list of headers
+fn export_metadata(headers: &[(String, String)]) -> Result<MetadataMap, TelemetrySetupError> {
+    let mut metadata = MetadataMap::with_capacity(headers.len());
+    for (key, value) in headers {
+        let key = MetadataKey::from_bytes(key.to_lowercase().as_bytes())
+            .map_err(err_str!(TelemetrySetupError::Tracer))?;
+        let value = MetadataValue::try_from(value.as_str())
+            .map_err(err_str!(TelemetrySetupError::Tracer))?;
+        metadata.insert(key, value);
+    }
+
+    Ok(metadata)
+}
+
+/// Check that an export to a Honeycomb endpoint carries the team header,
+/// without which Honeycomb silently rejects every span
+fn check_honeycomb_headers(
+    endpoint: &str,
+    headers: &[(String, String)],
+) -> Result<(), TelemetrySetupError> {
+    if !endpoint.contains(HONEYCOMB_DOMAIN) {
+        return Ok(());
+    }
+
+    let has_team = headers.iter().any(|(key, _)| key.eq_ignore_ascii_case(HONEYCOMB_TEAM_HEADER));
+    if !has_team {
+        let msg = format!(
+            "Honeycomb endpoint {endpoint} requires the `{HONEYCOMB_TEAM_HEADER}` header, \
+             set it to your Honeycomb API key"
+        );
+        return Err(TelemetrySetupError::Tracer(msg));
+    }
+
+    Ok(())
+}
+
+/// Build a TLS config which trusts the CA certificate at the given path
+fn tls_config(ca_path: &Path) -> Result<ClientTlsConfig, TelemetrySetupError> {
+    let pem = fs::read(ca_path).map_err(|e| {
+        TelemetrySetupError::Tracer(format!("cannot read CA cert {}: {e}", ca_path.display()))
+    })?;
+
+    Ok(ClientTlsConfig::new().ca_certificate(Certificate::from_pem(pem)))
+}
+
+/// Build a parent-based sampler which samples root traces at the given ratio
+fn sampler(ratio: f64) -> Result<Sampler, TelemetrySetupError> {
+    if !(0.0..=1.0).contains(&ratio) {
+        let msg = format!("sampling ratio must be in [0, 1], got {ratio}");
+        return Err(TelemetrySetupError::Tracer(msg));
+    }
+
+    Ok(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio))))
+}
+
+/// Build a batch span processor around the given exporter
+fn batch_processor<E: SpanExporter + 'static>(exporter: E) -> BatchSpanProcessor<runtime::Tokio> {
+    BatchSpanProcessor::builder(exporter, runtime::Tokio)
         .with_batch_config(BatchConfig::default())
-        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(collector_endpoint))
-        .install_batch(runtime::Tokio)
-        .map_err(err_str!(TelemetrySetupError::Tracer))
+        .build()
+}
+
+// ----------------------
+// | Collector Exporter |
+// ----------------------
+
+/// A span exporter which applies a collector's own sampling ratio, resource,
+/// and minimum span duration before delegating to the collector's exporter
+#[derive(Debug)]
+struct CollectorExporter {
+    /// The exporter that receives the collector's spans
+    inner: Box<dyn SpanExporter>,
+    /// The ratio of traces to export, `None` if every sampled trace is
+    /// exported
+    sampling_ratio: Option<f64>,
+    /// The resource attached to exported spans, `None` if the tracer
+    /// provider's resource is kept
+    resource: Option<Resource>,
+    /// The minimum duration of an exported span, `None` if spans of any
+    /// duration are exported
+    min_span_duration: Option<Duration>,
+}
+
+impl SpanExporter for CollectorExporter {
+    fn export(&mut self, mut batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        if let Some(ratio) = self.sampling_ratio {
+            batch.retain(|span| trace_id_sampled(span.span_context.trace_id(), ratio));
+        }
+        if let Some(min_duration) = self.min_span_duration {
+            batch.retain(|span| span_long_enough(span, min_duration));
+        }
+        if batch.is_empty() {
+            return Box::pin(future::ready(Ok(())));
+        }
+
+        if let Some(resource) = &self.resource {
+            for span in batch.iter_mut() {
+                span.resource = Cow::Owned(resource.clone());
+            }
+        }
+
+        self.inner.export(batch)
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown()
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        self.inner.force_flush()
+    }
+}
+
+/// Whether a trace falls within the given sampling ratio
+///
+/// Mirrors the SDK's `TraceIdRatioBased` sampler, so the traces a collector
+/// exports are a subset of those the tracer provider samples
+fn trace_id_sampled(trace_id: TraceId, ratio: f64) -> bool {
+    let upper_bound = (ratio.max(0.) * (1u64 << 63) as f64) as u64;
+    let bytes = trace_id.to_bytes();
+    let trace_id_low = u64::from_be_bytes(bytes[8..].try_into().unwrap());
+
+    (trace_id_low >> 1) < upper_bound
+}
+
+/// Whether a span lasted at least the given duration, or ended in error
+///
+/// Spans whose clock went backwards are treated as zero length
+fn span_long_enough(span: &SpanData, min_duration: Duration) -> bool {
+    if matches!(span.status, Status::Error { .. }) {
+        return true;
+    }
+
+    let duration = span.end_time.duration_since(span.start_time).unwrap_or_default();
+    duration >= min_duration
+}
+
+// ------------------------
+// | Attribute Flattening |
+// ------------------------
+
+/// A span exporter which flattens structured attribute values into dotted
+/// keys before delegating to an inner exporter
+#[derive(Debug)]
+struct FlattenedAttributesExporter<E: SpanExporter> {
+    /// The exporter that receives the flattened spans
+    inner: E,
+}
+
+impl<E: SpanExporter> SpanExporter for FlattenedAttributesExporter<E> {
+    fn export(&mut self, mut batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        for span in batch.iter_mut() {
+            let attributes = std::mem::take(&mut span.attributes);
+            span.attributes = flatten_attributes(attributes);
+        }
+
+        self.inner.export(batch)
+    }
+
+    fn shutdown(&mut self) {
+        self.inner.shutdown()
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        self.inner.force_flush()
+    }
+}
+
+/// Flatten a set of span attributes
+///
+/// Attributes whose value is a JSON object are expanded into one attribute per
+/// leaf, keyed by the dotted path to that leaf. All other attributes are left
+/// untouched
+fn flatten_attributes(attributes: Vec<KeyValue>) -> Vec<KeyValue> {
+    let mut flattened = Vec::with_capacity(attributes.len());
+    for kv in attributes {
+        let json = match &kv.value {
+            Value::String(s) => serde_json::from_str::<JsonValue>(s.as_str()).ok(),
+            _ => None,
+        };
+
+        match json {
+            Some(obj @ JsonValue::Object(_)) => flatten_json(kv.key.as_str(), obj, &mut flattened),
+            _ => flattened.push(kv),
+        }
+    }
+
+    flattened
+}
+
+/// Recursively flatten a JSON value into dotted key attributes
+fn flatten_json(prefix: &str, value: JsonValue, out: &mut Vec<KeyValue>) {
+    match value {
+        JsonValue::Object(map) => {
+            for (key, value) in map {
+                flatten_json(&format!("{prefix}.{key}"), value, out);
+            }
+        },
+        JsonValue::Bool(b) => out.push(KeyValue::new(prefix.to_string(), b)),
+        JsonValue::Number(n) => {
+            let value = match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => Value::I64(i),
+                (None, Some(f)) => Value::F64(f),
+                (None, None) => Value::String(n.to_string().into()),
+            };
+            out.push(KeyValue::new(prefix.to_string(), value));
+        },
+        JsonValue::String(s) => out.push(KeyValue::new(prefix.to_string(), s)),
+        // Arrays and nulls have no natural dotted representation, so we export
+        // them in their serialized form
+        other => out.push(KeyValue::new(prefix.to_string(), other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use opentelemetry::{KeyValue, Value, trace::TraceId};
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_semantic_conventions::resource::{DEPLOYMENT_ENVIRONMENT, HOST_NAME};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        path::Path,
+        sync::{
+            Mutex,
+            mpsc::{self, Receiver},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use super::{
+        OtlpConfig, OtlpProtocol, check_deployment_env, check_endpoint, check_honeycomb_headers,
+        configure_otlp_collector, configure_otlp_tracer_for_collectors, export_metadata,
+        flatten_attributes, sampler, tls_config, trace_id_sampled, tracer_resource,
+    };
+
+    /// Serializes tests which install a global tracer provider, so that one
+    /// test does not drop another's provider while it is in use
+    static GLOBAL_PROVIDER_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Spawn a mock OTLP/HTTP collector, returning its traces endpoint and a
+    /// channel on which each raw export request is sent
+    fn mock_collector() -> (String, Receiver<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+
+        let (send, recv) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let request = read_request(&mut stream);
+                let response = b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+                stream.write_all(response).unwrap();
+                if send.send(request).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (endpoint, recv)
+    }
+
+    /// Read a single HTTP request, including its body, from a stream
+    fn read_request(stream: &mut TcpStream) -> Vec<u8> {
+        let mut reader = BufReader::new(stream);
+        let mut request = Vec::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            request.extend_from_slice(line.as_bytes());
+            if line == "\r\n" || line.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        request.extend(body);
+        request
+    }
+
+    /// Whether the given bytes contain the given pattern
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    /// Tests that structured attributes are flattened into dotted keys
+    #[test]
+    fn test_flatten_attributes() {
+        let attributes = vec![
+            KeyValue::new("task", "pay-offline-fee"),
+            KeyValue::new("wallet", r#"{"id":"abcd","balances":{"count":2}}"#),
+        ];
+
+        let mut flattened = flatten_attributes(attributes);
+        flattened.sort_by(|a, b| a.key.as_str().cmp(b.key.as_str()));
+
+        assert_eq!(
+            flattened,
+            vec![
+                KeyValue::new("task", "pay-offline-fee"),
+                KeyValue::new("wallet.balances.count", Value::I64(2)),
+                KeyValue::new("wallet.id", "abcd"),
+            ]
+        );
+    }
+
+    /// Tests that non-object values are left untouched
+    #[test]
+    fn test_flatten_attributes_non_object() {
+        let attributes = vec![KeyValue::new("list", "[1,2]"), KeyValue::new("count", 1i64)];
+        let flattened = flatten_attributes(attributes.clone());
+
+        assert_eq!(flattened, attributes);
+    }
+
+    /// Tests that out of range sampling ratios are rejected
+    #[test]
+    fn test_sampler_ratio_bounds() {
+        assert!(sampler(0.).is_ok());
+        assert!(sampler(0.25).is_ok());
+        assert!(sampler(1.).is_ok());
+
+        assert!(sampler(-0.1).is_err());
+        assert!(sampler(1.5).is_err());
+        assert!(sampler(f64::NAN).is_err());
+    }
+
+    /// Tests building export metadata from headers
+    #[test]
+    fn test_export_metadata() {
+        let headers = vec![("X-Api-Key".to_string(), "secret".to_string())];
+        let metadata = export_metadata(&headers).unwrap();
+        assert_eq!(metadata.get("x-api-key").unwrap(), "secret");
+
+        let invalid = vec![("bad key".to_string(), "value".to_string())];
+        assert!(export_metadata(&invalid).is_err());
+    }
+
+    /// Tests that Honeycomb endpoints require the team header
+    #[test]
+    fn test_honeycomb_headers() {
+        let endpoint = "https://api.honeycomb.io:443";
+        assert!(check_honeycomb_headers(endpoint, &[]).is_err());
+        assert!(check_honeycomb_headers("http://localhost:4317", &[]).is_ok());
+
+        let config = OtlpConfig::default().with_honeycomb("key".to_string(), None);
+        assert!(check_honeycomb_headers(endpoint, &config.headers).is_ok());
+
+        // Header names are case insensitive
+        let headers = vec![("X-Honeycomb-Team".to_string(), "key".to_string())];
+        assert!(check_honeycomb_headers(endpoint, &headers).is_ok());
+    }
+
+    /// Tests validating collector endpoints for each export protocol
+    #[test]
+    fn test_check_endpoint() {
+        let grpc = OtlpProtocol::Grpc;
+        assert!(check_endpoint("http://localhost:4317", grpc).is_ok());
+        assert!(check_endpoint("https://collector:4317/", grpc).is_ok());
+        assert!(check_endpoint("http://localhost:4317/v1/traces", grpc).is_err());
+        assert!(check_endpoint("localhost:4317", grpc).is_err());
+
+        let http = OtlpProtocol::HttpProtobuf;
+        assert!(check_endpoint("http://localhost:4318/v1/traces", http).is_ok());
+        assert!(check_endpoint("http://localhost:4318", http).is_err());
+        assert!(check_endpoint("ftp://localhost:4318/v1/traces", http).is_err());
+        assert!(check_endpoint("not a url", http).is_err());
+    }
+
+    /// Tests that a missing CA certificate is rejected at setup
+    #[test]
+    fn test_tls_config_missing_ca() {
+        assert!(tls_config(Path::new("/does/not/exist.pem")).is_err());
+    }
+
+    /// Tests merging extra attributes into the tracer's resource
+    #[test]
+    fn test_tracer_resource() {
+        assert!(tracer_resource(false /* datadog_enabled */, &[]).unwrap().is_none());
+
+        let config =
+            OtlpConfig::default().with_resource_attributes([KeyValue::new(HOST_NAME, "relayer-0")]);
+        let resource =
+            tracer_resource(false /* datadog_enabled */, &config.resource_attributes)
+                .unwrap()
+                .unwrap();
+        assert_eq!(resource.get(HOST_NAME), Some("relayer-0".into()));
+
+        // The SDK's default attributes are kept
+        assert!(resource.len() > 1);
+    }
+
+    /// Tests that an empty deployment environment is rejected
+    #[test]
+    fn test_check_deployment_env() {
+        let resource = Resource::new([KeyValue::new(DEPLOYMENT_ENVIRONMENT, "mainnet")]);
+        assert!(check_deployment_env(Some(&resource)).is_ok());
+
+        let empty = Resource::new([KeyValue::new(DEPLOYMENT_ENVIRONMENT, "")]);
+        assert!(check_deployment_env(Some(&empty)).is_err());
+        assert!(check_deployment_env(Some(&Resource::empty())).is_err());
+        assert!(check_deployment_env(None).is_err());
+    }
+
+    /// Tests that sampling by trace ID respects the ratio bounds
+    #[test]
+    fn test_trace_id_sampled() {
+        let low = TraceId::from_bytes([0; 16]);
+        let high = TraceId::from_bytes([u8::MAX; 16]);
+
+        assert!(trace_id_sampled(low, 0.5));
+        assert!(!trace_id_sampled(high, 0.5));
+        assert!(!trace_id_sampled(low, 0.));
+    }
+
+    /// Tests that a single span is exported to every configured collector,
+    /// each with its own headers and sampling ratio
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_multiple_collectors() {
+        let _guard = GLOBAL_PROVIDER_LOCK.lock().unwrap();
+        let protocol = OtlpProtocol::HttpProtobuf;
+        let mut collectors = Vec::new();
+        let mut requests = Vec::new();
+        for (name, sampling_ratio) in [("one", 1.), ("two", 1.), ("unsampled", 0.)] {
+            let (endpoint, recv) = mock_collector();
+            let headers = vec![("x-collector".to_string(), name.to_string())];
+            let config = OtlpConfig { headers, sampling_ratio, ..Default::default() };
+
+            let collector = configure_otlp_collector(false, endpoint, protocol, &config).unwrap();
+            collectors.push(collector);
+            requests.push(recv);
+        }
+
+        let tracer = configure_otlp_tracer_for_collectors(collectors);
+        let provider = tracer.provider().unwrap();
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("dual-export-span").in_scope(|| {});
+        });
+        provider.force_flush();
+
+        for (recv, name) in requests.iter().zip(["one", "two"]) {
+            let request = recv.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert!(contains(&request, format!("x-collector: {name}").as_bytes()));
+            assert!(contains(&request, b"dual-export-span"));
+        }
+
+        // The unsampled collector drops the trace without an export request
+        assert!(requests[2].try_recv().is_err());
+    }
+
+    /// Tests that spans shorter than the minimum duration are dropped from
+    /// export unless they end in error
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_min_span_duration() {
+        let _guard = GLOBAL_PROVIDER_LOCK.lock().unwrap();
+        let (endpoint, recv) = mock_collector();
+        let min_span_duration = Some(Duration::from_millis(50));
+        let config = OtlpConfig { min_span_duration, ..Default::default() };
+        let collector =
+            configure_otlp_collector(false, endpoint, OtlpProtocol::HttpProtobuf, &config).unwrap();
+
+        let tracer = configure_otlp_tracer_for_collectors(vec![collector]);
+        let provider = tracer.provider().unwrap();
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("quick-span").in_scope(|| {});
+            tracing::info_span!("slow-span").in_scope(|| thread::sleep(Duration::from_millis(60)));
+            tracing::info_span!("failed-span").in_scope(|| tracing::error!("failed"));
+        });
+        provider.force_flush();
+
+        let request = recv.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(contains(&request, b"slow-span"));
+        assert!(contains(&request, b"failed-span"));
+        assert!(!contains(&request, b"quick-span"));
+    }
 }
@@ -0,0 +1,52 @@
+//! A panic hook which logs panics through `tracing`, so that they reach the
+//! same pipeline as the rest of the relayer's logs
+
+use std::{
+    backtrace::Backtrace,
+    panic::{self, PanicHookInfo},
+    thread,
+};
+
+use tracing::error;
+
+/// The payload logged when a panic's payload is not a string
+const UNKNOWN_PAYLOAD: &str = "Box<dyn Any>";
+
+/// Install a panic hook which logs each panic as an error event, then invokes
+/// the previously installed hook
+///
+/// The event is emitted on the panicking thread, so it is attributed to the
+/// span that is current when the panic occurs
+pub fn install_panic_hook() {
+    let prev_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        log_panic(info);
+        prev_hook(info);
+    }));
+}
+
+/// Log a panic along with its location and a backtrace
+fn log_panic(info: &PanicHookInfo<'_>) {
+    let thread = thread::current();
+    let thread_name = thread.name().unwrap_or("<unnamed>");
+    let location = info.location().map(ToString::to_string).unwrap_or_default();
+    let backtrace = Backtrace::force_capture();
+
+    error!(
+        panic.payload = panic_payload(info),
+        panic.location = location,
+        panic.thread = thread_name,
+        panic.backtrace = %backtrace,
+        "thread panicked"
+    );
+}
+
+/// Get the message a panic was raised with
+fn panic_payload<'a>(info: &'a PanicHookInfo<'_>) -> &'a str {
+    let payload = info.payload();
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or(UNKNOWN_PAYLOAD)
+}
@@ -1,6 +1,7 @@
 //! Helpers for propagating tracing information across processes
 use std::collections::HashMap;
 
+use http::{HeaderMap, HeaderName, HeaderValue};
 use opentelemetry::{
     Context, global,
     propagation::{Extractor, Injector},
@@ -31,6 +32,32 @@ impl Extractor for TraceContextExtractor<'_> {
     }
 }
 
+/// Helper struct for injecting tracing context into HTTP headers
+pub struct HeaderInjector<'a>(&'a mut HeaderMap);
+/// Helper struct for extracting tracing context from HTTP headers
+pub struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        // Skip keys or values which are not valid in an HTTP header
+        let name = HeaderName::from_bytes(key.as_bytes());
+        let value = HeaderValue::from_str(&value);
+        if let (Ok(name), Ok(value)) = (name, value) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
 /// Create a new TraceContext containing string-keyed trace context
 pub fn trace_context() -> TraceContext {
     let mut trace_context = TraceContext::new();
@@ -60,3 +87,53 @@ pub fn set_parent_span_from_context(headers: &TraceContext) {
 
     tracing::Span::current().set_parent(context);
 }
+
+/// Extract the trace context carried by a set of inbound HTTP headers
+///
+/// Returns an empty context if the headers carry no trace context
+pub fn extract_context_from_headers(headers: &HeaderMap) -> Context {
+    let extractor = HeaderExtractor(headers);
+    global::get_text_map_propagator(|prop| prop.extract(&extractor))
+}
+
+/// Inject a trace context into a set of outbound HTTP headers
+pub fn inject_context_into_headers(cx: &Context, headers: &mut HeaderMap) {
+    global::get_text_map_propagator(|prop| prop.inject_context(cx, &mut HeaderInjector(headers)));
+}
+
+#[cfg(test)]
+mod test {
+    use http::HeaderMap;
+    use opentelemetry::{
+        Context, global,
+        trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+    };
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+    use super::{extract_context_from_headers, inject_context_into_headers};
+
+    /// Tests that a trace context survives a round trip through HTTP headers
+    #[test]
+    fn test_header_context_roundtrip() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true, // is_remote
+            TraceState::default(),
+        );
+        let cx = Context::new().with_remote_span_context(span_context.clone());
+
+        let mut headers = HeaderMap::new();
+        inject_context_into_headers(&cx, &mut headers);
+        assert_eq!(
+            headers["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        let extracted = extract_context_from_headers(&headers);
+        assert_eq!(extracted.span().span_context(), &span_context);
+    }
+}
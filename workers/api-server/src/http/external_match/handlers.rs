@@ -90,7 +90,7 @@ impl RequestExternalQuoteHandler {
     ) -> Result<SignedExternalQuote, ApiServerError> {
         // Estimate the fees for the match
         let fees = self.estimate_fee_take(relayer_fee, match_res);
-        let quote = ApiExternalQuote::new(order, match_res, fees);
+        let quote = ApiExternalQuote::new(order, match_res, fees).map_err(internal_error)?;
         let quote_bytes = serde_json::to_vec(&quote).map_err(internal_error)?;
         let signature = self.admin_key.compute_mac(&quote_bytes);
         let signature_hex = bytes_to_hex_string(&signature);
@@ -513,7 +513,7 @@ impl ExternalMatchProcessor {
             settlement_tx = settlement_tx.gas_limit(gas);
         }
 
-        Ok(AtomicMatchApiBundle::new(&match_bundle, settlement_tx))
+        AtomicMatchApiBundle::new(&match_bundle, settlement_tx).map_err(internal_error)
     }
 
     /// Build a malleable API bundle from a malleable match bundle and internal
@@ -285,12 +285,12 @@ impl TypedHandler for CreateWalletHandler {
 
         // Modify the public shares of the new wallet to reflect the overwritten fields
         let circuit_wallet: SizedCircuitWallet = wallet.clone().into();
-        let (_, public_shares) = create_wallet_shares_from_private(
+        let (private_shares, public_shares) = create_wallet_shares_from_private(
             &circuit_wallet,
             &wallet.private_shares,
             wallet.blinder,
         );
-        wallet.blinded_public_shares = public_shares;
+        wallet.set_shares(private_shares, public_shares);
 
         wallet.wallet_id = wallet_id;
         let blinder_seed = biguint_to_scalar(&req.blinder_seed);
@@ -151,3 +151,23 @@ pub enum ProofJob {
         statement: SizedValidFeeRedemptionStatement,
     },
 }
+
+impl ProofJob {
+    /// The name of the circuit the job proves, used to label metrics
+    pub fn circuit_name(&self) -> &'static str {
+        match self {
+            ProofJob::ValidWalletCreate { .. } => "valid_wallet_create",
+            ProofJob::ValidReblind { .. } => "valid_reblind",
+            ProofJob::ValidCommitments { .. } => "valid_commitments",
+            ProofJob::ValidWalletUpdate { .. } => "valid_wallet_update",
+            ProofJob::ValidMatchSettleSingleprover { .. } => "valid_match_settle",
+            ProofJob::ValidMatchSettleAtomic { .. } => "valid_match_settle_atomic",
+            ProofJob::ValidMalleableMatchSettleAtomic { .. } => {
+                "valid_malleable_match_settle_atomic"
+            },
+            ProofJob::ValidRelayerFeeSettlement { .. } => "valid_relayer_fee_settlement",
+            ProofJob::ValidOfflineFeeSettlement { .. } => "valid_offline_fee_settlement",
+            ProofJob::ValidFeeRedemption { .. } => "valid_fee_redemption",
+        }
+    }
+}
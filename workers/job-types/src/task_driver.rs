@@ -16,6 +16,8 @@ pub type TaskDriverReceiver = TracedCrossbeamReceiver<TaskDriverJob>;
 pub type TaskNotificationSender = OneshotSender<Result<(), String>>;
 /// The receiver type of a task notification channel
 pub type TaskNotificationReceiver = OneshotReceiver<Result<(), String>>;
+/// The receiver type of a drain notification channel
+pub type TaskDrainReceiver = OneshotReceiver<()>;
 
 /// Create a new task driver queue
 pub fn new_task_driver_queue() -> (TaskDriverQueue, TaskDriverReceiver) {
@@ -45,6 +47,24 @@ pub enum TaskDriverJob {
         /// The channel on which to notify the worker
         channel: TaskNotificationSender,
     },
+    /// Cancel a running task
+    ///
+    /// A task that has not reached its commit point fails at its next step
+    /// boundary, cancellation at or past the commit point is ignored
+    Cancel {
+        /// The ID of the task to cancel
+        task_id: TaskIdentifier,
+    },
+    /// Stop accepting new tasks and halt running tasks once they are safe to
+    /// stop
+    ///
+    /// Tasks past their commit point run to completion, all others halt at
+    /// their next step boundary and remain queued for resumption
+    Drain {
+        /// The channel on which to notify the caller once all tasks have
+        /// stopped
+        channel: OneshotSender<()>,
+    },
 }
 
 impl TaskDriverJob {
@@ -64,4 +84,15 @@ impl TaskDriverJob {
         let (sender, receiver) = oneshot_channel();
         (Self::Notify { task_id, channel: sender }, receiver)
     }
+
+    /// Create a new cancellation job
+    pub fn cancel(task_id: TaskIdentifier) -> Self {
+        Self::Cancel { task_id }
+    }
+
+    /// Create a new drain job
+    pub fn new_drain() -> (Self, TaskDrainReceiver) {
+        let (sender, receiver) = oneshot_channel();
+        (Self::Drain { channel: sender }, receiver)
+    }
 }
@@ -1,6 +1,6 @@
 //! Helpers for `task-driver` integration tests
 
-use std::str::FromStr;
+use std::{str::FromStr, time::Duration};
 
 use alloy_primitives::Address;
 use circuit_types::{keychain::PublicSigningKey, transfers::ExternalTransfer};
@@ -27,7 +27,8 @@ use rand::thread_rng;
 use state::State;
 use system_bus::SystemBus;
 use task_driver::{
-    driver::RuntimeArgs,
+    driver::{RetryConfig, RuntimeArgs},
+    traits::TaskContext,
     worker::{TaskDriver, TaskDriverConfig},
 };
 use test_helpers::{
@@ -37,6 +38,7 @@ use test_helpers::{
         transfer_auth::gen_transfer_with_auth,
     },
 };
+use tokio_util::sync::CancellationToken;
 use util::concurrency::runtime::block_current;
 
 use crate::IntegrationTestArgs;
@@ -131,6 +133,22 @@ pub(crate) async fn await_wallet_task_queue_flush(
     Ok(())
 }
 
+/// Build a context for running a task directly, outside of the task driver
+pub(crate) fn mock_task_context(test_args: &IntegrationTestArgs) -> TaskContext {
+    TaskContext {
+        task_id: None,
+        darkpool_client: test_args.darkpool_client.clone(),
+        state: test_args.state.clone(),
+        network_queue: test_args.network_sender.clone(),
+        proof_queue: test_args.proof_job_queue.clone(),
+        event_queue: test_args.event_queue.clone(),
+        task_queue: test_args.task_queue.clone(),
+        bus: SystemBus::new(),
+        retry_config: RetryConfig::default(),
+        cancellation: CancellationToken::new(),
+    }
+}
+
 // ------------------------
 // | Contract Interaction |
 // ------------------------
@@ -228,6 +246,12 @@ pub fn new_mock_task_driver(
         backoff_ceiling_ms: 1_000, // 1 second
         initial_backoff_ms: 100,   // 100 milliseconds
         n_retries: 2,
+        step_timeout_ms: None,
+        retry_config: RetryConfig {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(100), // 100 milliseconds
+            max_delay: Duration::from_millis(1_000), // 1 second
+        },
     };
 
     let config = TaskDriverConfig {
@@ -240,6 +264,7 @@ pub fn new_mock_task_driver(
         proof_queue,
         event_queue,
         state,
+        cancellation: CancellationToken::new(),
     };
 
     // Start the driver
@@ -19,8 +19,8 @@ use crossbeam::channel::Sender as CrossbeamSender;
 use darkpool_client::{DarkpoolClient, client::DarkpoolClientConfig, constants::Chain};
 use helpers::new_mock_task_driver;
 use job_types::{
-    event_manager::{EventManagerReceiver, new_event_manager_queue},
-    network_manager::{NetworkManagerReceiver, new_network_manager_queue},
+    event_manager::{EventManagerQueue, EventManagerReceiver, new_event_manager_queue},
+    network_manager::{NetworkManagerQueue, NetworkManagerReceiver, new_network_manager_queue},
     proof_manager::{ProofManagerJob, new_proof_manager_queue},
     task_driver::{TaskDriverQueue, new_task_driver_queue},
 };
@@ -90,10 +90,14 @@ struct IntegrationTestArgs {
     darkpool_client: DarkpoolClient,
     /// The private key of the account used for the relayer
     pkey: PrivateKeySigner,
+    /// A sender to the network manager's work queue
+    network_sender: NetworkManagerQueue,
     /// A receiver for the network manager's work queue
     ///
     /// Held here to avoid closing the channel on `Drop`
     _network_receiver: Arc<NetworkManagerReceiver>,
+    /// A sender to the event manager's work queue
+    event_queue: EventManagerQueue,
     /// A receiver for the event manager's work queue
     ///
     /// Held here to avoid closing the channel on `Drop`
@@ -135,9 +139,9 @@ impl From<CliArgs> for IntegrationTestArgs {
             task_recv,
             task_queue.clone(),
             darkpool_client.clone(),
-            network_sender,
+            network_sender.clone(),
             proof_job_queue.clone(),
-            event_queue,
+            event_queue.clone(),
             state.clone(),
         );
 
@@ -161,7 +165,9 @@ impl From<CliArgs> for IntegrationTestArgs {
             permit2_addr,
             darkpool_client,
             pkey,
+            network_sender,
             _network_receiver: Arc::new(network_receiver),
+            event_queue,
             _event_receiver: Arc::new(event_receiver),
             proof_job_queue,
             state,
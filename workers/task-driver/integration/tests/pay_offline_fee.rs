@@ -1,17 +1,28 @@
 //! Protocol fee payment tests
-use circuit_types::balance::Balance;
+use circuit_types::{balance::Balance, elgamal::DecryptionKey};
 use circuits::test_helpers::random_wallet_amount;
-use common::types::{tasks::PayOfflineFeeTaskDescriptor, wallet_mocks::mock_empty_wallet};
+use common::types::{
+    tasks::{PayAllOfflineFeesTaskDescriptor, PayOfflineFeeTaskDescriptor},
+    wallet_mocks::mock_empty_wallet,
+};
 use constants::Scalar;
 use eyre::{Result, eyre};
 use rand::thread_rng;
 use renegade_crypto::fields::scalar_to_biguint;
-use test_helpers::{assert_eq_result, integration_test_async};
+use task_driver::{
+    tasks::{
+        pay_all_offline_fees::PayAllOfflineFeesTask,
+        pay_offline_fee::{PayOfflineFeeTask, PayOfflineFeeTaskState},
+    },
+    traits::Task,
+};
+use test_helpers::{assert_eq_result, assert_true_result, integration_test_async};
 
 use crate::{
     IntegrationTestArgs,
     helpers::{
-        await_task, lookup_wallet_and_check_result, setup_initial_wallet, setup_relayer_wallet,
+        await_task, lookup_wallet_and_check_result, mock_task_context, setup_initial_wallet,
+        setup_relayer_wallet,
     },
 };
 
@@ -97,3 +108,281 @@ async fn test_pay_offline_fees(test_args: IntegrationTestArgs) -> Result<()> {
     lookup_wallet_and_check_result(&expected_wallet, blinder_seed, share_seed, &test_args).await
 }
 integration_test_async!(test_pay_offline_fees);
+
+/// Tests that a dry run fee payment proves the payment without mutating the
+/// wallet
+#[allow(non_snake_case)]
+async fn test_pay_offline_fees__dry_run(test_args: IntegrationTestArgs) -> Result<()> {
+    let mut rng = thread_rng();
+    let state = &test_args.state;
+    setup_relayer_wallet(&test_args).await?;
+
+    // Create a wallet in the darkpool with a non-zero fee
+    let mut wallet = mock_empty_wallet();
+    let bal = random_balance_with_fees();
+    wallet.add_balance(bal.clone()).unwrap();
+
+    // Allocate the wallet
+    let blinder_seed = Scalar::random(&mut rng);
+    let share_seed = Scalar::random(&mut rng);
+    setup_initial_wallet(blinder_seed, share_seed, &mut wallet, &test_args).await?;
+
+    // Dry run the protocol fee payment
+    let descriptor = PayOfflineFeeTaskDescriptor::new_protocol_fee(wallet.wallet_id, bal.clone())
+        .expect("infallible")
+        .with_dry_run(true);
+    await_task(descriptor.into(), &test_args).await?;
+
+    // The wallet should be unchanged, both in global state and on-chain
+    let state_wallet = state
+        .get_wallet(&wallet.wallet_id)
+        .await?
+        .ok_or_else(|| eyre!("wallet not found in state"))?;
+
+    assert_eq_result!(state_wallet.blinded_public_shares, wallet.blinded_public_shares)?;
+    assert_eq_result!(state_wallet.private_shares, wallet.private_shares)?;
+    lookup_wallet_and_check_result(&wallet, blinder_seed, share_seed, &test_args).await
+}
+integration_test_async!(test_pay_offline_fees__dry_run);
+
+/// Tests that a protocol fee payment with an encryption key override is
+/// rejected
+#[allow(non_snake_case)]
+async fn test_pay_offline_fees__protocol_key_override(
+    test_args: IntegrationTestArgs,
+) -> Result<()> {
+    let mut rng = thread_rng();
+    setup_relayer_wallet(&test_args).await?;
+
+    // Create a wallet in the darkpool with a non-zero fee
+    let mut wallet = mock_empty_wallet();
+    let bal = random_balance_with_fees();
+    wallet.add_balance(bal.clone()).unwrap();
+
+    // Allocate the wallet
+    let blinder_seed = Scalar::random(&mut rng);
+    let share_seed = Scalar::random(&mut rng);
+    setup_initial_wallet(blinder_seed, share_seed, &mut wallet, &test_args).await?;
+
+    // Attempt to redirect the protocol fee to the wallet's managing cluster
+    let descriptor = PayOfflineFeeTaskDescriptor::new_protocol_fee(wallet.wallet_id, bal.clone())
+        .expect("infallible")
+        .with_encryption_key_override(wallet.managing_cluster);
+    assert_true_result!(await_task(descriptor.into(), &test_args).await.is_err())?;
+
+    // The wallet should be unchanged on-chain
+    lookup_wallet_and_check_result(&wallet, blinder_seed, share_seed, &test_args).await
+}
+integration_test_async!(test_pay_offline_fees__protocol_key_override);
+
+/// Tests relayer fee payments with an encryption key override, which is only
+/// accepted if it is the wallet's managing cluster key
+#[allow(non_snake_case)]
+async fn test_pay_offline_fees__relayer_key_override(test_args: IntegrationTestArgs) -> Result<()> {
+    let mut rng = thread_rng();
+    let state = &test_args.state;
+    setup_relayer_wallet(&test_args).await?;
+
+    // Create a wallet in the darkpool with a non-zero fee
+    let mut wallet = mock_empty_wallet();
+    let bal = random_balance_with_fees();
+    wallet.add_balance(bal.clone()).unwrap();
+
+    // Allocate the wallet
+    let blinder_seed = Scalar::random(&mut rng);
+    let share_seed = Scalar::random(&mut rng);
+    setup_initial_wallet(blinder_seed, share_seed, &mut wallet, &test_args).await?;
+
+    // Redirecting the relayer fee to another key is rejected, as the circuit
+    // encrypts relayer fee notes under the managing cluster key
+    let (_, other_key) = DecryptionKey::random_pair(&mut rng);
+    let descriptor = PayOfflineFeeTaskDescriptor::new_relayer_fee(wallet.wallet_id, bal.clone())
+        .expect("infallible")
+        .with_encryption_key_override(other_key);
+    assert_true_result!(await_task(descriptor.into(), &test_args).await.is_err())?;
+    lookup_wallet_and_check_result(&wallet, blinder_seed, share_seed, &test_args).await?;
+
+    // An override of the managing cluster key proves and settles the fee
+    let descriptor = PayOfflineFeeTaskDescriptor::new_relayer_fee(wallet.wallet_id, bal.clone())
+        .expect("infallible")
+        .with_encryption_key_override(wallet.managing_cluster);
+    await_task(descriptor.into(), &test_args).await?;
+
+    let mut expected_wallet = wallet.clone();
+    expected_wallet.get_balance_mut(&bal.mint).unwrap().relayer_fee_balance = 0;
+    expected_wallet.reblind_wallet();
+
+    let wallet = state
+        .get_wallet(&wallet.wallet_id)
+        .await?
+        .ok_or_else(|| eyre!("wallet not found in state"))?;
+
+    assert_eq_result!(wallet.blinded_public_shares, expected_wallet.blinded_public_shares)?;
+    assert_eq_result!(wallet.private_shares, expected_wallet.private_shares)?;
+    lookup_wallet_and_check_result(&expected_wallet, blinder_seed, share_seed, &test_args).await
+}
+integration_test_async!(test_pay_offline_fees__relayer_key_override);
+
+/// Tests that a payment re-enqueued with the idempotency key of a settled
+/// attempt resumes from `FindingOpening` rather than paying the fee twice
+#[allow(non_snake_case)]
+async fn test_pay_offline_fees__idempotency_key(test_args: IntegrationTestArgs) -> Result<()> {
+    let mut rng = thread_rng();
+    let state = &test_args.state;
+    setup_relayer_wallet(&test_args).await?;
+
+    // Create a wallet in the darkpool with a non-zero fee
+    let mut wallet = mock_empty_wallet();
+    let bal = random_balance_with_fees();
+    wallet.add_balance(bal.clone()).unwrap();
+
+    // Allocate the wallet
+    let blinder_seed = Scalar::random(&mut rng);
+    let share_seed = Scalar::random(&mut rng);
+    setup_initial_wallet(blinder_seed, share_seed, &mut wallet, &test_args).await?;
+
+    // Settle the payment but stop before the new wallet is recorded, as if the
+    // task failed after submitting
+    let descriptor = PayOfflineFeeTaskDescriptor::new_protocol_fee(wallet.wallet_id, bal.clone())
+        .expect("infallible");
+    let ctx = mock_task_context(&test_args);
+    let mut first_attempt = PayOfflineFeeTask::new(descriptor.clone(), ctx).await?;
+    while first_attempt.state() < PayOfflineFeeTaskState::FindingOpening {
+        first_attempt.step().await?;
+    }
+    let note_commitment = first_attempt.note.commitment();
+    let tx_hash = first_attempt.tx.as_ref().unwrap().transaction_hash;
+
+    // Re-enqueue the payment with the settled note's commitment, the task
+    // should skip straight to finding the wallet's opening
+    let descriptor = descriptor.with_idempotency_key(note_commitment);
+    let mut task = PayOfflineFeeTask::new(descriptor, mock_task_context(&test_args)).await?;
+    task.step().await?;
+    assert_eq_result!(task.state(), PayOfflineFeeTaskState::FindingOpening)?;
+    assert_true_result!(task.proof.is_none())?;
+
+    while !task.completed() {
+        task.step().await?;
+    }
+
+    // The result reports the note settled by the first attempt
+    let result = task.result().ok_or_else(|| eyre!("task has no result"))?;
+    assert_eq_result!(result.note_commitment, note_commitment)?;
+    assert_eq_result!(result.tx_hash, tx_hash)?;
+
+    // The fee is paid once
+    let mut expected_wallet = wallet.clone();
+    expected_wallet.get_balance_mut(&bal.mint).unwrap().protocol_fee_balance = 0;
+    expected_wallet.reblind_wallet();
+
+    let wallet = state
+        .get_wallet(&wallet.wallet_id)
+        .await?
+        .ok_or_else(|| eyre!("wallet not found in state"))?;
+
+    assert_eq_result!(wallet.blinded_public_shares, expected_wallet.blinded_public_shares)?;
+    assert_eq_result!(wallet.private_shares, expected_wallet.private_shares)?;
+    lookup_wallet_and_check_result(&expected_wallet, blinder_seed, share_seed, &test_args).await
+}
+integration_test_async!(test_pay_offline_fees__idempotency_key);
+
+/// Tests that fee payments report the payer's wallet nullifier, and not the
+/// nullifier of the note they create
+#[allow(non_snake_case)]
+async fn test_pay_offline_fees__nullifiers(test_args: IntegrationTestArgs) -> Result<()> {
+    let mut rng = thread_rng();
+    setup_relayer_wallet(&test_args).await?;
+
+    let mut wallet = mock_empty_wallet();
+    let bal = random_balance_with_fees();
+    wallet.add_balance(bal.clone()).unwrap();
+
+    let blinder_seed = Scalar::random(&mut rng);
+    let share_seed = Scalar::random(&mut rng);
+    setup_initial_wallet(blinder_seed, share_seed, &mut wallet, &test_args).await?;
+    let expected = vec![wallet.get_wallet_nullifier()];
+
+    // A single payment
+    let descriptor =
+        PayOfflineFeeTaskDescriptor::new_protocol_fee(wallet.wallet_id, bal).expect("infallible");
+    let task = PayOfflineFeeTask::new(descriptor, mock_task_context(&test_args)).await?;
+    assert_eq_result!(task.nullifiers(), expected)?;
+
+    // A batch of payments
+    let descriptor = PayAllOfflineFeesTaskDescriptor::new(wallet.wallet_id);
+    let task = PayAllOfflineFeesTask::new(descriptor, mock_task_context(&test_args)).await?;
+    assert_eq_result!(task.nullifiers(), expected)
+}
+integration_test_async!(test_pay_offline_fees__nullifiers);
+
+/// Tests paying all fees on a wallet with no outstanding fees, which should
+/// complete without modifying the wallet
+#[allow(non_snake_case)]
+async fn test_pay_all_offline_fees__no_fees(test_args: IntegrationTestArgs) -> Result<()> {
+    let mut rng = thread_rng();
+    let state = &test_args.state;
+
+    // Create a wallet in the darkpool with no fees
+    let mut wallet = mock_empty_wallet();
+    let blinder_seed = Scalar::random(&mut rng);
+    let share_seed = Scalar::random(&mut rng);
+    setup_initial_wallet(blinder_seed, share_seed, &mut wallet, &test_args).await?;
+
+    let descriptor = PayAllOfflineFeesTaskDescriptor::new(wallet.wallet_id);
+    await_task(descriptor.into(), &test_args).await?;
+
+    // The wallet should be unchanged, both in global state and on-chain
+    let state_wallet = state
+        .get_wallet(&wallet.wallet_id)
+        .await?
+        .ok_or_else(|| eyre!("wallet not found in state"))?;
+
+    assert_eq_result!(state_wallet.blinded_public_shares, wallet.blinded_public_shares)?;
+    assert_eq_result!(state_wallet.private_shares, wallet.private_shares)?;
+    lookup_wallet_and_check_result(&wallet, blinder_seed, share_seed, &test_args).await
+}
+integration_test_async!(test_pay_all_offline_fees__no_fees);
+
+/// Tests paying all fees on a wallet with fees owed on multiple balances
+#[allow(non_snake_case)]
+async fn test_pay_all_offline_fees__multiple_balances(
+    test_args: IntegrationTestArgs,
+) -> Result<()> {
+    let mut rng = thread_rng();
+    let state = &test_args.state;
+    setup_relayer_wallet(&test_args).await?;
+
+    // Create a wallet in the darkpool with relayer and protocol fees owed on two
+    // balances
+    let mut wallet = mock_empty_wallet();
+    let balances = [random_balance_with_fees(), random_balance_with_fees()];
+    for bal in balances.iter() {
+        wallet.add_balance(bal.clone()).unwrap();
+    }
+
+    let blinder_seed = Scalar::random(&mut rng);
+    let share_seed = Scalar::random(&mut rng);
+    setup_initial_wallet(blinder_seed, share_seed, &mut wallet, &test_args).await?;
+
+    let descriptor = PayAllOfflineFeesTaskDescriptor::new(wallet.wallet_id);
+    await_task(descriptor.into(), &test_args).await?;
+
+    // Every fee is paid, each payment reblinding the wallet once
+    let mut expected_wallet = wallet.clone();
+    for bal in balances.iter() {
+        let expected_bal = expected_wallet.get_balance_mut(&bal.mint).unwrap();
+        expected_bal.relayer_fee_balance = 0;
+        expected_bal.protocol_fee_balance = 0;
+    }
+    expected_wallet.reblind_n(2 * balances.len());
+
+    let wallet = state
+        .get_wallet(&wallet.wallet_id)
+        .await?
+        .ok_or_else(|| eyre!("wallet not found in state"))?;
+
+    assert_eq_result!(wallet.blinded_public_shares, expected_wallet.blinded_public_shares)?;
+    assert_eq_result!(wallet.private_shares, expected_wallet.private_shares)?;
+    lookup_wallet_and_check_result(&expected_wallet, blinder_seed, share_seed, &test_args).await
+}
+integration_test_async!(test_pay_all_offline_fees__multiple_balances);
@@ -3,18 +3,22 @@
 use circuit_types::balance::Balance;
 use circuits::test_helpers::random_wallet_amount;
 use common::types::{
-    tasks::PayOfflineFeeTaskDescriptor, wallet::Wallet, wallet_mocks::mock_empty_wallet,
+    tasks::{PayOfflineFeeTaskDescriptor, RedeemFeeTaskDescriptor},
+    wallet::Wallet,
+    wallet_mocks::mock_empty_wallet,
 };
 use constants::Scalar;
 use eyre::{Result, eyre};
 use rand::thread_rng;
 use renegade_crypto::fields::scalar_to_biguint;
+use task_driver::{tasks::redeem_fee::RedeemFeeTask, traits::Task};
 use test_helpers::{assert_eq_result, integration_test_async};
 
 use crate::{
     IntegrationTestArgs,
     helpers::{
-        await_task, await_wallet_task_queue_flush, setup_initial_wallet, setup_relayer_wallet,
+        await_task, await_wallet_task_queue_flush, mock_task_context, setup_initial_wallet,
+        setup_relayer_wallet,
     },
 };
 
@@ -83,3 +87,25 @@ async fn test_auto_redeem_relayer_fee(test_args: IntegrationTestArgs) -> Result<
     assert_eq_result!(new_bal, expected_balance)
 }
 integration_test_async!(test_auto_redeem_relayer_fee);
+
+/// Tests that a redemption reports both the relayer's wallet nullifier and the
+/// note's nullifier
+#[allow(non_snake_case)]
+async fn test_redeem_relayer_fee__nullifiers(test_args: IntegrationTestArgs) -> Result<()> {
+    setup_relayer_wallet(&test_args).await?;
+    let state = &test_args.state;
+    let relayer_wallet_id = state.get_relayer_wallet_id().await?.unwrap();
+    let relayer_wallet = state.get_wallet(&relayer_wallet_id).await?.unwrap();
+
+    // The note need not be settled for the task to be constructed
+    let fee_key = state.get_fee_key().await?;
+    let decryption_key = fee_key.secret_key().ok_or(eyre!("fee decryption key not found"))?;
+    let note = random_balance().create_relayer_note(fee_key.public_key());
+
+    let descriptor = RedeemFeeTaskDescriptor::new(relayer_wallet_id, note.clone(), decryption_key);
+    let task = RedeemFeeTask::new(descriptor, mock_task_context(&test_args)).await?;
+
+    let expected = vec![relayer_wallet.get_wallet_nullifier(), note.nullifier()];
+    assert_eq_result!(task.nullifiers(), expected)
+}
+integration_test_async!(test_redeem_relayer_fee__nullifiers);
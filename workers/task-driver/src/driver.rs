@@ -1,7 +1,15 @@
 //! The task driver drives a task forwards and executes partial retries
 //! of certain critical sections of a task
 
-use std::{collections::HashMap, fmt::Debug, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 
 use common::types::{
     tasks::{QueuedTask, TaskDescriptor, TaskIdentifier},
@@ -9,21 +17,22 @@ use common::types::{
 };
 use job_types::task_driver::{TaskDriverJob, TaskDriverReceiver, TaskNotificationSender};
 use state::State;
-use tokio::runtime::Builder as TokioRuntimeBuilder;
-use tracing::{error, info, instrument, warn};
+use tokio::{runtime::Builder as TokioRuntimeBuilder, sync::oneshot::Sender as OneshotSender};
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, error, info, instrument, warn};
 use util::{
     channels::TracedMessage,
     concurrency::{Shared, new_shared},
 };
 
 use crate::{
-    error::TaskDriverError,
+    error::{TaskCancelledError, TaskDriverError},
     running_task::RunnableTask,
     tasks::{
         create_new_wallet::NewWalletTask, lookup_wallet::LookupWalletTask,
-        node_startup::NodeStartupTask, pay_offline_fee::PayOfflineFeeTask,
-        pay_relayer_fee::PayRelayerFeeTask, redeem_fee::RedeemFeeTask,
-        refresh_wallet::RefreshWalletTask,
+        node_startup::NodeStartupTask, pay_all_offline_fees::PayAllOfflineFeesTask,
+        pay_offline_fee::PayOfflineFeeTask, pay_relayer_fee::PayRelayerFeeTask,
+        redeem_fee::RedeemFeeTask, refresh_wallet::RefreshWalletTask,
         settle_malleable_external_match::SettleMalleableExternalMatchTask,
         settle_match::SettleMatchTask, settle_match_external::SettleMatchExternalTask,
         settle_match_internal::SettleMatchInternalTask, update_merkle_proof::UpdateMerkleProofTask,
@@ -33,6 +42,8 @@ use crate::{
     worker::TaskDriverConfig,
 };
 
+pub use crate::utils::retry::RetryConfig;
+
 /// The amount to increase the backoff delay by every retry
 const BACKOFF_AMPLIFICATION_FACTOR: u32 = 2;
 /// The maximum to increase the backoff to in milliseconds
@@ -45,6 +56,8 @@ const TASK_DRIVER_THREAD_NAME: &str = "renegade-task-driver";
 const TASK_DRIVER_N_RETRIES: usize = 5;
 /// The stack size to allocate for task driver threads
 const DRIVER_THREAD_STACK_SIZE: usize = 50_000_000; // 50MB
+/// The interval at which to check for running tasks while draining
+const DRAIN_POLL_INTERVAL_MS: u64 = 100; // 100 milliseconds
 
 /// Error message sent on a notification when a task is not found
 const TASK_NOT_FOUND_ERROR: &str = "task not found";
@@ -55,6 +68,8 @@ const TASK_NOT_FOUND_ERROR: &str = "task not found";
 
 /// The type that indexes task notifications
 type TaskNotificationMap = Shared<HashMap<TaskIdentifier, Vec<TaskNotificationSender>>>;
+/// The type that indexes the cancellation tokens of running tasks
+type TaskCancellationMap = Shared<HashMap<TaskIdentifier, CancellationToken>>;
 
 /// Drives tasks to completion
 #[derive(Clone)]
@@ -68,6 +83,12 @@ pub struct TaskExecutor {
     task_context: TaskContext,
     /// The map of task notifications to send
     task_notifications: TaskNotificationMap,
+    /// The cancellation tokens of running tasks
+    task_cancellations: TaskCancellationMap,
+    /// Whether the driver is draining, in which case no new tasks are started
+    draining: Arc<AtomicBool>,
+    /// The number of tasks currently running in the driver
+    running_tasks: Arc<AtomicUsize>,
 }
 
 /// The config of the runtime arguments
@@ -83,6 +104,13 @@ pub struct RuntimeArgs {
     pub initial_backoff_ms: u64,
     /// The number of retries to attempt before propagating an error
     pub n_retries: usize,
+    /// The default timeout in milliseconds for a single task step, used for
+    /// states that do not define their own timeout
+    ///
+    /// `None` allows steps to run indefinitely
+    pub step_timeout_ms: Option<u64>,
+    /// The config for retrying transaction submissions within a step
+    pub retry_config: RetryConfig,
 }
 
 impl Default for RuntimeArgs {
@@ -92,6 +120,8 @@ impl Default for RuntimeArgs {
             backoff_ceiling_ms: BACKOFF_CEILING_MS,
             initial_backoff_ms: INITIAL_BACKOFF_MS,
             n_retries: TASK_DRIVER_N_RETRIES,
+            step_timeout_ms: None,
+            retry_config: RetryConfig::default(),
         }
     }
 }
@@ -100,6 +130,7 @@ impl TaskExecutor {
     /// Constructor
     pub fn new(config: TaskDriverConfig) -> Self {
         let task_context = TaskContext {
+            task_id: None,
             darkpool_client: config.darkpool_client,
             network_queue: config.network_queue,
             proof_queue: config.proof_queue,
@@ -107,6 +138,8 @@ impl TaskExecutor {
             task_queue: config.task_queue_sender,
             state: config.state,
             bus: config.system_bus.clone(),
+            retry_config: config.runtime_config.retry_config,
+            cancellation: config.cancellation,
         };
 
         Self {
@@ -114,6 +147,9 @@ impl TaskExecutor {
             runtime_config: config.runtime_config,
             task_context,
             task_notifications: new_shared(HashMap::new()),
+            task_cancellations: new_shared(HashMap::new()),
+            draining: Arc::new(AtomicBool::new(false)),
+            running_tasks: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -165,15 +201,73 @@ impl TaskExecutor {
     async fn handle_job(&self, job: TracedMessage<TaskDriverJob>) -> Result<(), TaskDriverError> {
         match job.consume() {
             TaskDriverJob::Run { task, channel } => {
+                // Count the task as running before checking for a drain, so that a
+                // concurrent drain either waits on the task or the task sees the drain
+                self.running_tasks.fetch_add(1, Ordering::SeqCst);
+                if self.is_draining() {
+                    // The task remains in its queue and is resumed on restart
+                    self.running_tasks.fetch_sub(1, Ordering::SeqCst);
+                    info!("driver is draining, not starting task {}", task.id);
+                    return Ok(());
+                }
+
                 let affected_wallets = task.descriptor.affected_wallets();
-                self.start_task(task.id, task, affected_wallets, channel).await
+                let res = self.start_task(task.id, task, affected_wallets, channel).await;
+                self.running_tasks.fetch_sub(1, Ordering::SeqCst);
+                res
             },
             TaskDriverJob::Notify { task_id, channel } => {
                 self.handle_notification_request(task_id, channel).await
             },
+            TaskDriverJob::Cancel { task_id } => self.handle_cancel(task_id),
+            TaskDriverJob::Drain { channel } => self.handle_drain(channel).await,
         }
     }
 
+    /// Handle a cancellation request
+    ///
+    /// The task observes the cancellation at its next step boundary, see
+    /// `run_task_to_completion`
+    #[instrument(skip_all, fields(task_id = %task_id))]
+    fn handle_cancel(&self, task_id: TaskIdentifier) -> Result<(), TaskDriverError> {
+        let cancellations = self.task_cancellations.read().expect("poisoned");
+        match cancellations.get(&task_id) {
+            Some(token) => {
+                info!("cancelling task {task_id:?}");
+                token.cancel();
+            },
+            None => warn!("got cancellation request for task {task_id:?} that is not running"),
+        }
+
+        Ok(())
+    }
+
+    /// Whether the driver is draining
+    fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Handle a drain request
+    ///
+    /// Stops new tasks from starting, then waits for all running tasks to
+    /// complete or halt before notifying the caller
+    #[instrument(skip_all)]
+    async fn handle_drain(&self, channel: OneshotSender<()>) -> Result<(), TaskDriverError> {
+        info!("draining task driver");
+        self.draining.store(true, Ordering::SeqCst);
+
+        // Pairs with the ordering in `handle_job`, a task either counted itself as
+        // running before this load or sees the drain flag and does not start
+        let poll_interval = Duration::from_millis(DRAIN_POLL_INTERVAL_MS);
+        while self.running_tasks.load(Ordering::SeqCst) > 0 {
+            tokio::time::sleep(poll_interval).await;
+        }
+
+        info!("task driver drained");
+        let _ = channel.send(());
+        Ok(())
+    }
+
     /// Handle a notification request
     #[instrument(skip_all, err, fields(task_id = %task_id))]
     async fn handle_notification_request(
@@ -223,46 +317,84 @@ impl TaskExecutor {
         }
 
         // Construct the task from the descriptor
+        let created_at = task.created_at;
         let res = match task.descriptor {
             TaskDescriptor::NewWallet(desc) => {
-                self.start_task_helper::<NewWalletTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<NewWalletTask>(id, created_at, desc, affected_wallets)
+                    .await
             },
             TaskDescriptor::LookupWallet(desc) => {
-                self.start_task_helper::<LookupWalletTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<LookupWalletTask>(id, created_at, desc, affected_wallets)
+                    .await
             },
             TaskDescriptor::RefreshWallet(desc) => {
-                self.start_task_helper::<RefreshWalletTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<RefreshWalletTask>(id, created_at, desc, affected_wallets)
+                    .await
             },
             TaskDescriptor::OfflineFee(desc) => {
-                self.start_task_helper::<PayOfflineFeeTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<PayOfflineFeeTask>(id, created_at, desc, affected_wallets)
+                    .await
+            },
+            TaskDescriptor::AllOfflineFees(desc) => {
+                self.start_task_helper::<PayAllOfflineFeesTask>(
+                    id,
+                    created_at,
+                    desc,
+                    affected_wallets,
+                )
+                .await
             },
             TaskDescriptor::RelayerFee(desc) => {
-                self.start_task_helper::<PayRelayerFeeTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<PayRelayerFeeTask>(id, created_at, desc, affected_wallets)
+                    .await
             },
             TaskDescriptor::RedeemFee(desc) => {
-                self.start_task_helper::<RedeemFeeTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<RedeemFeeTask>(id, created_at, desc, affected_wallets)
+                    .await
             },
             TaskDescriptor::UpdateWallet(desc) => {
-                self.start_task_helper::<UpdateWalletTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<UpdateWalletTask>(id, created_at, desc, affected_wallets)
+                    .await
             },
             TaskDescriptor::SettleMatch(desc) => {
-                self.start_task_helper::<SettleMatchTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<SettleMatchTask>(id, created_at, desc, affected_wallets)
+                    .await
             },
             TaskDescriptor::SettleMatchInternal(desc) => {
-                self.start_task_helper::<SettleMatchInternalTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<SettleMatchInternalTask>(
+                    id,
+                    created_at,
+                    desc,
+                    affected_wallets,
+                )
+                .await
             },
             TaskDescriptor::UpdateMerkleProof(desc) => {
-                self.start_task_helper::<UpdateMerkleProofTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<UpdateMerkleProofTask>(
+                    id,
+                    created_at,
+                    desc,
+                    affected_wallets,
+                )
+                .await
             },
             TaskDescriptor::NodeStartup(desc) => {
-                self.start_task_helper::<NodeStartupTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<NodeStartupTask>(id, created_at, desc, affected_wallets)
+                    .await
             },
             TaskDescriptor::SettleExternalMatch(desc) => {
-                self.start_task_helper::<SettleMatchExternalTask>(id, desc, affected_wallets).await
+                self.start_task_helper::<SettleMatchExternalTask>(
+                    id,
+                    created_at,
+                    desc,
+                    affected_wallets,
+                )
+                .await
             },
             TaskDescriptor::SettleMalleableExternalMatch(desc) => {
                 self.start_task_helper::<SettleMalleableExternalMatchTask>(
                     id,
+                    created_at,
                     desc,
                     affected_wallets,
                 )
@@ -270,6 +402,9 @@ impl TaskExecutor {
             },
         };
 
+        // The task is no longer running, so it may no longer be cancelled
+        self.task_cancellations.write().expect("poisoned").remove(&id);
+
         // Notify any listeners that the task has completed
         let str_res = res.clone().map_err(|e| e.to_string());
         for sender in self.task_notifications.write().unwrap().remove(&id).unwrap_or_default() {
@@ -284,15 +419,21 @@ impl TaskExecutor {
     async fn start_task_helper<T: Task>(
         &self,
         id: TaskIdentifier,
+        created_at: u64,
         descriptor: T::Descriptor,
         affected_wallets: Vec<WalletIdentifier>,
     ) -> Result<(), TaskDriverError> {
-        // Collect the arguments then spawn
-        let ctx = self.task_context();
+        // Collect the arguments then spawn, giving the task its own cancellation
+        // token so that it may be cancelled independently of other tasks
+        let mut ctx = self.task_context();
+        ctx.task_id = Some(id);
+        ctx.cancellation = ctx.cancellation.child_token();
+        self.task_cancellations.write().expect("poisoned").insert(id, ctx.cancellation.clone());
         let args = self.runtime_config;
 
         // Create and run the task
-        let task_res = RunnableTask::<T>::from_descriptor(id, descriptor, ctx).await;
+        let task_res =
+            RunnableTask::<T>::from_descriptor(id, descriptor, affected_wallets, ctx).await;
 
         // If we fail to create the task, pop it from the queue so it isn't stuck there
         // in a pending state. For immediate tasks, this is handled by queue
@@ -306,28 +447,56 @@ impl TaskExecutor {
         }
 
         let mut task = task_res.unwrap();
-        let res = Self::run_task_to_completion(&mut task, args).await;
+        task.record_pending_duration(created_at);
+        let span = task.span();
+        let res = Self::run_task_to_completion(&mut task, args, &self.draining)
+            .instrument(span.clone())
+            .await;
+
+        // A halted task is left in its queue so that it may be resumed, skip cleanup
+        if let Err(TaskDriverError::Halted) = res {
+            info!("halted task {id:?} in state {}", task.state());
+            return res;
+        }
 
         // Cleanup
-        let cleanup_res = task.cleanup(res.is_ok(), affected_wallets).await;
+        let cleanup_res = task.cleanup(res.is_ok()).instrument(span).await;
         res.and(cleanup_res)
     }
 
     /// Run a task to completion
+    ///
+    /// If the driver begins draining, a task that has not yet reached its
+    /// commit point is halted at the next step boundary. Likewise, a cancelled
+    /// task that has not yet reached its commit point fails at the next step
+    /// boundary, cancellation past the commit point is ignored
     async fn run_task_to_completion<T: Task>(
         task: &mut RunnableTask<T>,
         args: RuntimeArgs,
+        draining: &AtomicBool,
     ) -> Result<(), TaskDriverError> {
         let id = task.id();
         let backoff_ceiling = Duration::from_millis(args.backoff_ceiling_ms);
+        let step_timeout = args.step_timeout_ms.map(Duration::from_millis);
 
         // Run each step individually and update the state after each step
         'outer: while !task.completed() {
+            if draining.load(Ordering::Acquire) && !task.state().committed() {
+                return Err(TaskDriverError::Halted);
+            }
+
+            if task.cancelled() && !task.state().committed() {
+                let state = task.state().to_string();
+                info!("cancelled task {id:?} in state {state}");
+                task.record_failure(&TaskCancelledError { state });
+                return Err(TaskDriverError::Cancelled);
+            }
+
             // Take a step
             let mut retries = args.n_retries;
             let mut curr_backoff = Duration::from_millis(args.initial_backoff_ms);
 
-            while !task.step().await? {
+            while !task.step(step_timeout).await? {
                 retries -= 1;
                 if retries == 0 {
                     error!("retries exceeded... task failed");
@@ -346,3 +515,219 @@ impl TaskExecutor {
         if task.completed() { Ok(()) } else { Err(TaskDriverError::TaskFailed) }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicBool;
+
+    use async_trait::async_trait;
+    use metrics_util::debugging::DebuggingRecorder;
+    use renegade_metrics::labels::{TASK_ERROR_KIND_METRIC_TAG, TASK_FAILED_METRIC};
+    use state::test_helpers::mock_state;
+    use tokio_util::sync::CancellationToken;
+    use uuid::Uuid;
+
+    use super::{RuntimeArgs, TaskExecutor};
+    use crate::{
+        error::{TaskCancelledError, TaskDriverError},
+        running_task::RunnableTask,
+        task_state::StateWrapper,
+        tasks::pay_offline_fee::{PayOfflineFeeTaskError, PayOfflineFeeTaskState},
+        traits::{Task, TaskContext, TaskError},
+    };
+
+    /// The error returned when a mock task is constructed from a descriptor
+    const ERR_MOCK_CONSTRUCTED_DIRECTLY: &str = "mock tasks are constructed directly";
+
+    /// A task that steps through the fee payment states, cancelling itself
+    /// when it steps out of the given state
+    struct MockTask {
+        /// The current state of the task
+        state: PayOfflineFeeTaskState,
+        /// The state in which the task cancels itself
+        cancel_in: PayOfflineFeeTaskState,
+        /// The state in which the task's step fails, if any
+        fail_in: Option<PayOfflineFeeTaskState>,
+        /// The state from which the task reports itself completed
+        completed_in: PayOfflineFeeTaskState,
+        /// The task's cancellation token
+        cancellation: CancellationToken,
+    }
+
+    impl MockTask {
+        /// Create a mock task in the `Pending` state that neither fails nor
+        /// cancels itself, and completes in the `Completed` state
+        fn mock() -> Self {
+            Self {
+                state: PayOfflineFeeTaskState::Pending,
+                cancel_in: PayOfflineFeeTaskState::Completed,
+                fail_in: None,
+                completed_in: PayOfflineFeeTaskState::Completed,
+                cancellation: CancellationToken::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Task for MockTask {
+        type Descriptor = ();
+        type State = PayOfflineFeeTaskState;
+        type Error = PayOfflineFeeTaskError;
+
+        async fn new(_descriptor: (), _ctx: TaskContext) -> Result<Self, Self::Error> {
+            Err(PayOfflineFeeTaskError::State(ERR_MOCK_CONSTRUCTED_DIRECTLY.to_string()))
+        }
+
+        fn state(&self) -> Self::State {
+            self.state.clone()
+        }
+
+        fn completed(&self) -> bool {
+            self.state >= self.completed_in
+        }
+
+        fn name(&self) -> String {
+            "mock-task".to_string()
+        }
+
+        fn bypass_task_queue(&self) -> bool {
+            true
+        }
+
+        async fn step(&mut self) -> Result<(), Self::Error> {
+            if self.state == self.cancel_in {
+                self.cancellation.cancel();
+            }
+            if self.fail_in.as_ref() == Some(&self.state) {
+                return Err(PayOfflineFeeTaskError::State(format!("failed in {}", self.state)));
+            }
+
+            self.state = match self.state {
+                PayOfflineFeeTaskState::Pending => PayOfflineFeeTaskState::ProvingPayment,
+                PayOfflineFeeTaskState::ProvingPayment => PayOfflineFeeTaskState::SubmittingPayment,
+                PayOfflineFeeTaskState::SubmittingPayment => PayOfflineFeeTaskState::FindingOpening,
+                PayOfflineFeeTaskState::FindingOpening => {
+                    PayOfflineFeeTaskState::UpdatingValidityProofs
+                },
+                _ => PayOfflineFeeTaskState::Completed,
+            };
+            Ok(())
+        }
+    }
+
+    /// Run a mock task that cancels itself in the given state, returning the
+    /// driver's result and the state the task stopped in
+    async fn run_cancelled_task(
+        cancel_in: PayOfflineFeeTaskState,
+    ) -> (Result<(), TaskDriverError>, StateWrapper) {
+        let cancellation = CancellationToken::new();
+        let task = MockTask { cancel_in, cancellation: cancellation.clone(), ..MockTask::mock() };
+        let mut task =
+            RunnableTask::new(Uuid::new_v4(), task, vec![], mock_state().await, cancellation);
+
+        let draining = AtomicBool::new(false);
+        let res =
+            TaskExecutor::run_task_to_completion(&mut task, RuntimeArgs::default(), &draining)
+                .await;
+        (res, task.state())
+    }
+
+    /// Record a failure of a mock task, returning the error kinds the failure
+    /// metric was labeled with
+    async fn recorded_error_kinds<E: TaskError>(e: &E) -> Vec<String> {
+        let cancellation = CancellationToken::new();
+        let task = RunnableTask::new(
+            Uuid::new_v4(),
+            MockTask::mock(),
+            vec![],
+            mock_state().await,
+            cancellation,
+        );
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || task.record_failure(e));
+
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, ..)| key.key().name() == TASK_FAILED_METRIC)
+            .flat_map(|(key, ..)| {
+                key.key()
+                    .labels()
+                    .filter(|label| label.key() == TASK_ERROR_KIND_METRIC_TAG)
+                    .map(|label| label.value().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Tests that cancelling a task before its commit point stops it at the
+    /// next step boundary
+    #[tokio::test]
+    async fn test_cancel_before_commit_point() {
+        let (res, state) = run_cancelled_task(PayOfflineFeeTaskState::Pending).await;
+        assert!(matches!(res, Err(TaskDriverError::Cancelled)));
+        assert!(matches!(
+            state,
+            StateWrapper::PayOfflineFee(PayOfflineFeeTaskState::ProvingPayment)
+        ));
+    }
+
+    /// Tests that cancelling a task past its commit point is ignored
+    #[tokio::test]
+    async fn test_cancel_after_commit_point() {
+        let (res, state) = run_cancelled_task(PayOfflineFeeTaskState::SubmittingPayment).await;
+        assert!(res.is_ok());
+        assert!(matches!(state, StateWrapper::PayOfflineFee(PayOfflineFeeTaskState::Completed)));
+    }
+
+    /// Tests that task failures are labeled with the error's kind
+    #[tokio::test]
+    async fn test_record_failure_error_kind() {
+        let err = PayOfflineFeeTaskError::ProverUnavailable("channel closed".to_string());
+        assert_eq!(recorded_error_kinds(&err).await, vec!["proof"]);
+
+        let err = TaskCancelledError { state: PayOfflineFeeTaskState::Pending.to_string() };
+        assert_eq!(recorded_error_kinds(&err).await, vec!["cancelled"]);
+    }
+
+    /// Tests that `run_until` stops once the task reaches the target state
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn test_run_until__target() {
+        let mut task = MockTask::mock();
+        task.run_until(PayOfflineFeeTaskState::SubmittingPayment).await.unwrap();
+        assert_eq!(task.state, PayOfflineFeeTaskState::SubmittingPayment);
+
+        // A task already at the target is not stepped
+        task.run_until(PayOfflineFeeTaskState::ProvingPayment).await.unwrap();
+        assert_eq!(task.state, PayOfflineFeeTaskState::SubmittingPayment);
+    }
+
+    /// Tests that `run_until` returns the first step error without stepping
+    /// further
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn test_run_until__step_error() {
+        let fail_in = Some(PayOfflineFeeTaskState::ProvingPayment);
+        let mut task = MockTask { fail_in, ..MockTask::mock() };
+
+        let res = task.run_until(PayOfflineFeeTaskState::Completed).await;
+        assert!(matches!(res, Err(PayOfflineFeeTaskError::State(_))));
+        assert_eq!(task.state, PayOfflineFeeTaskState::ProvingPayment);
+    }
+
+    /// Tests that `run_until` stops once the task completes, even if the target
+    /// was never reached
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn test_run_until__completed() {
+        let completed_in = PayOfflineFeeTaskState::FindingOpening;
+        let mut task = MockTask { completed_in, ..MockTask::mock() };
+
+        task.run_until(PayOfflineFeeTaskState::Completed).await.unwrap();
+        assert_eq!(task.state, PayOfflineFeeTaskState::FindingOpening);
+    }
+}
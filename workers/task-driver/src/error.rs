@@ -1,8 +1,10 @@
 //! Error types for the task driver
 
+use std::time::Duration;
+
 use state::error::StateError;
 
-use crate::traits::TaskError;
+use crate::traits::{TaskError, TaskErrorKind};
 
 /// The error type emitted by the task driver
 #[derive(Clone, Debug, thiserror::Error)]
@@ -16,6 +18,13 @@ pub enum TaskDriverError {
     /// A task was preempted while running
     #[error("task was preempted while running")]
     Preempted,
+    /// A task was halted before its commit point because the driver is
+    /// draining
+    #[error("task halted before its commit point for shutdown")]
+    Halted,
+    /// A task was cancelled before its commit point
+    #[error("task cancelled before its commit point")]
+    Cancelled,
     /// An error querying global state
     #[error("state error: {0}")]
     State(String),
@@ -24,6 +33,56 @@ pub enum TaskDriverError {
     TaskError(String),
 }
 
+/// The error emitted when a task step exceeds its timeout
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("task step timed out after {timeout:?} in state {state}")]
+pub struct TaskTimeoutError {
+    /// The state the task was in when the step timed out
+    pub state: String,
+    /// The timeout that was exceeded
+    pub timeout: Duration,
+    /// Whether the task had passed its commit point when the step began
+    pub committed: bool,
+}
+
+impl TaskError for TaskTimeoutError {
+    fn retryable(&self) -> bool {
+        // Retrying a step past the commit point is unsafe, it may have partially
+        // executed before being abandoned
+        !self.committed
+    }
+
+    fn variant_name(&self) -> &'static str {
+        "Timeout"
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        TaskErrorKind::Timeout
+    }
+}
+
+/// The error recorded when a task is cancelled before its commit point
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("task cancelled in state {state}")]
+pub struct TaskCancelledError {
+    /// The state the task was in when it was cancelled
+    pub state: String,
+}
+
+impl TaskError for TaskCancelledError {
+    fn retryable(&self) -> bool {
+        false
+    }
+
+    fn variant_name(&self) -> &'static str {
+        "Cancelled"
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        TaskErrorKind::Cancelled
+    }
+}
+
 impl From<StateError> for TaskDriverError {
     fn from(e: StateError) -> Self {
         TaskDriverError::State(e.to_string())
@@ -1,14 +1,22 @@
 //! Encapsulates the running task's bookkeeping structure to simplify the driver
 //! logic
 
+use std::time::Duration;
+
 use common::types::{tasks::TaskIdentifier, wallet::WalletIdentifier};
+use renegade_metrics::labels::{
+    TASK_COMPLETED_METRIC, TASK_ERROR_KIND_METRIC_TAG, TASK_ERROR_METRIC_TAG, TASK_FAILED_METRIC,
+    TASK_NAME_METRIC_TAG, TASK_PENDING_DURATION_METRIC,
+};
 use state::{State, error::StateError};
-use tracing::{error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{Span, error, info, warn};
+use util::get_current_time_millis;
 
 use crate::{
-    error::TaskDriverError,
+    error::{TaskDriverError, TaskTimeoutError},
     task_state::StateWrapper,
-    traits::{Task, TaskContext, TaskError},
+    traits::{Task, TaskContext, TaskError, TaskState},
 };
 
 // ----------------
@@ -23,26 +31,38 @@ pub struct RunnableTask<T: Task> {
     task_id: TaskIdentifier,
     /// The underlying task
     task: T,
+    /// The wallets affected by the task
+    affected_wallets: Vec<WalletIdentifier>,
     /// A handle to the relayer-global state
     state: State,
+    /// The token used to cancel the task
+    cancellation: CancellationToken,
 }
 
 impl<T: Task> RunnableTask<T> {
     /// Creates a new running task from the given task and state
-    pub fn new(task_id: TaskIdentifier, task: T, state: State) -> Self {
-        Self { task_id, task, state }
+    pub fn new(
+        task_id: TaskIdentifier,
+        task: T,
+        affected_wallets: Vec<WalletIdentifier>,
+        state: State,
+        cancellation: CancellationToken,
+    ) -> Self {
+        Self { task_id, task, affected_wallets, state, cancellation }
     }
 
     /// Create a runnable from the given descriptor and context
     pub async fn from_descriptor(
         id: TaskIdentifier,
         descriptor: T::Descriptor,
+        affected_wallets: Vec<WalletIdentifier>,
         ctx: TaskContext,
     ) -> Result<Self, TaskDriverError> {
         let state = ctx.state.clone();
+        let cancellation = ctx.cancellation.clone();
         let task = T::new(descriptor, ctx).await?;
 
-        Ok(Self::new(id, task, state))
+        Ok(Self::new(id, task, affected_wallets, state, cancellation))
     }
 
     /// The ID of the underlying task
@@ -50,16 +70,39 @@ impl<T: Task> RunnableTask<T> {
         self.task_id
     }
 
+    /// Create the span under which the task runs, see `TaskContext::task_span`
+    pub fn span(&self) -> Span {
+        TaskContext::task_span(&self.task.name(), self.affected_wallets.first())
+    }
+
     /// Whether the underlying task completed
     pub fn completed(&self) -> bool {
         self.task.completed()
     }
 
+    /// Whether the task has been cancelled
+    ///
+    /// The driver only acts on this before the task's commit point
+    pub fn cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
     /// Returns the state of the underlying task
     pub fn state(&self) -> StateWrapper {
         self.task.state().into()
     }
 
+    /// Record the time the task spent pending, i.e. from its creation to the
+    /// driver beginning to step it
+    ///
+    /// `created_at` is the creation timestamp of the task in milliseconds
+    pub fn record_pending_duration(&self, created_at: u64) {
+        let pending_ms = get_current_time_millis().saturating_sub(created_at);
+        let pending_secs = pending_ms as f64 / 1000.;
+        metrics::histogram!(TASK_PENDING_DURATION_METRIC, TASK_NAME_METRIC_TAG => self.task.name())
+            .record(pending_secs);
+    }
+
     /// `true` if the task does not need to update the task queue during state
     /// transitions or cleanup
     pub fn bypass_task_queue(&self) -> bool {
@@ -72,18 +115,74 @@ impl<T: Task> RunnableTask<T> {
     ///
     /// This includes a state transition in the consensus engine, if this method
     /// returns an error the driver should abort the task
-    pub async fn step(&mut self) -> Result<bool, TaskDriverError> {
+    ///
+    /// The step is abandoned if it exceeds the current state's timeout, or
+    /// `default_timeout` if the state does not define one
+    pub async fn step(
+        &mut self,
+        default_timeout: Option<Duration>,
+    ) -> Result<bool, TaskDriverError> {
+        let state = self.task.state();
+        let step_res = match state.step_timeout().or(default_timeout) {
+            Some(timeout) => match tokio::time::timeout(timeout, self.task.timed_step()).await {
+                Ok(res) => res,
+                Err(_) => {
+                    let committed = state.committed();
+                    let e = TaskTimeoutError { state: state.to_string(), timeout, committed };
+                    error!("error executing task step: {e}");
+                    self.record_failure(&e);
+                    return if e.retryable() { Ok(false) } else { Err(e.into()) };
+                },
+            },
+            None => self.task.timed_step().await,
+        };
+
         // Handle a failed step
-        if let Err(e) = self.task.step().await {
+        if let Err(e) = step_res {
             error!("error executing task step: {e}");
+            self.record_failure(&e);
             return if e.retryable() { Ok(false) } else { Err(e.into()) };
         };
 
         // Successful step, attempt to transition the state
+        if state == T::State::commit_point() {
+            self.log_commit(&state);
+        }
+
         self.transition_state().await?;
+        if self.task.completed() {
+            metrics::counter!(TASK_COMPLETED_METRIC, TASK_NAME_METRIC_TAG => self.task.name())
+                .increment(1);
+        }
+
         Ok(true)
     }
 
+    /// Record a task failure in the task failure metrics, labeled by the
+    /// error's variant and kind
+    pub fn record_failure<E: TaskError>(&self, e: &E) {
+        metrics::counter!(
+            TASK_FAILED_METRIC,
+            TASK_NAME_METRIC_TAG => self.task.name(),
+            TASK_ERROR_METRIC_TAG => e.variant_name(),
+            TASK_ERROR_KIND_METRIC_TAG => e.kind().as_str(),
+        )
+        .increment(1);
+    }
+
+    /// Record that the task has stepped past its commit point, beyond which its
+    /// effects may be partially applied and the task is no longer retried
+    /// from scratch
+    fn log_commit(&self, commit_state: &T::State) {
+        warn!(
+            task = self.task.name(),
+            task_id = %self.task_id,
+            wallet_id = ?self.affected_wallets,
+            state = %commit_state,
+            "task committed in state {commit_state}",
+        );
+    }
+
     /// Attempts to transition the state of the underlying task in the consensus
     /// engine. If this method fails the driver should abort the task
     pub async fn transition_state(&self) -> Result<(), StateError> {
@@ -110,11 +209,7 @@ impl<T: Task> RunnableTask<T> {
     }
 
     /// Cleanup the underlying task
-    pub async fn cleanup(
-        &mut self,
-        success: bool,
-        affected_wallets: Vec<WalletIdentifier>,
-    ) -> Result<(), TaskDriverError> {
+    pub async fn cleanup(&mut self, success: bool) -> Result<(), TaskDriverError> {
         // Do not propagate errors from cleanup, continue to cleanup
         if let Err(e) = self.task.cleanup().await {
             error!("error cleaning up task: {e:?}");
@@ -133,7 +228,7 @@ impl<T: Task> RunnableTask<T> {
         // Note: it's important to do this after popping / resuming above, as those
         // code paths will clear task queues in the case of a failure.
         if !success && self.state().committed() {
-            for wallet_id in affected_wallets {
+            for &wallet_id in &self.affected_wallets {
                 let task_id = self.state.append_wallet_refresh_task(wallet_id).await?;
                 info!("enqueued wallet refresh task ({task_id}) for {wallet_id}");
             }
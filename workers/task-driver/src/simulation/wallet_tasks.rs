@@ -3,8 +3,9 @@
 use ark_mpc::PARTY0;
 use common::types::{
     tasks::{
-        NewWalletTaskDescriptor, PayOfflineFeeTaskDescriptor, SettleMatchInternalTaskDescriptor,
-        SettleMatchTaskDescriptor, TaskDescriptor, UpdateWalletTaskDescriptor,
+        NewWalletTaskDescriptor, PayAllOfflineFeesTaskDescriptor, PayOfflineFeeTaskDescriptor,
+        SettleMatchInternalTaskDescriptor, SettleMatchTaskDescriptor, TaskDescriptor,
+        UpdateWalletTaskDescriptor,
     },
     wallet::Wallet,
 };
@@ -60,6 +61,9 @@ fn simulate_single_wallet_task(
         TaskDescriptor::OfflineFee(desc) => {
             simulate_offline_fee_payment(wallet, &desc)?;
         },
+        TaskDescriptor::AllOfflineFees(desc) => {
+            simulate_all_offline_fee_payments(wallet, &desc)?;
+        },
 
         // Ignore non-wallet tasks
         _ => (),
@@ -185,3 +189,33 @@ fn simulate_offline_fee_payment(
 
     Ok(())
 }
+
+/// Simulate a `PayAllOfflineFees` task applied to a wallet
+fn simulate_all_offline_fee_payments(
+    wallet: &mut Wallet,
+    desc: &PayAllOfflineFeesTaskDescriptor,
+) -> Result<(), TaskSimulationError> {
+    if desc.wallet_id != wallet.wallet_id {
+        return Err(TaskSimulationError::InvalidTask(ERR_INVALID_WALLET_ID));
+    }
+
+    // The task reblinds the wallet once for each fee it settles
+    let mut n_settled = 0;
+    for balance in wallet.balances.iter_mut_values() {
+        if balance.relayer_fee_balance > 0 {
+            balance.relayer_fee_balance = 0;
+            n_settled += 1;
+        }
+
+        if balance.protocol_fee_balance > 0 {
+            balance.protocol_fee_balance = 0;
+            n_settled += 1;
+        }
+    }
+
+    for _ in 0..n_settled {
+        wallet.reblind_wallet();
+    }
+
+    Ok(())
+}
@@ -8,9 +8,9 @@ use serde::Serialize;
 use crate::{
     tasks::{
         create_new_wallet::NewWalletTaskState, lookup_wallet::LookupWalletTaskState,
-        node_startup::NodeStartupTaskState, pay_offline_fee::PayOfflineFeeTaskState,
-        pay_relayer_fee::PayRelayerFeeTaskState, redeem_fee::RedeemFeeTaskState,
-        refresh_wallet::RefreshWalletTaskState,
+        node_startup::NodeStartupTaskState, pay_all_offline_fees::PayAllOfflineFeesTaskState,
+        pay_offline_fee::PayOfflineFeeTaskState, pay_relayer_fee::PayRelayerFeeTaskState,
+        redeem_fee::RedeemFeeTaskState, refresh_wallet::RefreshWalletTaskState,
         settle_malleable_external_match::SettleMalleableExternalMatchTaskState,
         settle_match::SettleMatchTaskState, settle_match_external::SettleMatchExternalTaskState,
         settle_match_internal::SettleMatchInternalTaskState,
@@ -36,6 +36,8 @@ pub enum StateWrapper {
     NewWallet(NewWalletTaskState),
     /// The state object for the pay protocol fee task
     PayOfflineFee(PayOfflineFeeTaskState),
+    /// The state object for the pay all offline fees task
+    PayAllOfflineFees(PayAllOfflineFeesTaskState),
     /// The state object for the pay relayer fee task
     PayRelayerFee(PayRelayerFeeTaskState),
     /// The state object for the redeem relayer fees task
@@ -64,6 +66,7 @@ impl StateWrapper {
             StateWrapper::RefreshWallet(state) => state.committed(),
             StateWrapper::NewWallet(state) => state.committed(),
             StateWrapper::PayOfflineFee(state) => state.committed(),
+            StateWrapper::PayAllOfflineFees(state) => state.committed(),
             StateWrapper::PayRelayerFee(state) => state.committed(),
             StateWrapper::RedeemFee(state) => state.committed(),
             StateWrapper::SettleMatch(state) => state.committed(),
@@ -84,6 +87,9 @@ impl StateWrapper {
             StateWrapper::RefreshWallet(state) => state == &RefreshWalletTaskState::commit_point(),
             StateWrapper::NewWallet(state) => state == &NewWalletTaskState::commit_point(),
             StateWrapper::PayOfflineFee(state) => state == &PayOfflineFeeTaskState::commit_point(),
+            StateWrapper::PayAllOfflineFees(state) => {
+                state == &PayAllOfflineFeesTaskState::commit_point()
+            },
             StateWrapper::PayRelayerFee(state) => state == &PayRelayerFeeTaskState::commit_point(),
             StateWrapper::RedeemFee(state) => state == &RedeemFeeTaskState::commit_point(),
             StateWrapper::SettleMatch(state) => state == &SettleMatchTaskState::commit_point(),
@@ -111,6 +117,7 @@ impl StateWrapper {
             StateWrapper::RefreshWallet(state) => state.completed(),
             StateWrapper::NewWallet(state) => state.completed(),
             StateWrapper::PayOfflineFee(state) => state.completed(),
+            StateWrapper::PayAllOfflineFees(state) => state.completed(),
             StateWrapper::PayRelayerFee(state) => state.completed(),
             StateWrapper::RedeemFee(state) => state.completed(),
             StateWrapper::SettleMatch(state) => state.completed(),
@@ -131,6 +138,7 @@ impl Display for StateWrapper {
             StateWrapper::RefreshWallet(state) => state.to_string(),
             StateWrapper::NewWallet(state) => state.to_string(),
             StateWrapper::PayOfflineFee(state) => state.to_string(),
+            StateWrapper::PayAllOfflineFees(state) => state.to_string(),
             StateWrapper::PayRelayerFee(state) => state.to_string(),
             StateWrapper::RedeemFee(state) => state.to_string(),
             StateWrapper::SettleMatch(state) => state.to_string(),
@@ -35,7 +35,7 @@ use tracing::instrument;
 use util::err_str;
 
 use crate::task_state::StateWrapper;
-use crate::traits::{Task, TaskContext, TaskError, TaskState};
+use crate::traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState};
 use crate::utils::validity_proofs::{enqueue_proof_job, find_merkle_path_with_tx};
 
 /// The task name to display when logging
@@ -137,6 +137,27 @@ impl TaskError for NewWalletTaskError {
                 | NewWalletTaskError::State(_)
         )
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::InvalidShares(_) => "InvalidShares",
+            Self::ProofGeneration(_) => "ProofGeneration",
+            Self::Darkpool(_) => "Darkpool",
+            Self::SendMessage(_) => "SendMessage",
+            Self::Setup(_) => "Setup",
+            Self::State(_) => "State",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::InvalidShares(_) | Self::Setup(_) => TaskErrorKind::Validation,
+            Self::ProofGeneration(_) => TaskErrorKind::Proof,
+            Self::Darkpool(_) => TaskErrorKind::Darkpool,
+            Self::SendMessage(_) => TaskErrorKind::Network,
+            Self::State(_) => TaskErrorKind::State,
+        }
+    }
 }
 
 impl Display for NewWalletTaskError {
@@ -287,7 +308,8 @@ impl NewWalletTask {
     async fn find_merkle_path(&self) -> Result<(), NewWalletTaskError> {
         // Find the authentication path of the wallet's private shares
         let tx = self.tx.as_ref().unwrap();
-        let wallet_auth_path = find_merkle_path_with_tx(&self.wallet, &self.darkpool_client, tx)?;
+        let wallet_auth_path =
+            find_merkle_path_with_tx(&self.wallet, &self.darkpool_client, tx, None).await?;
 
         // Index the wallet in the global state
         let mut wallet = self.wallet.clone();
@@ -21,7 +21,7 @@ use tracing::instrument;
 
 use crate::{
     task_state::StateWrapper,
-    traits::{Task, TaskContext, TaskError, TaskState},
+    traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState},
     utils::{
         find_wallet::{find_latest_wallet_tx, gen_private_shares},
         validity_proofs::{find_merkle_path, update_wallet_validity_proofs},
@@ -115,6 +115,24 @@ impl TaskError for LookupWalletTaskError {
                 | LookupWalletTaskError::State(_)
         )
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "NotFound",
+            Self::ProofGeneration(_) => "ProofGeneration",
+            Self::Darkpool(_) => "Darkpool",
+            Self::State(_) => "State",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::NotFound(_) => TaskErrorKind::Validation,
+            Self::ProofGeneration(_) => TaskErrorKind::Proof,
+            Self::Darkpool(_) => TaskErrorKind::Darkpool,
+            Self::State(_) => TaskErrorKind::State,
+        }
+    }
 }
 
 impl Display for LookupWalletTaskError {
@@ -1,8 +1,13 @@
 //! Task definitions run by the driver
 
+use std::time::Duration;
+
+use crate::utils::retry::RetryConfig;
+
 pub mod create_new_wallet;
 pub mod lookup_wallet;
 pub mod node_startup;
+pub mod pay_all_offline_fees;
 pub mod pay_offline_fee;
 pub mod pay_relayer_fee;
 pub mod redeem_fee;
@@ -24,3 +29,9 @@ pub(crate) const ERR_NO_MERKLE_PROOF: &str = "no merkle proof found for wallet";
 pub(crate) const ERR_NO_VALIDITY_PROOF: &str = "no validity proofs found for order";
 /// Error message emitted when awaiting a proof fails
 pub(crate) const ERR_AWAITING_PROOF: &str = "error awaiting proof";
+
+/// The config for polling the darkpool for a wallet's Merkle opening after an
+/// offline fee payment, when it cannot be parsed from the settlement
+/// transaction
+pub(crate) const MERKLE_POLL_CONFIG: RetryConfig =
+    RetryConfig::fixed_interval(10 /* max_attempts */, Duration::from_secs(2));
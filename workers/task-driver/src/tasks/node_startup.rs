@@ -39,7 +39,7 @@ use crate::{
     await_task,
     state_migration::remove_phantom_orders,
     task_state::StateWrapper,
-    traits::{Task, TaskContext, TaskError, TaskState},
+    traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState},
     utils::ERR_WALLET_NOT_FOUND,
 };
 
@@ -147,6 +147,26 @@ impl TaskError for NodeStartupTaskError {
     fn retryable(&self) -> bool {
         false
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Darkpool(_) => "Darkpool",
+            Self::DeriveWallet(_) => "DeriveWallet",
+            Self::Enqueue(_) => "Enqueue",
+            Self::FetchConstants(_) => "FetchConstants",
+            Self::Setup(_) => "Setup",
+            Self::State(_) => "State",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::Darkpool(_) | Self::FetchConstants(_) => TaskErrorKind::Darkpool,
+            Self::DeriveWallet(_) | Self::Setup(_) => TaskErrorKind::Validation,
+            Self::Enqueue(_) => TaskErrorKind::Network,
+            Self::State(_) => TaskErrorKind::State,
+        }
+    }
 }
 
 impl Display for NodeStartupTaskError {
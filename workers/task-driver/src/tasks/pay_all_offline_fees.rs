@@ -0,0 +1,481 @@
+//! The `PayAllOfflineFees` task settles every outstanding fee on a wallet,
+//! one balance at a time, and updates the wallet's validity proofs once at
+//! the end
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+use alloy::rpc::types::TransactionReceipt;
+use async_trait::async_trait;
+use circuit_types::{note::Note, wallet::Nullifier};
+use common::types::{
+    proof_bundles::OfflineFeeSettlementBundle,
+    tasks::{PayAllOfflineFeesTaskDescriptor, PayOfflineFeeTaskDescriptor},
+    wallet::Wallet,
+};
+use darkpool_client::{DarkpoolClient, errors::DarkpoolClientError};
+use job_types::{
+    network_manager::NetworkManagerQueue,
+    proof_manager::{ProofJob, ProofManagerQueue},
+};
+use serde::Serialize;
+use state::{State, error::StateError};
+use tracing::{info, instrument};
+use util::err_str;
+
+use crate::{
+    task_state::StateWrapper,
+    traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState},
+    utils::{
+        retry::RetryConfig,
+        validity_proofs::{
+            enqueue_proof_job, enqueue_relayer_redeem_job, find_merkle_path_with_tx,
+            update_wallet_validity_proofs,
+        },
+    },
+};
+
+use super::{
+    ERR_WALLET_MISSING, MERKLE_POLL_CONFIG,
+    pay_offline_fee::{PayOfflineFeeTask, PayOfflineFeeTaskError},
+};
+
+/// The name of the task
+const TASK_NAME: &str = "pay-all-offline-fees";
+
+// --------------
+// | Task State |
+// --------------
+
+/// Defines the state of the pay all offline fees task
+///
+/// The task loops through `ProvingPayment`, `SubmittingPayment` and
+/// `FindingOpening` once for each fee it settles
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum PayAllOfflineFeesTaskState {
+    /// The task is awaiting scheduling
+    Pending,
+    /// The task is proving fee payment for the current balance
+    ProvingPayment,
+    /// The task is submitting a fee payment transaction
+    SubmittingPayment,
+    /// The task is finding the new Merkle opening for the wallet
+    FindingOpening,
+    /// The task is updating the validity proofs for the wallet
+    UpdatingValidityProofs,
+    /// The task has finished
+    Completed,
+}
+
+impl TaskState for PayAllOfflineFeesTaskState {
+    // The task commits before its first proof rather than its first submission,
+    // so that the payment loop never steps back behind the commit point
+    fn commit_point() -> Self {
+        PayAllOfflineFeesTaskState::ProvingPayment
+    }
+
+    fn completed(&self) -> bool {
+        matches!(self, PayAllOfflineFeesTaskState::Completed)
+    }
+}
+
+impl Display for PayAllOfflineFeesTaskState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            PayAllOfflineFeesTaskState::Pending => write!(f, "Pending"),
+            PayAllOfflineFeesTaskState::ProvingPayment => write!(f, "Proving Payment"),
+            PayAllOfflineFeesTaskState::SubmittingPayment => write!(f, "Submitting Payment"),
+            PayAllOfflineFeesTaskState::FindingOpening => write!(f, "Finding Opening"),
+            PayAllOfflineFeesTaskState::UpdatingValidityProofs => {
+                write!(f, "Updating Validity Proofs")
+            },
+            PayAllOfflineFeesTaskState::Completed => write!(f, "Completed"),
+        }
+    }
+}
+
+impl From<PayAllOfflineFeesTaskState> for StateWrapper {
+    fn from(value: PayAllOfflineFeesTaskState) -> Self {
+        StateWrapper::PayAllOfflineFees(value)
+    }
+}
+
+// ---------------
+// | Task Errors |
+// ---------------
+
+/// The error type for the pay all offline fees task
+#[derive(Clone, Debug)]
+pub enum PayAllOfflineFeesTaskError {
+    /// An error interacting with darkpool
+    ///
+    /// Transient submission errors are retried with backoff before being
+    /// surfaced, so this error is not retried by the driver
+    Darkpool(String),
+    /// A transient error querying the darkpool, e.g. an RPC failure, which may
+    /// succeed if the step is retried
+    DarkpoolTransient(String),
+    /// An error finding the Merkle opening for the new wallet
+    FindingOpening(String),
+    /// The proof manager's channel closed before a proof was returned, e.g.
+    /// because the prover crashed
+    ProverUnavailable(String),
+    /// An error interacting with the state
+    State(String),
+    /// An error updating validity proofs after the fees are settled
+    UpdateValidityProofs(String),
+}
+
+impl TaskError for PayAllOfflineFeesTaskError {
+    fn retryable(&self) -> bool {
+        match self {
+            PayAllOfflineFeesTaskError::Darkpool(_) => false,
+            PayAllOfflineFeesTaskError::DarkpoolTransient(_)
+            | PayAllOfflineFeesTaskError::FindingOpening(_)
+            | PayAllOfflineFeesTaskError::State(_)
+            | PayAllOfflineFeesTaskError::ProverUnavailable(_)
+            | PayAllOfflineFeesTaskError::UpdateValidityProofs(_) => true,
+        }
+    }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Darkpool(_) => "Darkpool",
+            Self::DarkpoolTransient(_) => "DarkpoolTransient",
+            Self::FindingOpening(_) => "FindingOpening",
+            Self::ProverUnavailable(_) => "ProverUnavailable",
+            Self::State(_) => "State",
+            Self::UpdateValidityProofs(_) => "UpdateValidityProofs",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::Darkpool(_) | Self::DarkpoolTransient(_) | Self::FindingOpening(_) => {
+                TaskErrorKind::Darkpool
+            },
+            Self::ProverUnavailable(_) | Self::UpdateValidityProofs(_) => TaskErrorKind::Proof,
+            Self::State(_) => TaskErrorKind::State,
+        }
+    }
+}
+
+impl Display for PayAllOfflineFeesTaskError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for PayAllOfflineFeesTaskError {}
+
+impl From<StateError> for PayAllOfflineFeesTaskError {
+    fn from(error: StateError) -> Self {
+        PayAllOfflineFeesTaskError::State(error.to_string())
+    }
+}
+
+impl From<DarkpoolClientError> for PayAllOfflineFeesTaskError {
+    fn from(error: DarkpoolClientError) -> Self {
+        if error.is_retryable() {
+            PayAllOfflineFeesTaskError::DarkpoolTransient(error.to_string())
+        } else {
+            PayAllOfflineFeesTaskError::Darkpool(error.to_string())
+        }
+    }
+}
+
+impl From<PayOfflineFeeTaskError> for PayAllOfflineFeesTaskError {
+    fn from(error: PayOfflineFeeTaskError) -> Self {
+        match error {
+            PayOfflineFeeTaskError::Darkpool(e) => PayAllOfflineFeesTaskError::Darkpool(e),
+            PayOfflineFeeTaskError::DarkpoolTransient(e) => {
+                PayAllOfflineFeesTaskError::DarkpoolTransient(e)
+            },
+            PayOfflineFeeTaskError::FindingOpening(e) => {
+                PayAllOfflineFeesTaskError::FindingOpening(e)
+            },
+            PayOfflineFeeTaskError::ProverUnavailable(e) => {
+                PayAllOfflineFeesTaskError::ProverUnavailable(e)
+            },
+            PayOfflineFeeTaskError::State(e) => PayAllOfflineFeesTaskError::State(e),
+            PayOfflineFeeTaskError::UpdateValidityProofs(e) => {
+                PayAllOfflineFeesTaskError::UpdateValidityProofs(e)
+            },
+        }
+    }
+}
+
+// -------------------
+// | Task Definition |
+// -------------------
+
+/// Defines the pay all offline fees task flow
+pub struct PayAllOfflineFeesTask {
+    /// The fee payments that have yet to be started
+    pub pending_payments: VecDeque<PayOfflineFeeTaskDescriptor>,
+    /// The fee payment currently being settled
+    pub current_payment: Option<PayOfflineFeeTaskDescriptor>,
+    /// The wallet before the current fee payment
+    pub old_wallet: Wallet,
+    /// The wallet after the current fee payment
+    pub new_wallet: Wallet,
+    /// The note generated by the current fee payment
+    pub note: Option<Note>,
+    /// The proof of `VALID OFFLINE FEE SETTLEMENT` for the current fee payment
+    pub proof: Option<OfflineFeeSettlementBundle>,
+    /// The transaction receipt of the current fee payment
+    pub tx: Option<TransactionReceipt>,
+    /// The darkpool client used for submitting transactions
+    pub darkpool_client: DarkpoolClient,
+    /// A handle to the global state
+    pub state: State,
+    /// The work queue for the proof manager
+    pub proof_queue: ProofManagerQueue,
+    /// A sender to the network manager's queue
+    pub network_sender: NetworkManagerQueue,
+    /// The config for retrying each fee payment submission
+    pub retry_config: RetryConfig,
+    /// The config for polling the darkpool for the new wallet's Merkle
+    /// opening after each fee payment
+    pub merkle_poll_config: RetryConfig,
+    /// The current state of the task
+    pub task_state: PayAllOfflineFeesTaskState,
+}
+
+#[async_trait]
+impl Task for PayAllOfflineFeesTask {
+    type State = PayAllOfflineFeesTaskState;
+    type Error = PayAllOfflineFeesTaskError;
+    type Descriptor = PayAllOfflineFeesTaskDescriptor;
+
+    async fn new(descriptor: Self::Descriptor, ctx: TaskContext) -> Result<Self, Self::Error> {
+        let old_wallet = ctx
+            .state
+            .get_wallet(&descriptor.wallet_id)
+            .await?
+            .ok_or_else(|| PayAllOfflineFeesTaskError::State(ERR_WALLET_MISSING.to_string()))?;
+        let pending_payments = Self::get_outstanding_payments(&old_wallet)?;
+
+        Ok(Self {
+            pending_payments,
+            current_payment: None,
+            new_wallet: old_wallet.clone(),
+            old_wallet,
+            note: None,
+            proof: None,
+            tx: None,
+            darkpool_client: ctx.darkpool_client,
+            state: ctx.state,
+            proof_queue: ctx.proof_queue,
+            network_sender: ctx.network_queue,
+            retry_config: ctx.retry_config,
+            merkle_poll_config: MERKLE_POLL_CONFIG,
+            task_state: PayAllOfflineFeesTaskState::Pending,
+        })
+    }
+
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(skip_all, err, fields(
+        task = self.name(),
+        state = %self.state(),
+        wallet_id = %self.old_wallet.wallet_id,
+    ))]
+    async fn step(&mut self) -> Result<(), Self::Error> {
+        match self.state() {
+            PayAllOfflineFeesTaskState::Pending => {
+                if self.pending_payments.is_empty() {
+                    info!("no outstanding fees to pay, skipping");
+                    self.task_state = PayAllOfflineFeesTaskState::Completed;
+                } else {
+                    self.prepare_next_payment()?;
+                    self.task_state = PayAllOfflineFeesTaskState::ProvingPayment;
+                }
+            },
+            PayAllOfflineFeesTaskState::ProvingPayment => {
+                self.generate_proof().await?;
+                self.task_state = PayAllOfflineFeesTaskState::SubmittingPayment;
+            },
+            PayAllOfflineFeesTaskState::SubmittingPayment => {
+                self.submit_payment().await?;
+                self.task_state = PayAllOfflineFeesTaskState::FindingOpening;
+            },
+            PayAllOfflineFeesTaskState::FindingOpening => {
+                self.find_merkle_opening().await?;
+                if self.pending_payments.is_empty() {
+                    self.task_state = PayAllOfflineFeesTaskState::UpdatingValidityProofs;
+                } else {
+                    self.prepare_next_payment()?;
+                    self.task_state = PayAllOfflineFeesTaskState::ProvingPayment;
+                }
+            },
+            PayAllOfflineFeesTaskState::UpdatingValidityProofs => {
+                self.update_validity_proofs().await?;
+                self.task_state = PayAllOfflineFeesTaskState::Completed;
+            },
+            PayAllOfflineFeesTaskState::Completed => {
+                panic!("step() called in state Completed")
+            },
+        }
+
+        Ok(())
+    }
+
+    fn completed(&self) -> bool {
+        self.task_state.completed()
+    }
+
+    fn state(&self) -> Self::State {
+        self.task_state.clone()
+    }
+
+    fn name(&self) -> String {
+        TASK_NAME.to_string()
+    }
+
+    // Each payment spends the wallet as it stands before that payment, the fee
+    // notes are created rather than spent
+    fn nullifiers(&self) -> Vec<Nullifier> {
+        vec![self.old_wallet.get_wallet_nullifier()]
+    }
+}
+
+// -----------------------
+// | Task Implementation |
+// -----------------------
+
+impl PayAllOfflineFeesTask {
+    /// Generate a proof of `VALID OFFLINE FEE SETTLEMENT` for the current
+    /// fee payment
+    async fn generate_proof(&mut self) -> Result<(), PayAllOfflineFeesTaskError> {
+        let payment = self.current_payment.as_ref().unwrap();
+        let note = self.note.as_ref().unwrap();
+        let (statement, witness) = PayOfflineFeeTask::get_witness_statement(
+            payment.is_protocol_fee,
+            &payment.mint,
+            note,
+            &self.old_wallet,
+            &self.new_wallet,
+        )?;
+        let job = ProofJob::ValidOfflineFeeSettlement { witness, statement };
+
+        let proof_recv = enqueue_proof_job(job, &self.proof_queue)
+            .map_err(PayAllOfflineFeesTaskError::ProverUnavailable)?;
+
+        // Await the proof
+        let bundle =
+            proof_recv.await.map_err(err_str!(PayAllOfflineFeesTaskError::ProverUnavailable))?;
+        self.proof = Some(bundle.proof.into());
+        Ok(())
+    }
+
+    /// Submit the `settle_offline_fee` transaction for the current fee payment
+    async fn submit_payment(&mut self) -> Result<(), PayAllOfflineFeesTaskError> {
+        let proof = self.proof.as_ref().unwrap();
+        let note_commitment = self.note.as_ref().unwrap().commitment();
+        let tx = PayOfflineFeeTask::settle_with_retries(
+            &self.darkpool_client,
+            proof,
+            note_commitment,
+            &self.retry_config,
+        )
+        .await?;
+        self.tx = Some(tx);
+        Ok(())
+    }
+
+    /// Find the Merkle opening for the new wallet and make it the base of the
+    /// next fee payment
+    async fn find_merkle_opening(&mut self) -> Result<(), PayAllOfflineFeesTaskError> {
+        let tx = self.tx.as_ref().unwrap();
+        let merkle_opening = find_merkle_path_with_tx(
+            &self.new_wallet,
+            &self.darkpool_client,
+            tx,
+            Some(&self.merkle_poll_config),
+        )
+        .await
+        .map_err(err_str!(PayAllOfflineFeesTaskError::FindingOpening))?;
+        self.new_wallet.merkle_proof = Some(merkle_opening);
+
+        // Update the global state to include the new wallet
+        let waiter = self.state.update_wallet(self.new_wallet.clone()).await?;
+        waiter.await?;
+
+        // If this was a relayer fee payment and auto-redeem is enabled, enqueue a job
+        // for the relayer to redeem the fee
+        let is_protocol_fee = self.current_payment.as_ref().unwrap().is_protocol_fee;
+        let auto_redeem = self.state.get_auto_redeem_fees().await?;
+        let decryption_key = self.state.get_fee_key().await?.secret_key();
+        if !is_protocol_fee && auto_redeem && decryption_key.is_some() {
+            let note = self.note.clone().unwrap();
+            enqueue_relayer_redeem_job(note, &self.state)
+                .await
+                .map_err(PayAllOfflineFeesTaskError::State)?;
+        }
+
+        self.old_wallet = self.new_wallet.clone();
+        Ok(())
+    }
+
+    /// Update the validity proofs for the wallet after all fees are paid
+    async fn update_validity_proofs(&self) -> Result<(), PayAllOfflineFeesTaskError> {
+        update_wallet_validity_proofs(
+            &self.new_wallet,
+            self.proof_queue.clone(),
+            self.state.clone(),
+            self.network_sender.clone(),
+        )
+        .await
+        .map_err(PayAllOfflineFeesTaskError::UpdateValidityProofs)
+    }
+
+    // -----------
+    // | Helpers |
+    // -----------
+
+    /// Get a fee payment for each non-zero relayer and protocol fee in the
+    /// wallet
+    fn get_outstanding_payments(
+        wallet: &Wallet,
+    ) -> Result<VecDeque<PayOfflineFeeTaskDescriptor>, PayAllOfflineFeesTaskError> {
+        let mut payments = VecDeque::new();
+        for balance in wallet.balances.values() {
+            if balance.relayer_fee_balance > 0 {
+                let desc =
+                    PayOfflineFeeTaskDescriptor::new_relayer_fee(wallet.wallet_id, balance.clone())
+                        .map_err(PayAllOfflineFeesTaskError::State)?;
+                payments.push_back(desc);
+            }
+
+            if balance.protocol_fee_balance > 0 {
+                let desc = PayOfflineFeeTaskDescriptor::new_protocol_fee(
+                    wallet.wallet_id,
+                    balance.clone(),
+                )
+                .map_err(PayAllOfflineFeesTaskError::State)?;
+                payments.push_back(desc);
+            }
+        }
+
+        Ok(payments)
+    }
+
+    /// Pop the next fee payment and construct the wallet that results from it
+    fn prepare_next_payment(&mut self) -> Result<(), PayAllOfflineFeesTaskError> {
+        let payment = self.pending_payments.front().unwrap();
+        let (note, new_wallet) = PayOfflineFeeTask::get_wallet_and_note(payment, &self.old_wallet)?;
+
+        // Only dequeue the payment once its wallet is constructed, so that a retry
+        // does not skip it
+        let payment = self.pending_payments.pop_front().unwrap();
+        self.note = Some(note);
+        self.new_wallet = new_wallet;
+        self.current_payment = Some(payment);
+        self.proof = None;
+        self.tx = None;
+        Ok(())
+    }
+}
@@ -6,15 +6,26 @@ use std::{
     fmt::{Display, Formatter, Result as FmtResult},
 };
 
-use alloy::rpc::types::TransactionReceipt;
+use alloy::{
+    primitives::TxHash,
+    rpc::types::{TransactionReceipt, TransactionRequest},
+};
 use async_trait::async_trait;
-use circuit_types::{native_helpers::encrypt_note, note::Note};
+use circuit_types::{
+    Amount,
+    native_helpers::encrypt_note,
+    note::Note,
+    wallet::{Nullifier, WalletShareStateCommitment},
+};
 use circuits::zk_circuits::valid_offline_fee_settlement::{
     SizedValidOfflineFeeSettlementStatement, SizedValidOfflineFeeSettlementWitness,
 };
 use common::types::{
-    proof_bundles::OfflineFeeSettlementBundle, tasks::PayOfflineFeeTaskDescriptor, wallet::Wallet,
+    proof_bundles::OfflineFeeSettlementBundle,
+    tasks::{PayOfflineFeeTaskDescriptor, TaskIdentifier},
+    wallet::Wallet,
 };
+use constants::Scalar;
 use darkpool_client::{DarkpoolClient, errors::DarkpoolClientError};
 use job_types::{
     network_manager::NetworkManagerQueue,
@@ -23,19 +34,22 @@ use job_types::{
 use num_bigint::BigUint;
 use serde::Serialize;
 use state::{State, error::StateError};
-use tracing::instrument;
+use tracing::{info, instrument};
 use util::{err_str, on_chain::get_protocol_pubkey};
 
 use crate::{
     task_state::StateWrapper,
-    traits::{Task, TaskContext, TaskError, TaskState},
-    utils::validity_proofs::{
-        enqueue_proof_job, enqueue_relayer_redeem_job, find_merkle_path_with_tx,
-        update_wallet_validity_proofs,
+    traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState},
+    utils::{
+        retry::{RetryConfig, retry_with_backoff},
+        validity_proofs::{
+            enqueue_proof_job, enqueue_relayer_redeem_job, find_merkle_path_with_tx,
+            update_wallet_validity_proofs,
+        },
     },
 };
 
-use super::{ERR_BALANCE_MISSING, ERR_NO_MERKLE_PROOF, ERR_WALLET_MISSING};
+use super::{ERR_BALANCE_MISSING, ERR_NO_MERKLE_PROOF, ERR_WALLET_MISSING, MERKLE_POLL_CONFIG};
 
 /// The name of the task
 const TASK_NAME: &str = "pay-offline-fee";
@@ -43,6 +57,21 @@ const TASK_NAME: &str = "pay-offline-fee";
 /// Error message emitted when the fee amount in the descriptor is more than the
 /// fees owed
 const ERR_INVALID_FEE_AMOUNT: &str = "Fee amount in descriptor does not equal paid amount";
+/// Error message emitted when a protocol fee payment specifies an encryption
+/// key override
+const ERR_PROTOCOL_FEE_KEY_OVERRIDE: &str =
+    "Protocol fees must be encrypted under the protocol key, encryption key override not allowed";
+/// Error message emitted when a relayer fee payment overrides the encryption
+/// key with a key other than the wallet's managing cluster key
+///
+/// `VALID OFFLINE FEE SETTLEMENT` constrains relayer fee notes to be encrypted
+/// under the managing cluster key, so any other key gives an unsatisfiable
+/// proof
+const ERR_RELAYER_FEE_KEY_OVERRIDE: &str =
+    "Relayer fees must be encrypted under the wallet's managing cluster key";
+/// Error message emitted when the note ciphertext does not decrypt to the note
+const ERR_NOTE_CIPHERTEXT_MISMATCH: &str =
+    "Note ciphertext is not an encryption of the note under the fee key";
 
 // --------------
 // | Task State |
@@ -102,9 +131,18 @@ impl From<PayOfflineFeeTaskState> for StateWrapper {
 #[derive(Clone, Debug)]
 pub enum PayOfflineFeeTaskError {
     /// An error interacting with darkpool
+    ///
+    /// Transient submission errors are retried with backoff before being
+    /// surfaced, so this error is not retried by the driver
     Darkpool(String),
-    /// An error generating a proof for fee payment
-    ProofGeneration(String),
+    /// A transient error querying the darkpool, e.g. an RPC failure, which may
+    /// succeed if the step is retried
+    DarkpoolTransient(String),
+    /// An error finding the Merkle opening for the new wallet
+    FindingOpening(String),
+    /// The proof manager's channel closed before a proof was returned, e.g.
+    /// because the prover crashed
+    ProverUnavailable(String),
     /// An error interacting with the state
     State(String),
     /// An error updating validity proofs after the fees are settled
@@ -114,12 +152,35 @@ pub enum PayOfflineFeeTaskError {
 impl TaskError for PayOfflineFeeTaskError {
     fn retryable(&self) -> bool {
         match self {
-            PayOfflineFeeTaskError::Darkpool(_)
+            PayOfflineFeeTaskError::Darkpool(_) => false,
+            PayOfflineFeeTaskError::DarkpoolTransient(_)
+            | PayOfflineFeeTaskError::FindingOpening(_)
             | PayOfflineFeeTaskError::State(_)
-            | PayOfflineFeeTaskError::ProofGeneration(_)
+            | PayOfflineFeeTaskError::ProverUnavailable(_)
             | PayOfflineFeeTaskError::UpdateValidityProofs(_) => true,
         }
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Darkpool(_) => "Darkpool",
+            Self::DarkpoolTransient(_) => "DarkpoolTransient",
+            Self::FindingOpening(_) => "FindingOpening",
+            Self::ProverUnavailable(_) => "ProverUnavailable",
+            Self::State(_) => "State",
+            Self::UpdateValidityProofs(_) => "UpdateValidityProofs",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::Darkpool(_) | Self::DarkpoolTransient(_) | Self::FindingOpening(_) => {
+                TaskErrorKind::Darkpool
+            },
+            Self::ProverUnavailable(_) | Self::UpdateValidityProofs(_) => TaskErrorKind::Proof,
+            Self::State(_) => TaskErrorKind::State,
+        }
+    }
 }
 
 impl Display for PayOfflineFeeTaskError {
@@ -138,7 +199,11 @@ impl From<StateError> for PayOfflineFeeTaskError {
 
 impl From<DarkpoolClientError> for PayOfflineFeeTaskError {
     fn from(error: DarkpoolClientError) -> Self {
-        PayOfflineFeeTaskError::Darkpool(error.to_string())
+        if error.is_retryable() {
+            PayOfflineFeeTaskError::DarkpoolTransient(error.to_string())
+        } else {
+            PayOfflineFeeTaskError::Darkpool(error.to_string())
+        }
     }
 }
 
@@ -146,8 +211,29 @@ impl From<DarkpoolClientError> for PayOfflineFeeTaskError {
 // | Task Definition |
 // -------------------
 
+/// The outcome of a settled fee payment
+#[derive(Clone, Debug)]
+pub struct FeeSettlementResult {
+    /// The commitment to the note created by the settlement
+    pub note_commitment: Scalar,
+    /// The nullifier of the wallet spent by the settlement
+    pub nullifier: Nullifier,
+    /// The amount of the fee paid
+    pub amount: Amount,
+    /// The commitment to the wallet after the fee is paid
+    pub new_wallet_commitment: WalletShareStateCommitment,
+    /// The hash of the settlement transaction
+    pub tx_hash: TxHash,
+}
+
 /// Defines the pay fees task flow
 pub struct PayOfflineFeeTask {
+    /// The ID of the task, if run by the driver
+    pub task_id: Option<TaskIdentifier>,
+    /// The descriptor of the task as stored in the task queue
+    ///
+    /// Updated with an idempotency key when the payment is submitted
+    pub descriptor: PayOfflineFeeTaskDescriptor,
     /// Whether the task pays a protocol fee or a relayer fee
     pub is_protocol_fee: bool,
     /// The balance to pay fees for
@@ -158,10 +244,21 @@ pub struct PayOfflineFeeTask {
     pub new_wallet: Wallet,
     /// The note generated by the settlement
     pub note: Note,
+    /// The commitment to a fee note settled by a previous attempt at this
+    /// payment, if any
+    pub idempotency_key: Option<Scalar>,
+    /// Whether a previous attempt at this payment was found on-chain, in which
+    /// case the task does not settle `note` itself
+    pub already_settled: bool,
     /// The proof of `VALID OFFLINE FEE SETTLEMENT` used to pay the fee
     pub proof: Option<OfflineFeeSettlementBundle>,
     /// The transaction receipt of the fee payment
     pub tx: Option<TransactionReceipt>,
+    /// Whether the task is a dry run, in which case it completes after
+    /// proving without submitting the payment
+    pub dry_run: bool,
+    /// The fee payment transaction that a dry run would have submitted
+    pub dry_run_tx: Option<TransactionRequest>,
     /// The darkpool client used for submitting transactions
     pub darkpool_client: DarkpoolClient,
     /// A hand to the global state
@@ -170,6 +267,11 @@ pub struct PayOfflineFeeTask {
     pub proof_queue: ProofManagerQueue,
     /// A sender to the network manager's queue
     pub network_sender: NetworkManagerQueue,
+    /// The config for retrying the fee payment submission
+    pub retry_config: RetryConfig,
+    /// The config for polling the darkpool for the new wallet's Merkle
+    /// opening
+    pub merkle_poll_config: RetryConfig,
     /// The current state of the task
     pub task_state: PayOfflineFeeTaskState,
 }
@@ -194,17 +296,25 @@ impl Task for PayOfflineFeeTask {
         }
 
         Ok(Self {
+            task_id: ctx.task_id,
             is_protocol_fee: descriptor.is_protocol_fee,
-            mint: descriptor.mint,
+            mint: descriptor.mint.clone(),
             old_wallet,
             new_wallet,
             note,
+            idempotency_key: descriptor.idempotency_key,
+            already_settled: false,
             proof: None,
             tx: None,
+            dry_run: descriptor.dry_run,
+            dry_run_tx: None,
+            descriptor,
             darkpool_client: ctx.darkpool_client,
             state: ctx.state,
             proof_queue: ctx.proof_queue,
             network_sender: ctx.network_queue,
+            retry_config: ctx.retry_config,
+            merkle_poll_config: MERKLE_POLL_CONFIG,
             task_state: PayOfflineFeeTaskState::Pending,
         })
     }
@@ -219,11 +329,20 @@ impl Task for PayOfflineFeeTask {
     async fn step(&mut self) -> Result<(), Self::Error> {
         match self.state() {
             PayOfflineFeeTaskState::Pending => {
-                self.task_state = PayOfflineFeeTaskState::ProvingPayment;
+                if self.find_prior_settlement().await? {
+                    self.task_state = PayOfflineFeeTaskState::FindingOpening;
+                } else {
+                    self.task_state = PayOfflineFeeTaskState::ProvingPayment;
+                }
             },
             PayOfflineFeeTaskState::ProvingPayment => {
                 self.generate_proof().await?;
-                self.task_state = PayOfflineFeeTaskState::SubmittingPayment;
+                if self.dry_run {
+                    self.build_dry_run_tx()?;
+                    self.task_state = PayOfflineFeeTaskState::Completed;
+                } else {
+                    self.task_state = PayOfflineFeeTaskState::SubmittingPayment;
+                }
             },
             PayOfflineFeeTaskState::SubmittingPayment => {
                 self.submit_payment().await?;
@@ -256,6 +375,12 @@ impl Task for PayOfflineFeeTask {
     fn name(&self) -> String {
         TASK_NAME.to_string()
     }
+
+    // The payment spends the old wallet, the fee note is created rather than
+    // spent so its nullifier is not consumed
+    fn nullifiers(&self) -> Vec<Nullifier> {
+        vec![self.old_wallet.get_wallet_nullifier()]
+    }
 }
 
 // -----------------------
@@ -263,33 +388,111 @@ impl Task for PayOfflineFeeTask {
 // -----------------------
 
 impl PayOfflineFeeTask {
+    /// Get the result of the fee settlement
+    ///
+    /// Returns `None` until the task has completed, and for dry runs, which
+    /// settle nothing
+    pub fn result(&self) -> Option<FeeSettlementResult> {
+        if !self.completed() {
+            return None;
+        }
+
+        let tx = self.tx.as_ref()?;
+        let note_commitment = match self.idempotency_key {
+            Some(key) if self.already_settled => key,
+            _ => self.note.commitment(),
+        };
+
+        Some(FeeSettlementResult {
+            note_commitment,
+            nullifier: self.old_wallet.get_wallet_nullifier(),
+            amount: self.note.amount,
+            new_wallet_commitment: self.new_wallet.get_wallet_share_commitment(),
+            tx_hash: tx.transaction_hash,
+        })
+    }
+
+    /// Check whether a previous attempt at this payment already settled the
+    /// fee note on-chain
+    ///
+    /// If so, the settlement transaction is stored so that the task may resume
+    /// from `FindingOpening`
+    async fn find_prior_settlement(&mut self) -> Result<bool, PayOfflineFeeTaskError> {
+        let note_commitment = match self.idempotency_key {
+            Some(key) if !self.dry_run => key,
+            _ => return Ok(false),
+        };
+
+        let tx = match self.darkpool_client.find_commitment_tx(note_commitment).await? {
+            Some(tx) => tx,
+            None => return Ok(false),
+        };
+
+        info!("fee already settled in tx {:#x}, skipping payment", tx.transaction_hash);
+        self.tx = Some(tx);
+        self.already_settled = true;
+        Ok(true)
+    }
+
     /// Generate a proof of `VALID OFFLINE FEE SETTLEMENT` for the given
     /// balance
+    #[instrument(skip_all)]
     async fn generate_proof(&mut self) -> Result<(), PayOfflineFeeTaskError> {
-        let (statement, witness) = self.get_witness_statement()?;
+        let (statement, witness) = Self::get_witness_statement(
+            self.is_protocol_fee,
+            &self.mint,
+            &self.note,
+            &self.old_wallet,
+            &self.new_wallet,
+        )?;
         let job = ProofJob::ValidOfflineFeeSettlement { witness, statement };
 
         let proof_recv = enqueue_proof_job(job, &self.proof_queue)
-            .map_err(PayOfflineFeeTaskError::ProofGeneration)?;
+            .map_err(PayOfflineFeeTaskError::ProverUnavailable)?;
 
         // Await the proof
-        let bundle = proof_recv.await.map_err(err_str!(PayOfflineFeeTaskError::ProofGeneration))?;
+        let bundle =
+            proof_recv.await.map_err(err_str!(PayOfflineFeeTaskError::ProverUnavailable))?;
         self.proof = Some(bundle.proof.into());
         Ok(())
     }
 
     /// Submit the `settle_offline_fee` transaction for the balance
+    ///
+    /// Transient submission errors are retried with backoff
     async fn submit_payment(&mut self) -> Result<(), PayOfflineFeeTaskError> {
-        let proof = self.proof.clone().unwrap();
-        let tx = self.darkpool_client.settle_offline_fee(&proof).await?;
+        self.persist_idempotency_key().await?;
+        let proof = self.proof.as_ref().unwrap();
+        let tx = Self::settle_with_retries(
+            &self.darkpool_client,
+            proof,
+            self.note.commitment(),
+            &self.retry_config,
+        )
+        .await?;
         self.tx = Some(tx);
         Ok(())
     }
 
+    /// Build the `settle_offline_fee` transaction without submitting it
+    fn build_dry_run_tx(&mut self) -> Result<(), PayOfflineFeeTaskError> {
+        let proof = self.proof.as_ref().unwrap();
+        let tx = self.darkpool_client.gen_settle_offline_fee_calldata(proof)?;
+        self.dry_run_tx = Some(tx);
+        Ok(())
+    }
+
     /// Find the Merkle opening for the new wallet
     async fn find_merkle_opening(&mut self) -> Result<(), PayOfflineFeeTaskError> {
         let tx = self.tx.as_ref().unwrap();
-        let merkle_opening = find_merkle_path_with_tx(&self.new_wallet, &self.darkpool_client, tx)?;
+        let merkle_opening = find_merkle_path_with_tx(
+            &self.new_wallet,
+            &self.darkpool_client,
+            tx,
+            Some(&self.merkle_poll_config),
+        )
+        .await
+        .map_err(err_str!(PayOfflineFeeTaskError::FindingOpening))?;
         self.new_wallet.merkle_proof = Some(merkle_opening);
 
         // Update the global state to include the new wallet
@@ -297,10 +500,12 @@ impl PayOfflineFeeTask {
         waiter.await?;
 
         // If this was a relayer fee payment and auto-redeem is enabled, enqueue a job
-        // for the relayer to redeem the fee
+        // for the relayer to redeem the fee. A note settled by a previous attempt
+        // is not known to this task, so it is left to that attempt to redeem
         let auto_redeem = self.state.get_auto_redeem_fees().await?;
         let decryption_key = self.state.get_fee_key().await?.secret_key();
-        if !self.is_protocol_fee && auto_redeem && decryption_key.is_some() {
+        if !self.is_protocol_fee && !self.already_settled && auto_redeem && decryption_key.is_some()
+        {
             enqueue_relayer_redeem_job(self.note.clone(), &self.state)
                 .await
                 .map_err(PayOfflineFeeTaskError::State)?;
@@ -325,11 +530,77 @@ impl PayOfflineFeeTask {
     // | Helpers |
     // -----------
 
+    /// Record the commitment to the note being settled as the idempotency key
+    /// of the task's stored descriptor
+    ///
+    /// If the task is re-enqueued, e.g. after a failover, it restarts from the
+    /// stored descriptor and finds the settlement instead of paying twice
+    async fn persist_idempotency_key(&mut self) -> Result<(), PayOfflineFeeTaskError> {
+        let note_commitment = self.note.commitment();
+        let task_id = match self.task_id {
+            Some(id) if self.descriptor.idempotency_key != Some(note_commitment) => id,
+            _ => return Ok(()),
+        };
+
+        let descriptor = self.descriptor.clone().with_idempotency_key(note_commitment);
+        let waiter = self.state.update_task_descriptor(task_id, descriptor.clone().into()).await?;
+        waiter.await?;
+
+        self.descriptor = descriptor;
+        Ok(())
+    }
+
+    /// Submit a `settle_offline_fee` transaction, retrying transient errors
+    /// with backoff
+    ///
+    /// An attempt that errors, e.g. on a timeout, may still have landed, and
+    /// resubmitting it would revert on the spent nullifier. So before each
+    /// retry the darkpool is checked for the note commitment, and the receipt
+    /// of the transaction that inserted it is returned if found. An error that
+    /// exhausts the retries is not retried by the driver
+    pub(crate) async fn settle_with_retries(
+        client: &DarkpoolClient,
+        proof: &OfflineFeeSettlementBundle,
+        note_commitment: Scalar,
+        retry_config: &RetryConfig,
+    ) -> Result<TransactionReceipt, PayOfflineFeeTaskError> {
+        let mut attempted = false;
+        retry_with_backoff(retry_config, DarkpoolClientError::is_retryable, || {
+            let is_retry = std::mem::replace(&mut attempted, true);
+            async move {
+                if is_retry && let Some(tx) = client.find_commitment_tx(note_commitment).await? {
+                    info!("fee settled by a previous submission in tx {:#x}", tx.transaction_hash);
+                    return Ok(tx);
+                }
+
+                client.settle_offline_fee(proof).await
+            }
+        })
+        .await
+        .map_err(err_str!(PayOfflineFeeTaskError::Darkpool))
+    }
+
     /// Clone the old wallet and update it to reflect the fee payment
-    fn get_wallet_and_note(
+    ///
+    /// Errors if the descriptor overrides the encryption key of a protocol fee,
+    /// or overrides the key of a relayer fee with any key other than the
+    /// wallet's managing cluster key, which the circuit requires
+    pub(crate) fn get_wallet_and_note(
         descriptor: &PayOfflineFeeTaskDescriptor,
         old_wallet: &Wallet,
     ) -> Result<(Note, Wallet), PayOfflineFeeTaskError> {
+        match descriptor.override_encryption_key {
+            Some(_) if descriptor.is_protocol_fee => {
+                let msg = ERR_PROTOCOL_FEE_KEY_OVERRIDE.to_string();
+                return Err(PayOfflineFeeTaskError::State(msg));
+            },
+            Some(key) if key != old_wallet.managing_cluster => {
+                let msg = ERR_RELAYER_FEE_KEY_OVERRIDE.to_string();
+                return Err(PayOfflineFeeTaskError::State(msg));
+            },
+            _ => {},
+        }
+
         let mut new_wallet = old_wallet.clone();
         let balance = new_wallet
             .get_balance_mut(&descriptor.mint)
@@ -346,15 +617,21 @@ impl PayOfflineFeeTask {
     }
 
     /// Get the witness and statement for the `VALID OFFLINE FEE SETTLEMENT`
-    fn get_witness_statement(
-        &self,
+    ///
+    /// Relayer fee notes are encrypted under the wallet's managing cluster key,
+    /// as the circuit requires
+    pub(crate) fn get_witness_statement(
+        is_protocol_fee: bool,
+        mint: &BigUint,
+        note: &Note,
+        old_wallet: &Wallet,
+        new_wallet: &Wallet,
     ) -> Result<
         (SizedValidOfflineFeeSettlementStatement, SizedValidOfflineFeeSettlementWitness),
         PayOfflineFeeTaskError,
     > {
         // Get the old wallet's state transition info
-        let note = &self.note;
-        let wallet = &self.old_wallet;
+        let wallet = old_wallet;
         let nullifier = wallet.get_wallet_nullifier();
         let opening = wallet
             .merkle_proof
@@ -362,17 +639,20 @@ impl PayOfflineFeeTask {
             .ok_or_else(|| PayOfflineFeeTaskError::State(ERR_NO_MERKLE_PROOF.to_string()))?;
         let original_wallet_public_shares = wallet.blinded_public_shares.clone();
         let original_wallet_private_shares = wallet.private_shares.clone();
-        let send_index = wallet.get_balance_index(&self.mint).unwrap();
+        let send_index = wallet.get_balance_index(mint).unwrap();
 
         // Encrypt the note
         let protocol_key = get_protocol_pubkey();
-        let key = if self.is_protocol_fee { protocol_key } else { wallet.managing_cluster };
+        let key = if is_protocol_fee { protocol_key } else { wallet.managing_cluster };
         let note_commitment = note.commitment();
 
         let (note_ciphertext, encryption_randomness) = encrypt_note(note, &key);
+        // Check the ciphertext against the note before spending prover time on it
+        if !note.verify_ciphertext(&note_ciphertext, &key, encryption_randomness) {
+            return Err(PayOfflineFeeTaskError::State(ERR_NOTE_CIPHERTEXT_MISMATCH.to_string()));
+        }
 
         // Generate new wallet shares
-        let new_wallet = &self.new_wallet;
         let new_wallet_commitment = new_wallet.get_wallet_share_commitment();
         let updated_wallet_public_shares = new_wallet.blinded_public_shares.clone();
         let updated_wallet_private_shares = new_wallet.private_shares.clone();
@@ -386,7 +666,7 @@ impl PayOfflineFeeTask {
             note_ciphertext,
             note_commitment,
             protocol_key,
-            is_protocol_fee: self.is_protocol_fee,
+            is_protocol_fee,
         };
 
         let witness = SizedValidOfflineFeeSettlementWitness {
@@ -26,7 +26,7 @@ use tracing::instrument;
 use util::err_str;
 
 use crate::task_state::StateWrapper;
-use crate::traits::{Task, TaskContext, TaskError, TaskState};
+use crate::traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState};
 use crate::utils::validity_proofs::{
     enqueue_proof_job, find_merkle_path_with_tx, update_wallet_validity_proofs,
 };
@@ -118,6 +118,25 @@ impl TaskError for PayRelayerFeeTaskError {
             PayRelayerFeeTaskError::Signature(_) => false,
         }
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Darkpool(_) => "Darkpool",
+            Self::ProofGeneration(_) => "ProofGeneration",
+            Self::Signature(_) => "Signature",
+            Self::State(_) => "State",
+            Self::UpdateValidityProofs(_) => "UpdateValidityProofs",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::Darkpool(_) => TaskErrorKind::Darkpool,
+            Self::ProofGeneration(_) | Self::UpdateValidityProofs(_) => TaskErrorKind::Proof,
+            Self::Signature(_) => TaskErrorKind::Validation,
+            Self::State(_) => TaskErrorKind::State,
+        }
+    }
 }
 
 impl Display for PayRelayerFeeTaskError {
@@ -309,12 +328,14 @@ impl PayRelayerFeeTask {
         // Find the opening for the sender's wallet
         let tx = self.tx.as_ref().unwrap();
         let sender_opening =
-            find_merkle_path_with_tx(&self.new_sender_wallet, &self.darkpool_client, tx)?;
+            find_merkle_path_with_tx(&self.new_sender_wallet, &self.darkpool_client, tx, None)
+                .await?;
         self.new_sender_wallet.merkle_proof = Some(sender_opening);
 
         // Find the opening for the recipient's wallet
         let recipient_opening =
-            find_merkle_path_with_tx(&self.new_recipient_wallet, &self.darkpool_client, tx)?;
+            find_merkle_path_with_tx(&self.new_recipient_wallet, &self.darkpool_client, tx, None)
+                .await?;
         self.new_recipient_wallet.merkle_proof = Some(recipient_opening);
 
         let waiter1 = self.state.update_wallet(self.new_sender_wallet.clone()).await?;
@@ -4,7 +4,7 @@ use std::{error::Error, fmt::Display};
 
 use alloy::rpc::types::TransactionReceipt;
 use async_trait::async_trait;
-use circuit_types::{balance::Balance, elgamal::DecryptionKey, note::Note};
+use circuit_types::{balance::Balance, elgamal::DecryptionKey, note::Note, wallet::Nullifier};
 use circuits::zk_circuits::valid_fee_redemption::{
     SizedValidFeeRedemptionStatement, SizedValidFeeRedemptionWitness,
 };
@@ -25,7 +25,7 @@ use util::err_str;
 use crate::{
     task_state::StateWrapper,
     tasks::ERR_NO_MERKLE_PROOF,
-    traits::{Task, TaskContext, TaskError, TaskState},
+    traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState},
     utils::validity_proofs::{enqueue_proof_job, find_merkle_path_with_tx},
 };
 
@@ -114,6 +114,25 @@ impl TaskError for RedeemFeeError {
             RedeemFeeError::Signature(_) => false,
         }
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Darkpool(_) => "Darkpool",
+            Self::ProofGeneration(_) => "ProofGeneration",
+            Self::Signature(_) => "Signature",
+            Self::State(_) => "State",
+            Self::UpdateValidityProofs(_) => "UpdateValidityProofs",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::Darkpool(_) => TaskErrorKind::Darkpool,
+            Self::ProofGeneration(_) | Self::UpdateValidityProofs(_) => TaskErrorKind::Proof,
+            Self::Signature(_) => TaskErrorKind::Validation,
+            Self::State(_) => TaskErrorKind::State,
+        }
+    }
 }
 
 impl Display for RedeemFeeError {
@@ -245,6 +264,11 @@ impl Task for RedeemFeeTask {
     fn name(&self) -> String {
         TASK_NAME.to_string()
     }
+
+    // Redemption spends both the recipient's wallet and the note
+    fn nullifiers(&self) -> Vec<Nullifier> {
+        vec![self.old_wallet.get_wallet_nullifier(), self.note.nullifier()]
+    }
 }
 
 // -----------------------
@@ -293,7 +317,8 @@ impl RedeemFeeTask {
     /// Find the opening for the relayer wallet
     async fn find_wallet_opening(&mut self) -> Result<(), RedeemFeeError> {
         let tx = self.tx.as_ref().unwrap();
-        let opening = find_merkle_path_with_tx(&self.new_wallet, &self.darkpool_client, tx)?;
+        let opening =
+            find_merkle_path_with_tx(&self.new_wallet, &self.darkpool_client, tx, None).await?;
         self.new_wallet.merkle_proof = Some(opening);
 
         let waiter = self.state.update_wallet(self.new_wallet.clone()).await?;
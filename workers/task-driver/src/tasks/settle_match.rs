@@ -29,7 +29,7 @@ use state::error::StateError;
 use tracing::instrument;
 
 use crate::task_state::StateWrapper;
-use crate::traits::{Task, TaskContext, TaskError, TaskState};
+use crate::traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState};
 use crate::utils::order_states::{record_order_fill, transition_order_settling};
 use crate::utils::validity_proofs::{
     find_merkle_path, find_merkle_path_with_tx, update_wallet_validity_proofs,
@@ -126,6 +126,26 @@ impl TaskError for SettleMatchTaskError {
                 | SettleMatchTaskError::UpdatingValidityProofs(_)
         )
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::ProofGeneration(_) => "ProofGeneration",
+            Self::SendMessage(_) => "SendMessage",
+            Self::Missing(_) => "Missing",
+            Self::Darkpool(_) => "Darkpool",
+            Self::UpdatingValidityProofs(_) => "UpdatingValidityProofs",
+            Self::State(_) => "State",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::ProofGeneration(_) | Self::UpdatingValidityProofs(_) => TaskErrorKind::Proof,
+            Self::SendMessage(_) => TaskErrorKind::Network,
+            Self::Missing(_) | Self::State(_) => TaskErrorKind::State,
+            Self::Darkpool(_) => TaskErrorKind::Darkpool,
+        }
+    }
 }
 
 impl Display for SettleMatchTaskError {
@@ -329,7 +349,7 @@ impl SettleMatchTask {
 
         // Find the wallet's new Merkle opening
         let opening = if let Some(tx) = self.tx.as_ref() {
-            find_merkle_path_with_tx(&wallet, &self.darkpool_client, tx)?
+            find_merkle_path_with_tx(&wallet, &self.darkpool_client, tx, None).await?
         } else {
             find_merkle_path(&wallet, &self.darkpool_client).await?
         };
@@ -6,7 +6,7 @@ use std::time::Duration;
 
 use crate::task_state::StateWrapper;
 use crate::tasks::ERR_AWAITING_PROOF;
-use crate::traits::{Task, TaskContext, TaskError, TaskState};
+use crate::traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState};
 use crate::utils::validity_proofs::enqueue_proof_job;
 use async_trait::async_trait;
 use circuit_types::fixed_point::FixedPoint;
@@ -152,6 +152,26 @@ impl TaskError for SettleMatchExternalTaskError {
     fn retryable(&self) -> bool {
         matches!(self, Self::Darkpool(_))
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Darkpool(_) => "Darkpool",
+            Self::AwaitingSettlement(_) => "AwaitingSettlement",
+            Self::EnqueuingJob(_) => "EnqueuingJob",
+            Self::ProofLinking(_) => "ProofLinking",
+            Self::State(_) => "State",
+            Self::SendEvent(_) => "SendEvent",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::Darkpool(_) | Self::AwaitingSettlement(_) => TaskErrorKind::Darkpool,
+            Self::EnqueuingJob(_) | Self::ProofLinking(_) => TaskErrorKind::Proof,
+            Self::State(_) => TaskErrorKind::State,
+            Self::SendEvent(_) => TaskErrorKind::Network,
+        }
+    }
 }
 
 impl Display for SettleMatchExternalTaskError {
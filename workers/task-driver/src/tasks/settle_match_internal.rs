@@ -5,7 +5,7 @@ use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
 use crate::task_state::StateWrapper;
-use crate::traits::{Task, TaskContext, TaskError, TaskState};
+use crate::traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState};
 use crate::utils::order_states::{record_order_fill, transition_order_settling};
 use crate::utils::validity_proofs::{
     enqueue_proof_job, find_merkle_path_with_tx, update_wallet_validity_proofs,
@@ -133,6 +133,27 @@ impl TaskError for SettleMatchInternalTaskError {
     fn retryable(&self) -> bool {
         matches!(self, Self::ProvingValidity(_) | Self::Darkpool(_) | Self::State(_))
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::EnqueuingJob(_) => "EnqueuingJob",
+            Self::MissingState(_) => "MissingState",
+            Self::ProvingValidity(_) => "ProvingValidity",
+            Self::Darkpool(_) => "Darkpool",
+            Self::WalletLocked(_) => "WalletLocked",
+            Self::State(_) => "State",
+            Self::SendEvent(_) => "SendEvent",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::EnqueuingJob(_) | Self::ProvingValidity(_) => TaskErrorKind::Proof,
+            Self::MissingState(_) | Self::WalletLocked(_) | Self::State(_) => TaskErrorKind::State,
+            Self::Darkpool(_) => TaskErrorKind::Darkpool,
+            Self::SendEvent(_) => TaskErrorKind::Network,
+        }
+    }
 }
 
 impl Display for SettleMatchInternalTaskError {
@@ -387,8 +408,8 @@ impl SettleMatchInternalTask {
         wallet1.update_from_shares(party0_private_shares, party0_public_shares);
         wallet2.update_from_shares(party1_private_shares, party1_public_shares);
 
-        self.find_opening(&mut wallet1)?;
-        self.find_opening(&mut wallet2)?;
+        self.find_opening(&mut wallet1).await?;
+        self.find_opening(&mut wallet2).await?;
 
         // Re-index the updated wallets in the global state
         let waiter1 = self.state.update_wallet(wallet1).await?;
@@ -550,9 +571,9 @@ impl SettleMatchInternalTask {
     }
 
     /// Find and update the merkle opening for the wallet
-    fn find_opening(&self, wallet: &mut Wallet) -> Result<(), SettleMatchInternalTaskError> {
+    async fn find_opening(&self, wallet: &mut Wallet) -> Result<(), SettleMatchInternalTaskError> {
         let tx = self.tx.as_ref().unwrap();
-        let opening = find_merkle_path_with_tx(wallet, &self.darkpool_client, tx)?;
+        let opening = find_merkle_path_with_tx(wallet, &self.darkpool_client, tx, None).await?;
         wallet.merkle_proof = Some(opening);
         Ok(())
     }
@@ -17,7 +17,7 @@ use tracing::instrument;
 
 use crate::{
     task_state::StateWrapper,
-    traits::{Task, TaskContext, TaskError, TaskState},
+    traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState},
     utils::validity_proofs::update_wallet_validity_proofs,
 };
 
@@ -104,6 +104,23 @@ impl TaskError for UpdateMerkleProofTaskError {
                 | UpdateMerkleProofTaskError::UpdatingValidityProofs(_)
         )
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::Darkpool(_) => "Darkpool",
+            Self::State(_) => "State",
+            Self::UpdatingValidityProofs(_) => "UpdatingValidityProofs",
+            Self::WalletLocked => "WalletLocked",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::Darkpool(_) => TaskErrorKind::Darkpool,
+            Self::State(_) | Self::WalletLocked => TaskErrorKind::State,
+            Self::UpdatingValidityProofs(_) => TaskErrorKind::Proof,
+        }
+    }
 }
 
 impl Display for UpdateMerkleProofTaskError {
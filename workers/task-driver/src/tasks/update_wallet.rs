@@ -38,7 +38,7 @@ use tracing::{info, instrument};
 use util::err_str;
 
 use crate::task_state::StateWrapper;
-use crate::traits::{Task, TaskContext, TaskError, TaskState};
+use crate::traits::{Task, TaskContext, TaskError, TaskErrorKind, TaskState};
 use crate::utils::validity_proofs::{
     enqueue_proof_job, find_merkle_path_with_tx, update_wallet_validity_proofs,
 };
@@ -158,6 +158,28 @@ impl TaskError for UpdateWalletTaskError {
                 | UpdateWalletTaskError::UpdatingValidityProofs(_)
         )
     }
+
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Self::InvalidShares(_) => "InvalidShares",
+            Self::ProofGeneration(_) => "ProofGeneration",
+            Self::Darkpool(_) => "Darkpool",
+            Self::Missing(_) => "Missing",
+            Self::State(_) => "State",
+            Self::UpdatingValidityProofs(_) => "UpdatingValidityProofs",
+            Self::SendEvent(_) => "SendEvent",
+        }
+    }
+
+    fn kind(&self) -> TaskErrorKind {
+        match self {
+            Self::InvalidShares(_) => TaskErrorKind::Validation,
+            Self::ProofGeneration(_) | Self::UpdatingValidityProofs(_) => TaskErrorKind::Proof,
+            Self::Darkpool(_) => TaskErrorKind::Darkpool,
+            Self::Missing(_) | Self::State(_) => TaskErrorKind::State,
+            Self::SendEvent(_) => TaskErrorKind::Network,
+        }
+    }
 }
 
 impl Display for UpdateWalletTaskError {
@@ -380,7 +402,8 @@ impl UpdateWalletTask {
         // Attach the opening to the new wallet, and index the wallet in the global
         // state
         let tx = self.tx.as_ref().unwrap();
-        let merkle_opening = find_merkle_path_with_tx(&self.new_wallet, &self.darkpool_client, tx)?;
+        let merkle_opening =
+            find_merkle_path_with_tx(&self.new_wallet, &self.darkpool_client, tx, None).await?;
         self.new_wallet.merkle_proof = Some(merkle_opening);
 
         // After the state is finalized on-chain, re-index the wallet in the global
@@ -422,8 +445,10 @@ impl UpdateWalletTask {
         // No reblind occurs here, so force the new wallet to have the same secret
         // shares as the old one
         let mut new_wallet = self.new_wallet.clone();
-        new_wallet.private_shares = self.old_wallet.private_shares.clone();
-        new_wallet.blinded_public_shares = self.old_wallet.blinded_public_shares.clone();
+        new_wallet.set_shares(
+            self.old_wallet.private_shares.clone(),
+            self.old_wallet.blinded_public_shares.clone(),
+        );
         new_wallet.blinder = self.old_wallet.blinder;
         new_wallet.merkle_proof = self.old_wallet.merkle_proof.clone();
 
@@ -1,19 +1,29 @@
 //! Defines traits that tasks must implement to be driven by the task driver and
 //! queued by the consensus engine
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
+use circuit_types::wallet::Nullifier;
+use common::types::{tasks::TaskIdentifier, wallet::WalletIdentifier};
 use darkpool_client::DarkpoolClient;
 use external_api::bus_message::SystemBusMessage;
 use job_types::{
     event_manager::EventManagerQueue, network_manager::NetworkManagerQueue,
     proof_manager::ProofManagerQueue, task_driver::TaskDriverQueue,
 };
+use renegade_metrics::labels::{
+    TASK_NAME_METRIC_TAG, TASK_STATE_DURATION_METRIC, TASK_STATE_METRIC_TAG,
+};
 use serde::{Deserialize, Serialize};
 use state::State;
 use system_bus::SystemBus;
+use tokio_util::sync::CancellationToken;
+use tracing::{Span, info_span};
 
-use crate::task_state::StateWrapper;
+use crate::{task_state::StateWrapper, utils::retry::RetryConfig};
 
 // ------------------
 // | Task and State |
@@ -27,6 +37,18 @@ use crate::task_state::StateWrapper;
 /// description of the task's state transition, and a set of dependency
 /// injections from the task driver that may include: network manager queue,
 /// darkpool client, etc.
+///
+/// A task may be cancelled by sending a `TaskDriverJob::Cancel` to the driver,
+/// which cancels the `cancellation` token in the task's `TaskContext`. The
+/// driver checks the token between steps, never during one:
+/// - Before the commit point, a cancelled task is stopped at the next step
+///   boundary and fails with `TaskDriverError::Cancelled`, which is not
+///   retried. Its `cleanup` runs as for any other failure
+/// - At or past the commit point, cancellation is ignored and the task runs to
+///   completion, so that its on-chain effects are never left half-applied
+///
+/// Long running steps may also poll the token themselves, but must only act on
+/// it before the commit point
 #[async_trait]
 pub trait Task: Send + Sized {
     /// The descriptor of a task, this may be used to construct the task
@@ -60,8 +82,48 @@ pub trait Task: Send + Sized {
     fn bypass_task_queue(&self) -> bool {
         false
     }
+    /// The nullifiers that the task will spend on-chain
+    ///
+    /// Tasks that spend the same nullifier conflict, so the scheduler may use
+    /// these to detect conflicting tasks before they begin proving
+    fn nullifiers(&self) -> Vec<Nullifier> {
+        Vec::new()
+    }
     /// Take a step in the task, steps should represent largely async behavior
     async fn step(&mut self) -> Result<(), Self::Error>;
+    /// Take a step in the task, recording the time spent handling the state
+    /// that the step completes
+    ///
+    /// The final step records the state that transitions into `Completed`, so
+    /// per-state durations sum to the task's total latency
+    async fn timed_step(&mut self) -> Result<(), Self::Error> {
+        let state = self.state().to_string();
+        let start = Instant::now();
+        self.step().await?;
+
+        let elapsed = start.elapsed().as_secs_f64();
+        metrics::histogram!(
+            TASK_STATE_DURATION_METRIC,
+            TASK_NAME_METRIC_TAG => self.name(),
+            TASK_STATE_METRIC_TAG => state,
+        )
+        .record(elapsed);
+        Ok(())
+    }
+    /// Step the task until its state reaches or passes `target`, returning
+    /// early with the first step error
+    ///
+    /// Returns immediately if the task is already at or past `target`, and
+    /// stops once the task completes even if `target` was never reached. This
+    /// gives tests deterministic control over how far a task runs, e.g. up to
+    /// its commit point
+    async fn run_until(&mut self, target: Self::State) -> Result<(), Self::Error> {
+        while self.state() < target && !self.completed() {
+            self.step().await?;
+        }
+
+        Ok(())
+    }
     /// A cleanup step that is run in the event of a task failure
     async fn cleanup(&mut self) -> Result<(), Self::Error> {
         Ok(())
@@ -83,6 +145,13 @@ pub trait TaskState: Debug + Display + Ord + Send + Serialize + Into<StateWrappe
     fn committed(&self) -> bool {
         *self >= Self::commit_point()
     }
+    /// The maximum time a step may spend handling this state before it is
+    /// abandoned with a timeout
+    ///
+    /// `None` defers to the driver's default step timeout
+    fn step_timeout(&self) -> Option<Duration> {
+        None
+    }
 }
 
 /// The error type of a task
@@ -90,6 +159,45 @@ pub trait TaskState: Debug + Display + Ord + Send + Serialize + Into<StateWrappe
 pub trait TaskError: Debug + Display + Send {
     /// Whether or not the error is retryable
     fn retryable(&self) -> bool;
+    /// The name of the error's variant, used to label task failure metrics
+    fn variant_name(&self) -> &'static str;
+    /// The task-independent kind of the error
+    fn kind(&self) -> TaskErrorKind;
+}
+
+/// A classification of task errors shared across all tasks, so that the
+/// driver may handle errors uniformly without matching on each task's error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskErrorKind {
+    /// An error interacting with the darkpool contract
+    Darkpool,
+    /// An error generating or linking proofs, or reaching the prover
+    Proof,
+    /// An error reading or writing the relayer state
+    State,
+    /// An error sending a message to another worker
+    Network,
+    /// A task step exceeded its timeout
+    Timeout,
+    /// The task was cancelled
+    Cancelled,
+    /// The task's inputs are invalid
+    Validation,
+}
+
+impl TaskErrorKind {
+    /// The name of the kind, used to label task failure metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Darkpool => "darkpool",
+            Self::Proof => "proof",
+            Self::State => "state",
+            Self::Network => "network",
+            Self::Timeout => "timeout",
+            Self::Cancelled => "cancelled",
+            Self::Validation => "validation",
+        }
+    }
 }
 
 // ------------------------------
@@ -103,6 +211,11 @@ pub trait TaskError: Debug + Display + Send {
 /// the driver
 #[derive(Clone)]
 pub struct TaskContext {
+    /// The ID of the task the context is given to
+    ///
+    /// Set by the driver when it starts the task, `None` for a task run outside
+    /// of the driver
+    pub task_id: Option<TaskIdentifier>,
     /// A darkpool client
     pub darkpool_client: DarkpoolClient,
     /// A handle on the global state
@@ -117,4 +230,61 @@ pub struct TaskContext {
     pub task_queue: TaskDriverQueue,
     /// A handle on the system bus
     pub bus: SystemBus<SystemBusMessage>,
+    /// The config for retrying transaction submissions
+    pub retry_config: RetryConfig,
+    /// The token used to cooperatively cancel the task, see `Task` for the
+    /// cancellation semantics
+    ///
+    /// Each task is given a child of the driver's token, so cancelling the
+    /// driver's token cancels every running task
+    pub cancellation: CancellationToken,
+}
+
+impl TaskContext {
+    /// Create the span under which a task runs
+    ///
+    /// Spans opened while the task runs are its descendants, so they may be
+    /// correlated by the task name and the ID of the wallet the task operates
+    /// on
+    pub fn task_span(task: &str, wallet_id: Option<&WalletIdentifier>) -> Span {
+        match wallet_id {
+            Some(wallet_id) => info_span!("task_run", task, wallet_id = %wallet_id),
+            None => info_span!("task_run", task),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use tracing::{Instrument, info_span};
+    use util::telemetry::TelemetryBuilder;
+    use uuid::Uuid;
+
+    use super::TaskContext;
+
+    /// Tests that spans opened while a task runs are children of the task's
+    /// span, which carries the task's wallet ID
+    #[test]
+    fn test_task_span_wallet_id() {
+        let (builder, capture) = TelemetryBuilder::default().with_capture();
+        let _guard = builder.build_scoped();
+
+        // Instrument the task's future as the driver does
+        let wallet_id = Uuid::new_v4();
+        let task_span = TaskContext::task_span("pay-offline-fee", Some(&wallet_id));
+        let task_span_id = task_span.id().unwrap();
+        let step = async {
+            let _span = info_span!("generate_proof").entered();
+        };
+        block_on(step.instrument(task_span));
+
+        let spans = capture.captured_spans();
+        let task_span = spans.iter().find(|span| span.id == task_span_id).unwrap();
+        assert_eq!(task_span.fields["wallet_id"], wallet_id.to_string());
+        assert_eq!(task_span.fields["task"], "pay-offline-fee");
+
+        let proof_span = spans.iter().find(|span| span.name == "generate_proof").unwrap();
+        assert_eq!(proof_span.parent, Some(task_span_id));
+    }
 }
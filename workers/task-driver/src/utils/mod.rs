@@ -2,16 +2,17 @@
 
 pub mod find_wallet;
 pub mod order_states;
+pub mod retry;
 pub mod validity_proofs;
 
 /// Error message emitted when enqueuing a job with the proof manager fails
 const ERR_ENQUEUING_JOB: &str = "error enqueuing job with proof manager";
-/// Error message emitted when a balance cannot be found for an order
-const ERR_BALANCE_NOT_FOUND: &str = "cannot find balance for order";
 /// Error message emitted when a wallet is given missing an authentication path
 const ERR_MISSING_AUTHENTICATION_PATH: &str = "wallet missing authentication path";
 /// Error message emitted when an order cannot be found in a wallet
 const ERR_ORDER_NOT_FOUND: &str = "cannot find order in wallet";
+/// Error message emitted when the proof manager drops a job's response channel
+const ERR_PROOF_CHANNEL_CLOSED: &str = "proof manager closed the response channel";
 /// Error message emitted when proving VALID COMMITMENTS fails
 const ERR_PROVE_COMMITMENTS_FAILED: &str = "failed to prove valid commitments";
 /// Error message emitted when proving VALID REBLIND fails
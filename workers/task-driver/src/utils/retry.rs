@@ -0,0 +1,124 @@
+//! Helpers for retrying fallible operations with exponential backoff
+
+use std::{fmt::Display, future::Future, time::Duration};
+
+use rand::{Rng, thread_rng};
+use tracing::warn;
+
+/// The default number of attempts to make before giving up
+const DEFAULT_MAX_ATTEMPTS: usize = 5;
+/// The default delay before the first retry in milliseconds
+const DEFAULT_BASE_DELAY_MS: u64 = 500; // 500 milliseconds
+/// The default maximum delay between retries in milliseconds
+const DEFAULT_MAX_DELAY_MS: u64 = 10_000; // 10 seconds
+
+/// The config for retrying an operation with exponential backoff
+#[derive(Copy, Clone, Debug)]
+pub struct RetryConfig {
+    /// The total number of attempts to make, including the first
+    pub max_attempts: usize,
+    /// The delay before the first retry, doubled on each subsequent retry
+    pub base_delay: Duration,
+    /// The maximum delay between retries
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(DEFAULT_BASE_DELAY_MS),
+            max_delay: Duration::from_millis(DEFAULT_MAX_DELAY_MS),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// A config which polls at a fixed interval, jittered as in
+    /// `backoff_delay`, for at most the given number of attempts
+    pub const fn fixed_interval(max_attempts: usize, interval: Duration) -> Self {
+        Self { max_attempts, base_delay: interval, max_delay: interval }
+    }
+
+    /// Get the delay to wait after the given (zero-indexed) failed attempt
+    ///
+    /// The delay doubles with each attempt up to `max_delay`, and is jittered
+    /// uniformly over the upper half of that range so that concurrent tasks
+    /// do not retry in lockstep
+    pub fn backoff_delay(&self, attempt: usize) -> Duration {
+        let exp = u32::try_from(attempt).unwrap_or(u32::MAX);
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(exp)).min(self.max_delay);
+
+        let half = delay / 2;
+        half + half.mul_f64(thread_rng().r#gen::<f64>())
+    }
+}
+
+/// Run an operation, retrying errors that `is_retryable` accepts with
+/// exponential backoff
+///
+/// Returns the last error once `max_attempts` attempts have failed, or the
+/// first error that is not retryable
+pub async fn retry_with_backoff<T, E, F, Fut>(
+    config: &RetryConfig,
+    is_retryable: impl Fn(&E) -> bool,
+    mut op: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(res) => return Ok(res),
+            Err(e) if attempt + 1 < config.max_attempts && is_retryable(&e) => {
+                let delay = config.backoff_delay(attempt);
+                warn!("attempt {} failed, retrying in {delay:?}: {e}", attempt + 1);
+
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            },
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RetryConfig;
+
+    /// Tests that the backoff delay grows exponentially within its jitter
+    /// bounds and is capped at the max delay
+    #[test]
+    fn test_backoff_delay_bounds() {
+        let config = RetryConfig {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1_000),
+        };
+
+        for (attempt, expected_ms) in
+            [(0, 100), (1, 200), (2, 400), (3, 800), (4, 1_000), (9, 1_000)]
+        {
+            let expected = Duration::from_millis(expected_ms);
+            let delay = config.backoff_delay(attempt);
+            assert!(delay >= expected / 2 && delay <= expected, "attempt {attempt}: {delay:?}");
+        }
+    }
+
+    /// Tests that a fixed interval config does not grow its delay
+    #[test]
+    fn test_fixed_interval_delay() {
+        let interval = Duration::from_millis(500);
+        let config = RetryConfig::fixed_interval(5, interval);
+
+        for attempt in 0..5 {
+            let delay = config.backoff_delay(attempt);
+            assert!(delay >= interval / 2 && delay <= interval, "attempt {attempt}: {delay:?}");
+        }
+    }
+}
@@ -1,11 +1,16 @@
 //! Utils for updating wallet validity proofs
 
-use std::sync::Arc;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+    time::Instant,
+};
 
 use alloy::rpc::types::TransactionReceipt;
 use circuit_types::SizedWallet;
 use circuit_types::balance::Balance;
-use circuit_types::r#match::OrderSettlementIndices;
 use circuit_types::native_helpers::{
     compute_wallet_private_share_commitment, create_wallet_shares_from_private, reblind_wallet,
     wallet_from_blinded_shares,
@@ -26,19 +31,21 @@ use common::types::tasks::RedeemFeeTaskDescriptor;
 use common::types::wallet::{OrderIdentifier, Wallet, WalletAuthenticationPath};
 use darkpool_client::DarkpoolClient;
 use darkpool_client::errors::DarkpoolClientError;
+use futures::future::try_join_all;
 use gossip_api::pubsub::PubsubMessage;
 use gossip_api::pubsub::orderbook::{ORDER_BOOK_TOPIC, OrderBookManagementMessage};
 use job_types::network_manager::{NetworkManagerJob, NetworkManagerQueue};
 use job_types::proof_manager::{ProofJob, ProofManagerJob, ProofManagerQueue};
-use num_bigint::BigUint;
+use renegade_metrics::labels::{CIRCUIT_METRIC_TAG, PROOF_GENERATION_LATENCY_METRIC};
 use state::State;
 use tokio::sync::oneshot;
-use tokio::sync::oneshot::Receiver as TokioReceiver;
-use tracing::instrument;
+use tokio::sync::oneshot::{Receiver as TokioReceiver, error::RecvError};
+use tracing::{instrument, warn};
 
 use super::{
-    ERR_BALANCE_NOT_FOUND, ERR_ENQUEUING_JOB, ERR_MISSING_AUTHENTICATION_PATH, ERR_ORDER_NOT_FOUND,
-    ERR_PROVE_COMMITMENTS_FAILED, ERR_PROVE_REBLIND_FAILED,
+    ERR_ENQUEUING_JOB, ERR_MISSING_AUTHENTICATION_PATH, ERR_ORDER_NOT_FOUND,
+    ERR_PROOF_CHANNEL_CLOSED, ERR_PROVE_COMMITMENTS_FAILED, ERR_PROVE_REBLIND_FAILED,
+    retry::{RetryConfig, retry_with_backoff},
 };
 
 /// The error message emitted by the task when the fee decryption key is missing
@@ -46,19 +53,76 @@ const ERR_FEE_KEY_MISSING: &str = "fee decryption key is missing";
 /// The error message emitted by the task when the relayer wallet is missing
 const ERR_RELAYER_WALLET_MISSING: &str = "relayer wallet is missing";
 
+/// A receiver for a proof from the proof manager
+///
+/// Records the time between enqueuing the job and receiving the proof, labeled
+/// by circuit, when awaited to completion
+pub(crate) struct ProofReceiver {
+    /// The channel on which the proof manager sends the proof
+    inner: TokioReceiver<ProofBundle>,
+    /// The name of the circuit being proven
+    circuit: &'static str,
+    /// The time at which the job was enqueued
+    start: Instant,
+}
+
+impl Future for ProofReceiver {
+    type Output = Result<ProofBundle, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let res = ready!(Pin::new(&mut this.inner).poll(cx));
+        if res.is_ok() {
+            let latency = this.start.elapsed().as_secs_f64();
+            metrics::histogram!(PROOF_GENERATION_LATENCY_METRIC, CIRCUIT_METRIC_TAG => this.circuit)
+                .record(latency);
+        }
+
+        Poll::Ready(res)
+    }
+}
+
 /// Enqueue a job with the proof manager
 ///
-/// Returns a channel on which the proof manager will send the response
+/// Returns a receiver on which the proof manager will send the response
 pub(crate) fn enqueue_proof_job(
     job: ProofJob,
     work_queue: &ProofManagerQueue,
-) -> Result<TokioReceiver<ProofBundle>, String> {
+) -> Result<ProofReceiver, String> {
+    let circuit = job.circuit_name();
     let (response_sender, response_receiver) = oneshot::channel();
     work_queue
         .send(ProofManagerJob { type_: job, response_channel: response_sender })
         .map_err(|_| ERR_ENQUEUING_JOB.to_string())?;
 
-    Ok(response_receiver)
+    Ok(ProofReceiver { inner: response_receiver, circuit, start: Instant::now() })
+}
+
+/// Enqueue a batch of jobs with the proof manager, returning a receiver for
+/// each job in the order the jobs were given
+///
+/// Jobs are proven concurrently up to the proof manager's parallelism
+pub(crate) fn enqueue_proof_jobs(
+    jobs: Vec<ProofJob>,
+    work_queue: &ProofManagerQueue,
+) -> Result<Vec<ProofReceiver>, String> {
+    jobs.into_iter().map(|job| enqueue_proof_job(job, work_queue)).collect()
+}
+
+/// Await a batch of proofs concurrently, returning them in the order of the
+/// receivers
+///
+/// Returns an error as soon as any receiver fails, dropping the remaining
+/// receivers
+pub(crate) async fn join_proof_receivers(
+    receivers: Vec<ProofReceiver>,
+) -> Result<Vec<ProofBundle>, String> {
+    let proofs = receivers.into_iter().map(|recv| {
+        let circuit = recv.circuit;
+        async move { recv.await.map_err(|_| format!("{ERR_PROOF_CHANNEL_CLOSED}: {circuit}")) }
+    });
+
+    try_join_all(proofs).await
 }
 
 /// Find the merkle authentication path of a wallet
@@ -73,20 +137,45 @@ pub(crate) async fn find_merkle_path(
 
 /// Find the merkle authentication path of a wallet given an updating
 /// transaction
-pub(crate) fn find_merkle_path_with_tx(
+///
+/// If a poll config is given and the path cannot be parsed from the
+/// transaction, the darkpool is polled for the wallet's commitment until it is
+/// indexed or the config's attempts are exhausted, in which case the last
+/// error is returned
+pub(crate) async fn find_merkle_path_with_tx(
     wallet: &Wallet,
     darkpool_client: &DarkpoolClient,
     tx: &TransactionReceipt,
+    poll_config: Option<&RetryConfig>,
 ) -> Result<WalletAuthenticationPath, DarkpoolClientError> {
     let commitment = wallet.get_wallet_share_commitment();
-    darkpool_client.find_merkle_authentication_path_with_tx(commitment, tx)
+    let err = match darkpool_client.find_merkle_authentication_path_with_tx(commitment, tx) {
+        Ok(path) => return Ok(path),
+        Err(e) => e,
+    };
+
+    let Some(poll_config) = poll_config else {
+        return Err(err);
+    };
+    warn!("Merkle path not found in tx, polling darkpool: {err}");
+    retry_with_backoff(poll_config, merkle_path_not_indexed, || {
+        darkpool_client.find_merkle_authentication_path(commitment)
+    })
+    .await
+}
+
+/// Whether a Merkle path lookup failed because the darkpool has not yet
+/// indexed the commitment, or transiently, so that polling should continue
+fn merkle_path_not_indexed(e: &DarkpoolClientError) -> bool {
+    matches!(e, DarkpoolClientError::CommitmentNotFound | DarkpoolClientError::TxNotFound(_))
+        || e.is_retryable()
 }
 
 /// Re-blind the wallet and prove `VALID REBLIND` for the wallet
 pub(crate) fn construct_wallet_reblind_proof(
     wallet: &Wallet,
     prover_queue: &ProofManagerQueue,
-) -> Result<(SizedValidReblindWitness, TokioReceiver<ProofBundle>), String> {
+) -> Result<(SizedValidReblindWitness, ProofReceiver), String> {
     // If the wallet doesn't have an authentication path return an error
     let authentication_path =
         wallet.merkle_proof.clone().ok_or_else(|| ERR_MISSING_AUTHENTICATION_PATH.to_string())?;
@@ -123,24 +212,36 @@ pub(crate) fn construct_wallet_reblind_proof(
     Ok((witness, recv))
 }
 
-/// Prove `VALID COMMITMENTS` for an order within a wallet
+/// Build a job proving `VALID COMMITMENTS` for an order within a wallet
 ///
 /// Returns a copy of the witness for indexing
-pub(crate) fn construct_order_commitment_proof(
-    order: Order,
+pub(crate) fn construct_order_commitment_job(
+    wallet: &Wallet,
+    order_id: &OrderIdentifier,
     valid_reblind_witness: &SizedValidReblindWitness,
-    proof_manager_work_queue: &ProofManagerQueue,
-) -> Result<(SizedValidCommitmentsWitness, TokioReceiver<ProofBundle>), String> {
-    // Build an augmented wallet
+) -> Result<(SizedValidCommitmentsWitness, ProofJob), String> {
+    let order: Order =
+        wallet.get_order(order_id).cloned().ok_or_else(|| ERR_ORDER_NOT_FOUND.to_string())?.into();
+
+    // Find the indices at which the local party will spend and receive their
+    // respective sides of the match
+    let indices = wallet.compute_settlement_indices(order_id).map_err(|e| e.to_string())?;
+
+    // Build an augmented wallet, placing a zero'd receive balance at the receive
+    // index if the wallet does not yet hold the receive mint
     let mut augmented_wallet: SizedWallet = wallet_from_blinded_shares(
         &valid_reblind_witness.reblinded_wallet_private_shares,
         &valid_reblind_witness.reblinded_wallet_public_shares,
     );
 
-    // Find balances at which the local party will spend and receive their
-    // respective sides of the match
-    let (indices, balance_send, balance_receive) =
-        find_balances_and_indices(&order, &mut augmented_wallet)?;
+    let receive_mint = order.receive_mint();
+    let receive_slot = &mut augmented_wallet.balances[indices.balance_receive];
+    if receive_slot.mint != *receive_mint {
+        *receive_slot = Balance::new_from_mint(receive_mint.clone());
+    }
+
+    let balance_send = augmented_wallet.balances[indices.balance_send].clone();
+    let balance_receive = augmented_wallet.balances[indices.balance_receive].clone();
 
     // Create new augmented public secret shares
     let reblinded_private_blinder = valid_reblind_witness.reblinded_wallet_private_shares.blinder;
@@ -164,81 +265,8 @@ pub(crate) fn construct_order_commitment_proof(
         balance_receive,
     };
 
-    // Dispatch a job to the proof manager to prove `VALID COMMITMENTS`
     let job = ProofJob::ValidCommitments { witness: witness.clone(), statement };
-    let recv = enqueue_proof_job(job, proof_manager_work_queue)?;
-
-    Ok((witness, recv))
-}
-
-/// Build the indices and fetch the balances for a given order
-///
-/// Returns the indices and the send and receive balances respectively for the
-/// order
-fn find_balances_and_indices(
-    order: &Order,
-    wallet: &mut SizedWallet,
-) -> Result<(OrderSettlementIndices, Balance, Balance), String> {
-    let send_mint = order.send_mint();
-    let receive_mint = order.receive_mint();
-
-    let (send_index, send_balance) =
-        find_or_augment_balance(send_mint, wallet, false /* augment */)
-            .ok_or_else(|| ERR_BALANCE_NOT_FOUND.to_string())?;
-    let (receive_index, receive_balance) =
-        find_or_augment_balance(receive_mint, wallet, true /* augment */)
-            .ok_or_else(|| ERR_BALANCE_NOT_FOUND.to_string())?;
-
-    // Find the order in the wallet
-    let order_index = find_order(order, wallet).ok_or_else(|| ERR_ORDER_NOT_FOUND.to_string())?;
-
-    Ok((
-        OrderSettlementIndices {
-            order: order_index,
-            balance_send: send_index,
-            balance_receive: receive_index,
-        },
-        send_balance,
-        receive_balance,
-    ))
-}
-
-/// Find a balance in the wallet
-///
-/// If the balance is not found and the `augment` flag is set, the method
-/// will find an empty balance and add a zero'd balance in its place
-///
-/// Returns the index at which the balance was found or augmented, if possible
-fn find_or_augment_balance(
-    mint: &BigUint,
-    wallet: &mut SizedWallet,
-    augment: bool,
-) -> Option<(usize, Balance)> {
-    let index = wallet.balances.iter().enumerate().find(|(_ind, balance)| mint.eq(&balance.mint));
-    match index {
-        Some((index, balance)) => Some((index, balance.clone())),
-        None => {
-            if !augment {
-                return None;
-            }
-
-            // Find an empty balance and augment it
-            let empty_balance_ind = wallet
-                .balances
-                .iter()
-                .enumerate()
-                .find(|(_ind, balance)| balance.is_zero())
-                .map(|(ind, _balance)| ind)?;
-
-            wallet.balances[empty_balance_ind] = Balance::new_from_mint(mint.clone());
-            Some((empty_balance_ind, wallet.balances[empty_balance_ind].clone()))
-        },
-    }
-}
-
-/// Find an order in the wallet, returns the index at which the order was found
-fn find_order(order: &Order, wallet: &SizedWallet) -> Option<usize> {
-    wallet.orders.iter().enumerate().find(|(_ind, o)| (*o).eq(order)).map(|(ind, _o)| ind)
+    Ok((witness, job))
 }
 
 /// Find a wallet on-chain, and update its validity proofs. That is, a proof of
@@ -265,28 +293,27 @@ pub(crate) async fn update_wallet_validity_proofs(
     let (reblind_witness, reblind_response_channel) =
         construct_wallet_reblind_proof(wallet, &proof_manager_work_queue)?;
 
-    // For each order, construct a proof of `VALID COMMITMENTS`
+    // For each order, dispatch a proof of `VALID COMMITMENTS`
     let mut commitments_instances = Vec::new();
-    for (id, order) in matchable_orders.iter() {
-        // Start a proof of `VALID COMMITMENTS`
-        let (commitments_witness, response_channel) = construct_order_commitment_proof(
-            order.clone().into(),
-            &reblind_witness,
-            &proof_manager_work_queue,
-        )?;
-        commitments_instances.push((*id, commitments_witness, response_channel));
+    let mut commitments_jobs = Vec::new();
+    for (id, _) in matchable_orders.iter() {
+        let (commitments_witness, job) =
+            construct_order_commitment_job(wallet, id, &reblind_witness)?;
+        commitments_instances.push((*id, commitments_witness));
+        commitments_jobs.push(job);
     }
+    let commitments_receivers = enqueue_proof_jobs(commitments_jobs, &proof_manager_work_queue)?;
 
     // Await the proof of `VALID REBLIND`
     let reblind_proof: ProofBundle =
         reblind_response_channel.await.map_err(|_| ERR_PROVE_REBLIND_FAILED.to_string())?;
 
     // Await proofs of `VALID COMMITMENTS` for each order, store them in the state
-    for (order_id, commitments_witness, receiver) in commitments_instances.into_iter() {
-        // Await a proof
-        let commitment_proof: ProofBundle =
-            receiver.await.map_err(|_| ERR_PROVE_COMMITMENTS_FAILED.to_string())?;
-
+    let commitments_proofs = join_proof_receivers(commitments_receivers)
+        .await
+        .map_err(|e| format!("{ERR_PROVE_COMMITMENTS_FAILED}: {e}"))?;
+    let instances = commitments_instances.into_iter().zip(commitments_proofs);
+    for ((order_id, commitments_witness), commitment_proof) in instances {
         link_and_store_proofs(
             &order_id,
             &commitments_witness,
@@ -361,3 +388,124 @@ pub(crate) async fn enqueue_relayer_redeem_job(note: Note, state: &State) -> Res
 
     state.append_task(descriptor.into()).await.map_err(|e| e.to_string()).map(|_| ())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{iter, sync::Arc, time::Duration};
+
+    use circuit_types::traits::BaseType;
+    use circuits::zk_circuits::{
+        valid_commitments::{SizedValidCommitmentsWitness, ValidCommitmentsStatement},
+        valid_reblind::{SizedValidReblindWitness, ValidReblindStatement},
+    };
+    use common::types::proof_bundles::{
+        ProofBundle, R1CSProofBundle,
+        mocks::{dummy_link_hint, dummy_valid_commitments_bundle, dummy_valid_reblind_bundle},
+    };
+    use constants::Scalar;
+    use darkpool_client::errors::DarkpoolClientError;
+    use job_types::proof_manager::{ProofJob, ProofManagerJob, new_proof_manager_queue};
+
+    use super::{enqueue_proof_jobs, join_proof_receivers, merkle_path_not_indexed};
+    use crate::utils::retry::{RetryConfig, retry_with_backoff};
+
+    /// Create a `VALID REBLIND` job followed by a `VALID COMMITMENTS` job
+    fn dummy_jobs() -> Vec<ProofJob> {
+        let mut scalars = iter::repeat(Scalar::one());
+        vec![
+            ProofJob::ValidReblind {
+                witness: SizedValidReblindWitness::from_scalars(&mut scalars),
+                statement: ValidReblindStatement::from_scalars(&mut scalars),
+            },
+            ProofJob::ValidCommitments {
+                witness: SizedValidCommitmentsWitness::from_scalars(&mut scalars),
+                statement: ValidCommitmentsStatement::from_scalars(&mut scalars),
+            },
+        ]
+    }
+
+    /// Create a dummy proof bundle for the circuit of the given job
+    fn dummy_bundle(job: &ProofJob) -> ProofBundle {
+        let proof = match job {
+            ProofJob::ValidReblind { .. } => {
+                R1CSProofBundle::ValidReblind(Arc::new(dummy_valid_reblind_bundle()))
+            },
+            ProofJob::ValidCommitments { .. } => {
+                R1CSProofBundle::ValidCommitments(Arc::new(dummy_valid_commitments_bundle()))
+            },
+            _ => unreachable!("only reblind and commitments jobs are mocked"),
+        };
+
+        ProofBundle { proof, link_hint: dummy_link_hint() }
+    }
+
+    /// Tests that proofs are returned in the order their jobs were enqueued,
+    /// regardless of the order in which they are proven
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn test_join_proof_receivers__order() {
+        let (queue, job_receiver) = new_proof_manager_queue();
+        let receivers = enqueue_proof_jobs(dummy_jobs(), &queue).unwrap();
+
+        let jobs: Vec<ProofManagerJob> =
+            iter::from_fn(|| job_receiver.try_recv().ok()).map(|msg| msg.consume()).collect();
+        let circuits: Vec<_> = jobs.iter().map(|job| job.type_.circuit_name()).collect();
+        assert_eq!(circuits, ["valid_reblind", "valid_commitments"]);
+
+        // Respond to the jobs in reverse order
+        for job in jobs.into_iter().rev() {
+            let bundle = dummy_bundle(&job.type_);
+            job.response_channel.send(bundle).unwrap();
+        }
+
+        let proofs = join_proof_receivers(receivers).await.unwrap();
+        assert_eq!(proofs.len(), 2);
+        assert!(matches!(proofs[0].proof, R1CSProofBundle::ValidReblind(_)));
+        assert!(matches!(proofs[1].proof, R1CSProofBundle::ValidCommitments(_)));
+    }
+
+    /// Tests that the join fails as soon as one job's response channel is
+    /// dropped, without waiting on the remaining jobs
+    #[tokio::test]
+    #[allow(non_snake_case)]
+    async fn test_join_proof_receivers__dropped_sender() {
+        let (queue, job_receiver) = new_proof_manager_queue();
+        let receivers = enqueue_proof_jobs(dummy_jobs(), &queue).unwrap();
+        let _reblind_job = job_receiver.try_recv().unwrap().consume();
+        let commitments_job = job_receiver.try_recv().unwrap().consume();
+
+        // Drop the second job's sender while the first job is still pending
+        drop(commitments_job);
+        let res = tokio::time::timeout(Duration::from_secs(1), join_proof_receivers(receivers))
+            .await
+            .expect("join did not short-circuit on the dropped sender");
+
+        let err = res.unwrap_err();
+        assert!(err.ends_with("valid_commitments"), "unexpected error: {err}");
+    }
+
+    /// Tests that polling for a Merkle path continues while the commitment is
+    /// not yet indexed and stops on the first other error
+    #[tokio::test]
+    async fn test_merkle_path_poll_predicate() {
+        let config = RetryConfig::fixed_interval(10 /* max_attempts */, Duration::ZERO);
+        let mut errors = vec![
+            DarkpoolClientError::CommitmentNotFound,
+            DarkpoolClientError::TxNotFound("tx not found".to_string()),
+            DarkpoolClientError::BlinderNotFound,
+            DarkpoolClientError::CommitmentNotFound,
+        ]
+        .into_iter();
+
+        let mut attempts = 0;
+        let res: Result<(), _> = retry_with_backoff(&config, merkle_path_not_indexed, || {
+            attempts += 1;
+            let err = errors.next().unwrap();
+            async move { Err(err) }
+        })
+        .await;
+
+        assert!(matches!(res, Err(DarkpoolClientError::BlinderNotFound)));
+        assert_eq!(attempts, 3);
+    }
+}
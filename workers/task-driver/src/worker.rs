@@ -14,6 +14,7 @@ use job_types::{
 };
 use state::State;
 use system_bus::SystemBus;
+use tokio_util::sync::CancellationToken;
 
 use crate::{
     driver::{RuntimeArgs, TaskExecutor},
@@ -46,6 +47,9 @@ pub struct TaskDriverConfig {
     pub system_bus: SystemBus<SystemBusMessage>,
     /// A handle on the global state
     pub state: State,
+    /// The token used to cancel running tasks, each task is given a child of
+    /// this token
+    pub cancellation: CancellationToken,
 }
 
 impl TaskDriverConfig {
@@ -71,6 +75,7 @@ impl TaskDriverConfig {
             event_queue,
             system_bus,
             state,
+            cancellation: CancellationToken::new(),
         }
     }
 }